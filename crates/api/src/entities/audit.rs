@@ -0,0 +1,55 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+  Create,
+  Update,
+  Delete,
+}
+
+impl fmt::Display for AuditAction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AuditAction::Create => write!(f, "create"),
+      AuditAction::Update => write!(f, "update"),
+      AuditAction::Delete => write!(f, "delete"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEntity {
+  User,
+  Project,
+  Task,
+  TaskTemplate,
+}
+
+impl fmt::Display for AuditEntity {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AuditEntity::User => write!(f, "user"),
+      AuditEntity::Project => write!(f, "project"),
+      AuditEntity::Task => write!(f, "task"),
+      AuditEntity::TaskTemplate => write!(f, "task_template"),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone, ToSchema)]
+pub struct AuditEntry {
+  pub id: Uuid,
+  pub actor_id: Option<Uuid>,
+  pub action: String,
+  pub entity_type: String,
+  pub entity_id: Uuid,
+  pub diff: Value,
+  pub created_at: DateTime<Utc>,
+}
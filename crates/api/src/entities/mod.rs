@@ -1,3 +1,7 @@
+pub mod audit;
+pub mod plugin;
 pub mod project;
+pub mod search;
 pub mod task;
+pub mod template;
 pub mod user;
@@ -0,0 +1,15 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+/// Options pushed for a plugin through `PUT /api/plugins/{name}/config`, used instead of the ones in config.json
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct PluginOptionsRow {
+  /// Name the plugin is configured under in config.json
+  pub name: String,
+  pub options: Value,
+  pub updated_by: Option<Uuid>,
+  pub updated_at: DateTime<Utc>,
+}
@@ -14,8 +14,12 @@ pub struct ProjectRow {
   pub code: String,
   pub options: Value,
   pub owner_id: Uuid,
+  pub created_by: Option<Uuid>,
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
+  /// Bumped on every update, exports made before it existed have none
+  #[serde(default)]
+  pub version: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
@@ -25,6 +29,10 @@ pub struct Project {
   pub code: String,
   pub options: Value,
   pub owner: User,
+  /// User who created the project, unset for projects created before creators were tracked
+  pub created_by: Option<Uuid>,
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
+  /// Bumped on every update, pass it as `version` to only update the project if nobody changed it since
+  pub version: i64,
 }
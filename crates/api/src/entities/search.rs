@@ -0,0 +1,23 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::task::TaskStatus;
+
+/// A project or a task matching a search term
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SearchResult {
+  Project {
+    id: Uuid,
+    name: String,
+    code: String,
+  },
+  Task {
+    id: Uuid,
+    name: String,
+    external_id: Option<String>,
+    project_id: Uuid,
+    status: TaskStatus,
+  },
+}
@@ -9,13 +9,17 @@ use uuid::Uuid;
 
 use super::project::ProjectRow;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum TaskStatus {
   New,
   InProgress,
   Finished,
   Failed,
   Retried,
+  Dead,
+  /// Stopped by an operator before it ran, kept until deleted unlike `finished` tasks
+  Cancelled,
 }
 
 impl fmt::Display for TaskStatus {
@@ -26,6 +30,8 @@ impl fmt::Display for TaskStatus {
       TaskStatus::Retried => write!(f, "retried"),
       TaskStatus::Failed => write!(f, "failed"),
       TaskStatus::Finished => write!(f, "finished"),
+      TaskStatus::Dead => write!(f, "dead"),
+      TaskStatus::Cancelled => write!(f, "cancelled"),
     }
   }
 }
@@ -40,15 +46,79 @@ impl FromStr for TaskStatus {
       "retried" => Ok(TaskStatus::Retried),
       "failed" => Ok(TaskStatus::Failed),
       "finished" => Ok(TaskStatus::Finished),
+      "dead" => Ok(TaskStatus::Dead),
+      "cancelled" => Ok(TaskStatus::Cancelled),
       _ => Err(format!("'{}' is not a valid variant", s)),
     }
   }
 }
 
+impl TaskStatus {
+  /// Parses a stored `status` column, failing with a decode error on values the enum doesn't know
+  pub fn decode(value: &str) -> Result<Self, sqlx::Error> {
+    value.parse().map_err(|e: String| sqlx::Error::ColumnDecode {
+      index: "status".to_string(),
+      source: e.into(),
+    })
+  }
+}
+
+/// What to do with the occurrences of a recurring task that were missed while it couldn't run
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MisfirePolicy {
+  /// Drop the missed occurrences and wait for the next one in the future
+  Skip,
+  /// Run once right away for all the missed occurrences together
+  #[default]
+  FireOnce,
+  /// Run every missed occurrence in turn, up to a bounded number of the most recent ones
+  CatchUp,
+}
+
+impl fmt::Display for MisfirePolicy {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      MisfirePolicy::Skip => write!(f, "skip"),
+      MisfirePolicy::FireOnce => write!(f, "fire_once"),
+      MisfirePolicy::CatchUp => write!(f, "catch_up"),
+    }
+  }
+}
+
+impl FromStr for MisfirePolicy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "skip" => Ok(MisfirePolicy::Skip),
+      "fire_once" => Ok(MisfirePolicy::FireOnce),
+      "catch_up" => Ok(MisfirePolicy::CatchUp),
+      _ => Err(format!("'{}' is not a valid variant", s)),
+    }
+  }
+}
+
+impl MisfirePolicy {
+  /// Parses a stored `misfire_policy` column, failing with a decode error on unknown values
+  pub fn decode(value: &str) -> Result<Self, sqlx::Error> {
+    value.parse().map_err(|e: String| sqlx::Error::ColumnDecode {
+      index: "misfire_policy".to_string(),
+      source: e.into(),
+    })
+  }
+}
+
+/// Exports made before tasks could be disabled have no `enabled` field
+fn default_enabled() -> bool {
+  true
+}
+
 #[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
 pub struct TaskRow {
   pub id: Uuid,
   pub r#type: String,
+  pub plugin_version: Option<String>,
   pub status: String,
   pub project_id: Uuid,
   pub retries: i32,
@@ -56,25 +126,85 @@ pub struct TaskRow {
   pub external_id: Option<String>,
   pub external_modified_at: Option<DateTime<Utc>>,
   pub schedule: Option<String>,
-  pub start_at: i32,
+  pub start_at: i64,
+  /// `start_at` as last set through the API, `@every` runs stay aligned to it however late they run
+  pub schedule_anchor: Option<i64>,
+  pub end_at: Option<i64>,
+  pub misfire_policy: String,
+  #[serde(default = "default_enabled")]
+  pub enabled: bool,
   pub options: Value,
+  pub created_by: Option<Uuid>,
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
+  /// When the task was soft-deleted, it's restorable until purged
+  pub deleted_at: Option<DateTime<Utc>>,
+  /// `EXECUTOR_ID` of the executor running the task
+  #[serde(default)]
+  pub locked_by: Option<String>,
+  /// Bumped on every update, exports made before it existed have none
+  #[serde(default)]
+  pub version: i64,
+  #[serde(default)]
+  pub failed_results: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct Task {
   pub id: Uuid,
   pub r#type: String,
-  pub status: String,
+  /// Semver requirement on the version of the `type` plugin, e.g. `^1.2`, any loaded version runs it when unset
+  pub plugin_version: Option<String>,
+  pub status: TaskStatus,
   pub project: ProjectRow,
   pub retries: i32,
   pub name: String,
   pub external_id: Option<String>,
   pub external_modified_at: Option<DateTime<Utc>>,
   pub schedule: Option<String>,
-  pub start_at: i32,
+  /// Unix timestamp of the next run
+  pub start_at: i64,
+  /// Unix timestamp `@every` schedules are aligned to, the `start_at` the task was created or last updated
+  /// with. Running a task late or on demand doesn't move it, so the cadence doesn't drift
+  pub schedule_anchor: Option<i64>,
+  /// Unix timestamp after which a recurring task is finished instead of rescheduled
+  pub end_at: Option<i64>,
+  /// `EXECUTOR_ID` of the executor running the task, unset unless it's `in_progress`
+  pub locked_by: Option<String>,
+  pub misfire_policy: MisfirePolicy,
+  /// Disabled tasks are skipped by the executor until enabled again
+  pub enabled: bool,
+  /// Timezone cron schedules are evaluated in, from `OCTABOT_DEFAULT_TZ`
+  pub timezone: String,
+  /// UTC offset of `timezone` at `start_at`, e.g. `+02:00`
+  pub utc_offset: String,
   pub options: Value,
+  /// User who created the task, unset for tasks created by plugins or before creators were tracked
+  pub created_by: Option<Uuid>,
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
+  /// Bumped on every update, the executor's included. Pass it as `version` to only update the task if nobody
+  /// changed it since
+  pub version: i64,
+  /// Results the last successful run dropped because they failed, only plugins with the `lenient` `sub_results`
+  /// policy finish a task despite failed results. See the task logs for why they failed
+  pub failed_results: i64,
+}
+
+/// A line a plugin logged while running a task
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TaskLog {
+  pub at: DateTime<Utc>,
+  /// `trace`, `debug`, `info`, `warn`, `error` or `critical`
+  pub level: String,
+  pub context: String,
+  pub message: String,
+}
+
+/// One line of an NDJSON task export, projects are written before the tasks referencing them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportRecord {
+  Project(ProjectRow),
+  Task(Box<TaskRow>),
 }
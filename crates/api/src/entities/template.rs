@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::task::MisfirePolicy;
+
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct TaskTemplateRow {
+  pub id: Uuid,
+  pub name: String,
+  pub description: Option<String>,
+  pub r#type: String,
+  pub plugin_version: Option<String>,
+  pub schedule: Option<String>,
+  pub misfire_policy: String,
+  pub options: Value,
+  pub created_by: Option<Uuid>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+/// A reusable task definition, instantiated into a project with `POST /api/projects/{id}/tasks/from-template/{template_id}`
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TaskTemplate {
+  pub id: Uuid,
+  pub name: String,
+  pub description: Option<String>,
+  pub r#type: String,
+  /// Semver requirement on the version of the `type` plugin, copied to the tasks created from the template
+  pub plugin_version: Option<String>,
+  pub schedule: Option<String>,
+  pub misfire_policy: MisfirePolicy,
+  /// Options skeleton, the options given when instantiating are merged over it
+  pub options: Value,
+  /// User who created the template
+  pub created_by: Option<Uuid>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<TaskTemplateRow> for TaskTemplate {
+  type Error = sqlx::Error;
+
+  fn try_from(row: TaskTemplateRow) -> Result<Self, Self::Error> {
+    Ok(Self {
+      id: row.id,
+      name: row.name,
+      description: row.description,
+      r#type: row.r#type,
+      plugin_version: row.plugin_version,
+      schedule: row.schedule,
+      misfire_policy: MisfirePolicy::decode(&row.misfire_policy)?,
+      options: row.options,
+      created_by: row.created_by,
+      created_at: row.created_at,
+      updated_at: row.updated_at,
+    })
+  }
+}
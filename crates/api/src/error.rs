@@ -1,9 +1,17 @@
 use axum::extract::rejection::JsonRejection;
 use axum::response::{IntoResponse, Response};
 use axum::{http::StatusCode, Json};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use sqlx::Error as SqlxError;
 use thiserror::Error;
+use validator::ValidationError;
+
+use crate::{
+  executor::{EnqueueError, PluginReloadError},
+  request_id,
+};
 
 pub type ApiResult<T = ()> = Result<T, ApiError>;
 
@@ -11,20 +19,50 @@ pub type ApiResult<T = ()> = Result<T, ApiError>;
 pub enum ApiError {
   #[error("Invalid credentials")]
   InvalidCredentials(),
+  #[error("{0}")]
+  Unauthorized(String),
+  #[error("You don't have permission to access this resource")]
+  Forbidden(),
+  #[error("Missing or mismatched CSRF token")]
+  InvalidCsrfToken(),
+  #[error("Account is locked until {0}")]
+  AccountLocked(DateTime<Utc>),
   #[error("User with email `{0}` already exists")]
   UserAlreadyExist(String),
   #[error("Entity `{0}` is not found")]
   ResourceNotFound(String),
   #[error("Project with code `{0}` already exists")]
   ProjectAlreadyExist(String),
+  #[error("Project code `{0}` must be 2 to 4 uppercase letters or digits")]
+  InvalidProjectCode(String),
+  #[error("{0}")]
+  Conflict(String),
+  #[error(transparent)]
+  PluginReload(#[from] PluginReloadError),
+  #[error(transparent)]
+  Enqueue(#[from] EnqueueError),
+  #[error("Invalid import record on line {0}: {1}")]
+  InvalidImport(usize, String),
   #[error("Database error: {0}")]
   DatabaseError(#[from] SqlxError),
   #[error(transparent)]
   JsonRejection(JsonRejection),
   #[error(transparent)]
   InvalidInputError(#[from] validator::ValidationErrors),
+  #[error("Invalid `{0}` header")]
+  InvalidHeader(String),
+  #[error("`{0}` must be an RFC 3339 timestamp, got `{1}`")]
+  InvalidTimestamp(String, String),
+  #[error("Task options are {0} bytes, the limit is {1}")]
+  OptionsTooLarge(usize, usize),
+  #[error("Plugin version `{0}` is not a semver requirement: {1}")]
+  InvalidPluginVersion(String, String),
+  #[error("Log level `{0}` is invalid: {1}")]
+  InvalidLogLevel(String, String),
   #[error("Invalid schedule format: {0}")]
   InvalidSchedule(String),
+  #[error("Schedule `{0}` runs every {1}s, the minimum interval is {2}s")]
+  ScheduleTooFrequent(String, u64, u64),
   #[error("Failed to calculate next run time: {0}")]
   ScheduleCalculation(String),
   #[error("an internal server error occurred")]
@@ -35,6 +73,14 @@ impl ApiError {
   pub fn response(self) -> (StatusCode, AppResponseError) {
     use ApiError::*;
     let message = self.to_string();
+    let field_errors = match &self {
+      InvalidInputError(err) => err
+        .field_errors()
+        .into_iter()
+        .flat_map(|(field, errors)| errors.iter().map(move |error| FieldError::new(&field, error)))
+        .collect(),
+      _ => vec![],
+    };
 
     let (kind, code, details, status_code) = match self {
       JsonRejection(rejection) => (
@@ -70,7 +116,16 @@ impl ApiError {
         vec![],
         StatusCode::INTERNAL_SERVER_ERROR,
       ),
-      DatabaseError(_) => todo!(),
+      DatabaseError(ref e) => {
+        tracing::error!("Database error: {:?}", e);
+
+        (
+          "INTERNAL_SERVER_ERROR".to_string(),
+          None,
+          vec![],
+          StatusCode::INTERNAL_SERVER_ERROR,
+        )
+      },
       UserAlreadyExist(_) => todo!(),
       ResourceNotFound(_) => ("RESOURCE_NOT_FOUND".to_string(), None, vec![], StatusCode::NOT_FOUND),
       InvalidCredentials() => (
@@ -79,6 +134,59 @@ impl ApiError {
         vec![],
         StatusCode::UNAUTHORIZED,
       ),
+      Unauthorized(_) => ("UNAUTHORIZED".to_string(), None, vec![], StatusCode::UNAUTHORIZED),
+      Forbidden() => ("FORBIDDEN".to_string(), None, vec![], StatusCode::FORBIDDEN),
+      InvalidCsrfToken() => ("INVALID_CSRF_TOKEN".to_string(), None, vec![], StatusCode::FORBIDDEN),
+      AccountLocked(_) => ("ACCOUNT_LOCKED".to_string(), None, vec![], StatusCode::LOCKED),
+      Conflict(_) => ("CONFLICT".to_string(), None, vec![], StatusCode::CONFLICT),
+      PluginReload(PluginReloadError::NotFound(_)) => {
+        ("RESOURCE_NOT_FOUND".to_string(), None, vec![], StatusCode::NOT_FOUND)
+      },
+      PluginReload(PluginReloadError::Failed(..)) => (
+        "PLUGIN_RELOAD_FAILED".to_string(),
+        None,
+        vec![],
+        StatusCode::UNPROCESSABLE_ENTITY,
+      ),
+      Enqueue(EnqueueError::AlreadyQueued(_)) => ("CONFLICT".to_string(), None, vec![], StatusCode::CONFLICT),
+      Enqueue(EnqueueError::QueueFull(_) | EnqueueError::Stopped(_)) => (
+        "SERVICE_UNAVAILABLE".to_string(),
+        None,
+        vec![],
+        StatusCode::SERVICE_UNAVAILABLE,
+      ),
+      InvalidHeader(_) => ("INVALID_HEADER".to_string(), None, vec![], StatusCode::BAD_REQUEST),
+      InvalidTimestamp(..) => (
+        "INVALID_TIMESTAMP".to_string(),
+        None,
+        vec![],
+        StatusCode::UNPROCESSABLE_ENTITY,
+      ),
+      InvalidImport(..) => ("INVALID_IMPORT".to_string(), None, vec![], StatusCode::BAD_REQUEST),
+      OptionsTooLarge(..) => (
+        "OPTIONS_TOO_LARGE".to_string(),
+        None,
+        vec![],
+        StatusCode::UNPROCESSABLE_ENTITY,
+      ),
+      InvalidPluginVersion(..) => (
+        "INVALID_PLUGIN_VERSION".to_string(),
+        None,
+        vec![],
+        StatusCode::UNPROCESSABLE_ENTITY,
+      ),
+      ScheduleTooFrequent(..) => (
+        "SCHEDULE_TOO_FREQUENT".to_string(),
+        None,
+        vec![],
+        StatusCode::UNPROCESSABLE_ENTITY,
+      ),
+      InvalidLogLevel(..) => (
+        "INVALID_LOG_LEVEL".to_string(),
+        None,
+        vec![],
+        StatusCode::UNPROCESSABLE_ENTITY,
+      ),
       Anyhow(ref e) => {
         tracing::error!("Generic error: {:?}", e);
 
@@ -89,10 +197,19 @@ impl ApiError {
           StatusCode::INTERNAL_SERVER_ERROR,
         )
       },
-      ProjectAlreadyExist(_) => todo!(),
+      ProjectAlreadyExist(_) => ("PROJECT_ALREADY_EXISTS".to_string(), None, vec![], StatusCode::CONFLICT),
+      InvalidProjectCode(_) => (
+        "INVALID_PROJECT_CODE".to_string(),
+        None,
+        vec![],
+        StatusCode::UNPROCESSABLE_ENTITY,
+      ),
     };
 
-    (status_code, AppResponseError::new(kind, message, code, details))
+    let mut body = AppResponseError::new(kind, message, code, details);
+    body.field_errors = field_errors;
+
+    (status_code, body)
   }
 }
 
@@ -115,6 +232,62 @@ pub struct AppResponseError {
   pub error_message: String,
   pub code: Option<i32>,
   pub details: Vec<(String, Vec<String>)>,
+  /// Every rejected field of an `INVALID_INPUT_ERROR` with a message to show, empty for other errors
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub field_errors: Vec<FieldError>,
+  pub request_id: Option<String>,
+}
+
+/// A field of the request that failed validation
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FieldError {
+  pub field: String,
+  /// Validator that rejected the field, e.g. `length` or `email`
+  pub code: String,
+  /// Readable explanation, e.g. `username must be at least 4 characters`
+  pub message: String,
+  /// Bounds of the validator and the rejected `value`, left out for secret fields such as passwords
+  pub params: Map<String, Value>,
+}
+
+impl FieldError {
+  fn new(field: &str, error: &ValidationError) -> Self {
+    let secret = ["password", "secret", "token"].iter().any(|name| field.contains(name));
+    let params = error
+      .params
+      .iter()
+      .filter(|(name, _)| !(secret && *name == "value"))
+      .map(|(name, value)| (name.to_string(), value.clone()))
+      .collect::<Map<_, _>>();
+
+    let message = match &error.message {
+      Some(message) => message.to_string(),
+      None => describe(field, &error.code, &params),
+    };
+
+    Self {
+      field: field.to_string(),
+      code: error.code.to_string(),
+      message,
+      params,
+    }
+  }
+}
+
+/// Message of the built-in validators, which don't set one themselves
+fn describe(field: &str, code: &str, params: &Map<String, Value>) -> String {
+  match (code, params.get("min"), params.get("max"), params.get("equal")) {
+    ("length", _, _, Some(equal)) => format!("{} must be exactly {} characters", field, equal),
+    ("length", Some(min), Some(max), _) => format!("{} must be between {} and {} characters", field, min, max),
+    ("length", Some(min), None, _) => format!("{} must be at least {} characters", field, min),
+    ("length", None, Some(max), _) => format!("{} must be at most {} characters", field, max),
+    ("range", Some(min), Some(max), _) => format!("{} must be between {} and {}", field, min, max),
+    ("range", Some(min), None, _) => format!("{} must be at least {}", field, min),
+    ("range", None, Some(max), _) => format!("{} must be at most {}", field, max),
+    ("email", ..) => format!("{} must be a valid email address", field),
+    ("url", ..) => format!("{} must be a valid URL", field),
+    _ => format!("{} is invalid ({})", field, code),
+  }
 }
 
 impl AppResponseError {
@@ -129,6 +302,45 @@ impl AppResponseError {
       error_message: message.into(),
       code,
       details,
+      field_errors: vec![],
+      request_id: request_id::current(),
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Validate)]
+  struct SignUp {
+    #[validate(length(min = 4))]
+    username: String,
+    #[validate(length(min = 8))]
+    password: String,
+  }
+
+  #[test]
+  fn test_field_errors_explain_rejection() {
+    let err = SignUp {
+      username: "bob".to_string(),
+      password: "hunter2".to_string(),
+    }
+    .validate()
+    .unwrap_err();
+
+    let (status, body) = ApiError::from(err).response();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let username = body.field_errors.iter().find(|e| e.field == "username").unwrap();
+    assert_eq!(username.code, "length");
+    assert_eq!(username.message, "username must be at least 4 characters");
+    assert_eq!(username.params.get("value"), Some(&Value::from("bob")));
+
+    let password = body.field_errors.iter().find(|e| e.field == "password").unwrap();
+    assert_eq!(password.message, "password must be at least 8 characters");
+    assert!(!password.params.contains_key("value"));
+  }
+}
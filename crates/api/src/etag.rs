@@ -0,0 +1,98 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::{
+  body::{to_bytes, Body},
+  extract::Request,
+  http::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+    HeaderValue, Method, StatusCode,
+  },
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+
+use crate::error::ApiError;
+
+/// Adds a weak `ETag` to successful JSON `GET` responses and answers `304 Not Modified` when the client sends a
+/// matching `If-None-Match`
+///
+/// The tag is a hash of the serialized body, so it changes with anything in the response, including fields like
+/// the task status that don't touch `updated_at`. The query still runs, only the transfer is saved. Streamed
+/// responses such as exports and task outputs are not JSON and are passed through untouched.
+pub async fn etag_layer(req: Request, next: Next) -> Response {
+  if req.method() != Method::GET {
+    return next.run(req).await;
+  }
+
+  let if_none_match = req.headers().get(IF_NONE_MATCH).cloned();
+  let response = next.run(req).await;
+
+  if response.status() != StatusCode::OK || !is_json(&response) {
+    return response;
+  }
+
+  let (mut parts, body) = response.into_parts();
+  let body = match to_bytes(body, usize::MAX).await {
+    Ok(body) => body,
+    Err(e) => return ApiError::Anyhow(anyhow::anyhow!(e)).into_response(),
+  };
+
+  let etag = weak_etag(&body);
+  if let Ok(value) = HeaderValue::from_str(&etag) {
+    parts.headers.insert(ETAG, value);
+  }
+
+  if if_none_match.is_some_and(|value| matches_etag(&value, &etag)) {
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(CONTENT_TYPE);
+    parts.headers.remove(CONTENT_LENGTH);
+
+    return Response::from_parts(parts, Body::empty());
+  }
+
+  Response::from_parts(parts, Body::from(body))
+}
+
+fn is_json(response: &Response) -> bool {
+  response
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .is_some_and(|value| value.starts_with("application/json"))
+}
+
+fn weak_etag(body: &[u8]) -> String {
+  let mut hasher = DefaultHasher::new();
+  body.hash(&mut hasher);
+
+  format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Weak comparison of `If-None-Match` against the tag, `*` or any of a comma separated list matches
+fn matches_etag(if_none_match: &HeaderValue, etag: &str) -> bool {
+  let Ok(value) = if_none_match.to_str() else {
+    return false;
+  };
+  let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+
+  value.trim() == "*" || value.split(',').any(|tag| opaque(tag) == opaque(etag))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_matches_etag() {
+    let etag = weak_etag(b"[]");
+
+    assert!(matches_etag(&HeaderValue::from_str(&etag).unwrap(), &etag));
+    assert!(matches_etag(&HeaderValue::from_static("*"), &etag));
+    assert!(matches_etag(
+      &HeaderValue::from_str(&format!("\"other\", {}", etag.trim_start_matches("W/"))).unwrap(),
+      &etag
+    ));
+    assert!(!matches_etag(&HeaderValue::from_static("W/\"other\""), &etag));
+    assert_ne!(weak_etag(b"[]"), weak_etag(b"[{}]"));
+  }
+}
@@ -0,0 +1,137 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::entities::task::{TaskRow, TaskStatus};
+
+/// Events kept for subscribers that fall behind, one lagging further is disconnected rather than buffered for
+const EVENTS_CAPACITY: usize = 1024;
+
+/// Changes made to tasks and projects, by the API as well as by the executor
+static EVENTS: Lazy<broadcast::Sender<ChangeEvent>> = Lazy::new(|| broadcast::channel(EVENTS_CAPACITY).0);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangedEntity {
+  Task,
+  Project,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+  Created,
+  Updated,
+  Deleted,
+}
+
+/// A task or project that was changed, streamed to `GET /api/ws` subscribers
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ChangeEvent {
+  pub entity: ChangedEntity,
+  pub change: Change,
+  pub id: Uuid,
+  /// Project of the task, or the project itself
+  pub project_id: Uuid,
+  /// Status of the task after the change, unset for projects
+  pub status: Option<TaskStatus>,
+  pub at: DateTime<Utc>,
+}
+
+/// Subscription sent by a client to pick the events it receives, every event matches the empty filter
+#[derive(Deserialize, Debug, Default, Clone, ToSchema)]
+#[serde(default)]
+pub struct EventFilter {
+  /// Only changes to these projects and their tasks
+  pub project_ids: Vec<Uuid>,
+  /// Only changes leaving a task in one of these statuses, project changes never match
+  pub statuses: Vec<TaskStatus>,
+}
+
+impl EventFilter {
+  pub fn matches(&self, event: &ChangeEvent) -> bool {
+    (self.project_ids.is_empty() || self.project_ids.contains(&event.project_id))
+      && (self.statuses.is_empty() || event.status.is_some_and(|status| self.statuses.contains(&status)))
+  }
+}
+
+/// Receives the changes made from now on
+pub fn subscribe() -> broadcast::Receiver<ChangeEvent> {
+  EVENTS.subscribe()
+}
+
+/// Notifies the subscribers of a task change, nothing is kept when there are none
+pub fn task_changed(task: &TaskRow, change: Change) {
+  publish(ChangeEvent {
+    entity: ChangedEntity::Task,
+    change,
+    id: task.id,
+    project_id: task.project_id,
+    status: TaskStatus::decode(&task.status).ok(),
+    at: Utc::now(),
+  });
+}
+
+/// Notifies the subscribers of a project change, nothing is kept when there are none
+pub fn project_changed(id: Uuid, change: Change) {
+  publish(ChangeEvent {
+    entity: ChangedEntity::Project,
+    change,
+    id,
+    project_id: id,
+    status: None,
+    at: Utc::now(),
+  });
+}
+
+fn publish(event: ChangeEvent) {
+  // Sending only fails without subscribers
+  let _ = EVENTS.send(event);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_filter_matches() {
+    let project_id = Uuid::new_v4();
+    let event = |entity, status| ChangeEvent {
+      entity,
+      change: Change::Updated,
+      id: Uuid::new_v4(),
+      project_id,
+      status,
+      at: Utc::now(),
+    };
+    let failed = event(ChangedEntity::Task, Some(TaskStatus::Failed));
+    let project = event(ChangedEntity::Project, None);
+
+    assert!(EventFilter::default().matches(&failed));
+    assert!(EventFilter::default().matches(&project));
+
+    let by_project = EventFilter {
+      project_ids: vec![project_id],
+      ..Default::default()
+    };
+    assert!(by_project.matches(&failed));
+    assert!(by_project.matches(&project));
+
+    let other_project = EventFilter {
+      project_ids: vec![Uuid::new_v4()],
+      ..Default::default()
+    };
+    assert!(!other_project.matches(&failed));
+
+    let by_status = EventFilter {
+      statuses: vec![TaskStatus::Failed, TaskStatus::Dead],
+      ..Default::default()
+    };
+    assert!(by_status.matches(&failed));
+    assert!(!by_status.matches(&event(ChangedEntity::Task, Some(TaskStatus::Finished))));
+    assert!(!by_status.matches(&project));
+  }
+}
@@ -0,0 +1,107 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::entities::task::Task;
+
+/// Metadata a plugin reports about itself when it's loaded
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PluginMetadata {
+  pub name: String,
+  pub version: String,
+  pub author: String,
+  pub description: String,
+}
+
+/// Tasks an executor is running, as recorded in the database by every executor sharing it
+#[derive(Debug, Clone, Serialize, ToSchema, sqlx::FromRow)]
+pub struct ExecutorStats {
+  /// `EXECUTOR_ID` of the executor, generated at startup when unset
+  pub executor_id: String,
+  pub running_tasks: i64,
+  /// Last time the executor claimed a task or refreshed its locks, a minute apart at most while it's alive
+  pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of the last `health` call of a plugin
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PluginHealth {
+  pub healthy: bool,
+  /// What the plugin reported as unavailable, or why the call itself failed
+  pub error: Option<String>,
+  pub checked_at: DateTime<Utc>,
+}
+
+/// A loaded plugin, one per configured file when several versions of a plugin are loaded
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PluginStatus {
+  /// Name tasks refer to the plugin by in config.json
+  pub name: String,
+  pub version: String,
+  pub path: String,
+  /// Whether the plugin exports `health`, plugins without it are never checked
+  pub reports_health: bool,
+  /// Result of the last check, `null` until the plugin is first checked
+  pub health: Option<PluginHealth>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PluginReloadError {
+  #[error("Plugin `{0}` is not configured")]
+  NotFound(String),
+  #[error("Failed to reload plugin `{0}`: {1}")]
+  Failed(String, String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnqueueError {
+  #[error("Task `{0}` is already queued or running")]
+  AlreadyQueued(Uuid),
+  #[error("Worker queue is full, task `{0}` was not queued")]
+  QueueFull(Uuid),
+  #[error("Executor is stopping, task `{0}` was not queued")]
+  Stopped(Uuid),
+}
+
+/// Live view of the task executor, implemented by the executor crate so the API can report on it
+/// without depending on it
+#[async_trait]
+pub trait ExecutorHandle: Send + Sync {
+  /// Identity of this executor among those sharing the database, written to `locked_by` of the tasks it runs
+  fn id(&self) -> &str;
+
+  /// Number of tasks waiting in the queue for a free worker
+  fn queue_depth(&self) -> usize;
+
+  /// Maximum number of tasks the queue can hold
+  fn queue_capacity(&self) -> usize;
+
+  /// Number of plugins that were loaded and initialized
+  fn plugin_count(&self) -> usize;
+
+  /// Every loaded plugin with the result of its last health check
+  fn plugins(&self) -> Vec<PluginStatus>;
+
+  /// Number of workers started by the executor
+  fn worker_count(&self) -> usize;
+
+  /// Number of workers currently processing a task
+  fn busy_workers(&self) -> usize;
+
+  /// Reads a configured plugin from disk again and initializes it with its configured options
+  ///
+  /// Tasks already running finish on the previous instance, tasks started afterwards use the new one.
+  async fn reload_plugin(&self, name: &str) -> Result<PluginMetadata, PluginReloadError>;
+
+  /// Initializes a new instance of a configured plugin with `options` instead of its configured ones, later
+  /// reloads keep using them
+  ///
+  /// The options are checked against the schema the plugin declares, on failure the previous instance is kept.
+  async fn configure_plugin(&self, name: &str, options: Value) -> Result<PluginMetadata, PluginReloadError>;
+
+  /// Hands a task the caller already marked `in_progress` straight to its worker queue, without waiting for room
+  fn enqueue(&self, task: Task) -> Result<(), EnqueueError>;
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use axum::{
+  extract::State,
+  middleware::{from_fn, from_fn_with_state},
+  Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+use utoipa_axum::{
+  router::{OpenApiRouter, UtoipaMethodRouterExt},
+  routes,
+};
+
+use crate::{
+  entities::user::User, error::ApiResult, executor::ExecutorStats, log_level::LogLevelHandle, service::query, AppJson,
+};
+
+use super::auth::{admin_guard, auth_guard};
+
+const ADMIN_TAG: &str = "admin";
+
+pub fn init_admin_routes(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
+  OpenApiRouter::new()
+    .routes(
+      routes!(get_log_level, set_log_level)
+        .layer(from_fn(admin_guard))
+        .layer(from_fn_with_state(state.clone(), auth_guard)),
+    )
+    .routes(
+      routes!(list_executors)
+        .layer(from_fn(admin_guard))
+        .layer(from_fn_with_state(state.clone(), auth_guard)),
+    )
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct LogLevel {
+  /// `EnvFilter` directives like `OCTABOT_LOG_LEVEL`, e.g. `debug` or `info,octabot_executor=trace`
+  level: String,
+}
+
+#[utoipa::path(
+  get,
+  path = "/log-level",
+  tag = ADMIN_TAG,
+  responses(
+    (status = 200, description = "Log filter in use", body = LogLevel),
+    (status = 401, description = "Unauthorized"),
+    (status = 403, description = "Forbidden")
+  )
+)]
+#[instrument(skip(log_level))]
+async fn get_log_level(Extension(log_level): Extension<LogLevelHandle>) -> ApiResult<Json<LogLevel>> {
+  Ok(Json(LogLevel {
+    level: log_level.current()?,
+  }))
+}
+
+#[utoipa::path(
+  post,
+  path = "/log-level",
+  tag = ADMIN_TAG,
+  request_body = LogLevel,
+  responses(
+    (status = 200, description = "Log filter replaced until the next change or restart", body = LogLevel),
+    (status = 401, description = "Unauthorized"),
+    (status = 403, description = "Forbidden"),
+    (status = 422, description = "Invalid filter directives, the filter in use is kept")
+  )
+)]
+#[instrument(skip(log_level, user))]
+async fn set_log_level(
+  Extension(log_level): Extension<LogLevelHandle>,
+  Extension(user): Extension<User>,
+  AppJson(input): AppJson<LogLevel>,
+) -> ApiResult<Json<LogLevel>> {
+  let level = log_level.set(&input.level)?;
+
+  info!("User {} changed the log level to {}", user.username, level);
+
+  Ok(Json(LogLevel { level }))
+}
+
+#[utoipa::path(
+  get,
+  path = "/executors",
+  tag = ADMIN_TAG,
+  responses(
+    (status = 200, description = "Executors running tasks against this database with their task counts", body = [ExecutorStats]),
+    (status = 401, description = "Unauthorized"),
+    (status = 403, description = "Forbidden")
+  )
+)]
+#[instrument(skip(pool))]
+async fn list_executors(State(pool): State<Arc<SqlitePool>>) -> ApiResult<Json<Vec<ExecutorStats>>> {
+  Ok(Json(query::tasks::executor_stats(&pool).await?))
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use axum::{
+  extract::{Query, State},
+  http::HeaderValue,
+  middleware::{from_fn, from_fn_with_state},
+  response::IntoResponse,
+  Json,
+};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use tracing::instrument;
+use utoipa::IntoParams;
+use utoipa_axum::{
+  router::{OpenApiRouter, UtoipaMethodRouterExt},
+  routes,
+};
+
+use crate::{entities::audit::AuditEntry, error::ApiResult, service::query};
+
+use super::{
+  auth::{admin_guard, auth_guard},
+  page_size, PAGE_SIZE_HEADER,
+};
+
+const AUDIT_TAG: &str = "audit";
+const DEFAULT_PAGE: i64 = 1;
+const DEFAULT_ENTRIES_PER_PAGE: i64 = 20;
+
+pub fn init_audit_routes(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
+  OpenApiRouter::new().routes(
+    routes!(list_audit)
+      .layer(from_fn(admin_guard))
+      .layer(from_fn_with_state(state.clone(), auth_guard)),
+  )
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct ListAuditParams {
+  page: Option<i64>,
+  /// At most `API_MAX_PAGE_SIZE`
+  entries_per_page: Option<i64>,
+}
+
+#[utoipa::path(
+  get,
+  path = "",
+  tag = AUDIT_TAG,
+  params(
+    ListAuditParams
+  ),
+  responses(
+    (status = 200, description = "List audit log entries successfully", body = [AuditEntry],
+      headers(("X-Page-Size" = i64, description = "Entries per page after applying `API_MAX_PAGE_SIZE`"))),
+    (status = 304, description = "Entries unchanged since the `ETag` sent in `If-None-Match`"),
+    (status = 401, description = "Unauthorized"),
+    (status = 403, description = "Forbidden")
+  )
+)]
+#[instrument(skip(pool))]
+async fn list_audit(
+  State(pool): State<Arc<SqlitePool>>,
+  Query(params): Query<ListAuditParams>,
+) -> ApiResult<impl IntoResponse> {
+  let page = params.page.unwrap_or(DEFAULT_PAGE);
+  let entries_per_page = page_size(params.entries_per_page, DEFAULT_ENTRIES_PER_PAGE)?;
+
+  let (entries, _num_pages) = query::audit::list(&pool, page, entries_per_page).await?;
+
+  Ok((
+    [(PAGE_SIZE_HEADER.clone(), HeaderValue::from(entries_per_page))],
+    Json(entries),
+  ))
+}
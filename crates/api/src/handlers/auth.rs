@@ -1,73 +1,262 @@
 use std::sync::Arc;
 
 use axum::{
-  extract::{Request, State},
-  http::{header, StatusCode},
+  extract::{Extension, Request, State},
+  http::{header, HeaderName, Method},
   middleware::Next,
   response::IntoResponse,
-  Json,
 };
 use axum_extra::extract::cookie::CookieJar;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use tracing::debug;
 use uuid::Uuid;
 
+use crate::entities::user::User;
 use crate::error::ApiError;
 use crate::service::query;
 
-#[derive(Debug, Serialize, Deserialize)]
+const ADMIN_ROLE: &str = "admin";
+const NOT_LOGGED_IN: &str = "You are not logged in, please provide token";
+pub(crate) const AUTH_COOKIE_NAME: &str = "token";
+pub(crate) const CSRF_COOKIE_NAME: &str = "csrf_token";
+pub static CSRF_HEADER: HeaderName = HeaderName::from_static("x-csrf-token");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
   pub sub: String, // User associated with token
+  pub jti: String, // Unique token id, used for revocation
   pub iat: usize,  // Issued at time of the token
   pub exp: usize,  // Expiry time of the token
 }
 
-#[derive(Debug, Serialize)]
-pub struct ErrorResponse {
-  pub status: &'static str,
-  pub message: String,
+/// Token lifetime, `JWT_MAXAGE` is a duration like `24h` or `7d`, or a plain number of minutes
+pub static JWT_MAXAGE: Lazy<Result<chrono::Duration, String>> = Lazy::new(load_jwt_maxage);
+
+/// Where `auth_guard` accepts the token from, set by `AUTH_TOKEN_SOURCE`
+pub static TOKEN_SOURCE: Lazy<Result<TokenSource, String>> = Lazy::new(load_token_source);
+
+/// Whether the Swagger UI and the OpenAPI document require a login, set by `API_DOCS_AUTH`
+pub static DOCS_AUTH: Lazy<Result<DocsAuth, String>> = Lazy::new(load_docs_auth);
+
+/// Signing keys selected by `JWT_ALGORITHM`:
+///
+/// * `HS256` (default) - shared secret from `JWT_SECRET`
+/// * `RS256` / `EdDSA` - PEM key pair from `JWT_PRIVATE_KEY_FILE` and `JWT_PUBLIC_KEY_FILE`,
+///   so other services can verify tokens with only the public key
+pub static KEYS: Lazy<Result<Keys, String>> = Lazy::new(load_keys);
+
+fn load_jwt_maxage() -> Result<chrono::Duration, String> {
+  let value = std::env::var("JWT_MAXAGE").map_err(|_| "JWT_MAXAGE must be set".to_string())?;
+
+  parse_jwt_maxage(&value).ok_or_else(|| {
+    format!(
+      "JWT_MAXAGE must be a positive duration like `24h` or a number of minutes, got `{}`",
+      value
+    )
+  })
+}
+
+/// Plain numbers keep meaning minutes, as before durations were accepted
+fn parse_jwt_maxage(value: &str) -> Option<chrono::Duration> {
+  let maxage = match value.trim().parse::<i64>() {
+    Ok(minutes) => chrono::Duration::try_minutes(minutes),
+    Err(_) => duration_str::parse(value)
+      .ok()
+      .and_then(|maxage| chrono::Duration::from_std(maxage).ok()),
+  };
+
+  maxage.filter(|maxage| *maxage > chrono::Duration::zero())
+}
+
+/// Accepted token locations
+///
+/// * `both` (default) - the `token` cookie, falling back to the `Authorization: Bearer` header
+/// * `cookie` - only the cookie, unsafe methods must also send the `csrf_token` cookie value in `X-CSRF-Token`
+/// * `header` - only the `Authorization` header, login doesn't set cookies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource {
+  Both,
+  Cookie,
+  Header,
+}
+
+impl TokenSource {
+  pub fn accepts_cookie(self) -> bool {
+    self != TokenSource::Header
+  }
+}
+
+fn load_token_source() -> Result<TokenSource, String> {
+  match std::env::var("AUTH_TOKEN_SOURCE").as_deref() {
+    Err(_) | Ok("both") => Ok(TokenSource::Both),
+    Ok("cookie") => Ok(TokenSource::Cookie),
+    Ok("header") => Ok(TokenSource::Header),
+    Ok(other) => Err(format!(
+      "Unsupported AUTH_TOKEN_SOURCE `{}`, expected one of both, cookie, header",
+      other
+    )),
+  }
 }
 
-pub static JWT_MAXAGE: Lazy<i64> = Lazy::new(|| {
-  std::env::var("JWT_MAXAGE")
-    .expect("JWT_MAXAGE must be set")
-    .parse::<i64>()
-    .unwrap()
-});
+/// Protection of `/swagger-ui` and `/api-docs/openapi.json`
+///
+/// * `none` (default) - served to anyone, handy in development
+/// * `jwt` - behind `auth_guard` like the api itself, the Swagger UI page fetches the document with the cookie
+///   so it needs the `both` or `cookie` token source in a browser
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocsAuth {
+  None,
+  Jwt,
+}
 
-pub static KEYS: Lazy<Keys> = Lazy::new(|| {
-  let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-  Keys::new(secret.as_bytes())
-});
+fn load_docs_auth() -> Result<DocsAuth, String> {
+  match std::env::var("API_DOCS_AUTH").as_deref() {
+    Err(_) | Ok("none") => Ok(DocsAuth::None),
+    Ok("jwt") => Ok(DocsAuth::Jwt),
+    Ok(other) => Err(format!(
+      "Unsupported API_DOCS_AUTH `{}`, expected one of none, jwt",
+      other
+    )),
+  }
+}
+
+fn load_keys() -> Result<Keys, String> {
+  let algorithm = std::env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string());
+
+  match algorithm.as_str() {
+    "HS256" => {
+      let secret = std::env::var("JWT_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| "JWT_SECRET must be set".to_string())?;
+      Ok(Keys::from_secret(secret.as_bytes()))
+    },
+    "RS256" => Keys::from_pem_files(Algorithm::RS256),
+    "EdDSA" => Keys::from_pem_files(Algorithm::EdDSA),
+    other => Err(format!(
+      "Unsupported JWT_ALGORITHM `{}`, expected one of HS256, RS256, EdDSA",
+      other
+    )),
+  }
+}
+
+/// Checks that `JWT_MAXAGE` from the environment is valid
+pub fn validate_jwt_maxage() -> anyhow::Result<()> {
+  JWT_MAXAGE
+    .as_ref()
+    .map(|_| ())
+    .map_err(|err| anyhow::anyhow!(err.clone()))
+}
+
+/// Checks that the signing keys selected by `JWT_ALGORITHM` can be loaded
+pub fn validate_jwt_keys() -> anyhow::Result<()> {
+  KEYS.as_ref().map(|_| ()).map_err(|err| anyhow::anyhow!(err.clone()))
+}
+
+/// Checks that `AUTH_TOKEN_SOURCE` from the environment is valid
+pub fn validate_token_source() -> anyhow::Result<()> {
+  TOKEN_SOURCE
+    .as_ref()
+    .map(|_| ())
+    .map_err(|err| anyhow::anyhow!(err.clone()))
+}
+
+pub fn token_source() -> Result<TokenSource, ApiError> {
+  TOKEN_SOURCE
+    .as_ref()
+    .copied()
+    .map_err(|err| ApiError::Anyhow(anyhow::anyhow!(err.clone())))
+}
+
+/// Checks that `API_DOCS_AUTH` from the environment is valid
+pub fn validate_docs_auth() -> anyhow::Result<()> {
+  DOCS_AUTH
+    .as_ref()
+    .map(|_| ())
+    .map_err(|err| anyhow::anyhow!(err.clone()))
+}
+
+pub fn docs_auth() -> Result<DocsAuth, ApiError> {
+  DOCS_AUTH
+    .as_ref()
+    .copied()
+    .map_err(|err| ApiError::Anyhow(anyhow::anyhow!(err.clone())))
+}
+
+fn keys() -> Result<&'static Keys, ApiError> {
+  KEYS
+    .as_ref()
+    .map_err(|err| ApiError::Anyhow(anyhow::anyhow!(err.clone())))
+}
 
 pub struct Keys {
+  pub algorithm: Algorithm,
   pub encoding: EncodingKey,
   pub decoding: DecodingKey,
 }
 
 impl Keys {
-  fn new(secret: &[u8]) -> Self {
+  fn from_secret(secret: &[u8]) -> Self {
     Self {
+      algorithm: Algorithm::HS256,
       encoding: EncodingKey::from_secret(secret),
       decoding: DecodingKey::from_secret(secret),
     }
   }
+
+  fn from_pem_files(algorithm: Algorithm) -> Result<Self, String> {
+    let private_key = read_key_file("JWT_PRIVATE_KEY_FILE")?;
+    let public_key = read_key_file("JWT_PUBLIC_KEY_FILE")?;
+
+    let (encoding, decoding) = match algorithm {
+      Algorithm::RS256 => (
+        EncodingKey::from_rsa_pem(&private_key)
+          .map_err(|_| "JWT_PRIVATE_KEY_FILE must contain an RSA private key".to_string())?,
+        DecodingKey::from_rsa_pem(&public_key)
+          .map_err(|_| "JWT_PUBLIC_KEY_FILE must contain an RSA public key".to_string())?,
+      ),
+      Algorithm::EdDSA => (
+        EncodingKey::from_ed_pem(&private_key)
+          .map_err(|_| "JWT_PRIVATE_KEY_FILE must contain an Ed25519 private key".to_string())?,
+        DecodingKey::from_ed_pem(&public_key)
+          .map_err(|_| "JWT_PUBLIC_KEY_FILE must contain an Ed25519 public key".to_string())?,
+      ),
+      _ => unreachable!("Only asymmetric algorithms are loaded from PEM files"),
+    };
+
+    Ok(Self {
+      algorithm,
+      encoding,
+      decoding,
+    })
+  }
+}
+
+fn read_key_file(var: &str) -> Result<Vec<u8>, String> {
+  let path = std::env::var(var).map_err(|_| format!("{} must be set", var))?;
+  std::fs::read(&path).map_err(|err| format!("Failed to read {} `{}`: {}", var, path, err))
 }
 
 pub fn encode_jwt(user_id: Uuid) -> Result<String, ApiError> {
   let now = chrono::Utc::now();
   let iat = now.timestamp() as usize;
-  let exp = (now + chrono::Duration::minutes(*JWT_MAXAGE)).timestamp() as usize;
+  let maxage = JWT_MAXAGE
+    .as_ref()
+    .map_err(|err| ApiError::Anyhow(anyhow::anyhow!(err.clone())))?;
+  let exp = (now + *maxage).timestamp() as usize;
   let claims: Claims = Claims {
     sub: user_id.to_string(),
+    jti: Uuid::new_v4().to_string(),
     exp,
     iat,
   };
 
-  encode(&Header::default(), &claims, &KEYS.encoding)
+  let keys = keys()?;
+
+  encode(&Header::new(keys.algorithm), &claims, &keys.encoding)
     .map_err(|_| ApiError::Anyhow(anyhow::anyhow!("Can't encode token")))
 }
 
@@ -76,59 +265,106 @@ pub async fn auth_guard(
   State(pool): State<Arc<SqlitePool>>,
   mut req: Request,
   next: Next,
-) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-  let token = cookie_jar
-    .get("token")
-    .map(|cookie| cookie.value().to_string())
-    .or_else(|| {
-      req
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|auth_header| auth_header.to_str().ok())
-        .and_then(|auth_value| {
-          auth_value
-            .strip_prefix("Bearer ")
-            .map(|auth_value| auth_value.to_owned())
-        })
-    });
-
-  let token = token.ok_or_else(|| {
-    let json_error = ErrorResponse {
-      status: "fail",
-      message: "You are not logged in, please provide token".to_string(),
-    };
-    (StatusCode::UNAUTHORIZED, Json(json_error))
-  })?;
-
-  let claims = decode::<Claims>(&token, &KEYS.decoding, &Validation::default())
-    .map_err(|_| {
-      let json_error = ErrorResponse {
-        status: "fail",
-        message: "Invalid token".to_string(),
-      };
-      (StatusCode::UNAUTHORIZED, Json(json_error))
-    })?
+) -> Result<impl IntoResponse, ApiError> {
+  let token = match token_source()? {
+    TokenSource::Both => cookie_token(&cookie_jar).or_else(|| bearer_token(&req)),
+    TokenSource::Cookie => {
+      let token = cookie_token(&cookie_jar);
+      if token.is_some() {
+        check_csrf(&cookie_jar, &req)?;
+      }
+      token
+    },
+    TokenSource::Header => bearer_token(&req),
+  };
+
+  let token = token.ok_or_else(|| ApiError::Unauthorized(NOT_LOGGED_IN.to_string()))?;
+
+  let keys = keys()?;
+
+  let claims = decode::<Claims>(&token, &keys.decoding, &Validation::new(keys.algorithm))
+    .map_err(|_| ApiError::Unauthorized("Invalid token".to_string()))?
     .claims;
 
-  let user = query::users::find_by_id(&pool.clone(), Uuid::parse_str(&claims.sub).unwrap())
+  let revoked = query::tokens::is_revoked(&pool, &claims.jti)
+    .await
+    .map_err(|_| ApiError::Unauthorized(NOT_LOGGED_IN.to_string()))?;
+
+  if revoked {
+    return Err(ApiError::Unauthorized("Token has been revoked".to_string()));
+  }
+
+  let user_id = Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("Invalid token".to_string()))?;
+  let user = query::users::find_by_id(&pool, user_id)
     .await
-    .map_err(|_| {
-      let json_error = ErrorResponse {
-        status: "fail",
-        message: "You are not logged in, please provide token".to_string(),
-      };
-      (StatusCode::UNAUTHORIZED, Json(json_error))
-    })?
-    .ok_or({
-      let json_error = ErrorResponse {
-        status: "fail",
-        message: "The user belonging to this token no longer exists".to_string(),
-      };
-      (StatusCode::UNAUTHORIZED, Json(json_error))
-    })?;
+    .map_err(|_| ApiError::Unauthorized(NOT_LOGGED_IN.to_string()))?
+    .ok_or_else(|| ApiError::Unauthorized("The user belonging to this token no longer exists".to_string()))?;
 
   debug!("fetch user model from db {:?}", user);
 
   req.extensions_mut().insert(user);
+  req.extensions_mut().insert(claims);
   Ok(next.run(req).await)
 }
+
+fn cookie_token(cookie_jar: &CookieJar) -> Option<String> {
+  cookie_jar
+    .get(AUTH_COOKIE_NAME)
+    .map(|cookie| cookie.value().to_string())
+}
+
+fn bearer_token(req: &Request) -> Option<String> {
+  req
+    .headers()
+    .get(header::AUTHORIZATION)
+    .and_then(|auth_header| auth_header.to_str().ok())
+    .and_then(|auth_value| {
+      auth_value
+        .strip_prefix("Bearer ")
+        .map(|auth_value| auth_value.to_owned())
+    })
+}
+
+/// Double-submit check, the `X-CSRF-Token` header of an unsafe request must repeat the `csrf_token` cookie,
+/// which a cross-site form can send but not read
+fn check_csrf(cookie_jar: &CookieJar, req: &Request) -> Result<(), ApiError> {
+  if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+    return Ok(());
+  }
+
+  let cookie = cookie_jar.get(CSRF_COOKIE_NAME).map(|cookie| cookie.value());
+  let header = req.headers().get(&CSRF_HEADER).and_then(|value| value.to_str().ok());
+
+  match (cookie, header) {
+    (Some(cookie), Some(header)) if !cookie.is_empty() && cookie == header => Ok(()),
+    _ => Err(ApiError::InvalidCsrfToken()),
+  }
+}
+
+/// Rejects requests from non-admin users, must be layered inside `auth_guard`
+pub async fn admin_guard(
+  Extension(user): Extension<User>,
+  req: Request,
+  next: Next,
+) -> Result<impl IntoResponse, ApiError> {
+  if user.role != ADMIN_ROLE {
+    return Err(ApiError::Forbidden());
+  }
+
+  Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_jwt_maxage() {
+    assert_eq!(parse_jwt_maxage("60"), Some(chrono::Duration::minutes(60)));
+    assert_eq!(parse_jwt_maxage("24h"), Some(chrono::Duration::hours(24)));
+    assert_eq!(parse_jwt_maxage("7d"), Some(chrono::Duration::days(7)));
+    assert_eq!(parse_jwt_maxage("0"), None);
+    assert_eq!(parse_jwt_maxage("-5"), None);
+    assert_eq!(parse_jwt_maxage("soon"), None);
+  }
+}
@@ -0,0 +1,219 @@
+use std::{borrow::Cow, convert::Infallible};
+
+use axum::{
+  body::Body,
+  http::{
+    header::{ACCEPT, CONTENT_TYPE},
+    HeaderMap, HeaderValue,
+  },
+  response::{IntoResponse, Response},
+};
+use futures::{stream, StreamExt};
+
+use crate::entities::{project::Project, task::Task};
+
+const CSV_MEDIA_TYPE: &str = "text/csv";
+const JSON_MEDIA_TYPE: &str = "application/json";
+const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// An item of a list endpoint as a CSV row, nested entities are flattened into prefixed columns
+pub(crate) trait CsvRecord {
+  const HEADER: &'static [&'static str];
+
+  fn fields(&self) -> Vec<String>;
+}
+
+/// Whether the client prefers CSV, i.e. `Accept` gives `text/csv` a higher quality than `application/json`, or
+/// lists it first when they're equal. JSON stays the default when the header is missing or accepts neither
+pub(crate) fn accepts_csv(headers: &HeaderMap) -> bool {
+  let mut csv = None;
+  let mut json = None;
+  let ranges = headers
+    .get_all(ACCEPT)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','));
+
+  for (position, range) in ranges.enumerate() {
+    let mut params = range.split(';');
+    let media_type = params.next().unwrap_or_default().trim();
+    let preference = if media_type.eq_ignore_ascii_case(CSV_MEDIA_TYPE) {
+      &mut csv
+    } else if media_type.eq_ignore_ascii_case(JSON_MEDIA_TYPE) {
+      &mut json
+    } else {
+      continue;
+    };
+    preference.get_or_insert((quality(params), position));
+  }
+
+  match (csv, json) {
+    (Some((csv, csv_position)), Some((json, json_position))) => {
+      csv > json || (csv == json && csv > 0.0 && csv_position < json_position)
+    },
+    (Some((csv, _)), None) => csv > 0.0,
+    _ => false,
+  }
+}
+
+/// The `q` parameter of a media range, 1 when it's missing and 0 when it's not a number
+fn quality<'a>(params: impl Iterator<Item = &'a str>) -> f32 {
+  params
+    .filter_map(|param| param.split_once('='))
+    .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+    .map_or(1.0, |(_, value)| {
+      value.trim().parse::<f32>().unwrap_or(0.0).clamp(0.0, 1.0)
+    })
+}
+
+/// Responds with a header line followed by one line per item, each row is encoded as the body is streamed
+pub(crate) fn csv_response<T: CsvRecord + Send + 'static>(items: Vec<T>) -> Response {
+  let header = stream::once(async { encode_line(T::HEADER.iter().copied()) });
+  let rows = stream::iter(items).map(|item| encode_line(item.fields().iter().map(String::as_str)));
+  let body = Body::from_stream(header.chain(rows).map(Ok::<_, Infallible>));
+
+  ([(CONTENT_TYPE, HeaderValue::from_static(CSV_CONTENT_TYPE))], body).into_response()
+}
+
+/// Joins the fields into an RFC 4180 line
+fn encode_line<'a>(fields: impl Iterator<Item = &'a str>) -> String {
+  let mut line = fields.map(escape).collect::<Vec<_>>().join(",");
+  line.push_str("\r\n");
+  line
+}
+
+/// Quotes the field when needed, and prefixes it with `'` when it starts like a formula so spreadsheets opening the
+/// file show it as text instead of evaluating it
+fn escape(field: &str) -> Cow<'_, str> {
+  let field = if field.starts_with(['=', '+', '-', '@']) {
+    Cow::Owned(format!("'{field}"))
+  } else {
+    Cow::Borrowed(field)
+  };
+
+  if field.contains([',', '"', '\r', '\n']) {
+    Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+  } else {
+    field
+  }
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+  value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+impl CsvRecord for Task {
+  const HEADER: &'static [&'static str] = &[
+    "id",
+    "name",
+    "type",
+    "plugin_version",
+    "status",
+    "enabled",
+    "schedule",
+    "start_at",
+    "schedule_anchor",
+    "end_at",
+    "misfire_policy",
+    "retries",
+    "external_id",
+    "external_modified_at",
+    "options",
+    "project_id",
+    "project_code",
+    "project_name",
+    "created_by",
+    "created_at",
+    "updated_at",
+  ];
+
+  fn fields(&self) -> Vec<String> {
+    vec![
+      self.id.to_string(),
+      self.name.clone(),
+      self.r#type.clone(),
+      optional(self.plugin_version.as_ref()),
+      self.status.to_string(),
+      self.enabled.to_string(),
+      optional(self.schedule.as_ref()),
+      self.start_at.to_string(),
+      optional(self.schedule_anchor),
+      optional(self.end_at),
+      self.misfire_policy.to_string(),
+      self.retries.to_string(),
+      optional(self.external_id.as_ref()),
+      optional(self.external_modified_at.map(|at| at.to_rfc3339())),
+      self.options.to_string(),
+      self.project.id.to_string(),
+      self.project.code.clone(),
+      self.project.name.clone(),
+      optional(self.created_by),
+      self.created_at.to_rfc3339(),
+      self.updated_at.to_rfc3339(),
+    ]
+  }
+}
+
+impl CsvRecord for Project {
+  const HEADER: &'static [&'static str] = &[
+    "id",
+    "name",
+    "code",
+    "options",
+    "owner_id",
+    "owner_username",
+    "created_by",
+    "created_at",
+    "updated_at",
+  ];
+
+  fn fields(&self) -> Vec<String> {
+    vec![
+      self.id.to_string(),
+      self.name.clone(),
+      self.code.clone(),
+      self.options.to_string(),
+      self.owner.id.to_string(),
+      self.owner.username.clone(),
+      optional(self.created_by),
+      self.created_at.to_rfc3339(),
+      self.updated_at.to_rfc3339(),
+    ]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_accepts_csv() {
+    let accept = |value: &'static str| {
+      let mut headers = HeaderMap::new();
+      headers.insert(ACCEPT, HeaderValue::from_static(value));
+      accepts_csv(&headers)
+    };
+
+    assert!(!accepts_csv(&HeaderMap::new()));
+    assert!(!accept("*/*"));
+    assert!(accept("text/csv"));
+    assert!(accept("text/csv;q=0.9, application/json;q=0.8"));
+    assert!(!accept("application/json, text/csv"));
+    assert!(accept("application/json;q=0.5, text/csv"));
+    assert!(!accept("text/csv;q=0.5, application/json"));
+    assert!(!accept("text/csv;q=0"));
+    assert!(!accept("text/csv;q=0, application/json;q=0"));
+  }
+
+  #[test]
+  fn test_encode_line() {
+    assert_eq!(
+      encode_line(["plain", "with,comma", "say \"hi\"", "two\nlines"].into_iter()),
+      "plain,\"with,comma\",\"say \"\"hi\"\"\",\"two\nlines\"\r\n"
+    );
+    assert_eq!(
+      encode_line(["=1+1", "+1", "-1", "@SUM(A1)", "=HYPERLINK(\"x\",\"y\")", "a=b"].into_iter()),
+      "'=1+1,'+1,'-1,'@SUM(A1),\"'=HYPERLINK(\"\"x\"\",\"\"y\"\")\",a=b\r\n"
+    );
+  }
+}
@@ -1,4 +1,214 @@
+use std::env;
+
+use anyhow::anyhow;
+use axum::{
+  http::{
+    header::{ACCEPT, IF_UNMODIFIED_SINCE, VARY},
+    HeaderMap, HeaderName, HeaderValue,
+  },
+  response::{IntoResponse, Response},
+  Json,
+};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::{
+  error::{ApiError, ApiResult},
+  service::query::TimeRange,
+};
+
+use self::csv::{accepts_csv, csv_response, CsvRecord};
+
+pub mod admin;
+pub mod audit;
 pub mod auth;
+mod csv;
+pub mod plugins;
 pub mod projects;
+pub mod search;
 pub mod tasks;
+pub mod templates;
 pub mod users;
+pub mod ws;
+
+const DEFAULT_MAX_PAGE_SIZE: i64 = 100;
+
+/// Set on a full page of a list endpoint to the id to pass as `after` for the next page
+pub static NEXT_CURSOR_HEADER: HeaderName = HeaderName::from_static("x-next-cursor");
+/// Items per page a list endpoint answering with a bare array used, after applying the default and the maximum
+pub static PAGE_SIZE_HEADER: HeaderName = HeaderName::from_static("x-page-size");
+
+/// Page sizes of the list endpoints, read from the environment:
+///
+/// * `API_DEFAULT_PAGE_SIZE` - items per page when the client doesn't ask for a size, each endpoint keeps its
+///   own default when unset
+/// * `API_MAX_PAGE_SIZE` - most items per page whatever the client asks for (default 100)
+static PAGE_SIZES: Lazy<Result<PageSizes, String>> = Lazy::new(load_page_sizes);
+
+struct PageSizes {
+  default_size: Option<i64>,
+  max_size: i64,
+}
+
+/// Checks that `API_DEFAULT_PAGE_SIZE` and `API_MAX_PAGE_SIZE` from the environment are valid
+pub fn validate_page_sizes() -> anyhow::Result<()> {
+  PAGE_SIZES.as_ref().map(|_| ()).map_err(|err| anyhow!(err.clone()))
+}
+
+fn load_page_sizes() -> Result<PageSizes, String> {
+  let parse = |name: &str| match env::var(name) {
+    Ok(value) => value
+      .parse::<i64>()
+      .ok()
+      .filter(|size| *size > 0)
+      .map(Some)
+      .ok_or_else(|| format!("{} must be a positive integer, got `{}`", name, value)),
+    Err(_) => Ok(None),
+  };
+
+  let default_size = parse("API_DEFAULT_PAGE_SIZE")?;
+  let max_size = parse("API_MAX_PAGE_SIZE")?.unwrap_or(DEFAULT_MAX_PAGE_SIZE);
+
+  if let Some(default_size) = default_size.filter(|size| *size > max_size) {
+    return Err(format!(
+      "API_DEFAULT_PAGE_SIZE of {} is larger than API_MAX_PAGE_SIZE of {}",
+      default_size, max_size
+    ));
+  }
+
+  Ok(PageSizes { default_size, max_size })
+}
+
+/// Items per page of a list endpoint, the size asked for by the client or the default, at most
+/// `API_MAX_PAGE_SIZE`
+pub(crate) fn page_size(requested: Option<i64>, endpoint_default: i64) -> ApiResult<i64> {
+  let sizes = PAGE_SIZES
+    .as_ref()
+    .map_err(|err| ApiError::Anyhow(anyhow!(err.clone())))?;
+
+  Ok(
+    requested
+      .or(sizes.default_size)
+      .unwrap_or(endpoint_default)
+      .clamp(1, sizes.max_size),
+  )
+}
+
+/// Deserializes a field that can be omitted or explicitly `null`, for partial updates: a missing field
+/// stays `None` through `#[serde(default)]` while `null` becomes `Some(None)`
+pub(crate) fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+  T: Deserialize<'de>,
+  D: Deserializer<'de>,
+{
+  Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Reads the `If-Unmodified-Since` precondition of an update, either an HTTP date or the RFC 3339
+/// `updated_at` of the entity as returned by the API
+pub(crate) fn unmodified_since(headers: &HeaderMap) -> ApiResult<Option<DateTime<Utc>>> {
+  let Some(value) = headers.get(IF_UNMODIFIED_SINCE) else {
+    return Ok(None);
+  };
+
+  let invalid = || ApiError::InvalidHeader(IF_UNMODIFIED_SINCE.to_string());
+  let value = value.to_str().map_err(|_| invalid())?;
+
+  DateTime::parse_from_rfc3339(value)
+    .or_else(|_| DateTime::parse_from_rfc2822(value))
+    .map(|date| Some(date.to_utc()))
+    .map_err(|_| invalid())
+}
+
+/// Creation and update time bounds of a list endpoint, RFC 3339 timestamps with exclusive bounds
+#[derive(Debug, Deserialize, IntoParams)]
+pub(crate) struct TimeRangeParams {
+  /// Only return items created after this time
+  created_after: Option<String>,
+  /// Only return items created before this time
+  created_before: Option<String>,
+  /// Only return items last updated after this time
+  updated_after: Option<String>,
+  /// Only return items last updated before this time
+  updated_before: Option<String>,
+}
+
+impl TimeRangeParams {
+  pub(crate) fn parse(&self) -> ApiResult<TimeRange> {
+    Ok(TimeRange {
+      created_after: parse_timestamp("created_after", &self.created_after)?,
+      created_before: parse_timestamp("created_before", &self.created_before)?,
+      updated_after: parse_timestamp("updated_after", &self.updated_after)?,
+      updated_before: parse_timestamp("updated_before", &self.updated_before)?,
+    })
+  }
+}
+
+fn parse_timestamp(name: &str, value: &Option<String>) -> ApiResult<Option<DateTime<Utc>>> {
+  value
+    .as_deref()
+    .map(|value| {
+      DateTime::parse_from_rfc3339(value)
+        .map(|date| date.to_utc())
+        .map_err(|_| ApiError::InvalidTimestamp(name.to_string(), value.to_string()))
+    })
+    .transpose()
+}
+
+/// Responds with a page of items as JSON or as CSV when the client asks for it, adding `X-Page-Size` and
+/// `X-Next-Cursor` when the page is full and more items may follow
+pub(crate) fn cursor_page<T>(headers: &HeaderMap, items: Vec<T>, limit: i64, id: impl Fn(&T) -> Uuid) -> Response
+where
+  T: Serialize + CsvRecord + Send + 'static,
+{
+  let next_cursor = items.last().filter(|_| items.len() as i64 >= limit).map(id);
+  let mut response = if accepts_csv(headers) {
+    csv_response(items)
+  } else {
+    Json(items).into_response()
+  };
+  response
+    .headers_mut()
+    .insert(VARY, HeaderValue::from_static(ACCEPT.as_str()));
+  response
+    .headers_mut()
+    .insert(PAGE_SIZE_HEADER.clone(), HeaderValue::from(limit));
+
+  if let Some(next_cursor) = next_cursor {
+    let value = HeaderValue::from_str(&next_cursor.to_string()).expect("uuid is a valid header value");
+    response.headers_mut().insert(NEXT_CURSOR_HEADER.clone(), value);
+  }
+
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_time_range_params_parse() {
+    let params = TimeRangeParams {
+      created_after: Some("2026-01-01T00:00:00+02:00".to_string()),
+      created_before: None,
+      updated_after: None,
+      updated_before: Some("yesterday".to_string()),
+    };
+    assert!(matches!(
+      params.parse(),
+      Err(ApiError::InvalidTimestamp(name, value)) if name == "updated_before" && value == "yesterday"
+    ));
+
+    let range = TimeRangeParams {
+      updated_before: None,
+      ..params
+    }
+    .parse()
+    .unwrap();
+    assert_eq!(range.created_after.unwrap().to_rfc3339(), "2025-12-31T22:00:00+00:00");
+    assert!(range.created_before.is_none());
+  }
+}
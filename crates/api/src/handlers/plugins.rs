@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use axum::{
+  extract::{Path, State},
+  middleware::{from_fn, from_fn_with_state},
+  Extension, Json,
+};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use tracing::{info, instrument};
+use utoipa_axum::{
+  router::{OpenApiRouter, UtoipaMethodRouterExt},
+  routes,
+};
+
+use crate::{
+  entities::user::User,
+  error::ApiResult,
+  executor::{ExecutorHandle, PluginMetadata, PluginStatus},
+  service::mutation,
+  AppJson,
+};
+
+use super::auth::{admin_guard, auth_guard};
+
+const PLUGINS_TAG: &str = "plugins";
+
+pub fn init_plugins_routes(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
+  OpenApiRouter::new()
+    .routes(routes!(list_plugins).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(
+      routes!(reload_plugin)
+        .layer(from_fn(admin_guard))
+        .layer(from_fn_with_state(state.clone(), auth_guard)),
+    )
+    .routes(
+      routes!(configure_plugin)
+        .layer(from_fn(admin_guard))
+        .layer(from_fn_with_state(state.clone(), auth_guard)),
+    )
+}
+
+#[utoipa::path(
+  get,
+  path = "",
+  tag = PLUGINS_TAG,
+  responses(
+    (status = 200, description = "Loaded plugins with their last health check", body = [PluginStatus]),
+    (status = 401, description = "Unauthorized")
+  )
+)]
+#[instrument(skip(executor))]
+async fn list_plugins(Extension(executor): Extension<Arc<dyn ExecutorHandle>>) -> Json<Vec<PluginStatus>> {
+  Json(executor.plugins())
+}
+
+#[utoipa::path(
+  post,
+  path = "/{name}/reload",
+  tag = PLUGINS_TAG,
+  responses(
+    (status = 200, description = "Plugin reloaded, returns the metadata of the new instance", body = PluginMetadata),
+    (status = 401, description = "Unauthorized"),
+    (status = 403, description = "Forbidden"),
+    (status = 404, description = "Plugin is not configured"),
+    (status = 422, description = "Plugin failed to load or initialize, the previous instance is kept")
+  ),
+  params(
+    ("name" = String, Path, description = "Plugin name from config.json")
+  )
+)]
+#[instrument(skip(executor, user))]
+async fn reload_plugin(
+  Extension(executor): Extension<Arc<dyn ExecutorHandle>>,
+  Extension(user): Extension<User>,
+  Path(name): Path<String>,
+) -> ApiResult<Json<PluginMetadata>> {
+  let metadata = executor.reload_plugin(&name).await?;
+
+  info!(
+    "User {} reloaded plugin {} (version {})",
+    user.username, name, metadata.version
+  );
+
+  Ok(Json(metadata))
+}
+
+#[utoipa::path(
+  put,
+  path = "/{name}/config",
+  tag = PLUGINS_TAG,
+  request_body(content = Object, description = "Options passed to the plugin's `init` in place of the configured ones"),
+  responses(
+    (status = 200, description = "Plugin initialized with the new options, kept across restarts", body = PluginMetadata),
+    (status = 401, description = "Unauthorized"),
+    (status = 403, description = "Forbidden"),
+    (status = 404, description = "Plugin is not configured"),
+    (status = 422, description = "Options don't match the plugin schema or its `init` failed, the previous instance is kept")
+  ),
+  params(
+    ("name" = String, Path, description = "Plugin name from config.json")
+  )
+)]
+#[instrument(skip(pool, executor, user, options))]
+async fn configure_plugin(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(executor): Extension<Arc<dyn ExecutorHandle>>,
+  Extension(user): Extension<User>,
+  Path(name): Path<String>,
+  AppJson(options): AppJson<Value>,
+) -> ApiResult<Json<PluginMetadata>> {
+  let metadata = executor.configure_plugin(&name, options.clone()).await?;
+  mutation::plugins::save_options(&pool, Some(user.id), &name, &options).await?;
+
+  info!(
+    "User {} reconfigured plugin {} (version {})",
+    user.username, name, metadata.version
+  );
+
+  Ok(Json(metadata))
+}
@@ -2,13 +2,16 @@ use std::sync::Arc;
 
 use axum::{
   extract::{Path, Query, State},
+  http::HeaderMap,
   middleware::from_fn_with_state,
-  Json,
+  response::IntoResponse,
+  Extension, Json,
 };
+use chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::SqlitePool;
-use tracing::{debug, instrument};
+use tracing::{debug, error, instrument};
 use utoipa::IntoParams;
 use utoipa_axum::{
   router::{OpenApiRouter, UtoipaMethodRouterExt},
@@ -18,29 +21,46 @@ use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
-  entities::project::Project,
+  entities::{
+    project::Project,
+    task::{MisfirePolicy, Task},
+    user::User,
+  },
   error::ApiResult,
-  service::{mutation, query},
+  service::{logs, mutation, outputs, query},
   AppJson,
 };
 
-use super::auth::auth_guard;
+use super::{
+  auth::auth_guard, cursor_page, page_size, tasks::calculate_next_execution_time, unmodified_since, TimeRangeParams,
+};
 
 const PROJECTS_TAG: &str = "projects";
 const DEFAULT_PAGE: i64 = 1;
 const DEFAULT_PROJECTS_PER_PAGE: i64 = 5;
 
 pub fn init_projects_routes(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
-  OpenApiRouter::new().routes(
-    routes!(list_projects, create_project, update_project, delete_project)
+  OpenApiRouter::new()
+    .routes(
+      routes!(
+        list_projects,
+        create_project,
+        update_project,
+        patch_project,
+        delete_project
+      )
       .layer(from_fn_with_state(state.clone(), auth_guard)),
-  )
+    )
+    .routes(routes!(instantiate_template).layer(from_fn_with_state(state.clone(), auth_guard)))
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
 struct ListProjectsParams {
   page: Option<i64>,
+  /// At most `API_MAX_PAGE_SIZE`
   projects_per_page: Option<i64>,
+  /// `X-Next-Cursor` of the previous page, pages by id instead of offset and ignores `page`
+  after: Option<Uuid>,
 }
 
 #[utoipa::path(
@@ -48,30 +68,44 @@ struct ListProjectsParams {
   path = "",
   tag = PROJECTS_TAG,
   params(
-    ListProjectsParams
+    ListProjectsParams,
+    TimeRangeParams
   ),
   responses(
-    (status = 200, description = "List all projects successfully", body = [Project])
+    (status = 200, description = "List all projects successfully, as CSV with `Accept: text/csv`",
+      content(([Project] = "application/json"), (String = "text/csv")),
+      headers(
+        ("X-Page-Size" = i64, description = "Items per page after applying the default and `API_MAX_PAGE_SIZE`"),
+        ("X-Next-Cursor" = Uuid, description = "Set on a full page, pass as `after` for the next one")
+      )),
+    (status = 304, description = "Projects unchanged since the `ETag` sent in `If-None-Match`"),
+    (status = 422, description = "A time range bound isn't an RFC 3339 timestamp"),
   )
 )]
 #[instrument(skip(pool))]
 async fn list_projects(
   State(pool): State<Arc<SqlitePool>>,
+  headers: HeaderMap,
   Query(params): Query<ListProjectsParams>,
-) -> ApiResult<Json<Vec<Project>>> {
+  Query(range): Query<TimeRangeParams>,
+) -> ApiResult<impl IntoResponse> {
+  let range = range.parse()?;
   let page = params.page.unwrap_or(DEFAULT_PAGE);
-  let projects_per_page = params.projects_per_page.unwrap_or(DEFAULT_PROJECTS_PER_PAGE);
+  let projects_per_page = page_size(params.projects_per_page, DEFAULT_PROJECTS_PER_PAGE)?;
 
-  let (projects, _num_pages) = query::projects::list(&pool, page, projects_per_page).await?;
+  let projects = match params.after {
+    Some(after) => query::projects::list_after(&pool, &range, after, projects_per_page).await?,
+    None => query::projects::list(&pool, &range, page, projects_per_page).await?.0,
+  };
 
-  Ok(Json(projects))
+  Ok(cursor_page(&headers, projects, projects_per_page, |project| project.id))
 }
 
 #[derive(Debug, Validate, Deserialize, Serialize, IntoParams)]
 pub struct CreateProject {
   #[validate(length(min = 4))]
   name: String,
-  #[validate(length(min = 2, max = 4))]
+  /// 2 to 4 letters or digits, stored uppercase
   code: String,
   owner: Uuid,
   options: Option<Value>,
@@ -86,10 +120,13 @@ pub struct CreateProject {
   ),
   responses(
     (status = 201, description = "Project created successfully", body = Project),
+    (status = 409, description = "A project with the same code exists"),
+    (status = 422, description = "Invalid project code"),
   )
 )]
 async fn create_project(
   State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
   AppJson(input): AppJson<CreateProject>,
 ) -> ApiResult<Json<Project>> {
   debug!("Register new project with request: {:?}", input);
@@ -98,6 +135,7 @@ async fn create_project(
 
   let project = mutation::projects::create(
     &pool,
+    Some(user.id),
     mutation::projects::CreateProjectParams {
       name: input.name,
       code: input.code,
@@ -114,9 +152,11 @@ async fn create_project(
 pub struct UpdateProject {
   #[validate(length(min = 4))]
   name: String,
-  #[validate(length(min = 2, max = 4))]
+  /// 2 to 4 letters or digits, stored uppercase
   code: String,
   options: Option<Value>,
+  /// Only update the project if it's still at this `version`, 409 otherwise
+  version: Option<i64>,
 }
 
 #[utoipa::path(
@@ -128,12 +168,19 @@ pub struct UpdateProject {
   ),
   responses(
     (status = 200, description = "Project updated successfully", body = Project),
+    (
+      status = 409,
+      description = "Project was modified after `If-Unmodified-Since`, is no longer at `version` or another project has the same code"
+    ),
+    (status = 422, description = "Invalid project code"),
   )
 )]
-#[instrument(skip(pool), fields(project_id = %id))]
+#[instrument(skip(pool, user), fields(project_id = %id))]
 async fn update_project(
   State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
   Path(id): Path<Uuid>,
+  headers: HeaderMap,
   Json(input): Json<UpdateProject>,
 ) -> ApiResult<Json<Project>> {
   debug!("Update project with id {} and params {:?}", id, input);
@@ -142,11 +189,71 @@ async fn update_project(
 
   let project = mutation::projects::update(
     &pool,
+    Some(user.id),
     id,
     mutation::projects::UpdateProjectParams {
       name: input.name,
       code: input.code,
       options: input.options,
+      unmodified_since: unmodified_since(&headers)?,
+      version: input.version,
+    },
+  )
+  .await?;
+
+  Ok(Json(project))
+}
+
+#[derive(Debug, Validate, Deserialize, Serialize, IntoParams)]
+pub struct PatchProject {
+  #[validate(length(min = 4))]
+  name: Option<String>,
+  /// 2 to 4 letters or digits, stored uppercase
+  code: Option<String>,
+  options: Option<Value>,
+  /// Only update the project if it's still at this `version`, 409 otherwise
+  version: Option<i64>,
+}
+
+#[utoipa::path(
+  patch,
+  path = "/{id}",
+  tag = PROJECTS_TAG,
+  params(
+    PatchProject
+  ),
+  responses(
+    (status = 200, description = "Project updated successfully, omitted fields are left untouched", body = Project),
+    (status = 404, description = "Project not found"),
+    (
+      status = 409,
+      description = "Project was modified after `If-Unmodified-Since`, is no longer at `version` or another project has the same code"
+    ),
+    (status = 422, description = "Invalid project code"),
+  )
+)]
+#[instrument(skip(pool, user), fields(project_id = %id))]
+async fn patch_project(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+  headers: HeaderMap,
+  AppJson(input): AppJson<PatchProject>,
+) -> ApiResult<Json<Project>> {
+  debug!("Patch project with id {} and params {:?}", id, input);
+
+  input.validate()?;
+
+  let project = mutation::projects::patch(
+    &pool,
+    Some(user.id),
+    id,
+    mutation::projects::PatchProjectParams {
+      name: input.name,
+      code: input.code,
+      options: input.options,
+      unmodified_since: unmodified_since(&headers)?,
+      version: input.version,
     },
   )
   .await?;
@@ -165,11 +272,89 @@ async fn update_project(
     ("id" = Uuid, Path, description = "Project id")
   )
 )]
-#[instrument(skip(pool), fields(project_id = %id))]
-async fn delete_project(State(pool): State<Arc<SqlitePool>>, Path(id): Path<Uuid>) -> ApiResult<()> {
+#[instrument(skip(pool, user), fields(project_id = %id))]
+async fn delete_project(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+) -> ApiResult<()> {
   debug!("Remove project with id {}", id);
 
-  mutation::projects::delete(&pool, id).await?;
+  let task_ids = mutation::projects::delete(&pool, Some(user.id), id).await?;
+
+  for task_id in task_ids {
+    if let Err(e) = outputs::remove(task_id).await {
+      error!("Failed to remove the output of task {}: {}", task_id, e);
+    }
+    logs::remove(task_id);
+  }
 
   Ok(())
 }
+
+#[derive(Debug, Validate, Deserialize, Serialize, IntoParams)]
+pub struct InstantiateTemplate {
+  #[validate(length(min = 4))]
+  name: String,
+  start_at: DateTime<FixedOffset>,
+  /// Stop rescheduling a recurring task after this time
+  end_at: Option<DateTime<FixedOffset>>,
+  /// Replaces the template schedule
+  schedule: Option<String>,
+  /// Replaces the template plugin version requirement
+  plugin_version: Option<String>,
+  /// Replaces the template misfire policy
+  misfire_policy: Option<MisfirePolicy>,
+  /// Merged over the template options, nested objects key by key
+  options: Option<Value>,
+}
+
+#[utoipa::path(
+  post,
+  path = "/{id}/tasks/from-template/{template_id}",
+  tag = PROJECTS_TAG,
+  params(
+    ("id" = Uuid, Path, description = "Project id"),
+    ("template_id" = Uuid, Path, description = "Template id"),
+    InstantiateTemplate
+  ),
+  responses(
+    (status = 201, description = "Task created from the template", body = Task),
+    (status = 404, description = "Template not found"),
+    (status = 422, description = "Options too large, invalid plugin version or schedule under `TASK_MIN_SCHEDULE_INTERVAL`"),
+  )
+)]
+#[instrument(skip(pool, user, input), fields(project_id = %id, template_id = %template_id))]
+async fn instantiate_template(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path((id, template_id)): Path<(Uuid, Uuid)>,
+  AppJson(input): AppJson<InstantiateTemplate>,
+) -> ApiResult<Json<Task>> {
+  debug!("Create task from template {} with request: {:?}", template_id, input);
+
+  input.validate()?;
+
+  let template = query::templates::find(&pool, template_id).await?;
+  let schedule = input.schedule.as_ref().or(template.schedule.as_ref());
+  let start_at = calculate_next_execution_time(schedule, input.start_at)?;
+
+  let task = mutation::templates::instantiate(
+    &pool,
+    Some(user.id),
+    template,
+    mutation::templates::InstantiateTemplateParams {
+      project_id: id,
+      name: input.name,
+      start_at,
+      end_at: input.end_at.map(|end_at| end_at.timestamp()),
+      schedule: input.schedule,
+      plugin_version: input.plugin_version,
+      misfire_policy: input.misfire_policy,
+      options: input.options,
+    },
+  )
+  .await?;
+
+  Ok(Json(task))
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use axum::{
+  extract::{Query, State},
+  middleware::from_fn_with_state,
+  Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::{
+  router::{OpenApiRouter, UtoipaMethodRouterExt},
+  routes,
+};
+use validator::Validate;
+
+use crate::{entities::search::SearchResult, error::ApiResult, service::query};
+
+use super::{auth::auth_guard, page_size};
+
+const SEARCH_TAG: &str = "search";
+const DEFAULT_PAGE: i64 = 1;
+const DEFAULT_RESULTS_PER_PAGE: i64 = 20;
+
+pub fn init_search_routes(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
+  OpenApiRouter::new().routes(routes!(search).layer(from_fn_with_state(state.clone(), auth_guard)))
+}
+
+#[derive(Debug, Validate, Deserialize, IntoParams)]
+struct SearchParams {
+  /// Case-insensitive substring of a project name or code, or of a task name or external id
+  #[validate(length(min = 1))]
+  q: String,
+  page: Option<i64>,
+  /// At most `API_MAX_PAGE_SIZE`
+  results_per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct SearchResults {
+  results: Vec<SearchResult>,
+  page: i64,
+  /// Results per page after applying the default and `API_MAX_PAGE_SIZE`
+  results_per_page: i64,
+  total_count: i64,
+  total_pages: i64,
+}
+
+#[utoipa::path(
+  get,
+  path = "",
+  tag = SEARCH_TAG,
+  params(
+    SearchParams
+  ),
+  responses(
+    (status = 200, description = "Matching projects followed by matching tasks", body = SearchResults),
+    (status = 400, description = "Empty search term"),
+    (status = 401, description = "Unauthorized")
+  )
+)]
+#[instrument(skip(pool))]
+async fn search(
+  State(pool): State<Arc<SqlitePool>>,
+  Query(params): Query<SearchParams>,
+) -> ApiResult<Json<SearchResults>> {
+  params.validate()?;
+
+  let page = params.page.unwrap_or(DEFAULT_PAGE).max(1);
+  let results_per_page = page_size(params.results_per_page, DEFAULT_RESULTS_PER_PAGE)?;
+
+  let (results, total_count, total_pages) = query::search::search(&pool, &params.q, page, results_per_page).await?;
+
+  Ok(Json(SearchResults {
+    results,
+    page,
+    results_per_page,
+    total_count,
+    total_pages,
+  }))
+}
@@ -2,17 +2,22 @@ use std::{str::FromStr, sync::Arc};
 
 use anyhow::Result;
 use axum::{
+  body::Body,
   extract::{Path, Query, State},
+  http::{header, HeaderMap, StatusCode},
   middleware::{self, from_fn_with_state},
-  Json,
+  response::IntoResponse,
+  Extension, Json,
 };
 use chrono::{DateTime, FixedOffset, Utc};
 use cron::Schedule;
 use duration_str::parse;
+use futures::{StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
-use tracing::{debug, instrument};
-use utoipa::IntoParams;
+use tokio_util::io::ReaderStream;
+use tracing::{debug, info, instrument};
+use utoipa::{IntoParams, ToSchema};
 use utoipa_axum::{
   router::{OpenApiRouter, UtoipaMethodRouterExt},
   routes,
@@ -21,31 +26,57 @@ use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
-  entities::task::Task,
+  entities::{
+    task::{ExportRecord, MisfirePolicy, Task, TaskLog, TaskStatus},
+    user::User,
+  },
   error::{ApiError, ApiResult},
-  service::{mutation, query},
-  AppJson,
+  executor::{EnqueueError, ExecutorHandle},
+  service::{
+    logs, mutation,
+    mutation::tasks::{BulkAction, BulkFilter, BulkSummary, ImportSummary},
+    outputs, query,
+  },
+  timezone, AppJson,
 };
 
-use super::auth::auth_guard;
+use super::{auth::auth_guard, cursor_page, double_option, page_size, unmodified_since, TimeRangeParams};
 
 const TASKS_TAG: &str = "tasks";
 const DEFAULT_PAGE: i64 = 1;
 const DEFAULT_TASKS_PER_PAGE: i64 = 5;
 const EVERY_PREFIX: &str = "@every ";
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const OCTET_STREAM_CONTENT_TYPE: &str = "application/octet-stream";
 
 pub fn init_tasks_routes(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
   OpenApiRouter::new()
+    .routes(routes!(list_tasks, create_task).layer(from_fn_with_state(state.clone(), auth_guard)))
     .routes(
-      routes!(list_tasks, create_task, update_task, delete_task).layer(from_fn_with_state(state.clone(), auth_guard)),
+      routes!(get_task, update_task, patch_task, delete_task).layer(from_fn_with_state(state.clone(), auth_guard)),
     )
+    .routes(routes!(get_task_by_external_id).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(revive_task).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(restore_task).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(trigger_task).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(run_task_now).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(enable_task).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(disable_task).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(task_output).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(task_logs).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(export_tasks).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(import_tasks).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(routes!(bulk_status).layer(from_fn_with_state(state.clone(), auth_guard)))
     .route_layer(middleware::from_fn_with_state(state.clone(), auth_guard))
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
 struct ListTasksParams {
   page: Option<i64>,
+  /// At most `API_MAX_PAGE_SIZE`
   tasks_per_page: Option<i64>,
+  /// `X-Next-Cursor` of the previous page, pages by id instead of offset and ignores `page`
+  after: Option<Uuid>,
 }
 
 #[utoipa::path(
@@ -53,23 +84,76 @@ struct ListTasksParams {
   path = "",
   tag = TASKS_TAG,
   params(
-    ListTasksParams
+    ListTasksParams,
+    TimeRangeParams
   ),
   responses(
-    (status = 200, description = "List all tasks successfully", body = [Task])
+    (status = 200, description = "List all tasks successfully, as CSV with `Accept: text/csv`",
+      content(([Task] = "application/json"), (String = "text/csv")),
+      headers(
+        ("X-Page-Size" = i64, description = "Items per page after applying the default and `API_MAX_PAGE_SIZE`"),
+        ("X-Next-Cursor" = Uuid, description = "Set on a full page, pass as `after` for the next one")
+      )),
+    (status = 304, description = "Tasks unchanged since the `ETag` sent in `If-None-Match`"),
+    (status = 422, description = "A time range bound isn't an RFC 3339 timestamp"),
   )
 )]
 #[instrument(skip(pool))]
 async fn list_tasks(
   State(pool): State<Arc<SqlitePool>>,
+  headers: HeaderMap,
   Query(params): Query<ListTasksParams>,
-) -> ApiResult<Json<Vec<Task>>> {
+  Query(range): Query<TimeRangeParams>,
+) -> ApiResult<impl IntoResponse> {
+  let range = range.parse()?;
   let page = params.page.unwrap_or(DEFAULT_PAGE);
-  let tasks_per_page = params.tasks_per_page.unwrap_or(DEFAULT_TASKS_PER_PAGE);
+  let tasks_per_page = page_size(params.tasks_per_page, DEFAULT_TASKS_PER_PAGE)?;
 
-  let (tasks, _num_pages) = query::tasks::list(&pool, page, tasks_per_page).await?;
+  let tasks = match params.after {
+    Some(after) => query::tasks::list_after(&pool, &range, after, tasks_per_page).await?,
+    None => query::tasks::list(&pool, &range, page, tasks_per_page).await?.0,
+  };
+
+  Ok(cursor_page(&headers, tasks, tasks_per_page, |task| task.id))
+}
+
+#[utoipa::path(
+  get,
+  path = "/{id}",
+  tag = TASKS_TAG,
+  responses(
+    (status = 200, description = "Task found", body = Task),
+    (status = 304, description = "Task unchanged since the `ETag` sent in `If-None-Match`"),
+    (status = 404, description = "Task not found"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Task id")
+  )
+)]
+#[instrument(skip(pool), fields(task_id = %id))]
+async fn get_task(State(pool): State<Arc<SqlitePool>>, Path(id): Path<Uuid>) -> ApiResult<Json<Task>> {
+  Ok(Json(query::tasks::find(&pool, id).await?))
+}
 
-  Ok(Json(tasks))
+#[utoipa::path(
+  get,
+  path = "/by-external-id/{external_id}",
+  tag = TASKS_TAG,
+  responses(
+    (status = 200, description = "Task found", body = Task),
+    (status = 304, description = "Task unchanged since the `ETag` sent in `If-None-Match`"),
+    (status = 404, description = "No task has this external id"),
+  ),
+  params(
+    ("external_id" = String, Path, description = "Id of the task in the system it's synced from")
+  )
+)]
+#[instrument(skip(pool))]
+async fn get_task_by_external_id(
+  State(pool): State<Arc<SqlitePool>>,
+  Path(external_id): Path<String>,
+) -> ApiResult<Json<Task>> {
+  Ok(Json(query::tasks::find_by_external_id(&pool, &external_id).await?))
 }
 
 #[derive(Debug, Validate, Deserialize, Serialize, IntoParams)]
@@ -77,9 +161,15 @@ pub struct CreateTask {
   #[validate(length(min = 4))]
   name: String,
   r#type: String,
+  /// Semver requirement on the plugin version, e.g. `^1.2`, any loaded version runs the task when omitted
+  plugin_version: Option<String>,
   schedule: Option<String>,
   project_id: Uuid,
   start_at: DateTime<FixedOffset>,
+  /// Stop rescheduling a recurring task after this time
+  end_at: Option<DateTime<FixedOffset>>,
+  /// How runs missed while the task couldn't run are handled, `fire_once` by default
+  misfire_policy: Option<MisfirePolicy>,
   options: serde_json::Value,
 }
 
@@ -92,11 +182,13 @@ pub struct CreateTask {
   ),
   responses(
     (status = 201, description = "Task created successfully", body = Task),
+    (status = 422, description = "Options too large, invalid plugin version or schedule under `TASK_MIN_SCHEDULE_INTERVAL`"),
   )
 )]
-#[instrument(skip(pool, input))]
+#[instrument(skip(pool, user, input))]
 async fn create_task(
   State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
   AppJson(input): AppJson<CreateTask>,
 ) -> ApiResult<Json<Task>> {
   debug!("Register new task with request: {:?}", input);
@@ -107,14 +199,18 @@ async fn create_task(
 
   let task = mutation::tasks::create(
     &pool,
+    Some(user.id),
     mutation::tasks::CreateTaskParams {
       name: input.name,
       r#type: input.r#type,
+      plugin_version: input.plugin_version,
       project_id: input.project_id,
       external_id: None,
       external_modified_at: None,
       schedule: input.schedule,
       start_at,
+      end_at: input.end_at.map(|end_at| end_at.timestamp()),
+      misfire_policy: input.misfire_policy.unwrap_or_default(),
       options: input.options,
     },
   )
@@ -127,9 +223,21 @@ async fn create_task(
 pub struct UpdateTask {
   #[validate(length(min = 4))]
   name: String,
+  /// New plugin type, kept unchanged when omitted
+  r#type: Option<String>,
+  /// Semver requirement on the plugin version, any loaded version runs the task when omitted
+  plugin_version: Option<String>,
+  /// Project to move the task to, kept unchanged when omitted
+  project_id: Option<Uuid>,
   schedule: Option<String>,
   start_at: DateTime<FixedOffset>,
+  /// Stop rescheduling a recurring task after this time
+  end_at: Option<DateTime<FixedOffset>>,
+  /// How runs missed while the task couldn't run are handled, kept unchanged when omitted
+  misfire_policy: Option<MisfirePolicy>,
   options: serde_json::Value,
+  /// Only update the task if it's still at this `version`, 409 otherwise
+  version: Option<i64>,
 }
 
 #[utoipa::path(
@@ -141,12 +249,17 @@ pub struct UpdateTask {
   ),
   responses(
     (status = 200, description = "Task updated successfully", body = Task),
+    (status = 404, description = "Task or project not found"),
+    (status = 409, description = "Task was modified after `If-Unmodified-Since` or is no longer at `version`"),
+    (status = 422, description = "Options too large, invalid plugin version or schedule under `TASK_MIN_SCHEDULE_INTERVAL`"),
   )
 )]
-#[instrument(skip(pool), fields(task_id = %id))]
+#[instrument(skip(pool, user), fields(task_id = %id))]
 async fn update_task(
   State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
   Path(id): Path<Uuid>,
+  headers: HeaderMap,
   Json(input): Json<UpdateTask>,
 ) -> ApiResult<Json<Task>> {
   debug!("Update task with id {} and params {:?}", id, input);
@@ -157,12 +270,20 @@ async fn update_task(
 
   let task = mutation::tasks::update(
     &pool,
+    Some(user.id),
     id,
     mutation::tasks::UpdateTaskParams {
       name: input.name,
+      r#type: input.r#type,
+      plugin_version: input.plugin_version,
+      project_id: input.project_id,
       schedule: input.schedule,
       start_at,
+      end_at: input.end_at.map(|end_at| end_at.timestamp()),
+      misfire_policy: input.misfire_policy,
       options: input.options,
+      unmodified_since: unmodified_since(&headers)?,
+      version: input.version,
     },
   )
   .await?;
@@ -170,36 +291,472 @@ async fn update_task(
   Ok(Json(task))
 }
 
+#[derive(Debug, Validate, Deserialize, Serialize, IntoParams)]
+pub struct PatchTask {
+  #[validate(length(min = 4))]
+  name: Option<String>,
+  r#type: Option<String>,
+  /// `null` unpins the plugin version so any loaded version runs the task
+  #[serde(default, deserialize_with = "double_option")]
+  plugin_version: Option<Option<String>>,
+  project_id: Option<Uuid>,
+  /// `null` removes the schedule so the task runs once
+  #[serde(default, deserialize_with = "double_option")]
+  schedule: Option<Option<String>>,
+  start_at: Option<DateTime<FixedOffset>>,
+  /// `null` removes the end so a recurring task runs indefinitely
+  #[serde(default, deserialize_with = "double_option")]
+  end_at: Option<Option<DateTime<FixedOffset>>>,
+  misfire_policy: Option<MisfirePolicy>,
+  options: Option<serde_json::Value>,
+  /// Only update the task if it's still at this `version`, 409 otherwise
+  version: Option<i64>,
+}
+
+#[utoipa::path(
+  patch,
+  path = "/{id}",
+  tag = TASKS_TAG,
+  params(
+    PatchTask
+  ),
+  responses(
+    (status = 200, description = "Task updated successfully, omitted fields are left untouched", body = Task),
+    (status = 404, description = "Task or project not found"),
+    (status = 409, description = "Task was modified after `If-Unmodified-Since` or is no longer at `version`"),
+    (status = 422, description = "Options too large, invalid plugin version or schedule under `TASK_MIN_SCHEDULE_INTERVAL`"),
+  )
+)]
+#[instrument(skip(pool, user), fields(task_id = %id))]
+async fn patch_task(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+  headers: HeaderMap,
+  AppJson(input): AppJson<PatchTask>,
+) -> ApiResult<Json<Task>> {
+  debug!("Patch task with id {} and params {:?}", id, input);
+
+  input.validate()?;
+
+  // The next run depends on the schedule, fall back to the stored one when only the start changes
+  let start_at = match input.start_at {
+    Some(start_at) => {
+      let schedule = match &input.schedule {
+        Some(schedule) => schedule.clone(),
+        None => query::tasks::find(&pool, id).await?.schedule,
+      };
+
+      Some(calculate_next_execution_time(schedule.as_ref(), start_at)?)
+    },
+    None => None,
+  };
+
+  let task = mutation::tasks::patch(
+    &pool,
+    Some(user.id),
+    id,
+    mutation::tasks::PatchTaskParams {
+      name: input.name,
+      r#type: input.r#type,
+      plugin_version: input.plugin_version,
+      project_id: input.project_id,
+      schedule: input.schedule,
+      start_at,
+      end_at: input.end_at.map(|end_at| end_at.map(|end_at| end_at.timestamp())),
+      misfire_policy: input.misfire_policy,
+      options: input.options,
+      unmodified_since: unmodified_since(&headers)?,
+      version: input.version,
+    },
+  )
+  .await?;
+
+  Ok(Json(task))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct DeleteTaskParams {
+  /// Remove the task permanently instead of moving it to the trash, also works on a deleted task
+  #[serde(default)]
+  hard: bool,
+}
+
 #[utoipa::path(
   delete,
   path = "/{id}",
   tag = TASKS_TAG,
   responses(
-    (status = 200, description = "Task successfully deleted"),
+    (status = 200, description = "Task deleted, restorable until `TASK_DELETED_RETENTION` unless `hard` is set"),
+    (status = 404, description = "Task not found"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Task id"),
+    DeleteTaskParams
+  )
+)]
+#[instrument(skip(pool, user), fields(task_id = %id))]
+async fn delete_task(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+  Query(params): Query<DeleteTaskParams>,
+) -> ApiResult<()> {
+  debug!("Remove task with id {}, hard: {}", id, params.hard);
+
+  if !params.hard {
+    return mutation::tasks::soft_delete(&pool, Some(user.id), id).await;
+  }
+
+  mutation::tasks::delete(&pool, Some(user.id), id).await?;
+  outputs::remove(id).await.map_err(|e| ApiError::Anyhow(e.into()))?;
+  logs::remove(id);
+
+  Ok(())
+}
+
+#[utoipa::path(
+  post,
+  path = "/{id}/restore",
+  tag = TASKS_TAG,
+  responses(
+    (status = 200, description = "Deleted task restored", body = Task),
+    (status = 404, description = "Task not found or already purged"),
+    (status = 409, description = "Task is not deleted"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Task id")
+  )
+)]
+#[instrument(skip(pool, user), fields(task_id = %id))]
+async fn restore_task(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+) -> ApiResult<Json<Task>> {
+  debug!("Restore deleted task with id {}", id);
+
+  let task = mutation::tasks::restore(&pool, Some(user.id), id).await?;
+
+  Ok(Json(task))
+}
+
+#[utoipa::path(
+  post,
+  path = "/{id}/revive",
+  tag = TASKS_TAG,
+  responses(
+    (status = 200, description = "Dead task revived", body = Task),
+    (status = 404, description = "Task not found"),
+    (status = 409, description = "Task is not dead"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Task id")
+  )
+)]
+#[instrument(skip(pool, user), fields(task_id = %id))]
+async fn revive_task(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+) -> ApiResult<Json<Task>> {
+  debug!("Revive dead task with id {}", id);
+
+  let task = mutation::tasks::revive(&pool, Some(user.id), id).await?;
+
+  Ok(Json(task))
+}
+
+#[utoipa::path(
+  post,
+  path = "/{id}/trigger",
+  tag = TASKS_TAG,
+  responses(
+    (status = 202, description = "Task handed to the executor, it runs as soon as a worker is free", body = Task),
+    (status = 404, description = "Task not found"),
+    (status = 409, description = "Task is in progress, dead or disabled"),
+    (status = 503, description = "Worker queue is full or the executor is stopping"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Task id")
+  )
+)]
+#[instrument(skip(pool, executor, user), fields(task_id = %id))]
+async fn trigger_task(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(executor): Extension<Arc<dyn ExecutorHandle>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+) -> ApiResult<(StatusCode, Json<Task>)> {
+  let task = mutation::tasks::claim(&pool, id, executor.id()).await?;
+
+  if let Err(e) = executor.enqueue(task.clone()) {
+    // The task is back to `new` so it can be triggered again, unless it's the one already running
+    if !matches!(e, EnqueueError::AlreadyQueued(_)) {
+      mutation::tasks::release_task(&pool, id).await?;
+    }
+    return Err(e.into());
+  }
+
+  info!("User {} triggered task {}", user.username, id);
+
+  Ok((StatusCode::ACCEPTED, Json(task)))
+}
+
+#[utoipa::path(
+  post,
+  path = "/{id}/run-now",
+  tag = TASKS_TAG,
+  responses(
+    (status = 200, description = "Task due right away, the next poll runs it and its schedule is kept", body = Task),
+    (status = 404, description = "Task not found"),
+    (status = 409, description = "Task is in progress or disabled"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Task id")
+  )
+)]
+#[instrument(skip(pool, user), fields(task_id = %id))]
+async fn run_task_now(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+) -> ApiResult<Json<Task>> {
+  debug!("Run task with id {} now", id);
+
+  let task = mutation::tasks::run_now(&pool, Some(user.id), id).await?;
+
+  Ok(Json(task))
+}
+
+#[utoipa::path(
+  post,
+  path = "/{id}/enable",
+  tag = TASKS_TAG,
+  responses(
+    (status = 200, description = "Task enabled, missed runs are handled by its misfire policy", body = Task),
+    (status = 404, description = "Task not found"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Task id")
+  )
+)]
+#[instrument(skip(pool, user), fields(task_id = %id))]
+async fn enable_task(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+) -> ApiResult<Json<Task>> {
+  debug!("Enable task with id {}", id);
+
+  let task = mutation::tasks::set_enabled(&pool, Some(user.id), id, true).await?;
+
+  Ok(Json(task))
+}
+
+#[utoipa::path(
+  post,
+  path = "/{id}/disable",
+  tag = TASKS_TAG,
+  responses(
+    (status = 200, description = "Task disabled, the executor skips it until enabled again", body = Task),
+    (status = 404, description = "Task not found"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Task id")
+  )
+)]
+#[instrument(skip(pool, user), fields(task_id = %id))]
+async fn disable_task(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+) -> ApiResult<Json<Task>> {
+  debug!("Disable task with id {}", id);
+
+  let task = mutation::tasks::set_enabled(&pool, Some(user.id), id, false).await?;
+
+  Ok(Json(task))
+}
+
+#[utoipa::path(
+  get,
+  path = "/{id}/output",
+  tag = TASKS_TAG,
+  responses(
+    (
+      status = 200,
+      description = "Output streamed by the plugin during the last run of the task",
+      content_type = "application/octet-stream"
+    ),
+    (status = 404, description = "Task not found or it has no output"),
   ),
   params(
     ("id" = Uuid, Path, description = "Task id")
   )
 )]
 #[instrument(skip(pool), fields(task_id = %id))]
-async fn delete_task(State(pool): State<Arc<SqlitePool>>, Path(id): Path<Uuid>) -> ApiResult<()> {
-  debug!("Remove task with id {}", id);
+async fn task_output(State(pool): State<Arc<SqlitePool>>, Path(id): Path<Uuid>) -> ApiResult<impl IntoResponse> {
+  query::tasks::find(&pool, id).await?;
 
-  mutation::tasks::delete(&pool, id).await?;
+  let file = match tokio::fs::File::open(outputs::output_path(id)).await {
+    Ok(file) => file,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(ApiError::ResourceNotFound(id.to_string())),
+    Err(e) => return Err(ApiError::Anyhow(e.into())),
+  };
 
-  Ok(())
+  Ok((
+    [(header::CONTENT_TYPE, OCTET_STREAM_CONTENT_TYPE)],
+    Body::from_stream(ReaderStream::new(file)),
+  ))
+}
+
+#[utoipa::path(
+  get,
+  path = "/{id}/logs",
+  tag = TASKS_TAG,
+  responses(
+    (
+      status = 200,
+      description = "Lines the plugin logged during the last run of the task since the server started",
+      body = [TaskLog]
+    ),
+    (status = 404, description = "Task not found"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Task id")
+  )
+)]
+#[instrument(skip(pool), fields(task_id = %id))]
+async fn task_logs(State(pool): State<Arc<SqlitePool>>, Path(id): Path<Uuid>) -> ApiResult<Json<Vec<TaskLog>>> {
+  query::tasks::find(&pool, id).await?;
+
+  Ok(Json(logs::get(id)))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct ExportTasksParams {
+  /// Also export the projects, written before the tasks so the output can be imported into an empty database
+  projects: Option<bool>,
+}
+
+#[utoipa::path(
+  get,
+  path = "/export",
+  tag = TASKS_TAG,
+  params(
+    ExportTasksParams
+  ),
+  responses(
+    (
+      status = 200,
+      description = "NDJSON stream of every task, one `kind`-tagged record per line",
+      content_type = "application/x-ndjson"
+    ),
+  )
+)]
+#[instrument(skip(pool))]
+async fn export_tasks(
+  State(pool): State<Arc<SqlitePool>>,
+  Query(params): Query<ExportTasksParams>,
+) -> impl IntoResponse {
+  let records = query::tasks::export(pool.as_ref().clone(), params.projects.unwrap_or(false));
+  let lines = records.map(|record| record.and_then(to_ndjson_line));
+
+  ([(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)], Body::from_stream(lines))
+}
+
+#[utoipa::path(
+  post,
+  path = "/import",
+  tag = TASKS_TAG,
+  request_body(
+    content = String,
+    description = "NDJSON in the format produced by the export",
+    content_type = "application/x-ndjson"
+  ),
+  responses(
+    (status = 200, description = "Export imported, projects and tasks are upserted by id", body = ImportSummary),
+    (status = 400, description = "Invalid record, nothing was imported"),
+  )
+)]
+#[instrument(skip(pool, user, body))]
+async fn import_tasks(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  body: Body,
+) -> ApiResult<Json<ImportSummary>> {
+  let chunks = body.into_data_stream().map_err(|e| ApiError::Anyhow(e.into()));
+
+  let summary = mutation::tasks::import(&pool, Some(user.id), chunks).await?;
+
+  debug!("Imported {} projects and {} tasks", summary.projects, summary.tasks);
+
+  Ok(Json(summary))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkStatus {
+  /// Only tasks in this status
+  status: Option<TaskStatus>,
+  /// Only tasks of this project
+  project_id: Option<Uuid>,
+  /// Only tasks of this plugin type
+  r#type: Option<String>,
+  action: BulkAction,
+}
+
+#[utoipa::path(
+  post,
+  path = "/bulk-status",
+  tag = TASKS_TAG,
+  request_body = BulkStatus,
+  responses(
+    (
+      status = 200,
+      description = "Action applied to the matching tasks it's valid for, the others are counted as skipped",
+      body = BulkSummary
+    ),
+  )
+)]
+#[instrument(skip(pool, user))]
+async fn bulk_status(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  AppJson(input): AppJson<BulkStatus>,
+) -> ApiResult<Json<BulkSummary>> {
+  let filter = BulkFilter {
+    status: input.status,
+    project_id: input.project_id,
+    r#type: input.r#type,
+  };
+
+  let summary = mutation::tasks::bulk_status(&pool, Some(user.id), filter, input.action).await?;
+
+  info!(
+    "User {} applied {:?} to {} tasks, skipped {}",
+    user.username, input.action, summary.updated, summary.skipped
+  );
+
+  Ok(Json(summary))
+}
+
+fn to_ndjson_line(record: ExportRecord) -> ApiResult<String> {
+  let mut line = serde_json::to_string(&record).map_err(anyhow::Error::from)?;
+  line.push('\n');
+  Ok(line)
 }
 
-fn calculate_next_execution_time(schedule: Option<&String>, start_at: DateTime<FixedOffset>) -> Result<i32> {
+pub(super) fn calculate_next_execution_time(schedule: Option<&String>, start_at: DateTime<FixedOffset>) -> Result<i64> {
   let current_time = Utc::now().timestamp();
   let start_timestamp = start_at.to_utc().timestamp();
 
   if start_timestamp >= current_time {
-    return Ok(start_timestamp as i32);
+    return Ok(start_timestamp);
   }
 
   let Some(schedule) = schedule else {
-    return Ok(start_timestamp as i32);
+    return Ok(start_timestamp);
   };
 
   if schedule.starts_with(EVERY_PREFIX) {
@@ -209,22 +766,22 @@ fn calculate_next_execution_time(schedule: Option<&String>, start_at: DateTime<F
   }
 }
 
-fn calculate_interval_based_time(schedule: &str, start_timestamp: i64) -> Result<i32> {
+fn calculate_interval_based_time(schedule: &str, start_timestamp: i64) -> Result<i64> {
   let duration_str = schedule.trim_start_matches(EVERY_PREFIX);
   let duration = parse(duration_str).map_err(|e| ApiError::InvalidSchedule(e.to_string()))?;
 
   let interval = chrono::Duration::from_std(duration).map_err(|e| ApiError::ScheduleCalculation(e.to_string()))?;
 
-  Ok((start_timestamp + interval.num_seconds()) as i32)
+  Ok(start_timestamp + interval.num_seconds())
 }
 
-fn calculate_cron_based_time(schedule: &str, start_at: DateTime<FixedOffset>) -> Result<i32> {
+fn calculate_cron_based_time(schedule: &str, start_at: DateTime<FixedOffset>) -> Result<i64> {
   let schedule = Schedule::from_str(schedule).map_err(|e| ApiError::InvalidSchedule(e.to_string()))?;
 
   let next_run = schedule
-    .after(&start_at.to_utc())
+    .after(&start_at.with_timezone(&timezone::default_tz()))
     .next()
     .ok_or_else(|| ApiError::ScheduleCalculation("Failed to calculate next run".into()))?;
 
-  Ok(next_run.timestamp() as i32)
+  Ok(next_run.timestamp())
 }
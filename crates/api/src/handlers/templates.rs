@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use axum::{
+  extract::{Path, State},
+  middleware::from_fn_with_state,
+  Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use tracing::{debug, instrument};
+use utoipa::IntoParams;
+use utoipa_axum::{
+  router::{OpenApiRouter, UtoipaMethodRouterExt},
+  routes,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+  entities::{task::MisfirePolicy, template::TaskTemplate, user::User},
+  error::ApiResult,
+  service::{mutation, query},
+  AppJson,
+};
+
+use super::auth::auth_guard;
+
+const TEMPLATES_TAG: &str = "templates";
+
+pub fn init_templates_routes(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
+  OpenApiRouter::new()
+    .routes(routes!(list_templates, create_template).layer(from_fn_with_state(state.clone(), auth_guard)))
+    .routes(
+      routes!(get_template, update_template, delete_template).layer(from_fn_with_state(state.clone(), auth_guard)),
+    )
+}
+
+#[utoipa::path(
+  get,
+  path = "",
+  tag = TEMPLATES_TAG,
+  responses(
+    (status = 200, description = "List all task templates ordered by name", body = [TaskTemplate]),
+  )
+)]
+#[instrument(skip(pool))]
+async fn list_templates(State(pool): State<Arc<SqlitePool>>) -> ApiResult<Json<Vec<TaskTemplate>>> {
+  Ok(Json(query::templates::list(&pool).await?))
+}
+
+#[utoipa::path(
+  get,
+  path = "/{id}",
+  tag = TEMPLATES_TAG,
+  responses(
+    (status = 200, description = "Template found", body = TaskTemplate),
+    (status = 404, description = "Template not found"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Template id")
+  )
+)]
+#[instrument(skip(pool), fields(template_id = %id))]
+async fn get_template(State(pool): State<Arc<SqlitePool>>, Path(id): Path<Uuid>) -> ApiResult<Json<TaskTemplate>> {
+  Ok(Json(query::templates::find(&pool, id).await?))
+}
+
+#[derive(Debug, Validate, Deserialize, Serialize, IntoParams)]
+pub struct SaveTemplate {
+  #[validate(length(min = 4))]
+  name: String,
+  description: Option<String>,
+  r#type: String,
+  /// Semver requirement on the plugin version, e.g. `^1.2`, any loaded version runs the tasks when omitted
+  plugin_version: Option<String>,
+  schedule: Option<String>,
+  /// How runs missed while the task couldn't run are handled, `fire_once` by default
+  misfire_policy: Option<MisfirePolicy>,
+  /// Options skeleton, the options given when instantiating are merged over it
+  options: Option<Value>,
+}
+
+impl From<SaveTemplate> for mutation::templates::TemplateParams {
+  fn from(input: SaveTemplate) -> Self {
+    Self {
+      name: input.name,
+      description: input.description,
+      r#type: input.r#type,
+      plugin_version: input.plugin_version,
+      schedule: input.schedule,
+      misfire_policy: input.misfire_policy.unwrap_or_default(),
+      options: input.options.unwrap_or_else(|| Value::Object(Default::default())),
+    }
+  }
+}
+
+#[utoipa::path(
+  post,
+  path = "",
+  tag = TEMPLATES_TAG,
+  params(
+    SaveTemplate
+  ),
+  responses(
+    (status = 201, description = "Template created successfully", body = TaskTemplate),
+    (status = 409, description = "A template with the same name already exists"),
+    (status = 422, description = "Options too large, invalid plugin version or schedule under `TASK_MIN_SCHEDULE_INTERVAL`"),
+  )
+)]
+async fn create_template(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  AppJson(input): AppJson<SaveTemplate>,
+) -> ApiResult<Json<TaskTemplate>> {
+  debug!("Register new task template with request: {:?}", input);
+
+  input.validate()?;
+
+  let template = mutation::templates::create(&pool, Some(user.id), input.into()).await?;
+
+  Ok(Json(template))
+}
+
+#[utoipa::path(
+  put,
+  path = "/{id}",
+  tag = TEMPLATES_TAG,
+  params(
+    SaveTemplate
+  ),
+  responses(
+    (status = 200, description = "Template updated successfully, tasks created from it are left as they are", body = TaskTemplate),
+    (status = 404, description = "Template not found"),
+    (status = 409, description = "Another template has the same name"),
+    (status = 422, description = "Options too large, invalid plugin version or schedule under `TASK_MIN_SCHEDULE_INTERVAL`"),
+  )
+)]
+#[instrument(skip(pool, user), fields(template_id = %id))]
+async fn update_template(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+  AppJson(input): AppJson<SaveTemplate>,
+) -> ApiResult<Json<TaskTemplate>> {
+  debug!("Update task template with id {} and params {:?}", id, input);
+
+  input.validate()?;
+
+  let template = mutation::templates::update(&pool, Some(user.id), id, input.into()).await?;
+
+  Ok(Json(template))
+}
+
+#[utoipa::path(
+  delete,
+  path = "/{id}",
+  tag = TEMPLATES_TAG,
+  responses(
+    (status = 200, description = "Template successfully deleted, tasks created from it are kept"),
+    (status = 404, description = "Template not found"),
+  ),
+  params(
+    ("id" = Uuid, Path, description = "Template id")
+  )
+)]
+#[instrument(skip(pool, user), fields(template_id = %id))]
+async fn delete_template(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(user): Extension<User>,
+  Path(id): Path<Uuid>,
+) -> ApiResult<()> {
+  debug!("Remove task template with id {}", id);
+
+  mutation::templates::delete(&pool, Some(user.id), id).await?;
+
+  Ok(())
+}
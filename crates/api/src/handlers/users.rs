@@ -24,15 +24,14 @@ use validator::Validate;
 use crate::{
   entities::user::User,
   error::ApiResult,
-  handlers::auth::encode_jwt,
-  service::{mutation, query},
+  handlers::auth::{encode_jwt, token_source, Claims, TokenSource, AUTH_COOKIE_NAME, CSRF_COOKIE_NAME},
+  service::{mutation, query, query::users::UserFilter},
   AppJson,
 };
 
-use super::auth::auth_guard;
+use super::{auth::auth_guard, page_size};
 
 const USERS_TAG: &str = "users";
-const AUTH_COOKIE_NAME: &str = "token";
 const DEFAULT_PAGE_SIZE: i64 = 10;
 
 pub fn init_users_routes(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
@@ -61,6 +60,9 @@ pub struct LoginParams {
 struct LoginResponse {
   status: String,
   token: String,
+  /// Value to send in `X-CSRF-Token` on unsafe requests, only set when `AUTH_TOKEN_SOURCE=cookie`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  csrf_token: Option<String>,
 }
 
 #[utoipa::path(
@@ -73,7 +75,8 @@ struct LoginResponse {
   responses(
     (status = 200, description = "Login successful", body = LoginResponse),
     (status = 401, description = "Invalid credentials"),
-    (status = 422, description = "Validation error")
+    (status = 422, description = "Validation error"),
+    (status = 423, description = "Account is temporarily locked after too many failed attempts")
   )
 )]
 #[instrument(skip(pool, input))]
@@ -90,16 +93,29 @@ async fn login(pool: State<Arc<SqlitePool>>, Json(input): Json<LoginParams>) ->
   let user = mutation::users::login(&pool, params).await?;
   let token = encode_jwt(user.id)?;
 
-  let cookie = build_auth_cookie(token.clone(), true);
+  let source = token_source()?;
+  let mut cookies = Vec::new();
+  if source.accepts_cookie() {
+    cookies.push(build_auth_cookie(AUTH_COOKIE_NAME, token.clone(), true, true));
+  }
+
+  let csrf_token = (source == TokenSource::Cookie).then(|| Uuid::new_v4().simple().to_string());
+  if let Some(csrf_token) = &csrf_token {
+    cookies.push(build_auth_cookie(CSRF_COOKIE_NAME, csrf_token.clone(), true, false));
+  }
+
   let response = LoginResponse {
     status: "success".to_string(),
     token,
+    csrf_token,
   };
 
   let mut response = Response::new(serde_json::to_string(&response).unwrap());
-  response
-    .headers_mut()
-    .insert(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+  for cookie in cookies {
+    response
+      .headers_mut()
+      .append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+  }
 
   Ok(response)
 }
@@ -110,6 +126,7 @@ async fn login(pool: State<Arc<SqlitePool>>, Json(input): Json<LoginParams>) ->
   tag = USERS_TAG,
   responses(
     (status = OK, description = "Return current logged user", body = User),
+    (status = 304, description = "User unchanged since the `ETag` sent in `If-None-Match`"),
     (status = 401, description = "Unauthorized")
   )
 )]
@@ -122,23 +139,50 @@ async fn get_me(Extension(user): Extension<User>) -> ApiResult<Json<User>> {
   path = "/logout",
   tag = USERS_TAG,
   responses(
-    (status = 200, description = "Logout successful")
+    (status = 200, description = "Logout successful"),
+    (status = 401, description = "Unauthorized")
   )
 )]
-async fn logout() -> ApiResult<impl IntoResponse> {
-  let cookie = build_auth_cookie("".to_string(), false);
+#[instrument(skip(pool, claims))]
+async fn logout(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(claims): Extension<Claims>,
+) -> ApiResult<impl IntoResponse> {
+  mutation::tokens::revoke(&pool, &claims.jti, claims.exp as i64).await?;
 
   let mut response = Response::new(json!({"status": "success"}).to_string());
-  response
-    .headers_mut()
-    .insert(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+  for (name, http_only) in [(AUTH_COOKIE_NAME, true), (CSRF_COOKIE_NAME, false)] {
+    let cookie = build_auth_cookie(name, "".to_string(), false, http_only);
+    response
+      .headers_mut()
+      .append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+  }
   Ok(response)
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
 struct ListUsersParams {
   page: Option<i64>,
+  /// At most `API_MAX_PAGE_SIZE`
   users_per_page: Option<i64>,
+  /// Only return users with exactly this role
+  role: Option<String>,
+  /// Only return users whose username contains this value (case-insensitive)
+  username: Option<String>,
+  /// Only return users whose email contains this value (case-insensitive)
+  email: Option<String>,
+  /// Return a bare array of users without pagination metadata, for clients of the old response shape
+  raw: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct UserList {
+  users: Vec<User>,
+  page: i64,
+  /// Users per page after applying the default and `API_MAX_PAGE_SIZE`
+  users_per_page: i64,
+  total_count: i64,
+  total_pages: i64,
 }
 
 #[utoipa::path(
@@ -149,7 +193,8 @@ struct ListUsersParams {
     ListUsersParams
   ),
   responses(
-    (status = 200, description = "List all users successfully", body = [User]),
+    (status = 200, description = "List all users successfully", body = UserList),
+    (status = 304, description = "Users unchanged since the `ETag` sent in `If-None-Match`"),
     (status = 401, description = "Unauthorized")
   )
 )]
@@ -157,13 +202,32 @@ struct ListUsersParams {
 async fn list_users(
   State(pool): State<Arc<SqlitePool>>,
   Query(params): Query<ListUsersParams>,
-) -> ApiResult<Json<Vec<User>>> {
+) -> ApiResult<impl IntoResponse> {
   let page = params.page.unwrap_or(1);
-  let users_per_page = params.users_per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+  let users_per_page = page_size(params.users_per_page, DEFAULT_PAGE_SIZE)?;
 
-  let (users, _num_pages) = query::users::list(&pool, page, users_per_page).await?;
+  let filter = UserFilter {
+    role: params.role,
+    username: params.username,
+    email: params.email,
+  };
 
-  Ok(Json(users))
+  let (users, total_count, total_pages) = query::users::list(&pool, &filter, page, users_per_page).await?;
+
+  if params.raw.unwrap_or(false) {
+    return Ok(Json(users).into_response());
+  }
+
+  Ok(
+    Json(UserList {
+      users,
+      page,
+      users_per_page,
+      total_count,
+      total_pages,
+    })
+    .into_response(),
+  )
 }
 
 #[derive(Debug, Validate, Deserialize, IntoParams)]
@@ -189,9 +253,10 @@ pub struct CreateUser {
     (status = 422, description = "Validation error")
   )
 )]
-#[instrument(skip(pool, input))]
+#[instrument(skip(pool, actor, input))]
 async fn create_user(
   State(pool): State<Arc<SqlitePool>>,
+  Extension(actor): Extension<User>,
   AppJson(input): AppJson<CreateUser>,
 ) -> ApiResult<Json<User>> {
   input.validate()?;
@@ -204,7 +269,7 @@ async fn create_user(
 
   debug!("Register new user with request: {:?}", params);
 
-  let user = mutation::users::create(&pool, params).await?;
+  let user = mutation::users::create(&pool, Some(actor.id), params).await?;
 
   Ok(Json(user))
 }
@@ -234,9 +299,10 @@ pub struct UpdateUser {
     (status = 422, description = "Validation error")
   )
 )]
-#[instrument(skip(pool, input))]
+#[instrument(skip(pool, actor, input))]
 async fn update_user(
   State(pool): State<Arc<SqlitePool>>,
+  Extension(actor): Extension<User>,
   Path(id): Path<Uuid>,
   Json(input): Json<UpdateUser>,
 ) -> ApiResult<Json<User>> {
@@ -251,7 +317,7 @@ async fn update_user(
 
   debug!("Update user with id {} and params {:?}", id, params);
 
-  let user = mutation::users::update(&pool, id, params).await?;
+  let user = mutation::users::update(&pool, Some(actor.id), id, params).await?;
 
   Ok(Json(user))
 }
@@ -271,16 +337,17 @@ async fn update_user(
 )]
 #[instrument]
 async fn delete_user(State(pool): State<Arc<SqlitePool>>, Path(id): Path<Uuid>) -> ApiResult<StatusCode> {
-  // mutation::users::delete(&pool, id).await?;
+  // mutation::users::delete(&pool, None, id).await?;
 
   Ok(StatusCode::OK)
 }
 
-fn build_auth_cookie(token: String, is_login: bool) -> Cookie<'static> {
-  Cookie::build((AUTH_COOKIE_NAME, token))
+/// The CSRF cookie isn't `HttpOnly` so same-site scripts can copy it into `X-CSRF-Token`
+fn build_auth_cookie(name: &'static str, value: String, is_login: bool, http_only: bool) -> Cookie<'static> {
+  Cookie::build((name, value))
     .path("/")
     .max_age(Duration::hours(if is_login { 24 } else { -1 }))
     .same_site(SameSite::Lax)
-    .http_only(true)
+    .http_only(http_only)
     .build()
 }
@@ -0,0 +1,93 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+  extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+  middleware::from_fn_with_state,
+  response::Response,
+};
+use sqlx::SqlitePool;
+use tokio::{sync::broadcast::error::RecvError, time::timeout};
+use tracing::{debug, instrument, warn};
+use utoipa_axum::{
+  router::{OpenApiRouter, UtoipaMethodRouterExt},
+  routes,
+};
+
+use crate::events::{self, ChangeEvent, EventFilter};
+
+use super::auth::auth_guard;
+
+const EVENTS_TAG: &str = "events";
+/// How long a client may take to accept an event before it's disconnected
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn init_ws_routes(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
+  OpenApiRouter::new().routes(routes!(subscribe_events).layer(from_fn_with_state(state.clone(), auth_guard)))
+}
+
+/// Upgrades to a WebSocket streaming task and project changes as JSON text messages
+///
+/// Every change is sent until the client sends an [`EventFilter`] as a text message, each one replacing the
+/// previous. A client that can't keep up is disconnected with close code 1013 instead of having events
+/// buffered for it, it should reconnect and reload what it displays.
+#[utoipa::path(
+  get,
+  path = "",
+  tag = EVENTS_TAG,
+  responses(
+    (status = 101, description = "Switching to the WebSocket protocol, then one message per change", body = ChangeEvent),
+    (status = 401, description = "Unauthorized")
+  )
+)]
+#[instrument(skip(ws))]
+async fn subscribe_events(ws: WebSocketUpgrade) -> Response {
+  ws.on_upgrade(stream_events)
+}
+
+async fn stream_events(mut socket: WebSocket) {
+  let mut events = events::subscribe();
+  let mut filter = EventFilter::default();
+
+  loop {
+    tokio::select! {
+      message = socket.recv() => match message {
+        Some(Ok(Message::Text(text))) => match serde_json::from_str::<EventFilter>(text.as_str()) {
+          Ok(subscription) => filter = subscription,
+          Err(e) => debug!("Ignoring invalid event subscription: {}", e),
+        },
+        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+        // Pings are answered by the WebSocket implementation
+        Some(Ok(_)) => {},
+      },
+      event = events.recv() => match event {
+        Ok(event) if filter.matches(&event) => {
+          let Ok(text) = serde_json::to_string(&event) else {
+            continue;
+          };
+
+          match timeout(SEND_TIMEOUT, socket.send(Message::Text(text.into()))).await {
+            Ok(Ok(())) => {},
+            Ok(Err(_)) => break,
+            Err(_) => {
+              warn!("Disconnecting an event subscriber that didn't accept an event within {:?}", SEND_TIMEOUT);
+              break;
+            },
+          }
+        },
+        Ok(_) => {},
+        Err(RecvError::Lagged(skipped)) => {
+          warn!("Disconnecting an event subscriber that fell {} events behind", skipped);
+
+          let _ = socket
+            .send(Message::Close(Some(CloseFrame {
+              code: close_code::AGAIN,
+              reason: "Too slow to receive events, reconnect".into(),
+            })))
+            .await;
+          break;
+        },
+        Err(RecvError::Closed) => break,
+      },
+    }
+  }
+}
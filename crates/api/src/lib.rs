@@ -1,17 +1,19 @@
-use std::{env, sync::Arc};
+use std::sync::Arc;
 
 use axum::{
   extract::{FromRequest, State},
   http::{
-    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
     HeaderValue, Method,
   },
+  middleware::{from_fn, from_fn_with_state},
   response::IntoResponse,
   routing::get,
+  Extension, Router,
 };
 use error::ApiError;
 use serde_json::json;
-use sqlx::SqlitePool;
+use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
 use tokio::net::TcpListener;
 use tokio_util::sync::CancellationToken;
 use tower_cookies::CookieManagerLayer;
@@ -21,16 +23,39 @@ use utoipa::OpenApi;
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_swagger_ui::SwaggerUi;
 
-use handlers::{projects::init_projects_routes, tasks::init_tasks_routes, users::init_users_routes};
+use executor::ExecutorHandle;
+use handlers::{
+  admin::init_admin_routes, audit::init_audit_routes, auth::DocsAuth, plugins::init_plugins_routes,
+  projects::init_projects_routes, search::init_search_routes, tasks::init_tasks_routes,
+  templates::init_templates_routes, users::init_users_routes, ws::init_ws_routes,
+};
+use listen::ListenAddr;
+use log_level::LogLevelHandle;
 
 pub mod entities;
 mod error;
+mod etag;
+pub mod events;
+pub mod executor;
 mod handlers;
+pub mod listen;
+pub mod log_level;
+pub mod metrics;
+mod request_id;
 pub mod service;
+pub mod timezone;
 pub mod workers;
 
 const OCTABOT_TAG: &str = "octabot";
 
+#[derive(OpenApi)]
+#[openapi(
+  tags(
+    (name = OCTABOT_TAG, description = "Bot management API")
+  )
+)]
+struct ApiDoc;
+
 #[derive(FromRequest)]
 #[from_request(via(axum::Json), rejection(ApiError))]
 struct AppJson<T>(T);
@@ -52,46 +77,191 @@ async fn health_handler(State(pool): State<Arc<SqlitePool>>) -> impl IntoRespons
   }
 }
 
-pub async fn run(state: Arc<SqlitePool>, cancel_token: CancellationToken) -> anyhow::Result<()> {
-  let host = env::var("HOST").expect("HOST is not set in .env file");
-  let port = env::var("PORT").expect("PORT is not set in .env file");
-  let server_url = format!("{host}:{port}");
+/// Handle detailed health check requests, reporting on the database and the executor
+async fn health_detail_handler(
+  State(pool): State<Arc<SqlitePool>>,
+  Extension(executor): Extension<Arc<dyn ExecutorHandle>>,
+) -> impl IntoResponse {
+  let database_ok = sqlx::query("SELECT 1").execute(&*pool).await.is_ok();
+  let processing = executor.worker_count() > 0 && executor.plugin_count() > 0;
 
-  // Initialize cors settings
-  let cors = CorsLayer::new()
-    .allow_origin("http://localhost:3000".parse::<HeaderValue>()?)
-    .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
-    .allow_credentials(true)
-    .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
+  json!({
+    "code": if database_ok { "200" } else { "500" },
+    "success": database_ok && processing,
+    "database": {
+      "success": database_ok,
+    },
+    "executor": {
+      "id": executor.id(),
+      "plugins": executor.plugin_count(),
+      "workers": executor.worker_count(),
+      "busy_workers": executor.busy_workers(),
+      "queue_depth": executor.queue_depth(),
+      "queue_capacity": executor.queue_capacity(),
+    },
+  })
+  .to_string()
+}
+
+/// Removes a socket file left behind by a previous run, binding would fail on it otherwise. Anything that isn't
+/// a socket is left alone.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> anyhow::Result<()> {
+  use std::os::unix::fs::FileTypeExt;
 
-  #[derive(OpenApi)]
-  #[openapi(
-    tags(
-      (name = OCTABOT_TAG, description = "Bot management API")
-    )
-  )]
-  struct ApiDoc;
+  match std::fs::symlink_metadata(path) {
+    Ok(metadata) if metadata.file_type().is_socket() => Ok(std::fs::remove_file(path)?),
+    Ok(_) => anyhow::bail!(
+      "Can't listen on {}, the path exists and is not a socket",
+      path.display()
+    ),
+    Err(_) => Ok(()),
+  }
+}
 
-  let (router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
+/// Every route of the api, documented ones included
+fn api_router(state: Arc<SqlitePool>) -> OpenApiRouter<Arc<SqlitePool>> {
+  OpenApiRouter::with_openapi(ApiDoc::openapi())
     .route("/health", get(health_handler))
+    .route("/health/detail", get(health_detail_handler))
+    .route("/metrics", get(metrics::metrics_handler))
     .nest("/api/users", init_users_routes(state.clone()))
     .nest("/api/projects", init_projects_routes(state.clone()))
     .nest("/api/tasks", init_tasks_routes(state.clone()))
+    .nest("/api/audit", init_audit_routes(state.clone()))
+    .nest("/api/admin", init_admin_routes(state.clone()))
+    .nest("/api/templates", init_templates_routes(state.clone()))
+    .nest("/api/plugins", init_plugins_routes(state.clone()))
+    .nest("/api/search", init_search_routes(state.clone()))
+    .nest("/api/ws", init_ws_routes(state))
+}
+
+/// Swagger UI and the OpenAPI document, behind `auth_guard` when `API_DOCS_AUTH=jwt`
+fn docs_router(state: Arc<SqlitePool>, api: utoipa::openapi::OpenApi) -> anyhow::Result<Router> {
+  let docs = Router::from(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", api));
+
+  Ok(match handlers::auth::docs_auth()? {
+    DocsAuth::None => docs,
+    DocsAuth::Jwt => docs
+      .layer(from_fn_with_state(state, handlers::auth::auth_guard))
+      .layer(CookieManagerLayer::new()),
+  })
+}
+
+/// Builds the OpenAPI document served at `/api-docs/openapi.json` without starting the server, e.g. to
+/// generate a client from it
+///
+/// Must be called within a Tokio runtime, the routes are built over a database pool that never connects.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+  let pool = SqlitePoolOptions::new()
+    .connect_lazy("sqlite::memory:")
+    .expect("in-memory database url is valid");
+
+  api_router(Arc::new(pool)).split_for_parts().1
+}
+
+/// Checks every setting the api reads from the environment, returning all the invalid ones
+pub fn validate_settings() -> Vec<anyhow::Error> {
+  [
+    handlers::auth::validate_jwt_maxage(),
+    handlers::auth::validate_jwt_keys(),
+    handlers::auth::validate_token_source(),
+    handlers::auth::validate_docs_auth(),
+    handlers::validate_page_sizes(),
+    service::mutation::users::validate_argon2_params(),
+    service::mutation::users::validate_lockout_policy(),
+    service::mutation::tasks::validate_max_retries(),
+    service::mutation::tasks::validate_max_options_bytes(),
+    service::mutation::tasks::validate_exchange_task_max_age(),
+    service::mutation::tasks::validate_deleted_retention(),
+    service::mutation::tasks::validate_min_schedule_interval(),
+    service::mutation::tasks::validate_task_ttl(),
+    timezone::validate_default_tz(),
+  ]
+  .into_iter()
+  .filter_map(Result::err)
+  .collect()
+}
+
+pub async fn run(
+  listen: ListenAddr,
+  state: Arc<SqlitePool>,
+  executor: Arc<dyn ExecutorHandle>,
+  log_level: LogLevelHandle,
+  cancel_token: CancellationToken,
+) -> anyhow::Result<()> {
+  // Initialize cors settings
+  let cors = CorsLayer::new()
+    .allow_origin("http://localhost:3000".parse::<HeaderValue>()?)
+    .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+    .allow_credentials(true)
+    .allow_headers([
+      AUTHORIZATION,
+      ACCEPT,
+      CONTENT_TYPE,
+      IF_NONE_MATCH,
+      handlers::auth::CSRF_HEADER.clone(),
+      request_id::REQUEST_ID_HEADER.clone(),
+    ])
+    .expose_headers([
+      ETAG,
+      handlers::NEXT_CURSOR_HEADER.clone(),
+      request_id::REQUEST_ID_HEADER.clone(),
+    ]);
+
+  let (router, api) = api_router(state.clone())
+    .layer(from_fn(etag::etag_layer))
     .layer(CookieManagerLayer::new())
     .layer(cors)
-    .with_state(state)
+    .layer(from_fn(request_id::request_id_layer))
+    .layer(Extension(executor))
+    .layer(Extension(log_level))
+    .with_state(state.clone())
     .split_for_parts();
 
-  let router = router.merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", api.clone()));
+  let router = router.merge(docs_router(state, api)?);
 
-  info!("Starting api server...");
+  info!("Starting api server on {}...", listen);
 
-  let listener = TcpListener::bind(&server_url).await?;
-  axum::serve(listener, router.into_make_service())
-    .with_graceful_shutdown(Box::pin(async move { cancel_token.cancelled().await }))
-    .await?;
+  let shutdown = Box::pin(async move { cancel_token.cancelled().await });
+
+  match listen {
+    ListenAddr::Tcp(addr) => {
+      let listener = TcpListener::bind(&addr).await?;
+      axum::serve(listener, router.into_make_service())
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    },
+    #[cfg(unix)]
+    ListenAddr::Unix(path) => {
+      remove_stale_socket(&path)?;
+
+      let listener = tokio::net::UnixListener::bind(&path)?;
+      let served = axum::serve(listener, router.into_make_service())
+        .with_graceful_shutdown(shutdown)
+        .await;
+
+      let _ = std::fs::remove_file(&path);
+      served?;
+    },
+    #[cfg(not(unix))]
+    ListenAddr::Unix(_) => unreachable!("unix listen addresses are rejected when parsed"),
+  }
 
   info!("Stopped api server");
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_openapi_without_server() {
+    let api = openapi();
+
+    assert!(api.paths.paths.contains_key("/api/tasks/{id}"));
+    assert!(api.paths.paths.contains_key("/api/search"));
+  }
+}
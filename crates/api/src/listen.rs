@@ -0,0 +1,86 @@
+use std::{env, fmt, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, bail};
+
+const UNIX_PREFIX: &str = "unix:";
+
+/// Address the api server listens on, read from `OCTABOT_LISTEN` as `host:port` or `unix:/path/to.sock`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+  Tcp(String),
+  Unix(PathBuf),
+}
+
+impl ListenAddr {
+  /// Reads `OCTABOT_LISTEN`, falling back to `HOST` and `PORT` when it is not set
+  pub fn from_env() -> anyhow::Result<Self> {
+    if let Ok(spec) = env::var("OCTABOT_LISTEN") {
+      return spec.parse();
+    }
+
+    match (env::var("HOST"), env::var("PORT")) {
+      (Ok(host), Ok(port)) => format!("{host}:{port}").parse(),
+      _ => bail!("HOST and PORT must be set when OCTABOT_LISTEN is not"),
+    }
+  }
+}
+
+impl FromStr for ListenAddr {
+  type Err = anyhow::Error;
+
+  fn from_str(spec: &str) -> Result<Self, Self::Err> {
+    let invalid = || anyhow!("Invalid listen address `{spec}`, expected `host:port` or `unix:/path/to.sock`");
+
+    if let Some(path) = spec.strip_prefix(UNIX_PREFIX) {
+      if path.is_empty() {
+        return Err(invalid());
+      }
+      if !cfg!(unix) {
+        bail!("Unix sockets are not supported on this platform, can't listen on `{spec}`");
+      }
+
+      return Ok(Self::Unix(PathBuf::from(path)));
+    }
+
+    let (host, port) = spec.rsplit_once(':').ok_or_else(invalid)?;
+    if host.is_empty() || port.parse::<u16>().is_err() {
+      return Err(invalid());
+    }
+
+    Ok(Self::Tcp(spec.to_string()))
+  }
+}
+
+impl fmt::Display for ListenAddr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Tcp(addr) => write!(f, "{addr}"),
+      Self::Unix(path) => write!(f, "{UNIX_PREFIX}{}", path.display()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_listen_addr() {
+    assert_eq!(
+      "0.0.0.0:8080".parse::<ListenAddr>().unwrap(),
+      ListenAddr::Tcp("0.0.0.0:8080".to_string())
+    );
+    assert_eq!(
+      "[::1]:8080".parse::<ListenAddr>().unwrap(),
+      ListenAddr::Tcp("[::1]:8080".to_string())
+    );
+    assert_eq!(
+      "unix:/run/octabot.sock".parse::<ListenAddr>().unwrap(),
+      ListenAddr::Unix(PathBuf::from("/run/octabot.sock"))
+    );
+
+    for spec in ["", "localhost", ":8080", "localhost:http", "localhost:70000", "unix:"] {
+      assert!(spec.parse::<ListenAddr>().is_err(), "`{spec}` should be rejected");
+    }
+  }
+}
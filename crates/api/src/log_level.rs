@@ -0,0 +1,39 @@
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::error::{ApiError, ApiResult};
+
+/// Reload handle of the global tracing filter, lets admins change the verbosity without a restart
+#[derive(Clone)]
+pub struct LogLevelHandle {
+  handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogLevelHandle {
+  /// Wraps the filter into a reloadable layer, install the layer on the registry and pass the handle to [`crate::run`]
+  pub fn new(filter: EnvFilter) -> (reload::Layer<EnvFilter, Registry>, Self) {
+    let (layer, handle) = reload::Layer::new(filter);
+
+    (layer, Self { handle })
+  }
+
+  /// Directives of the filter in use, e.g. `info,octabot_executor=debug`
+  pub fn current(&self) -> ApiResult<String> {
+    self
+      .handle
+      .with_current(|filter| filter.to_string())
+      .map_err(|err| ApiError::Anyhow(err.into()))
+  }
+
+  /// Replaces the whole filter, directives from `RUST_LOG` at startup included
+  ///
+  /// # Errors
+  /// - InvalidLogLevel if the directives don't parse, the filter in use is kept
+  pub fn set(&self, directives: &str) -> ApiResult<String> {
+    let filter = EnvFilter::try_new(directives)
+      .map_err(|err| ApiError::InvalidLogLevel(directives.to_string(), err.to_string()))?;
+
+    self.handle.reload(filter).map_err(|err| ApiError::Anyhow(err.into()))?;
+
+    self.current()
+  }
+}
@@ -0,0 +1,23 @@
+use anyhow::anyhow;
+use axum::{http::StatusCode, response::IntoResponse};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::OnceCell;
+
+static PROMETHEUS: OnceCell<PrometheusHandle> = OnceCell::new();
+
+/// Installs the global metrics recorder rendered by `/metrics`, metrics emitted before this are dropped
+pub fn install_recorder() -> anyhow::Result<()> {
+  let handle = PrometheusBuilder::new().install_recorder()?;
+
+  PROMETHEUS
+    .set(handle)
+    .map_err(|_| anyhow!("Metrics recorder is already installed"))
+}
+
+/// Handle metrics requests in the Prometheus text format
+pub(crate) async fn metrics_handler() -> impl IntoResponse {
+  match PROMETHEUS.get() {
+    Some(handle) => (StatusCode::OK, handle.render()),
+    None => (StatusCode::NOT_FOUND, "Metrics recorder is not installed".to_string()),
+  }
+}
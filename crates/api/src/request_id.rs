@@ -0,0 +1,50 @@
+use axum::{
+  extract::Request,
+  http::{HeaderName, HeaderValue},
+  middleware::Next,
+  response::Response,
+};
+use tracing::{info_span, Instrument};
+use uuid::Uuid;
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+  static REQUEST_ID: String;
+}
+
+/// Returns the id of the request currently being handled, if any
+pub fn current() -> Option<String> {
+  REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Reads the `X-Request-Id` header or generates a new id, runs the rest of the stack inside
+/// a span carrying it and echoes it back in the response headers
+pub async fn request_id_layer(req: Request, next: Next) -> Response {
+  let request_id = req
+    .headers()
+    .get(&REQUEST_ID_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LEN)
+    .map(ToOwned::to_owned)
+    .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+  let span = info_span!(
+    "request",
+    request_id = %request_id,
+    method = %req.method(),
+    uri = %req.uri(),
+  );
+
+  let mut response = REQUEST_ID
+    .scope(request_id.clone(), next.run(req).instrument(span))
+    .await;
+
+  if let Ok(value) = HeaderValue::from_str(&request_id) {
+    response.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+  }
+
+  response
+}
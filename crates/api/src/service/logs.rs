@@ -0,0 +1,79 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::Mutex,
+};
+
+use once_cell::sync::Lazy;
+use uuid::Uuid;
+
+use crate::entities::task::TaskLog;
+
+/// Most tasks whose logs are kept, the logs of the task that ran least recently are dropped first
+const MAX_TASKS: usize = 1000;
+/// Most lines kept for a single run of a task, the oldest are dropped first
+const MAX_LINES_PER_TASK: usize = 1000;
+
+/// Lines plugins logged during the last run of each task, kept in memory so they are lost on restart
+static TASK_LOGS: Lazy<Mutex<TaskLogs>> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct TaskLogs {
+  /// Task ids from the least to the most recently run
+  order: VecDeque<Uuid>,
+  lines: HashMap<Uuid, VecDeque<TaskLog>>,
+}
+
+impl TaskLogs {
+  fn remove(&mut self, task_id: &Uuid) {
+    if self.lines.remove(task_id).is_some() {
+      self.order.retain(|id| id != task_id);
+    }
+  }
+}
+
+/// Drops the logs of the previous run of a task, called when a new run starts
+pub fn start_run(task_id: Uuid) {
+  let mut logs = TASK_LOGS.lock().expect("task logs lock poisoned");
+
+  logs.remove(&task_id);
+  while logs.order.len() >= MAX_TASKS {
+    if let Some(oldest) = logs.order.pop_front() {
+      logs.lines.remove(&oldest);
+    }
+  }
+
+  logs.order.push_back(task_id);
+  logs.lines.insert(task_id, VecDeque::new());
+}
+
+/// Appends lines to the current run of a task, lines of a task without a started run are dropped
+pub fn append(task_id: Uuid, new_lines: impl IntoIterator<Item = TaskLog>) {
+  let mut logs = TASK_LOGS.lock().expect("task logs lock poisoned");
+
+  let Some(lines) = logs.lines.get_mut(&task_id) else {
+    return;
+  };
+
+  for line in new_lines {
+    if lines.len() == MAX_LINES_PER_TASK {
+      lines.pop_front();
+    }
+    lines.push_back(line);
+  }
+}
+
+/// Lines logged during the last run of a task, empty when it hasn't run since the server started
+pub fn get(task_id: Uuid) -> Vec<TaskLog> {
+  let logs = TASK_LOGS.lock().expect("task logs lock poisoned");
+
+  logs
+    .lines
+    .get(&task_id)
+    .map(|lines| lines.iter().cloned().collect())
+    .unwrap_or_default()
+}
+
+/// Removes the logs of a deleted task
+pub fn remove(task_id: Uuid) {
+  TASK_LOGS.lock().expect("task logs lock poisoned").remove(&task_id);
+}
@@ -1,2 +1,8 @@
+pub mod logs;
 pub mod mutation;
+pub mod options;
+pub mod outputs;
 pub mod query;
+#[cfg(test)]
+pub mod test_utils;
+pub mod webhook;
@@ -0,0 +1,63 @@
+use serde_json::{json, Map, Value};
+use sqlx::{Executor, Sqlite};
+use uuid::Uuid;
+
+use crate::{
+  entities::audit::{AuditAction, AuditEntity},
+  error::ApiResult,
+};
+
+const INSERT_AUDIT_ENTRY: &str = r#"
+  INSERT INTO audit_log (id, actor_id, action, entity_type, entity_id, diff)
+  VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+"#;
+
+/// Records a successfully applied mutation in the audit log
+///
+/// # Arguments
+/// * `executor` - The database connection pool, or a transaction to record the entry with the mutation
+/// * `actor_id` - The user who performed the action, `None` for system actions
+/// * `action` - The kind of mutation
+/// * `entity` - The type of the mutated entity
+/// * `entity_id` - The id of the mutated entity
+/// * `diff` - The changes made by the action
+pub async fn record<'e, E: Executor<'e, Database = Sqlite>>(
+  executor: E,
+  actor_id: Option<Uuid>,
+  action: AuditAction,
+  entity: AuditEntity,
+  entity_id: Uuid,
+  diff: Value,
+) -> ApiResult<()> {
+  sqlx::query(INSERT_AUDIT_ENTRY)
+    .bind(Uuid::new_v4())
+    .bind(actor_id)
+    .bind(action.to_string())
+    .bind(entity.to_string())
+    .bind(entity_id)
+    .bind(diff)
+    .execute(executor)
+    .await?;
+
+  Ok(())
+}
+
+/// Builds a `{field: {from, to}}` object with the top-level fields that differ between two entity snapshots
+pub fn diff(before: &Value, after: &Value) -> Value {
+  let empty = Map::new();
+  let before = before.as_object().unwrap_or(&empty);
+  let after = after.as_object().unwrap_or(&empty);
+
+  let changes = after
+    .iter()
+    .filter(|(key, value)| before.get(*key) != Some(*value))
+    .map(|(key, value)| {
+      (
+        key.clone(),
+        json!({ "from": before.get(key).cloned().unwrap_or(Value::Null), "to": value }),
+      )
+    })
+    .collect::<Map<String, Value>>();
+
+  Value::Object(changes)
+}
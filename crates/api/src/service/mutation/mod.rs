@@ -1,3 +1,7 @@
+pub mod audit;
+pub mod plugins;
 pub mod projects;
 pub mod tasks;
+pub mod templates;
+pub mod tokens;
 pub mod users;
@@ -0,0 +1,58 @@
+use serde_json::Value;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{entities::plugin::PluginOptionsRow, error::ApiResult};
+
+// SQL Query Constants
+const UPSERT_PLUGIN_OPTIONS: &str = r#"
+  INSERT INTO plugin_options (name, options, updated_by)
+  VALUES (?1, ?2, ?3)
+  ON CONFLICT (name) DO UPDATE SET
+    options = excluded.options,
+    updated_by = excluded.updated_by,
+    updated_at = CURRENT_TIMESTAMP
+  RETURNING *
+"#;
+
+/// Stores the options a plugin was reconfigured with, so the executor initializes it with them after a restart
+pub async fn save_options(
+  pool: &SqlitePool,
+  actor_id: Option<Uuid>,
+  name: &str,
+  options: &Value,
+) -> ApiResult<PluginOptionsRow> {
+  Ok(
+    sqlx::query_as::<_, PluginOptionsRow>(UPSERT_PLUGIN_OPTIONS)
+      .bind(name)
+      .bind(options)
+      .bind(actor_id)
+      .fetch_one(pool)
+      .await?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+  use crate::service::{query, test_utils::test_pool};
+
+  #[tokio::test]
+  async fn test_save_options_replaces_previous() {
+    let pool = test_pool().await;
+
+    save_options(&pool, None, "github", &json!({ "poll": "octahive/octabot" }))
+      .await
+      .unwrap();
+    save_options(&pool, None, "github", &json!({ "poll": "octahive/octafile" }))
+      .await
+      .unwrap();
+
+    let stored = query::plugins::list_options(&pool).await.unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].name, "github");
+    assert_eq!(stored[0].options, json!({ "poll": "octahive/octafile" }));
+  }
+}
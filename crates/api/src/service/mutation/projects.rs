@@ -1,32 +1,43 @@
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use serde_json::{json, Value};
-use sqlx::SqlitePool;
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
 use uuid::Uuid;
 
 use crate::{
   entities::{
+    audit::{AuditAction, AuditEntity},
     project::{Project, ProjectRow},
+    task::TaskRow,
     user::User,
   },
   error::{ApiError, ApiResult},
+  events::{self, Change},
 };
 
+use super::audit;
+
+const PROJECT_CODE_MIN_LEN: usize = 2;
+const PROJECT_CODE_MAX_LEN: usize = 4;
+
 // SQL Query Constants
-const FIND_PROJECT_BY_CODE: &str = "SELECT * FROM projects WHERE code = ?1";
+const FIND_OTHER_PROJECT_BY_CODE: &str =
+  "SELECT * FROM projects WHERE code = ?1 COLLATE NOCASE AND (?2 IS NULL OR id != ?2)";
 const FIND_PROJECT_BY_ID: &str = "SELECT * FROM projects WHERE id = ?1";
 const FIND_USER: &str = "SELECT * FROM users WHERE id = ?1";
 const INSERT_PROJECT: &str = r#"
-    INSERT INTO projects (id, name, code, owner_id, options)
-    VALUES (?1, ?2, ?3, ?4, ?5)
+    INSERT INTO projects (id, name, code, owner_id, options, created_by)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
     RETURNING *
 "#;
 const UPDATE_PROJECT: &str = r#"
     UPDATE projects
-    SET name = ?1, code = ?2, options = ?3
-    WHERE id = ?4
+    SET name = ?1, code = ?2, options = ?3, updated_at = CURRENT_TIMESTAMP, version = version + 1
+    WHERE id = ?4 AND (?5 IS NULL OR datetime(updated_at) <= datetime(?5)) AND (?6 IS NULL OR version = ?6)
     RETURNING *
 "#;
 const DELETE_PROJECT: &str = "DELETE FROM projects WHERE id = ?";
+const SELECT_PROJECT_TASKS: &str = "SELECT * FROM tasks WHERE project_id = ?1";
 
 #[derive(Debug, Deserialize)]
 pub struct CreateProjectParams {
@@ -38,13 +49,32 @@ pub struct CreateProjectParams {
 
 /// Creates a new project with the given parameters
 ///
+/// The code is normalized to uppercase before it is validated and stored
+///
 /// # Errors
-/// - ProjectAlreadyExist if a project with the same code exists
+/// - InvalidProjectCode if the code isn't 2-4 letters or digits
+/// - ProjectAlreadyExist if a project with the same code exists, compared case-insensitively
 /// - DatabaseError for any database-related issues
-pub async fn create(pool: &SqlitePool, params: CreateProjectParams) -> ApiResult<Project> {
-  ensure_project_not_exists(pool, &params.code).await?;
+pub async fn create(pool: &SqlitePool, actor_id: Option<Uuid>, mut params: CreateProjectParams) -> ApiResult<Project> {
+  params.code = normalize_code(&params.code)?;
+  ensure_project_not_exists(pool, &params.code, None).await?;
+
+  let mut tx = pool.begin().await?;
+  let project = create_project_row(&mut tx, actor_id, &params).await?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Create,
+    AuditEntity::Project,
+    project.id,
+    json!(project),
+  )
+  .await?;
+
+  tx.commit().await?;
+  events::project_changed(project.id, Change::Created);
 
-  let project = create_project_row(pool, &params).await?;
   let owner = get_user(pool, params.owner_id).await?;
 
   Ok(build_project(project, owner))
@@ -55,38 +85,169 @@ pub struct UpdateProjectParams {
   pub name: String,
   pub code: String,
   pub options: Option<Value>,
+  /// Only update the project if it wasn't modified after this time, to the second
+  pub unmodified_since: Option<DateTime<Utc>>,
+  /// Only update the project if it's still at this version
+  pub version: Option<i64>,
 }
 
-/// Updates an existing project by ID
+/// Updates an existing project by ID, normalizing the code like [`create`]
 ///
 /// # Errors
 /// - ResourceNotFound if project doesn't exist
+/// - InvalidProjectCode if the code isn't 2-4 letters or digits
+/// - ProjectAlreadyExist if another project has the same code, compared case-insensitively
+/// - Conflict if the project was modified after `unmodified_since` or is no longer at `version`
 /// - DatabaseError for any database-related issues
-pub async fn update(pool: &SqlitePool, id: Uuid, params: UpdateProjectParams) -> ApiResult<Project> {
+pub async fn update(
+  pool: &SqlitePool,
+  actor_id: Option<Uuid>,
+  id: Uuid,
+  mut params: UpdateProjectParams,
+) -> ApiResult<Project> {
   let existing = get_project(pool, id).await?;
 
-  let project = update_project_row(pool, id, params, existing.options).await?;
+  params.code = normalize_code(&params.code)?;
+  ensure_project_not_exists(pool, &params.code, Some(id)).await?;
+
+  let mut tx = pool.begin().await?;
+  let project = update_project_row(&mut tx, id, params, existing.options.clone()).await?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::Project,
+    id,
+    audit::diff(&json!(existing), &json!(project)),
+  )
+  .await?;
+
+  tx.commit().await?;
+  events::project_changed(project.id, Change::Updated);
+
   let owner = get_user(pool, project.owner_id).await?;
 
   Ok(build_project(project, owner))
 }
 
-/// Deletes a project by ID
+/// Fields of a partial project update, `None` leaves the column untouched
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct PatchProjectParams {
+  pub name: Option<String>,
+  pub code: Option<String>,
+  pub options: Option<Value>,
+  /// Only update the project if it wasn't modified after this time, to the second
+  pub unmodified_since: Option<DateTime<Utc>>,
+  /// Only update the project if it's still at this version
+  pub version: Option<i64>,
+}
+
+/// Updates only the given fields of a project, normalizing the code like [`create`]
+///
+/// A patch setting no field returns the project as is, without audit entry or event.
 ///
 /// # Errors
 /// - ResourceNotFound if project doesn't exist
+/// - InvalidProjectCode if the code isn't 2-4 letters or digits
+/// - ProjectAlreadyExist if another project has the same code, compared case-insensitively
+/// - Conflict if the project was modified after `unmodified_since` or is no longer at `version`
 /// - DatabaseError for any database-related issues
-pub async fn delete(pool: &SqlitePool, id: Uuid) -> ApiResult<()> {
-  ensure_project_exists(pool, id).await?;
+pub async fn patch(
+  pool: &SqlitePool,
+  actor_id: Option<Uuid>,
+  id: Uuid,
+  mut params: PatchProjectParams,
+) -> ApiResult<Project> {
+  let existing = get_project(pool, id).await?;
 
-  sqlx::query(DELETE_PROJECT).bind(id).execute(pool).await?;
+  if let Some(code) = &params.code {
+    let code = normalize_code(code)?;
+    ensure_project_not_exists(pool, &code, Some(id)).await?;
+    params.code = Some(code);
+  }
+
+  let mut tx = pool.begin().await?;
+  let Some(project) = patch_project_row(&mut tx, id, &params).await? else {
+    // Nothing changed, so nothing to audit or publish
+    tx.rollback().await?;
+    let owner = get_user(pool, existing.owner_id).await?;
+    return Ok(build_project(existing, owner));
+  };
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::Project,
+    id,
+    audit::diff(&json!(existing), &json!(project)),
+  )
+  .await?;
+
+  tx.commit().await?;
+  events::project_changed(project.id, Change::Updated);
+
+  let owner = get_user(pool, project.owner_id).await?;
 
-  Ok(())
+  Ok(build_project(project, owner))
+}
+
+/// Deletes a project by ID along with its tasks, deleted ones included
+///
+/// # Returns
+/// The ids of the deleted tasks, whose outputs and logs are left to the caller like for [`super::tasks::delete`]
+///
+/// # Errors
+/// - ResourceNotFound if project doesn't exist
+/// - DatabaseError for any database-related issues
+pub async fn delete(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid) -> ApiResult<Vec<Uuid>> {
+  let existing = get_project(pool, id).await?;
+
+  let mut tx = pool.begin().await?;
+  let tasks = sqlx::query_as::<_, TaskRow>(SELECT_PROJECT_TASKS)
+    .bind(id)
+    .fetch_all(&mut *tx)
+    .await?;
+  sqlx::query(DELETE_PROJECT).bind(id).execute(&mut *tx).await?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Delete,
+    AuditEntity::Project,
+    id,
+    json!(existing),
+  )
+  .await?;
+
+  tx.commit().await?;
+  events::project_changed(id, Change::Deleted);
+  for task in &tasks {
+    events::task_changed(task, Change::Deleted);
+  }
+
+  Ok(tasks.into_iter().map(|task| task.id).collect())
+}
+
+/// Uppercases a project code and checks that it matches `^[A-Z0-9]{2,4}$`
+pub(crate) fn normalize_code(code: &str) -> ApiResult<String> {
+  let code = code.trim().to_uppercase();
+
+  let valid = (PROJECT_CODE_MIN_LEN..=PROJECT_CODE_MAX_LEN).contains(&code.len())
+    && code.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+
+  if valid {
+    Ok(code)
+  } else {
+    Err(ApiError::InvalidProjectCode(code))
+  }
 }
 
-async fn ensure_project_not_exists(pool: &SqlitePool, code: &str) -> ApiResult<()> {
-  let exists = sqlx::query_as::<_, ProjectRow>(FIND_PROJECT_BY_CODE)
+async fn ensure_project_not_exists(pool: &SqlitePool, code: &str, except_id: Option<Uuid>) -> ApiResult<()> {
+  let exists = sqlx::query_as::<_, ProjectRow>(FIND_OTHER_PROJECT_BY_CODE)
     .bind(code)
+    .bind(except_id)
     .fetch_optional(pool)
     .await
     .map_err(ApiError::DatabaseError)?;
@@ -97,10 +258,6 @@ async fn ensure_project_not_exists(pool: &SqlitePool, code: &str) -> ApiResult<(
   }
 }
 
-async fn ensure_project_exists(pool: &SqlitePool, id: Uuid) -> ApiResult<()> {
-  get_project(pool, id).await.map(|_| ())
-}
-
 async fn get_project(pool: &SqlitePool, id: Uuid) -> ApiResult<ProjectRow> {
   sqlx::query_as::<_, ProjectRow>(FIND_PROJECT_BY_ID)
     .bind(id)
@@ -117,20 +274,25 @@ async fn get_user(pool: &SqlitePool, user_id: Uuid) -> ApiResult<User> {
     .map_err(Into::into)
 }
 
-async fn create_project_row(pool: &SqlitePool, params: &CreateProjectParams) -> ApiResult<ProjectRow> {
+async fn create_project_row(
+  conn: &mut SqliteConnection,
+  actor_id: Option<Uuid>,
+  params: &CreateProjectParams,
+) -> ApiResult<ProjectRow> {
   sqlx::query_as::<_, ProjectRow>(INSERT_PROJECT)
     .bind(Uuid::new_v4())
     .bind(&params.name)
     .bind(&params.code)
     .bind(params.owner_id)
     .bind(params.options.clone().unwrap_or_else(|| json!({})))
-    .fetch_one(pool)
+    .bind(actor_id)
+    .fetch_one(conn)
     .await
     .map_err(Into::into)
 }
 
 async fn update_project_row(
-  pool: &SqlitePool,
+  conn: &mut SqliteConnection,
   id: Uuid,
   params: UpdateProjectParams,
   existing_options: Value,
@@ -140,9 +302,74 @@ async fn update_project_row(
     .bind(&params.code)
     .bind(params.options.unwrap_or(existing_options))
     .bind(id)
-    .fetch_one(pool)
-    .await
-    .map_err(Into::into)
+    .bind(params.unmodified_since)
+    .bind(params.version)
+    .fetch_optional(conn)
+    .await?
+    .ok_or_else(|| modified_conflict(id))
+}
+
+/// Updates the columns set in `params`, returns `None` without touching the row when there are none
+///
+/// The `version` and `unmodified_since` guards apply either way, a stale empty patch is a conflict as well.
+async fn patch_project_row(
+  conn: &mut SqliteConnection,
+  id: Uuid,
+  params: &PatchProjectParams,
+) -> ApiResult<Option<ProjectRow>> {
+  let mut query = QueryBuilder::<Sqlite>::new("UPDATE projects SET ");
+  let mut columns = query.separated(", ");
+  let mut changed = false;
+
+  if let Some(name) = &params.name {
+    columns.push("name = ").push_bind_unseparated(name);
+    changed = true;
+  }
+  if let Some(code) = &params.code {
+    columns.push("code = ").push_bind_unseparated(code);
+    changed = true;
+  }
+  if let Some(options) = &params.options {
+    columns.push("options = ").push_bind_unseparated(options);
+    changed = true;
+  }
+
+  let mut query = if changed {
+    columns.push("updated_at = CURRENT_TIMESTAMP");
+    columns.push("version = version + 1");
+    query
+  } else {
+    // Nothing to write, but a stale `version` or `unmodified_since` is still rejected
+    QueryBuilder::new("SELECT * FROM projects")
+  };
+
+  query.push(" WHERE id = ").push_bind(id);
+  if let Some(unmodified_since) = params.unmodified_since {
+    query
+      .push(" AND datetime(updated_at) <= datetime(")
+      .push_bind(unmodified_since)
+      .push(")");
+  }
+  if let Some(version) = params.version {
+    query.push(" AND version = ").push_bind(version);
+  }
+  if changed {
+    query.push(" RETURNING *");
+  }
+
+  query
+    .build_query_as::<ProjectRow>()
+    .fetch_optional(conn)
+    .await?
+    .map(|row| changed.then_some(row))
+    .ok_or_else(|| modified_conflict(id))
+}
+
+fn modified_conflict(id: Uuid) -> ApiError {
+  ApiError::Conflict(format!(
+    "Project `{}` was modified in the meantime, reload it and retry",
+    id
+  ))
 }
 
 fn build_project(project: ProjectRow, owner: User) -> Project {
@@ -152,7 +379,154 @@ fn build_project(project: ProjectRow, owner: User) -> Project {
     code: project.code,
     options: project.options,
     owner,
+    created_by: project.created_by,
     created_at: project.created_at,
     updated_at: project.updated_at,
+    version: project.version,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::StatusCode;
+  use chrono::Duration;
+
+  use super::*;
+  use crate::service::test_utils::{task_params, test_pool, SEED_PROJECT_ID};
+
+  #[test]
+  fn test_normalize_code() {
+    assert_eq!(normalize_code("ppf").unwrap(), "PPF");
+    assert_eq!(normalize_code("A1").unwrap(), "A1");
+    assert!(matches!(normalize_code("A"), Err(ApiError::InvalidProjectCode(_))));
+    assert!(matches!(normalize_code("ABCDE"), Err(ApiError::InvalidProjectCode(_))));
+    assert!(matches!(normalize_code("A B"), Err(ApiError::InvalidProjectCode(_))));
+    assert!(matches!(normalize_code("Ä1"), Err(ApiError::InvalidProjectCode(_))));
+  }
+
+  #[tokio::test]
+  async fn test_delete_returns_cascaded_tasks() {
+    let pool = test_pool().await;
+
+    let id = Uuid::parse_str(SEED_PROJECT_ID).unwrap();
+    let task = crate::service::mutation::tasks::create(&pool, None, task_params("cascaded"))
+      .await
+      .unwrap();
+
+    // Their outputs are removed by the caller, so every task gone with the project must be returned
+    assert_eq!(delete(&pool, None, id).await.unwrap(), vec![task.id]);
+    assert!(matches!(
+      get_project(&pool, id).await,
+      Err(ApiError::ResourceNotFound(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_duplicate_code_rejected() {
+    let pool = test_pool().await;
+
+    let params = |code: &str| CreateProjectParams {
+      name: "platform".to_string(),
+      code: code.to_string(),
+      owner_id: Uuid::from_u128(0x01020304050607080910111213141516),
+      options: None,
+    };
+    let project = create(&pool, None, params("DUP")).await.unwrap();
+
+    let duplicate = create(&pool, None, params("dup")).await.unwrap_err();
+    assert!(matches!(duplicate, ApiError::ProjectAlreadyExist(ref code) if code == "DUP"));
+    assert_eq!(duplicate.response().0, StatusCode::CONFLICT);
+
+    // Keeping its own code isn't a conflict, taking the code of the seed project is
+    let keep = PatchProjectParams {
+      code: Some("dup".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(patch(&pool, None, project.id, keep).await.unwrap().code, "DUP");
+    let take = PatchProjectParams {
+      code: Some("ppf".to_string()),
+      ..Default::default()
+    };
+    assert!(matches!(
+      patch(&pool, None, project.id, take).await,
+      Err(ApiError::ProjectAlreadyExist(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_patch_rejects_stale_update() {
+    let pool = test_pool().await;
+
+    let id = Uuid::parse_str(SEED_PROJECT_ID).unwrap();
+    let project = get_project(&pool, id).await.unwrap();
+
+    let stale = PatchProjectParams {
+      name: Some("renamed".to_string()),
+      unmodified_since: Some(project.updated_at - Duration::seconds(1)),
+      ..Default::default()
+    };
+    assert!(matches!(
+      patch(&pool, None, id, stale).await,
+      Err(ApiError::Conflict(_))
+    ));
+
+    let current = PatchProjectParams {
+      name: Some("renamed".to_string()),
+      unmodified_since: Some(project.updated_at),
+      ..Default::default()
+    };
+    assert_eq!(patch(&pool, None, id, current).await.unwrap().name, "renamed");
+  }
+
+  #[tokio::test]
+  async fn test_patch_rejects_stale_version() {
+    let pool = test_pool().await;
+
+    let id = Uuid::parse_str(SEED_PROJECT_ID).unwrap();
+    let version = get_project(&pool, id).await.unwrap().version;
+
+    let first = PatchProjectParams {
+      name: Some("first".to_string()),
+      version: Some(version),
+      ..Default::default()
+    };
+    let project = patch(&pool, None, id, first).await.unwrap();
+    assert_eq!(project.version, version + 1);
+
+    // Same second as the first update, only the version tells them apart
+    let second = PatchProjectParams {
+      name: Some("second".to_string()),
+      version: Some(version),
+      ..Default::default()
+    };
+    assert!(matches!(
+      patch(&pool, None, id, second).await,
+      Err(ApiError::Conflict(_))
+    ));
+
+    // Setting no column doesn't skip the guard
+    let empty = PatchProjectParams {
+      version: Some(version),
+      ..Default::default()
+    };
+    assert!(matches!(
+      patch(&pool, None, id, empty).await,
+      Err(ApiError::Conflict(_))
+    ));
+    let current = PatchProjectParams {
+      version: Some(project.version),
+      ..Default::default()
+    };
+    assert_eq!(patch(&pool, None, id, current).await.unwrap().version, project.version);
+
+    let params = UpdateProjectParams {
+      name: "second".to_string(),
+      code: "PPF".to_string(),
+      options: None,
+      unmodified_since: None,
+      version: Some(project.version),
+    };
+    assert_eq!(update(&pool, None, id, params).await.unwrap().version, version + 2);
+    assert_eq!(get_project(&pool, id).await.unwrap().name, "second");
   }
 }
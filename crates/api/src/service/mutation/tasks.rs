@@ -1,28 +1,55 @@
+use std::{env, str::FromStr};
+
+use anyhow::anyhow;
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
-use serde_json::Value;
+use futures::{Stream, TryStreamExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use sqlx::sqlite::SqliteRow;
-use sqlx::{SqlitePool, Row};
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+use tracing::warn;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
   entities::{
+    audit::{AuditAction, AuditEntity},
     project::ProjectRow,
-    task::{Task, TaskRow, TaskStatus},
+    task::{ExportRecord, MisfirePolicy, Task, TaskRow, TaskStatus},
   },
   error::{ApiError, ApiResult},
+  events::{self, Change},
+  timezone,
 };
 
+use super::{audit, projects::normalize_code};
+
 // SQL Query Constants
 const INSERT_TASK: &str = r#"
-  INSERT INTO tasks (id, type, project_id, name, external_id, external_modified_at, schedule, start_at, options)
-  VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+  INSERT INTO tasks (
+    id, type, project_id, name, external_id, external_modified_at, schedule, start_at, end_at, misfire_policy, options,
+    created_by, plugin_version, schedule_anchor
+  )
+  VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?8)
   ON CONFLICT (external_id) DO UPDATE SET
     name = excluded.name,
+    plugin_version = excluded.plugin_version,
     start_at = excluded.start_at,
+    schedule_anchor = excluded.schedule_anchor,
+    end_at = excluded.end_at,
+    misfire_policy = excluded.misfire_policy,
     schedule = excluded.schedule,
     external_modified_at = excluded.external_modified_at,
     options = excluded.options,
+    -- A failed task changed upstream since it was last synced runs again, decided on the row being replaced so
+    -- concurrent syncs can't lose the reset
+    status = CASE
+      WHEN tasks.status = 'failed' AND julianday(excluded.external_modified_at) > julianday(tasks.external_modified_at)
+      THEN 'new'
+      ELSE tasks.status
+    END,
+    deleted_at = NULL,
     updated_at = CURRENT_TIMESTAMP
   RETURNING *
 "#;
@@ -30,8 +57,10 @@ const INSERT_TASK: &str = r#"
 const SELECT_TASKS_TO_RUN: &str = r#"
   SELECT t.id
   FROM tasks t
-  WHERE t.status NOT IN ('finished', 'in_progress')
-  AND t.retries < 3
+  WHERE t.status NOT IN ('finished', 'in_progress', 'dead', 'cancelled')
+  AND t.enabled = 1
+  AND t.deleted_at IS NULL
+  AND t.retries < ?1
   AND t.start_at <= unixepoch()
   AND (t.locked_at IS NULL OR t.locked_at < datetime('now', '-5 minutes'))
   ORDER BY t.id
@@ -40,9 +69,30 @@ const SELECT_TASKS_TO_RUN: &str = r#"
 const UPDATE_TASKS_STATUS: &str = r#"
   UPDATE tasks
   SET status = 'in_progress',
-    locked_at = datetime('now')
+    locked_at = datetime('now'),
+    locked_by = ?,
+    version = version + 1
   WHERE id IN
 "#;
+/// Tasks left `in_progress` by another executor that stopped refreshing its locks, most likely because it died
+const RECLAIM_STALE_TASKS: &str = r#"
+  UPDATE tasks
+  SET status = 'new', locked_at = NULL, locked_by = NULL, version = version + 1
+  WHERE status = 'in_progress' AND locked_by IS NOT ?1 AND locked_at < datetime('now', '-5 minutes')
+  RETURNING *
+"#;
+/// Tasks an executor with the same `EXECUTOR_ID` was running before it restarted, none of them is running anymore
+const RELEASE_LOCKS: &str = r#"
+  UPDATE tasks
+  SET status = 'new', locked_at = NULL, locked_by = NULL, version = version + 1
+  WHERE status = 'in_progress' AND locked_by = ?1
+  RETURNING *
+"#;
+const REFRESH_LOCKS: &str = r#"
+  UPDATE tasks
+  SET locked_at = datetime('now')
+  WHERE locked_by = ?1 AND status = 'in_progress'
+"#;
 
 const SELECT_TASKS_WITH_PROJECTS: &str = r#"
   SELECT
@@ -51,20 +101,31 @@ const SELECT_TASKS_WITH_PROJECTS: &str = r#"
     p.code as project_code,
     p.options as project_options,
     p.owner_id as project_owner_id,
+    p.created_by as project_created_by,
     p.created_at as project_created_at,
     p.updated_at as project_updated_at,
+    p.version as project_version,
     t.id as task_id,
     t.type as task_type,
+    t.plugin_version as task_plugin_version,
     t.status as task_status,
     t.options as task_options,
     t.start_at as task_start_at,
+    t.schedule_anchor as task_schedule_anchor,
+    t.locked_by as task_locked_by,
+    t.end_at as task_end_at,
+    t.misfire_policy as task_misfire_policy,
+    t.enabled as task_enabled,
     t.schedule as task_schedule,
     t.name as task_name,
     t.retries as task_retries,
     t.external_id as task_external_id,
     t.external_modified_at as task_external_modified_at,
+    t.created_by as task_created_by,
     t.created_at as task_created_at,
-    t.updated_at as task_updated_at
+    t.updated_at as task_updated_at,
+    t.version as task_version,
+    t.failed_results as task_failed_results
   FROM tasks t
   LEFT JOIN projects p ON t.project_id = p.id
   WHERE t.id IN
@@ -72,263 +133,1661 @@ const SELECT_TASKS_WITH_PROJECTS: &str = r#"
 
 const UPDATE_TASK: &str = r#"
   UPDATE tasks
-  SET name = ?1, schedule = ?2, start_at = ?3, options = ?4
-  WHERE id = ?5
+  SET name = ?1, schedule = ?2, start_at = ?3, schedule_anchor = ?3, end_at = ?4, options = ?5,
+    type = COALESCE(?6, type),
+    project_id = COALESCE(?7, project_id),
+    misfire_policy = COALESCE(?8, misfire_policy),
+    plugin_version = ?11,
+    updated_at = CURRENT_TIMESTAMP, version = version + 1
+  WHERE id = ?9 AND (?10 IS NULL OR datetime(updated_at) <= datetime(?10)) AND (?12 IS NULL OR version = ?12)
   RETURNING *
 "#;
 
-const FIND_TASK: &str = "SELECT * FROM tasks WHERE id = ?1";
+const FIND_TASK: &str = "SELECT * FROM tasks WHERE id = ?1 AND deleted_at IS NULL";
+const FIND_TASK_WITH_DELETED: &str = "SELECT * FROM tasks WHERE id = ?1";
 const FIND_TASK_BY_EXTERNAL_ID: &str = "SELECT * FROM tasks WHERE external_id = ?1";
 const FIND_PROJECT: &str = "SELECT * FROM projects WHERE id = ?1";
+const FIND_OTHER_PROJECT_BY_CODE: &str = "SELECT * FROM projects WHERE code = ?1 COLLATE NOCASE AND id != ?2";
 const DELETE_TASK: &str = "DELETE FROM tasks WHERE id = ?";
-const SCHEDULE_TASK: &str = "UPDATE tasks SET status = ?1, start_at = ?2 WHERE id = ?3 RETURNING *";
-const UPDATE_TASK_STATUS: &str = "UPDATE tasks SET status = ?1 WHERE id = ?2 RETURNING *";
-const DELETE_OLD_TASKS: &str = "DELETE FROM tasks WHERE status = 'finished' AND updated_at < date('now','-1 day')";
+const SOFT_DELETE_TASK: &str = r#"
+  UPDATE tasks
+  SET deleted_at = CURRENT_TIMESTAMP, locked_at = NULL, locked_by = NULL
+  WHERE id = ?1 AND deleted_at IS NULL
+"#;
+const RESTORE_TASK: &str =
+  "UPDATE tasks SET deleted_at = NULL, version = version + 1 WHERE id = ?1 AND deleted_at IS NOT NULL RETURNING *";
+const SCHEDULE_TASK: &str =
+  "UPDATE tasks SET status = ?1, start_at = ?2, locked_by = NULL, version = version + 1 WHERE id = ?3 RETURNING *";
+const UPDATE_TASK_STATUS: &str =
+  "UPDATE tasks SET status = ?1, locked_by = NULL, version = version + 1 WHERE id = ?2 RETURNING *";
+const FAIL_TASK: &str = r#"
+  UPDATE tasks
+  SET status = 'failed', retries = retries + 1, locked_by = NULL, version = version + 1
+  WHERE id = ?1
+  RETURNING *
+"#;
+const SET_FAILED_RESULTS: &str =
+  "UPDATE tasks SET failed_results = ?2, version = version + 1 WHERE id = ?1 RETURNING *";
+const RESET_TASK_RETRIES: &str = "UPDATE tasks SET retries = 0, version = version + 1 WHERE id = ?1 RETURNING *";
+const ESCALATE_DEAD_TASKS: &str = r#"
+  UPDATE tasks
+  SET status = 'dead', locked_at = NULL, locked_by = NULL, version = version + 1
+  WHERE status = 'failed' AND retries >= ?1
+  RETURNING *
+"#;
+const RELEASE_TASK: &str = r#"
+  UPDATE tasks
+  SET status = 'new', locked_at = NULL, locked_by = NULL, version = version + 1
+  WHERE id = ?1 AND status = 'in_progress'
+  RETURNING *
+"#;
+const DEFER_TASK: &str = r#"
+  UPDATE tasks
+  SET status = 'new', start_at = ?1, locked_at = NULL, locked_by = NULL, version = version + 1
+  WHERE id = ?2 AND status = 'in_progress'
+  RETURNING *
+"#;
+const REVIVE_TASK: &str = r#"
+  UPDATE tasks
+  SET status = 'new', retries = 0, locked_at = NULL, locked_by = NULL, version = version + 1
+  WHERE id = ?1 AND status = 'dead'
+  RETURNING *
+"#;
+const CLAIM_TASK: &str = r#"
+  UPDATE tasks
+  SET status = 'in_progress', locked_at = datetime('now'), locked_by = ?2, version = version + 1
+  WHERE id = ?1 AND status NOT IN ('in_progress', 'dead') AND enabled = 1
+  RETURNING *
+"#;
+const RUN_TASK_NOW: &str = r#"
+  UPDATE tasks
+  SET status = 'new', start_at = unixepoch(), retries = 0, locked_at = NULL, locked_by = NULL,
+    updated_at = CURRENT_TIMESTAMP, version = version + 1
+  WHERE id = ?1 AND status != 'in_progress' AND enabled
+  RETURNING *
+"#;
+const SELECT_TASKS_FOR_BULK: &str = "SELECT * FROM tasks WHERE deleted_at IS NULL";
+const CANCEL_TASK: &str = r#"
+  UPDATE tasks
+  SET status = 'cancelled', locked_at = NULL, locked_by = NULL, updated_at = CURRENT_TIMESTAMP, version = version + 1
+  WHERE id = ?1
+  RETURNING *
+"#;
+const RETRY_TASK: &str = r#"
+  UPDATE tasks
+  SET status = 'new', retries = 0, locked_at = NULL, locked_by = NULL, updated_at = CURRENT_TIMESTAMP,
+    version = version + 1
+  WHERE id = ?1
+  RETURNING *
+"#;
+const SET_TASK_ENABLED: &str = r#"
+  UPDATE tasks
+  SET enabled = ?1, updated_at = CURRENT_TIMESTAMP, version = version + 1
+  WHERE id = ?2
+  RETURNING *
+"#;
+const IMPORT_PROJECT: &str = r#"
+  INSERT INTO projects (id, name, code, options, owner_id, created_at, updated_at, created_by)
+  VALUES (
+    ?1, ?2, ?3, ?4, COALESCE((SELECT id FROM users WHERE id = ?5), ?6), ?7, ?8, (SELECT id FROM users WHERE id = ?9)
+  )
+  ON CONFLICT (id) DO UPDATE SET
+    name = excluded.name,
+    code = excluded.code,
+    options = excluded.options,
+    owner_id = excluded.owner_id,
+    updated_at = excluded.updated_at
+  RETURNING *
+"#;
+const IMPORT_TASK: &str = r#"
+  INSERT INTO tasks (
+    id, type, status, project_id, retries, name, external_id, external_modified_at, schedule, start_at, end_at,
+    misfire_policy, enabled, options, created_at, updated_at, created_by, plugin_version, deleted_at, schedule_anchor
+  )
+  VALUES (
+    ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, (SELECT id FROM users WHERE id = ?17), ?18,
+    ?19, COALESCE(?20, ?10)
+  )
+  ON CONFLICT (id) DO UPDATE SET
+    type = excluded.type,
+    plugin_version = COALESCE(excluded.plugin_version, tasks.plugin_version),
+    status = excluded.status,
+    project_id = excluded.project_id,
+    retries = excluded.retries,
+    name = excluded.name,
+    external_id = excluded.external_id,
+    external_modified_at = excluded.external_modified_at,
+    schedule = excluded.schedule,
+    start_at = excluded.start_at,
+    schedule_anchor = excluded.schedule_anchor,
+    end_at = excluded.end_at,
+    misfire_policy = excluded.misfire_policy,
+    enabled = excluded.enabled,
+    options = excluded.options,
+    updated_at = excluded.updated_at,
+    deleted_at = excluded.deleted_at,
+    locked_at = NULL, locked_by = NULL
+  RETURNING *
+"#;
+const DELETE_OLD_TASKS: &str =
+  "DELETE FROM tasks WHERE status = 'finished' AND updated_at < date('now','-1 day') RETURNING *";
 const DELETE_STALE_TASKS: &str =
-  "DELETE FROM tasks WHERE external_id IS NOT NULL AND updated_at <= date('now','-10 seconds')";
+  "DELETE FROM tasks WHERE external_id IS NOT NULL AND datetime(updated_at) <= datetime('now', ?1) RETURNING *";
+const PURGE_DELETED_TASKS: &str =
+  "DELETE FROM tasks WHERE deleted_at IS NOT NULL AND datetime(deleted_at) <= datetime('now', ?1) RETURNING *";
+const EXPIRE_TASKS: &str = r#"
+  UPDATE tasks
+  SET status = 'dead', locked_at = NULL, locked_by = NULL, version = version + 1
+  WHERE status IN ('new', 'failed') AND schedule IS NULL AND enabled = 1 AND deleted_at IS NULL
+  AND MAX(start_at, unixepoch(created_at)) <= unixepoch('now', ?1)
+  RETURNING *
+"#;
+
+const DEFAULT_TASK_MAX_RETRIES: i32 = 3;
+const DEFAULT_TASK_MAX_OPTIONS_BYTES: usize = 64 * 1024;
+const DEFAULT_EXCHANGE_TASK_MAX_AGE: &str = "30m";
+const DEFAULT_TASK_DELETED_RETENTION: &str = "7d";
+const DEFAULT_TASK_MIN_SCHEDULE_INTERVAL: &str = "1m";
+const DEFAULT_TASK_TTL: &str = "30d";
+/// Upcoming cron fire times compared to find the shortest gap of a cron schedule
+const CRON_INTERVAL_SAMPLES: usize = 100;
+const EVERY_PREFIX: &str = "@every ";
+
+/// Number of failed runs after which a task stops being retried and is moved to `dead`,
+/// read from `TASK_MAX_RETRIES` (default 3)
+static TASK_MAX_RETRIES: Lazy<Result<i32, String>> = Lazy::new(load_max_retries);
+
+/// Largest serialized task `options` accepted, read from `TASK_MAX_OPTIONS_BYTES` (default 64 KiB)
+static TASK_MAX_OPTIONS_BYTES: Lazy<Result<usize, String>> = Lazy::new(load_max_options_bytes);
+
+/// How long an external task may go without being updated by a sync before it's deleted, read from
+/// `EXCHANGE_TASK_MAX_AGE`, e.g. `30m` or `2h` (default 30m)
+static EXCHANGE_TASK_MAX_AGE: Lazy<Result<std::time::Duration, String>> = Lazy::new(load_exchange_task_max_age);
+
+/// How long a deleted task can be restored before it's purged, read from `TASK_DELETED_RETENTION`, e.g. `12h`
+/// or `30d` (default 7d)
+static TASK_DELETED_RETENTION: Lazy<Result<std::time::Duration, String>> = Lazy::new(load_deleted_retention);
+
+/// Shortest time allowed between two runs of a recurring task, read from `TASK_MIN_SCHEDULE_INTERVAL`, e.g. `10s`
+/// or `0s` for no minimum (default 1m)
+static TASK_MIN_SCHEDULE_INTERVAL: Lazy<Result<std::time::Duration, String>> = Lazy::new(load_min_schedule_interval);
+
+/// How long after its start time a one-shot task may stay `new` or `failed` before it's moved to `dead`, read
+/// from `TASK_TTL`, e.g. `12h` or `0s` to keep such tasks forever (default 30d)
+static TASK_TTL: Lazy<Result<std::time::Duration, String>> = Lazy::new(load_task_ttl);
 
 #[derive(Debug, Deserialize)]
 pub struct CreateTaskParams {
   pub r#type: String,
+  /// Semver requirement on the plugin version, any loaded version runs the task when `None`
+  pub plugin_version: Option<String>,
   pub name: String,
   pub project_id: Uuid,
   pub schedule: Option<String>,
   pub external_id: Option<String>,
   pub external_modified_at: Option<DateTime<Utc>>,
-  pub start_at: i32,
+  pub start_at: i64,
+  /// Unix timestamp after which a recurring task stops being rescheduled
+  pub end_at: Option<i64>,
+  pub misfire_policy: MisfirePolicy,
   pub options: Value,
 }
 
-pub async fn create(pool: &SqlitePool, params: CreateTaskParams) -> ApiResult<Task> {
+pub async fn create(pool: &SqlitePool, actor_id: Option<Uuid>, params: CreateTaskParams) -> ApiResult<Task> {
+  check_options_size(&params.options)?;
+  check_plugin_version(params.plugin_version.as_deref())?;
+  check_schedule_interval(params.schedule.as_deref())?;
+
+  // Only read for the audit diff, whether the task was created or updated is told by the upsert itself
   let existing_task = match &params.external_id {
     Some(external_id) => get_task_by_external_id(pool, external_id).await?,
     None => None,
   };
 
-  let task = create_task_row(pool, &params).await?;
-  let project = get_project(pool, params.project_id).await?;
+  let id = Uuid::new_v4();
+  let mut tx = pool.begin().await?;
+  let task = create_task_row(&mut tx, id, actor_id, &params).await?;
 
-  if let Some(existing_task) = existing_task {
-    let should_update = match (existing_task.external_modified_at, params.external_modified_at) {
-      (Some(existing_modified_at), Some(task_modified_at)) => {
-        is_status_update_needed(&existing_task, existing_modified_at, task_modified_at)
-      },
-      _ => false,
+  let change = if task.id == id {
+    audit::record(
+      &mut *tx,
+      actor_id,
+      AuditAction::Create,
+      AuditEntity::Task,
+      task.id,
+      json!(task),
+    )
+    .await?;
+    Change::Created
+  } else {
+    let diff = match &existing_task {
+      Some(existing_task) => audit::diff(&json!(existing_task), &json!(task)),
+      None => json!(task),
     };
+    audit::record(
+      &mut *tx,
+      actor_id,
+      AuditAction::Update,
+      AuditEntity::Task,
+      task.id,
+      diff,
+    )
+    .await?;
+    Change::Updated
+  };
 
-    if should_update {
-      update_task_status(pool, existing_task.id, TaskStatus::New).await?;
-    }
-  }
+  tx.commit().await?;
+  events::task_changed(&task, change);
 
-  Ok(build_task(task, project))
+  let project = get_project(pool, params.project_id).await?;
+  build_task(task, project)
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateTaskParams {
   pub name: String,
+  /// New plugin type, unchanged when `None`
+  pub r#type: Option<String>,
+  /// Semver requirement on the plugin version, `None` lets any loaded version run the task
+  pub plugin_version: Option<String>,
+  /// Project to move the task to, unchanged when `None`
+  pub project_id: Option<Uuid>,
   pub schedule: Option<String>,
-  pub start_at: i32,
+  pub start_at: i64,
+  /// Unix timestamp after which a recurring task stops being rescheduled
+  pub end_at: Option<i64>,
+  /// How missed runs are handled, unchanged when `None`
+  pub misfire_policy: Option<MisfirePolicy>,
   pub options: Value,
+  /// Only update the task if it wasn't modified after this time, to the second
+  pub unmodified_since: Option<DateTime<Utc>>,
+  /// Only update the task if it's still at this version
+  pub version: Option<i64>,
 }
 
-pub async fn update(pool: &SqlitePool, id: Uuid, params: UpdateTaskParams) -> ApiResult<Task> {
-  ensure_task_exists(pool, id).await?;
+pub async fn update(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid, params: UpdateTaskParams) -> ApiResult<Task> {
+  check_options_size(&params.options)?;
+  check_plugin_version(params.plugin_version.as_deref())?;
+  check_schedule_interval(params.schedule.as_deref())?;
+  let existing = get_task(pool, id).await?;
+
+  if let Some(project_id) = params.project_id {
+    ensure_project_exists(pool, project_id).await?;
+  }
+
+  let mut tx = pool.begin().await?;
+  let task = update_task_row(&mut tx, id, &params).await?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::Task,
+    id,
+    audit::diff(&json!(existing), &json!(task)),
+  )
+  .await?;
+
+  tx.commit().await?;
+  events::task_changed(&task, Change::Updated);
 
-  let task = update_task_row(pool, id, &params).await?;
   let project = get_project(pool, task.project_id).await?;
+  build_task(task, project)
+}
+
+/// Fields of a partial task update, `None` leaves the column untouched
+#[derive(Debug, Default, Deserialize)]
+pub struct PatchTaskParams {
+  pub name: Option<String>,
+  pub r#type: Option<String>,
+  /// `Some(None)` unpins the plugin version
+  pub plugin_version: Option<Option<String>>,
+  pub project_id: Option<Uuid>,
+  /// `Some(None)` clears the schedule, making the task run once
+  pub schedule: Option<Option<String>>,
+  pub start_at: Option<i64>,
+  /// `Some(None)` clears the end, making a recurring task run indefinitely
+  pub end_at: Option<Option<i64>>,
+  pub misfire_policy: Option<MisfirePolicy>,
+  pub options: Option<Value>,
+  /// Only update the task if it wasn't modified after this time, to the second
+  pub unmodified_since: Option<DateTime<Utc>>,
+  /// Only update the task if it's still at this version
+  pub version: Option<i64>,
+}
+
+/// Updates only the given fields of a task
+///
+/// A patch setting no field returns the task as is, without audit entry or event.
+///
+/// # Errors
+/// - ResourceNotFound if the task or the new project doesn't exist
+/// - Conflict if the task was modified after `unmodified_since` or is no longer at `version`
+/// - OptionsTooLarge if the new options exceed `TASK_MAX_OPTIONS_BYTES`
+/// - InvalidPluginVersion if the new plugin version is not a semver requirement
+/// - ScheduleTooFrequent if the new schedule fires more often than `TASK_MIN_SCHEDULE_INTERVAL`
+/// - DatabaseError for any database-related issues
+pub async fn patch(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid, params: PatchTaskParams) -> ApiResult<Task> {
+  if let Some(options) = &params.options {
+    check_options_size(options)?;
+  }
+  if let Some(plugin_version) = &params.plugin_version {
+    check_plugin_version(plugin_version.as_deref())?;
+  }
+  if let Some(schedule) = &params.schedule {
+    check_schedule_interval(schedule.as_deref())?;
+  }
+  let existing = get_task(pool, id).await?;
+
+  if let Some(project_id) = params.project_id {
+    ensure_project_exists(pool, project_id).await?;
+  }
+
+  let mut tx = pool.begin().await?;
+  let Some(task) = patch_task_row(&mut tx, id, &params).await? else {
+    // Nothing changed, so nothing to audit or publish
+    tx.rollback().await?;
+    let project = get_project(pool, existing.project_id).await?;
+    return build_task(existing, project);
+  };
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::Task,
+    id,
+    audit::diff(&json!(existing), &json!(task)),
+  )
+  .await?;
+
+  tx.commit().await?;
+  events::task_changed(&task, Change::Updated);
 
-  Ok(build_task(task, project))
+  let project = get_project(pool, task.project_id).await?;
+  build_task(task, project)
 }
 
 pub async fn run_task(pool: &SqlitePool, id: Uuid) -> ApiResult<TaskRow> {
   update_task_status(pool, id, TaskStatus::InProgress).await
 }
 
+/// Puts a task picked up by the poller back in the queue without counting it as a run
+pub async fn release_task(pool: &SqlitePool, id: Uuid) -> ApiResult<()> {
+  let task = sqlx::query_as::<_, TaskRow>(RELEASE_TASK)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+  if let Some(task) = task {
+    events::task_changed(&task, Change::Updated);
+  }
+
+  Ok(())
+}
+
+/// Like `release_task`, but the poller only picks the task up again from `start_at`
+pub async fn defer_task(pool: &SqlitePool, id: Uuid, start_at: i64) -> ApiResult<()> {
+  let task = sqlx::query_as::<_, TaskRow>(DEFER_TASK)
+    .bind(start_at)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+  if let Some(task) = task {
+    events::task_changed(&task, Change::Updated);
+  }
+
+  Ok(())
+}
+
+/// Marks the task as failed and counts the attempt towards `TASK_MAX_RETRIES`
 pub async fn failed_task(pool: &SqlitePool, id: Uuid) -> ApiResult<TaskRow> {
-  update_task_status(pool, id, TaskStatus::Failed).await
+  ensure_task_exists(pool, id).await?;
+
+  let task = sqlx::query_as::<_, TaskRow>(FAIL_TASK).bind(id).fetch_one(pool).await?;
+  events::task_changed(&task, Change::Updated);
+
+  Ok(task)
+}
+
+/// Clears the failed runs of a task after a successful one, so the retry budget applies to each run
+/// rather than adding up over the lifetime of a recurring task
+pub async fn reset_retries(pool: &SqlitePool, id: Uuid) -> ApiResult<TaskRow> {
+  ensure_task_exists(pool, id).await?;
+
+  let task = sqlx::query_as::<_, TaskRow>(RESET_TASK_RETRIES)
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+
+  events::task_changed(&task, Change::Updated);
+
+  Ok(task)
+}
+
+/// Records how many results the run that just succeeded dropped under the lenient sub-result policy
+pub async fn set_failed_results(pool: &SqlitePool, id: Uuid, failed_results: i64) -> ApiResult<TaskRow> {
+  let task = sqlx::query_as::<_, TaskRow>(SET_FAILED_RESULTS)
+    .bind(id)
+    .bind(failed_results)
+    .fetch_one(pool)
+    .await?;
+
+  events::task_changed(&task, Change::Updated);
+
+  Ok(task)
 }
 
 pub async fn completed_task(pool: &SqlitePool, id: Uuid) -> ApiResult<TaskRow> {
   update_task_status(pool, id, TaskStatus::Finished).await
 }
 
-pub async fn schedule_task(pool: &SqlitePool, id: Uuid, start_at: i32) -> ApiResult<TaskRow> {
+pub async fn schedule_task(pool: &SqlitePool, id: Uuid, start_at: i64) -> ApiResult<TaskRow> {
   ensure_task_exists(pool, id).await?;
 
-  sqlx::query_as::<_, TaskRow>(SCHEDULE_TASK)
+  let task = sqlx::query_as::<_, TaskRow>(SCHEDULE_TASK)
     .bind(TaskStatus::New.to_string())
     .bind(start_at)
     .bind(id)
     .fetch_one(pool)
-    .await
-    .map_err(Into::into)
+    .await?;
+  events::task_changed(&task, Change::Updated);
+
+  Ok(task)
 }
 
-pub async fn delete(pool: &SqlitePool, id: Uuid) -> ApiResult<()> {
-  ensure_task_exists(pool, id).await?;
+/// Permanently removes a task, deleted or not
+///
+/// # Errors
+/// - ResourceNotFound if the task doesn't exist
+pub async fn delete(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid) -> ApiResult<()> {
+  let existing = get_task_with_deleted(pool, id).await?;
+
+  let mut tx = pool.begin().await?;
+  sqlx::query(DELETE_TASK).bind(id).execute(&mut *tx).await?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Delete,
+    AuditEntity::Task,
+    id,
+    json!(existing),
+  )
+  .await?;
 
-  sqlx::query(DELETE_TASK).bind(id).execute(pool).await?;
+  tx.commit().await?;
+  events::task_changed(&existing, Change::Deleted);
 
   Ok(())
 }
 
-pub async fn delete_completed_tasks(pool: &SqlitePool) -> ApiResult<u64> {
-  Ok(sqlx::query(DELETE_OLD_TASKS).execute(pool).await?.rows_affected())
-}
+/// Marks a task deleted, it's hidden from the API and skipped by the poller until restored or purged after
+/// `TASK_DELETED_RETENTION`
+///
+/// # Errors
+/// - ResourceNotFound if the task doesn't exist or is already deleted
+pub async fn soft_delete(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid) -> ApiResult<()> {
+  let existing = get_task(pool, id).await?;
 
-pub async fn delete_by_update_date(pool: &SqlitePool) -> ApiResult<u64> {
-  Ok(sqlx::query(DELETE_STALE_TASKS).execute(pool).await?.rows_affected())
-}
+  let mut tx = pool.begin().await?;
+  sqlx::query(SOFT_DELETE_TASK).bind(id).execute(&mut *tx).await?;
 
-async fn create_task_row(pool: &SqlitePool, params: &CreateTaskParams) -> ApiResult<TaskRow> {
-  sqlx::query_as::<_, TaskRow>(INSERT_TASK)
-    .bind(Uuid::new_v4())
-    .bind(&params.r#type)
-    .bind(params.project_id)
-    .bind(&params.name)
-    .bind(&params.external_id)
-    .bind(params.external_modified_at)
-    .bind(&params.schedule)
-    .bind(params.start_at)
-    .bind(&params.options)
-    .fetch_one(pool)
-    .await
-    .map_err(Into::into)
-}
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Delete,
+    AuditEntity::Task,
+    id,
+    json!(existing),
+  )
+  .await?;
 
-async fn update_task_row(pool: &SqlitePool, id: Uuid, params: &UpdateTaskParams) -> ApiResult<TaskRow> {
-  sqlx::query_as::<_, TaskRow>(UPDATE_TASK)
-    .bind(&params.name)
-    .bind(&params.schedule)
-    .bind(params.start_at)
-    .bind(&params.options)
-    .bind(id)
-    .fetch_one(pool)
-    .await
-    .map_err(Into::into)
+  tx.commit().await?;
+  events::task_changed(&existing, Change::Deleted);
+
+  Ok(())
 }
 
-pub async fn get_tasks_to_run(pool: &SqlitePool) -> ApiResult<Vec<Task>> {
+/// Brings back a deleted task as it was when deleted
+///
+/// # Errors
+/// - ResourceNotFound if the task doesn't exist
+/// - Conflict if the task is not deleted
+pub async fn restore(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid) -> ApiResult<Task> {
+  let existing = get_task_with_deleted(pool, id).await?;
+
   let mut tx = pool.begin().await?;
+  let task = sqlx::query_as::<_, TaskRow>(RESTORE_TASK)
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::Conflict(format!("Task `{}` is not deleted", id)))?;
 
-  let task_ids: Vec<Uuid> = sqlx::query_scalar(SELECT_TASKS_TO_RUN)
-      .fetch_all(&mut *tx)
-      .await?;
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::Task,
+    id,
+    audit::diff(&json!(existing), &json!(task)),
+  )
+  .await?;
 
-  if task_ids.is_empty() {
-    tx.commit().await?;
-    return Ok(vec![]);
+  tx.commit().await?;
+  events::task_changed(&task, Change::Updated);
+
+  let project = get_project(pool, task.project_id).await?;
+  build_task(task, project)
+}
+
+/// Moves failed tasks that have used up their retries to `dead` so the poller stops picking them up
+///
+/// # Returns
+/// The tasks that were escalated
+pub async fn escalate_dead_tasks(pool: &SqlitePool) -> ApiResult<Vec<TaskRow>> {
+  let tasks = sqlx::query_as::<_, TaskRow>(ESCALATE_DEAD_TASKS)
+    .bind(max_retries()?)
+    .fetch_all(pool)
+    .await?;
+  for task in &tasks {
+    events::task_changed(task, Change::Updated);
   }
 
-  // Формируем строку с плейсхолдерами для IN условия
-  let placeholders = format!("({})", std::iter::repeat("?").take(task_ids.len()).collect::<Vec<_>>().join(","));
+  Ok(tasks)
+}
 
-  let update_query = format!("{}{}", UPDATE_TASKS_STATUS, placeholders);
-  let select_query = format!("{}{}", SELECT_TASKS_WITH_PROJECTS, placeholders);
+/// Moves one-shot tasks that didn't complete within `TASK_TTL` of their start time to `dead`, like tasks of a
+/// removed plugin that would otherwise stay `new` forever
+///
+/// Tasks created with a start time in the past get the TTL from their creation instead, and disabled tasks are
+/// left alone as they aren't expected to run.
+///
+/// # Returns
+/// The tasks that were expired, none when `TASK_TTL` is `0s`
+pub async fn expire_tasks(pool: &SqlitePool) -> ApiResult<Vec<TaskRow>> {
+  let ttl = TASK_TTL
+    .as_ref()
+    .copied()
+    .map_err(|err| ApiError::Anyhow(anyhow!(err.clone())))?;
 
-  // Создаем запрос и привязываем каждый UUID отдельно
-  let mut query = sqlx::query(&update_query);
-  for id in &task_ids {
-    query = query.bind(id);
+  expire_tasks_older_than(pool, ttl).await
+}
+
+async fn expire_tasks_older_than(pool: &SqlitePool, ttl: std::time::Duration) -> ApiResult<Vec<TaskRow>> {
+  if ttl.is_zero() {
+    return Ok(vec![]);
   }
-  query.execute(&mut *tx).await?;
 
-  let mut query = sqlx::query(&select_query);
-  for id in &task_ids {
-    query = query.bind(id);
+  let tasks = sqlx::query_as::<_, TaskRow>(EXPIRE_TASKS)
+    .bind(format!("-{} seconds", ttl.as_secs()))
+    .fetch_all(pool)
+    .await?;
+  for task in &tasks {
+    events::task_changed(task, Change::Updated);
   }
-  let tasks = query.map(map_task).fetch_all(&mut *tx).await?;
 
-  tx.commit().await?;
   Ok(tasks)
 }
 
-async fn get_project(pool: &SqlitePool, project_id: Uuid) -> ApiResult<ProjectRow> {
-  sqlx::query_as::<_, ProjectRow>(FIND_PROJECT)
-    .bind(project_id)
-    .fetch_one(pool)
-    .await
-    .map_err(Into::into)
+/// Brings a dead task back into the queue with a fresh retry budget
+///
+/// # Errors
+/// - ResourceNotFound if the task doesn't exist
+/// - Conflict if the task is not dead
+pub async fn revive(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid) -> ApiResult<Task> {
+  let existing = get_task(pool, id).await?;
+
+  let mut tx = pool.begin().await?;
+  let task = sqlx::query_as::<_, TaskRow>(REVIVE_TASK)
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| {
+      ApiError::Conflict(format!(
+        "Task `{}` is `{}`, only dead tasks can be revived",
+        id, existing.status
+      ))
+    })?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::Task,
+    id,
+    audit::diff(&json!(existing), &json!(task)),
+  )
+  .await?;
+
+  tx.commit().await?;
+  events::task_changed(&task, Change::Updated);
+
+  let project = get_project(pool, task.project_id).await?;
+  build_task(task, project)
 }
 
-async fn ensure_task_exists(pool: &SqlitePool, id: Uuid) -> ApiResult<()> {
-  let exists = sqlx::query_as::<_, TaskRow>(FIND_TASK)
+/// Marks a task `in_progress` and locked by `executor_id` so it can be handed to that executor outside of the
+/// poller
+///
+/// # Errors
+/// - ResourceNotFound if the task doesn't exist
+/// - Conflict if the task is in progress, dead or disabled
+pub async fn claim(pool: &SqlitePool, id: Uuid, executor_id: &str) -> ApiResult<Task> {
+  let existing = get_task(pool, id).await?;
+
+  let task = sqlx::query_as::<_, TaskRow>(CLAIM_TASK)
     .bind(id)
+    .bind(executor_id)
     .fetch_optional(pool)
-    .await?;
+    .await?
+    .ok_or_else(|| {
+      ApiError::Conflict(format!(
+        "Task `{}` is `{}`{}, only enabled tasks that are not in progress or dead can be triggered",
+        id,
+        existing.status,
+        if existing.enabled { "" } else { " and disabled" }
+      ))
+    })?;
+  let project = get_project(pool, task.project_id).await?;
 
-  match exists {
-    Some(_) => Ok(()),
-    None => Err(ApiError::ResourceNotFound(id.to_string())),
-  }
+  events::task_changed(&task, Change::Updated);
+
+  build_task(task, project)
 }
 
-async fn get_task_by_external_id(pool: &SqlitePool, external_id: &str) -> ApiResult<Option<TaskRow>> {
-  sqlx::query_as::<_, TaskRow>(FIND_TASK_BY_EXTERNAL_ID)
-    .bind(external_id)
-    .fetch_optional(pool)
-    .await
-    .map_err(Into::into)
+/// Makes a task due right away so the next poll runs it, whatever its status and schedule
+///
+/// The schedule is kept, once the run is done the next one is calculated from it as usual. The retries are
+/// reset so a dead task runs as well. A disabled task is skipped by the executor, so it has to be enabled first.
+///
+/// # Errors
+/// - ResourceNotFound if the task doesn't exist
+/// - Conflict if the task is in progress or disabled
+pub async fn run_now(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid) -> ApiResult<Task> {
+  let existing = get_task(pool, id).await?;
+
+  let mut tx = pool.begin().await?;
+  let task = sqlx::query_as::<_, TaskRow>(RUN_TASK_NOW)
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| {
+      ApiError::Conflict(if existing.enabled {
+        format!("Task `{}` is already in progress", id)
+      } else {
+        format!("Task `{}` is disabled, enable it to run it", id)
+      })
+    })?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::Task,
+    id,
+    audit::diff(&json!(existing), &json!(task)),
+  )
+  .await?;
+
+  tx.commit().await?;
+  events::task_changed(&task, Change::Updated);
+
+  let project = get_project(pool, task.project_id).await?;
+  build_task(task, project)
 }
 
-async fn update_task_status(pool: &SqlitePool, id: Uuid, status: TaskStatus) -> ApiResult<TaskRow> {
-  ensure_task_exists(pool, id).await?;
+/// Pauses or resumes a task without touching its schedule
+///
+/// A disabled task is skipped by the executor but keeps its `start_at`, so once re-enabled the missed runs
+/// are handled by its misfire policy, the same way as runs missed while the executor was down.
+///
+/// # Errors
+/// - ResourceNotFound if the task doesn't exist
+pub async fn set_enabled(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid, enabled: bool) -> ApiResult<Task> {
+  let existing = get_task(pool, id).await?;
 
-  sqlx::query_as::<_, TaskRow>(UPDATE_TASK_STATUS)
-    .bind(status.to_string())
+  let mut tx = pool.begin().await?;
+  let task = sqlx::query_as::<_, TaskRow>(SET_TASK_ENABLED)
+    .bind(enabled)
     .bind(id)
-    .fetch_one(pool)
-    .await
-    .map_err(Into::into)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::ResourceNotFound(id.to_string()))?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::Task,
+    id,
+    audit::diff(&json!(existing), &json!(task)),
+  )
+  .await?;
+
+  tx.commit().await?;
+  events::task_changed(&task, Change::Updated);
+
+  let project = get_project(pool, task.project_id).await?;
+  build_task(task, project)
 }
 
-fn is_status_update_needed(
-  existing_task: &TaskRow,
-  existing_modified_at: DateTime<Utc>,
-  task_modified_at: DateTime<Utc>,
-) -> bool {
-  existing_task
-    .status
-    .parse::<TaskStatus>()
-    .map(|status| status == TaskStatus::Failed && task_modified_at > existing_modified_at)
-    .unwrap_or(false)
+/// Status change applied at once to every task matching a filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+  /// Stops tasks that are waiting to run by marking them `cancelled`
+  Cancel,
+  /// Requeues failed, dead and cancelled tasks as `new` with a fresh retry budget
+  Retry,
 }
 
-fn build_task(task: TaskRow, project: ProjectRow) -> Task {
-  Task {
-    id: task.id,
-    name: task.name,
-    r#type: task.r#type,
-    status: task.status,
-    project,
-    retries: task.retries,
-    external_id: task.external_id,
-    external_modified_at: task.external_modified_at,
-    schedule: task.schedule,
+impl BulkAction {
+  /// Statuses the action applies to, matching tasks in any other status are skipped
+  fn applies_to(self, status: TaskStatus) -> bool {
+    match self {
+      BulkAction::Cancel => matches!(status, TaskStatus::New | TaskStatus::Failed | TaskStatus::Retried),
+      BulkAction::Retry => matches!(
+        status,
+        TaskStatus::Failed | TaskStatus::Retried | TaskStatus::Dead | TaskStatus::Cancelled
+      ),
+    }
+  }
+
+  fn query(self) -> &'static str {
+    match self {
+      BulkAction::Cancel => CANCEL_TASK,
+      BulkAction::Retry => RETRY_TASK,
+    }
+  }
+}
+
+/// Tasks a bulk action applies to, every task matches when all fields are `None`
+#[derive(Debug, Default, Deserialize)]
+pub struct BulkFilter {
+  pub status: Option<TaskStatus>,
+  pub project_id: Option<Uuid>,
+  pub r#type: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct BulkSummary {
+  /// Matching tasks the action was applied to
+  pub updated: u64,
+  /// Matching tasks left untouched because the action doesn't apply to their status
+  pub skipped: u64,
+}
+
+/// Applies a status change to every task matching the filter in a single transaction
+///
+/// Tasks the action doesn't apply to, e.g. a running task for `cancel`, are skipped and counted. Every updated
+/// task is recorded in the audit log.
+pub async fn bulk_status(
+  pool: &SqlitePool,
+  actor_id: Option<Uuid>,
+  filter: BulkFilter,
+  action: BulkAction,
+) -> ApiResult<BulkSummary> {
+  let mut tx = pool.begin().await?;
+  let mut summary = BulkSummary::default();
+  let mut updated = Vec::new();
+
+  let mut query = QueryBuilder::<Sqlite>::new(SELECT_TASKS_FOR_BULK);
+  if let Some(status) = filter.status {
+    query.push(" AND status = ").push_bind(status.to_string());
+  }
+  if let Some(project_id) = filter.project_id {
+    query.push(" AND project_id = ").push_bind(project_id);
+  }
+  if let Some(r#type) = filter.r#type {
+    query.push(" AND type = ").push_bind(r#type);
+  }
+
+  let tasks = query.build_query_as::<TaskRow>().fetch_all(&mut *tx).await?;
+
+  for existing in tasks {
+    if !action.applies_to(TaskStatus::decode(&existing.status)?) {
+      summary.skipped += 1;
+      continue;
+    }
+
+    let task = sqlx::query_as::<_, TaskRow>(action.query())
+      .bind(existing.id)
+      .fetch_one(&mut *tx)
+      .await?;
+
+    audit::record(
+      &mut *tx,
+      actor_id,
+      AuditAction::Update,
+      AuditEntity::Task,
+      task.id,
+      audit::diff(&json!(existing), &json!(task)),
+    )
+    .await?;
+
+    summary.updated += 1;
+    updated.push(task);
+  }
+
+  tx.commit().await?;
+
+  for task in &updated {
+    events::task_changed(task, Change::Updated);
+  }
+
+  Ok(summary)
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ImportSummary {
+  pub projects: u64,
+  pub tasks: u64,
+}
+
+/// Rows written by an import, published to the subscribers once it's committed
+#[derive(Default)]
+struct Imported {
+  projects: Vec<(Uuid, Change)>,
+  tasks: Vec<(TaskRow, Change)>,
+}
+
+/// Imports an NDJSON task export, upserting projects and tasks by id in a single transaction
+///
+/// The input is read chunk by chunk and parsed line by line, so the whole export is never held in memory.
+/// Tasks exported while `in_progress` are imported as `new` since no executor owns them here, and projects
+/// whose owner doesn't exist in this database are given to `actor_id`. A `created_by` user missing here is
+/// left unset. A task exported without a plugin version keeps the one already pinned here. Records go through
+/// the checks of [`create`] and are recorded in the audit log like it does.
+///
+/// # Errors
+/// - InvalidImport if a line is not a valid record, fails a check of [`create`] or conflicts with existing data,
+///   nothing is imported then
+pub async fn import<S, B>(pool: &SqlitePool, actor_id: Option<Uuid>, mut chunks: S) -> ApiResult<ImportSummary>
+where
+  S: Stream<Item = ApiResult<B>> + Unpin,
+  B: AsRef<[u8]>,
+{
+  let mut tx = pool.begin().await?;
+  let mut imported = Imported::default();
+  let mut buffer = Vec::new();
+  let mut line_number = 0;
+
+  while let Some(chunk) = chunks.try_next().await? {
+    buffer.extend_from_slice(chunk.as_ref());
+
+    while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+      let line: Vec<u8> = buffer.drain(..=end).collect();
+      line_number += 1;
+      import_line(&mut tx, actor_id, line_number, &line, &mut imported).await?;
+    }
+  }
+
+  if !buffer.is_empty() {
+    import_line(&mut tx, actor_id, line_number + 1, &buffer, &mut imported).await?;
+  }
+
+  tx.commit().await?;
+
+  for (id, change) in &imported.projects {
+    events::project_changed(*id, *change);
+  }
+  for (task, change) in &imported.tasks {
+    events::task_changed(task, *change);
+  }
+
+  Ok(ImportSummary {
+    projects: imported.projects.len() as u64,
+    tasks: imported.tasks.len() as u64,
+  })
+}
+
+async fn import_line(
+  conn: &mut SqliteConnection,
+  actor_id: Option<Uuid>,
+  line_number: usize,
+  line: &[u8],
+  imported: &mut Imported,
+) -> ApiResult<()> {
+  let line = line.trim_ascii();
+  if line.is_empty() {
+    return Ok(());
+  }
+
+  let record: ExportRecord =
+    serde_json::from_slice(line).map_err(|e| ApiError::InvalidImport(line_number, e.to_string()))?;
+
+  let result = match record {
+    ExportRecord::Project(project) => import_project(conn, actor_id, &project)
+      .await
+      .map(|change| imported.projects.push((project.id, change))),
+    ExportRecord::Task(task) => import_task(conn, actor_id, line_number, &task)
+      .await
+      .map(|imported_task| imported.tasks.push(imported_task)),
+  };
+
+  result.map_err(|err| match err {
+    ApiError::DatabaseError(sqlx::Error::Database(e)) if e.is_unique_violation() || e.is_foreign_key_violation() => {
+      ApiError::InvalidImport(line_number, e.message().to_string())
+    },
+    err @ (ApiError::InvalidProjectCode(_)
+    | ApiError::ProjectAlreadyExist(_)
+    | ApiError::OptionsTooLarge(..)
+    | ApiError::InvalidPluginVersion(..)
+    | ApiError::InvalidSchedule(_)
+    | ApiError::ScheduleTooFrequent(..)) => ApiError::InvalidImport(line_number, err.to_string()),
+    err => err,
+  })
+}
+
+async fn import_project(
+  conn: &mut SqliteConnection,
+  actor_id: Option<Uuid>,
+  project: &ProjectRow,
+) -> ApiResult<Change> {
+  let code = normalize_code(&project.code)?;
+  let duplicate = sqlx::query_as::<_, ProjectRow>(FIND_OTHER_PROJECT_BY_CODE)
+    .bind(&code)
+    .bind(project.id)
+    .fetch_optional(&mut *conn)
+    .await?;
+  if duplicate.is_some() {
+    return Err(ApiError::ProjectAlreadyExist(code));
+  }
+
+  let existing = sqlx::query_as::<_, ProjectRow>(FIND_PROJECT)
+    .bind(project.id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+  let imported = sqlx::query_as::<_, ProjectRow>(IMPORT_PROJECT)
+    .bind(project.id)
+    .bind(&project.name)
+    .bind(&code)
+    .bind(&project.options)
+    .bind(project.owner_id)
+    .bind(actor_id)
+    .bind(project.created_at)
+    .bind(project.updated_at)
+    .bind(project.created_by)
+    .fetch_one(&mut *conn)
+    .await?;
+
+  audit_import(
+    conn,
+    actor_id,
+    AuditEntity::Project,
+    project.id,
+    existing.as_ref(),
+    &imported,
+  )
+  .await
+}
+
+async fn import_task(
+  conn: &mut SqliteConnection,
+  actor_id: Option<Uuid>,
+  line_number: usize,
+  task: &TaskRow,
+) -> ApiResult<(TaskRow, Change)> {
+  check_options_size(&task.options)?;
+  check_plugin_version(task.plugin_version.as_deref())?;
+  check_schedule_interval(task.schedule.as_deref())?;
+
+  let status = match TaskStatus::from_str(&task.status) {
+    Ok(TaskStatus::InProgress) => TaskStatus::New,
+    Ok(status) => status,
+    Err(e) => return Err(ApiError::InvalidImport(line_number, e)),
+  };
+  let misfire_policy =
+    MisfirePolicy::from_str(&task.misfire_policy).map_err(|e| ApiError::InvalidImport(line_number, e))?;
+
+  let existing = sqlx::query_as::<_, TaskRow>(FIND_TASK_WITH_DELETED)
+    .bind(task.id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+  let imported = sqlx::query_as::<_, TaskRow>(IMPORT_TASK)
+    .bind(task.id)
+    .bind(&task.r#type)
+    .bind(status.to_string())
+    .bind(task.project_id)
+    .bind(task.retries)
+    .bind(&task.name)
+    .bind(&task.external_id)
+    .bind(task.external_modified_at)
+    .bind(&task.schedule)
+    .bind(task.start_at)
+    .bind(task.end_at)
+    .bind(misfire_policy.to_string())
+    .bind(task.enabled)
+    .bind(&task.options)
+    .bind(task.created_at)
+    .bind(task.updated_at)
+    .bind(task.created_by)
+    .bind(&task.plugin_version)
+    .bind(task.deleted_at)
+    .bind(task.schedule_anchor)
+    .fetch_one(&mut *conn)
+    .await?;
+
+  let change = audit_import(conn, actor_id, AuditEntity::Task, task.id, existing.as_ref(), &imported).await?;
+
+  Ok((imported, change))
+}
+
+/// Records an imported row in the audit log, as created or with the diff to the row it replaced
+async fn audit_import<T: Serialize>(
+  conn: &mut SqliteConnection,
+  actor_id: Option<Uuid>,
+  entity: AuditEntity,
+  id: Uuid,
+  existing: Option<&T>,
+  imported: &T,
+) -> ApiResult<Change> {
+  let (action, diff, change) = match existing {
+    Some(existing) => (
+      AuditAction::Update,
+      audit::diff(&json!(existing), &json!(imported)),
+      Change::Updated,
+    ),
+    None => (AuditAction::Create, json!(imported), Change::Created),
+  };
+  audit::record(conn, actor_id, action, entity, id, diff).await?;
+
+  Ok(change)
+}
+
+/// Checks that `TASK_MAX_RETRIES` from the environment is valid
+pub fn validate_max_retries() -> anyhow::Result<()> {
+  TASK_MAX_RETRIES
+    .as_ref()
+    .map(|_| ())
+    .map_err(|err| anyhow!(err.clone()))
+}
+
+/// Checks that `TASK_MAX_OPTIONS_BYTES` from the environment is valid
+pub fn validate_max_options_bytes() -> anyhow::Result<()> {
+  TASK_MAX_OPTIONS_BYTES
+    .as_ref()
+    .map(|_| ())
+    .map_err(|err| anyhow!(err.clone()))
+}
+
+/// Checks that `EXCHANGE_TASK_MAX_AGE` from the environment is valid
+pub fn validate_exchange_task_max_age() -> anyhow::Result<()> {
+  EXCHANGE_TASK_MAX_AGE
+    .as_ref()
+    .map(|_| ())
+    .map_err(|err| anyhow!(err.clone()))
+}
+
+/// Checks that `TASK_DELETED_RETENTION` from the environment is valid
+pub fn validate_deleted_retention() -> anyhow::Result<()> {
+  TASK_DELETED_RETENTION
+    .as_ref()
+    .map(|_| ())
+    .map_err(|err| anyhow!(err.clone()))
+}
+
+/// Checks that `TASK_MIN_SCHEDULE_INTERVAL` from the environment is valid
+pub fn validate_min_schedule_interval() -> anyhow::Result<()> {
+  TASK_MIN_SCHEDULE_INTERVAL
+    .as_ref()
+    .map(|_| ())
+    .map_err(|err| anyhow!(err.clone()))
+}
+
+/// Checks that `TASK_TTL` from the environment is valid
+pub fn validate_task_ttl() -> anyhow::Result<()> {
+  TASK_TTL.as_ref().map(|_| ()).map_err(|err| anyhow!(err.clone()))
+}
+
+/// Permanently removes tasks deleted more than `TASK_DELETED_RETENTION` ago
+///
+/// # Returns
+/// The ids of the purged tasks
+pub async fn purge_deleted_tasks(pool: &SqlitePool) -> ApiResult<Vec<Uuid>> {
+  let retention = TASK_DELETED_RETENTION
+    .as_ref()
+    .copied()
+    .map_err(|err| ApiError::Anyhow(anyhow!(err.clone())))?;
+
+  let purged = sqlx::query_as::<_, TaskRow>(PURGE_DELETED_TASKS)
+    .bind(format!("-{} seconds", retention.as_secs()))
+    .fetch_all(pool)
+    .await?;
+
+  Ok(deleted_ids(&purged))
+}
+
+/// Deletes tasks finished more than a day ago, cancelled tasks are kept
+///
+/// # Returns
+/// The ids of the deleted tasks
+pub async fn delete_completed_tasks(pool: &SqlitePool) -> ApiResult<Vec<Uuid>> {
+  let deleted = sqlx::query_as::<_, TaskRow>(DELETE_OLD_TASKS).fetch_all(pool).await?;
+
+  Ok(deleted_ids(&deleted))
+}
+
+/// Deletes external tasks no sync has updated for `EXCHANGE_TASK_MAX_AGE`
+///
+/// # Returns
+/// The ids of the deleted tasks
+pub async fn delete_by_update_date(pool: &SqlitePool) -> ApiResult<Vec<Uuid>> {
+  let max_age = EXCHANGE_TASK_MAX_AGE
+    .as_ref()
+    .copied()
+    .map_err(|err| ApiError::Anyhow(anyhow!(err.clone())))?;
+
+  delete_stale_external_tasks(pool, max_age).await
+}
+
+async fn delete_stale_external_tasks(pool: &SqlitePool, max_age: std::time::Duration) -> ApiResult<Vec<Uuid>> {
+  let modifier = format!("-{} seconds", max_age.as_secs());
+
+  let deleted = sqlx::query_as::<_, TaskRow>(DELETE_STALE_TASKS)
+    .bind(modifier)
+    .fetch_all(pool)
+    .await?;
+
+  Ok(deleted_ids(&deleted))
+}
+
+/// Notifies the subscribers of tasks deleted in bulk, returning their ids
+fn deleted_ids(tasks: &[TaskRow]) -> Vec<Uuid> {
+  tasks
+    .iter()
+    .map(|task| {
+      events::task_changed(task, Change::Deleted);
+      task.id
+    })
+    .collect()
+}
+
+async fn create_task_row(
+  conn: &mut SqliteConnection,
+  id: Uuid,
+  actor_id: Option<Uuid>,
+  params: &CreateTaskParams,
+) -> ApiResult<TaskRow> {
+  sqlx::query_as::<_, TaskRow>(INSERT_TASK)
+    .bind(id)
+    .bind(&params.r#type)
+    .bind(params.project_id)
+    .bind(&params.name)
+    .bind(&params.external_id)
+    .bind(params.external_modified_at)
+    .bind(&params.schedule)
+    .bind(params.start_at)
+    .bind(params.end_at)
+    .bind(params.misfire_policy.to_string())
+    .bind(&params.options)
+    .bind(actor_id)
+    .bind(&params.plugin_version)
+    .fetch_one(conn)
+    .await
+    .map_err(Into::into)
+}
+
+async fn update_task_row(conn: &mut SqliteConnection, id: Uuid, params: &UpdateTaskParams) -> ApiResult<TaskRow> {
+  sqlx::query_as::<_, TaskRow>(UPDATE_TASK)
+    .bind(&params.name)
+    .bind(&params.schedule)
+    .bind(params.start_at)
+    .bind(params.end_at)
+    .bind(&params.options)
+    .bind(&params.r#type)
+    .bind(params.project_id)
+    .bind(params.misfire_policy.map(|policy| policy.to_string()))
+    .bind(id)
+    .bind(params.unmodified_since)
+    .bind(&params.plugin_version)
+    .bind(params.version)
+    .fetch_optional(conn)
+    .await?
+    .ok_or_else(|| modified_conflict(id))
+}
+
+/// Updates the columns set in `params`, returns `None` without touching the row when there are none
+///
+/// The `version` and `unmodified_since` guards apply either way, a stale empty patch is a conflict as well.
+async fn patch_task_row(conn: &mut SqliteConnection, id: Uuid, params: &PatchTaskParams) -> ApiResult<Option<TaskRow>> {
+  let misfire_policy = params.misfire_policy.map(|policy| policy.to_string());
+
+  let mut query = QueryBuilder::<Sqlite>::new("UPDATE tasks SET ");
+  let mut columns = query.separated(", ");
+  let mut changed = false;
+
+  if let Some(name) = &params.name {
+    columns.push("name = ").push_bind_unseparated(name);
+    changed = true;
+  }
+  if let Some(r#type) = &params.r#type {
+    columns.push("type = ").push_bind_unseparated(r#type);
+    changed = true;
+  }
+  if let Some(plugin_version) = &params.plugin_version {
+    columns.push("plugin_version = ").push_bind_unseparated(plugin_version);
+    changed = true;
+  }
+  if let Some(project_id) = params.project_id {
+    columns.push("project_id = ").push_bind_unseparated(project_id);
+    changed = true;
+  }
+  if let Some(schedule) = &params.schedule {
+    columns.push("schedule = ").push_bind_unseparated(schedule);
+    changed = true;
+  }
+  if let Some(start_at) = params.start_at {
+    columns.push("start_at = ").push_bind_unseparated(start_at);
+    columns.push("schedule_anchor = ").push_bind_unseparated(start_at);
+    changed = true;
+  }
+  if let Some(end_at) = params.end_at {
+    columns.push("end_at = ").push_bind_unseparated(end_at);
+    changed = true;
+  }
+  if let Some(misfire_policy) = &misfire_policy {
+    columns.push("misfire_policy = ").push_bind_unseparated(misfire_policy);
+    changed = true;
+  }
+  if let Some(options) = &params.options {
+    columns.push("options = ").push_bind_unseparated(options);
+    changed = true;
+  }
+
+  let mut query = if changed {
+    columns.push("updated_at = CURRENT_TIMESTAMP");
+    columns.push("version = version + 1");
+    query
+  } else {
+    // Nothing to write, but a stale `version` or `unmodified_since` is still rejected
+    QueryBuilder::new("SELECT * FROM tasks")
+  };
+
+  query.push(" WHERE id = ").push_bind(id);
+  if let Some(unmodified_since) = params.unmodified_since {
+    query
+      .push(" AND datetime(updated_at) <= datetime(")
+      .push_bind(unmodified_since)
+      .push(")");
+  }
+  if let Some(version) = params.version {
+    query.push(" AND version = ").push_bind(version);
+  }
+  if changed {
+    query.push(" RETURNING *");
+  }
+
+  query
+    .build_query_as::<TaskRow>()
+    .fetch_optional(conn)
+    .await?
+    .map(|row| changed.then_some(row))
+    .ok_or_else(|| modified_conflict(id))
+}
+
+fn modified_conflict(id: Uuid) -> ApiError {
+  ApiError::Conflict(format!(
+    "Task `{}` was modified in the meantime, reload it and retry",
+    id
+  ))
+}
+
+/// Claims every due task for `executor_id`, first putting back the tasks of executors that stopped refreshing
+/// their locks
+///
+/// Locks of `executor_id` itself are never reclaimed, its tasks may simply run for long. The ones it held before
+/// a restart are put back by [`release_locks`] instead.
+pub async fn get_tasks_to_run(pool: &SqlitePool, executor_id: &str) -> ApiResult<Vec<Task>> {
+  let mut tx = pool.begin().await?;
+
+  let reclaimed = sqlx::query_as::<_, TaskRow>(RECLAIM_STALE_TASKS)
+    .bind(executor_id)
+    .fetch_all(&mut *tx)
+    .await?;
+  for task in &reclaimed {
+    warn!("Reclaimed task {} from an executor whose lock went stale", task.id);
+  }
+
+  let task_ids: Vec<Uuid> = sqlx::query_scalar(SELECT_TASKS_TO_RUN)
+    .bind(max_retries()?)
+    .fetch_all(&mut *tx)
+    .await?;
+
+  if task_ids.is_empty() {
+    tx.commit().await?;
+    for task in &reclaimed {
+      events::task_changed(task, Change::Updated);
+    }
+    return Ok(vec![]);
+  }
+
+  // Формируем строку с плейсхолдерами для IN условия
+  let placeholders = format!(
+    "({})",
+    std::iter::repeat("?")
+      .take(task_ids.len())
+      .collect::<Vec<_>>()
+      .join(",")
+  );
+
+  let update_query = format!("{}{} RETURNING *", UPDATE_TASKS_STATUS, placeholders);
+  let select_query = format!("{}{}", SELECT_TASKS_WITH_PROJECTS, placeholders);
+
+  // Создаем запрос и привязываем каждый UUID отдельно
+  let mut query = sqlx::query_as::<_, TaskRow>(&update_query).bind(executor_id);
+  for id in &task_ids {
+    query = query.bind(id);
+  }
+  let claimed = query.fetch_all(&mut *tx).await?;
+
+  let mut query = sqlx::query(&select_query);
+  for id in &task_ids {
+    query = query.bind(id);
+  }
+  let tasks = query.try_map(map_task).fetch_all(&mut *tx).await?;
+
+  tx.commit().await?;
+
+  for task in reclaimed.iter().chain(&claimed) {
+    events::task_changed(task, Change::Updated);
+  }
+
+  Ok(tasks)
+}
+
+/// Puts back the tasks `executor_id` left `in_progress` when it stopped, called before it claims any task
+///
+/// Without it the tasks of an executor that crashed and came back with the same id would stay locked, as it
+/// keeps refreshing their locks and no other executor reclaims them.
+pub async fn release_locks(pool: &SqlitePool, executor_id: &str) -> ApiResult<Vec<Uuid>> {
+  let released = sqlx::query_as::<_, TaskRow>(RELEASE_LOCKS)
+    .bind(executor_id)
+    .fetch_all(pool)
+    .await?;
+
+  for task in &released {
+    warn!(
+      "Released task {} left in progress by a previous run of executor {}",
+      task.id, executor_id
+    );
+    events::task_changed(task, Change::Updated);
+  }
+
+  Ok(released.into_iter().map(|task| task.id).collect())
+}
+
+/// Renews the locks of the tasks `executor_id` is running, so other executors don't reclaim them
+pub async fn refresh_locks(pool: &SqlitePool, executor_id: &str) -> ApiResult<u64> {
+  let result = sqlx::query(REFRESH_LOCKS).bind(executor_id).execute(pool).await?;
+
+  Ok(result.rows_affected())
+}
+
+async fn get_project(pool: &SqlitePool, project_id: Uuid) -> ApiResult<ProjectRow> {
+  sqlx::query_as::<_, ProjectRow>(FIND_PROJECT)
+    .bind(project_id)
+    .fetch_one(pool)
+    .await
+    .map_err(Into::into)
+}
+
+async fn ensure_project_exists(pool: &SqlitePool, project_id: Uuid) -> ApiResult<()> {
+  let exists = sqlx::query_as::<_, ProjectRow>(FIND_PROJECT)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+  match exists {
+    Some(_) => Ok(()),
+    None => Err(ApiError::ResourceNotFound(project_id.to_string())),
+  }
+}
+
+async fn get_task(pool: &SqlitePool, id: Uuid) -> ApiResult<TaskRow> {
+  sqlx::query_as::<_, TaskRow>(FIND_TASK)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::ResourceNotFound(id.to_string()))
+}
+
+async fn get_task_with_deleted(pool: &SqlitePool, id: Uuid) -> ApiResult<TaskRow> {
+  sqlx::query_as::<_, TaskRow>(FIND_TASK_WITH_DELETED)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::ResourceNotFound(id.to_string()))
+}
+
+/// Checks the task exists, deleted or not, so a run finishing after its task was deleted still records its outcome
+async fn ensure_task_exists(pool: &SqlitePool, id: Uuid) -> ApiResult<()> {
+  let exists = sqlx::query_as::<_, TaskRow>(FIND_TASK_WITH_DELETED)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+  match exists {
+    Some(_) => Ok(()),
+    None => Err(ApiError::ResourceNotFound(id.to_string())),
+  }
+}
+
+async fn get_task_by_external_id(pool: &SqlitePool, external_id: &str) -> ApiResult<Option<TaskRow>> {
+  sqlx::query_as::<_, TaskRow>(FIND_TASK_BY_EXTERNAL_ID)
+    .bind(external_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(Into::into)
+}
+
+async fn update_task_status(pool: &SqlitePool, id: Uuid, status: TaskStatus) -> ApiResult<TaskRow> {
+  ensure_task_exists(pool, id).await?;
+
+  let task = sqlx::query_as::<_, TaskRow>(UPDATE_TASK_STATUS)
+    .bind(status.to_string())
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+  events::task_changed(&task, Change::Updated);
+
+  Ok(task)
+}
+
+fn load_max_retries() -> Result<i32, String> {
+  match env::var("TASK_MAX_RETRIES") {
+    Ok(value) => value
+      .parse::<i32>()
+      .ok()
+      .filter(|retries| *retries > 0)
+      .ok_or_else(|| format!("TASK_MAX_RETRIES must be a positive integer, got `{}`", value)),
+    Err(_) => Ok(DEFAULT_TASK_MAX_RETRIES),
+  }
+}
+
+fn load_max_options_bytes() -> Result<usize, String> {
+  match env::var("TASK_MAX_OPTIONS_BYTES") {
+    Ok(value) => value
+      .parse::<usize>()
+      .ok()
+      .filter(|bytes| *bytes > 0)
+      .ok_or_else(|| format!("TASK_MAX_OPTIONS_BYTES must be a positive integer, got `{}`", value)),
+    Err(_) => Ok(DEFAULT_TASK_MAX_OPTIONS_BYTES),
+  }
+}
+
+fn load_deleted_retention() -> Result<std::time::Duration, String> {
+  let value = env::var("TASK_DELETED_RETENTION").unwrap_or_else(|_| DEFAULT_TASK_DELETED_RETENTION.to_string());
+
+  duration_str::parse(&value)
+    .ok()
+    .ok_or_else(|| format!("TASK_DELETED_RETENTION must be a duration like `7d`, got `{}`", value))
+}
+
+fn load_min_schedule_interval() -> Result<std::time::Duration, String> {
+  let value = env::var("TASK_MIN_SCHEDULE_INTERVAL").unwrap_or_else(|_| DEFAULT_TASK_MIN_SCHEDULE_INTERVAL.to_string());
+
+  duration_str::parse(&value).ok().ok_or_else(|| {
+    format!(
+      "TASK_MIN_SCHEDULE_INTERVAL must be a duration like `1m`, got `{}`",
+      value
+    )
+  })
+}
+
+fn load_task_ttl() -> Result<std::time::Duration, String> {
+  let value = env::var("TASK_TTL").unwrap_or_else(|_| DEFAULT_TASK_TTL.to_string());
+
+  duration_str::parse(&value)
+    .ok()
+    .ok_or_else(|| format!("TASK_TTL must be a duration like `30d`, got `{}`", value))
+}
+
+fn load_exchange_task_max_age() -> Result<std::time::Duration, String> {
+  let value = env::var("EXCHANGE_TASK_MAX_AGE").unwrap_or_else(|_| DEFAULT_EXCHANGE_TASK_MAX_AGE.to_string());
+
+  duration_str::parse(&value)
+    .ok()
+    .filter(|max_age| max_age.as_secs() > 0)
+    .ok_or_else(|| format!("EXCHANGE_TASK_MAX_AGE must be a duration like `30m`, got `{}`", value))
+}
+
+/// Rejects a plugin version that is not a semver requirement, the executor would otherwise fail the task on
+/// every run
+pub(crate) fn check_plugin_version(plugin_version: Option<&str>) -> ApiResult<()> {
+  match plugin_version {
+    Some(version) => semver::VersionReq::parse(version)
+      .map(|_| ())
+      .map_err(|e| ApiError::InvalidPluginVersion(version.to_string(), e.to_string())),
+    None => Ok(()),
+  }
+}
+
+/// Rejects a schedule firing more often than `TASK_MIN_SCHEDULE_INTERVAL`, so a task can't run its plugin in a
+/// hot loop
+pub(crate) fn check_schedule_interval(schedule: Option<&str>) -> ApiResult<()> {
+  let Some(schedule) = schedule else {
+    return Ok(());
+  };
+  let minimum = TASK_MIN_SCHEDULE_INTERVAL
+    .as_ref()
+    .copied()
+    .map_err(|err| ApiError::Anyhow(anyhow!(err.clone())))?;
+
+  match schedule_interval(schedule)? {
+    Some(interval) if interval < minimum => Err(ApiError::ScheduleTooFrequent(
+      schedule.to_string(),
+      interval.as_secs(),
+      minimum.as_secs(),
+    )),
+    _ => Ok(()),
+  }
+}
+
+/// Shortest time between two runs of a schedule, the `@every` duration or the smallest gap between the next
+/// cron fire times. `None` for a cron schedule that never fires again
+fn schedule_interval(schedule: &str) -> ApiResult<Option<std::time::Duration>> {
+  if let Some(duration) = schedule.strip_prefix(EVERY_PREFIX) {
+    return duration_str::parse(duration)
+      .map(Some)
+      .map_err(|e| ApiError::InvalidSchedule(e.to_string()));
+  }
+
+  let cron = cron::Schedule::from_str(schedule).map_err(|e| ApiError::InvalidSchedule(e.to_string()))?;
+  let fire_times = cron
+    .upcoming(timezone::default_tz())
+    .take(CRON_INTERVAL_SAMPLES)
+    .collect::<Vec<_>>();
+
+  Ok(
+    fire_times
+      .windows(2)
+      .filter_map(|pair| (pair[1] - pair[0]).to_std().ok())
+      .min(),
+  )
+}
+
+/// Rejects options whose serialized JSON is larger than `TASK_MAX_OPTIONS_BYTES`, they would otherwise be
+/// stored and read back on every list and dispatch
+pub(crate) fn check_options_size(options: &Value) -> ApiResult<()> {
+  let limit = TASK_MAX_OPTIONS_BYTES
+    .as_ref()
+    .copied()
+    .map_err(|err| ApiError::Anyhow(anyhow!(err.clone())))?;
+  let size = serde_json::to_vec(options).map_err(anyhow::Error::from)?.len();
+
+  if size > limit {
+    return Err(ApiError::OptionsTooLarge(size, limit));
+  }
+
+  Ok(())
+}
+
+fn max_retries() -> ApiResult<i32> {
+  TASK_MAX_RETRIES
+    .as_ref()
+    .copied()
+    .map_err(|err| ApiError::Anyhow(anyhow!(err.clone())))
+}
+
+fn build_task(task: TaskRow, project: ProjectRow) -> ApiResult<Task> {
+  Ok(Task {
+    id: task.id,
+    name: task.name,
+    r#type: task.r#type,
+    plugin_version: task.plugin_version,
+    status: TaskStatus::decode(&task.status)?,
+    project,
+    retries: task.retries,
+    external_id: task.external_id,
+    external_modified_at: task.external_modified_at,
+    schedule: task.schedule,
     start_at: task.start_at,
+    schedule_anchor: task.schedule_anchor,
+    end_at: task.end_at,
+    locked_by: task.locked_by,
+    misfire_policy: MisfirePolicy::decode(&task.misfire_policy)?,
+    enabled: task.enabled,
+    timezone: timezone::default_tz().to_string(),
+    utc_offset: timezone::utc_offset(task.start_at),
     options: task.options,
+    created_by: task.created_by,
     created_at: task.created_at,
     updated_at: task.updated_at,
-  }
+    version: task.version,
+    failed_results: task.failed_results,
+  })
 }
 
-fn map_task(row: SqliteRow) -> Task {
-  Task {
+fn map_task(row: SqliteRow) -> Result<Task, sqlx::Error> {
+  Ok(Task {
     id: row.get("task_id"),
     r#type: row.get("task_type"),
-    status: row.get("task_status"),
+    plugin_version: row.get("task_plugin_version"),
+    status: TaskStatus::decode(row.get("task_status"))?,
     options: row.get("task_options"),
     start_at: row.get("task_start_at"),
+    schedule_anchor: row.get("task_schedule_anchor"),
+    end_at: row.get("task_end_at"),
+    locked_by: row.get("task_locked_by"),
+    misfire_policy: MisfirePolicy::decode(row.get("task_misfire_policy"))?,
+    enabled: row.get("task_enabled"),
+    timezone: timezone::default_tz().to_string(),
+    utc_offset: timezone::utc_offset(row.get::<i64, _>("task_start_at")),
     schedule: row.get("task_schedule"),
     name: row.get("task_name"),
     retries: row.get("task_retries"),
     external_id: row.get("task_external_id"),
     external_modified_at: row.get("task_external_modified_at"),
+    created_by: row.get("task_created_by"),
     project: map_project_row(&row),
     created_at: row.get("task_created_at"),
     updated_at: row.get("task_updated_at"),
-  }
+    version: row.get("task_version"),
+    failed_results: row.get("task_failed_results"),
+  })
 }
 
 fn map_project_row(row: &SqliteRow) -> ProjectRow {
@@ -338,7 +1797,666 @@ fn map_project_row(row: &SqliteRow) -> ProjectRow {
     code: row.get("project_code"),
     options: row.get("project_options"),
     owner_id: row.get("project_owner_id"),
+    created_by: row.get("project_created_by"),
     created_at: row.get("project_created_at"),
     updated_at: row.get("project_updated_at"),
+    version: row.get("project_version"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::service::test_utils::{task_params, test_pool, SEED_PROJECT_ID};
+
+  async fn import_records(pool: &SqlitePool, records: &[ExportRecord]) -> ApiResult<ImportSummary> {
+    let ndjson: String = records
+      .iter()
+      .map(|record| serde_json::to_string(record).unwrap() + "\n")
+      .collect();
+
+    import(pool, None, futures::stream::iter([Ok::<_, ApiError>(ndjson)])).await
   }
-}
\ No newline at end of file
+
+  #[tokio::test]
+  async fn test_retries_reset_after_success() {
+    let pool = test_pool().await;
+
+    let task = create(
+      &pool,
+      None,
+      CreateTaskParams {
+        schedule: Some("@every 1m".to_string()),
+        ..task_params("recurring")
+      },
+    )
+    .await
+    .unwrap();
+
+    failed_task(&pool, task.id).await.unwrap();
+    assert_eq!(failed_task(&pool, task.id).await.unwrap().retries, 2);
+
+    assert_eq!(reset_retries(&pool, task.id).await.unwrap().retries, 0);
+    schedule_task(&pool, task.id, 0).await.unwrap();
+
+    assert_eq!(failed_task(&pool, task.id).await.unwrap().retries, 1);
+    assert!(escalate_dead_tasks(&pool).await.unwrap().is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_failed_results_recorded() {
+    let pool = test_pool().await;
+
+    let task = create(&pool, None, task_params("partial")).await.unwrap();
+    assert_eq!(task.failed_results, 0);
+
+    set_failed_results(&pool, task.id, 2).await.unwrap();
+    completed_task(&pool, task.id).await.unwrap();
+
+    let task = crate::service::query::tasks::find(&pool, task.id).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Finished);
+    assert_eq!(task.failed_results, 2);
+  }
+
+  #[tokio::test]
+  async fn test_mutation_rolled_back_without_audit_entry() {
+    let pool = test_pool().await;
+    let task = create(&pool, None, task_params("audited")).await.unwrap();
+
+    // The audit entry can't be written, the change it records mustn't be either
+    sqlx::query("DROP TABLE audit_log").execute(&pool).await.unwrap();
+    assert!(set_enabled(&pool, None, task.id, false).await.is_err());
+    assert!(get_task(&pool, task.id).await.unwrap().enabled);
+  }
+
+  #[tokio::test]
+  async fn test_import_keeps_pinned_plugin_version() {
+    let pool = test_pool().await;
+    let task = create(
+      &pool,
+      None,
+      CreateTaskParams {
+        plugin_version: Some("^1.2".to_string()),
+        ..task_params("pinned")
+      },
+    )
+    .await
+    .unwrap();
+    let exported = get_task(&pool, task.id).await.unwrap();
+
+    let unpinned = TaskRow {
+      plugin_version: None,
+      ..exported.clone()
+    };
+    import_records(&pool, &[ExportRecord::Task(Box::new(unpinned))])
+      .await
+      .unwrap();
+    assert_eq!(
+      get_task(&pool, task.id).await.unwrap().plugin_version.as_deref(),
+      Some("^1.2")
+    );
+
+    let invalid = TaskRow {
+      plugin_version: Some("one".to_string()),
+      ..exported
+    };
+    assert!(matches!(
+      import_records(&pool, &[ExportRecord::Task(Box::new(invalid))]).await,
+      Err(ApiError::InvalidImport(1, _))
+    ));
+    assert_eq!(
+      get_task(&pool, task.id).await.unwrap().plugin_version.as_deref(),
+      Some("^1.2")
+    );
+  }
+
+  #[tokio::test]
+  async fn test_import_checks_records_like_create() {
+    let pool = test_pool().await;
+    let seed = get_project(&pool, Uuid::parse_str(SEED_PROJECT_ID).unwrap())
+      .await
+      .unwrap();
+    let exported = create(&pool, None, task_params("exported")).await.unwrap();
+    let exported = get_task(&pool, exported.id).await.unwrap();
+
+    let project = ProjectRow {
+      id: Uuid::new_v4(),
+      code: "imp".to_string(),
+      ..seed.clone()
+    };
+    let task = TaskRow {
+      id: Uuid::new_v4(),
+      project_id: project.id,
+      ..exported.clone()
+    };
+    let summary = import_records(
+      &pool,
+      &[
+        ExportRecord::Project(project.clone()),
+        ExportRecord::Task(Box::new(task.clone())),
+      ],
+    )
+    .await
+    .unwrap();
+    assert_eq!((summary.projects, summary.tasks), (1, 1));
+    assert_eq!(get_project(&pool, project.id).await.unwrap().code, "IMP");
+
+    let (audited,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM audit_log WHERE entity_id IN (?1, ?2)")
+      .bind(project.id)
+      .bind(task.id)
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+    assert_eq!(audited, 2);
+
+    // Rejected like they would be by `create`, with the line of the record
+    let duplicate_code = ProjectRow {
+      id: Uuid::new_v4(),
+      code: "PPF".to_string(),
+      ..seed
+    };
+    assert!(matches!(
+      import_records(&pool, &[ExportRecord::Project(duplicate_code)]).await,
+      Err(ApiError::InvalidImport(1, _))
+    ));
+
+    let too_frequent = TaskRow {
+      id: Uuid::new_v4(),
+      schedule: Some("@every 5s".to_string()),
+      ..exported
+    };
+    assert!(matches!(
+      import_records(&pool, &[ExportRecord::Task(Box::new(too_frequent.clone()))]).await,
+      Err(ApiError::InvalidImport(1, _))
+    ));
+    assert!(get_task_with_deleted(&pool, too_frequent.id).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_disabled_task_survives_reschedule() {
+    let pool = test_pool().await;
+
+    let task = create(
+      &pool,
+      None,
+      CreateTaskParams {
+        schedule: Some("@every 1m".to_string()),
+        ..task_params("recurring")
+      },
+    )
+    .await
+    .unwrap();
+
+    assert!(!set_enabled(&pool, None, task.id, false).await.unwrap().enabled);
+    schedule_task(&pool, task.id, 0).await.unwrap();
+    assert!(get_tasks_to_run(&pool, "test").await.unwrap().is_empty());
+
+    assert!(set_enabled(&pool, None, task.id, true).await.unwrap().enabled);
+    let tasks = get_tasks_to_run(&pool, "test").await.unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].start_at, 0);
+  }
+
+  #[tokio::test]
+  async fn test_stale_external_tasks_deleted_after_max_age() {
+    let pool = test_pool().await;
+
+    let params = |external_id: Option<&str>| CreateTaskParams {
+      external_id: external_id.map(str::to_string),
+      ..task_params("synced")
+    };
+    let external = create(&pool, None, params(Some("ISSUE-1"))).await.unwrap();
+    create(&pool, None, params(None)).await.unwrap();
+
+    // A task the sync just wrote is kept
+    let max_age = std::time::Duration::from_secs(30 * 60);
+    assert!(delete_stale_external_tasks(&pool, max_age).await.unwrap().is_empty());
+
+    // The trigger would reset updated_at on every update, drop it to age the rows
+    sqlx::query("DROP TRIGGER trig_tasks_updated_at")
+      .execute(&pool)
+      .await
+      .unwrap();
+    sqlx::query("UPDATE tasks SET updated_at = datetime('now', '-1 hour')")
+      .execute(&pool)
+      .await
+      .unwrap();
+
+    // Only the external task is deleted once it's older than the window
+    assert_eq!(
+      delete_stale_external_tasks(&pool, max_age).await.unwrap(),
+      vec![external.id]
+    );
+    let (remaining,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tasks")
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+    assert_eq!(remaining, 1);
+  }
+
+  #[tokio::test]
+  async fn test_expire_tasks_past_ttl() {
+    let pool = test_pool().await;
+
+    let params = |name: &str, schedule: Option<&str>, start_at: i64| CreateTaskParams {
+      r#type: "removed-plugin".to_string(),
+      schedule: schedule.map(str::to_string),
+      start_at,
+      ..task_params(name)
+    };
+    let day_ago = Utc::now().timestamp() - 24 * 60 * 60;
+    let stuck = create(&pool, None, params("stuck", None, day_ago)).await.unwrap();
+    let failing = create(&pool, None, params("failing", None, day_ago)).await.unwrap();
+    failed_task(&pool, failing.id).await.unwrap();
+    create(&pool, None, params("recurring", Some("@every 1h"), day_ago))
+      .await
+      .unwrap();
+    create(&pool, None, params("recent", None, Utc::now().timestamp()))
+      .await
+      .unwrap();
+    let disabled = create(&pool, None, params("disabled", None, day_ago)).await.unwrap();
+    set_enabled(&pool, None, disabled.id, false).await.unwrap();
+    sqlx::query("UPDATE tasks SET created_at = datetime('now', '-1 day')")
+      .execute(&pool)
+      .await
+      .unwrap();
+    // Backfilled with a past start time, its TTL runs from now
+    create(&pool, None, params("backfilled", None, day_ago)).await.unwrap();
+
+    let ttl = std::time::Duration::from_secs(60 * 60);
+    assert!(expire_tasks_older_than(&pool, std::time::Duration::ZERO)
+      .await
+      .unwrap()
+      .is_empty());
+
+    let mut expired: Vec<Uuid> = expire_tasks_older_than(&pool, ttl)
+      .await
+      .unwrap()
+      .iter()
+      .map(|task| task.id)
+      .collect();
+    expired.sort();
+    let mut expected = vec![stuck.id, failing.id];
+    expected.sort();
+    assert_eq!(expired, expected);
+    assert_eq!(get_task(&pool, stuck.id).await.unwrap().status, "dead");
+    assert!(expire_tasks_older_than(&pool, ttl).await.unwrap().is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_cancelled_tasks_kept() {
+    let pool = test_pool().await;
+
+    let task = create(&pool, None, task_params("cancelled")).await.unwrap();
+    let cancel = |status| {
+      bulk_status(
+        &pool,
+        None,
+        BulkFilter {
+          status: Some(status),
+          ..Default::default()
+        },
+        BulkAction::Cancel,
+      )
+    };
+    assert_eq!(cancel(TaskStatus::New).await.unwrap().updated, 1);
+    assert_eq!(get_task(&pool, task.id).await.unwrap().status, "cancelled");
+    assert!(get_tasks_to_run(&pool, "test").await.unwrap().is_empty());
+
+    // Cleanup of finished tasks leaves it alone however long ago it was cancelled
+    sqlx::query("UPDATE tasks SET updated_at = datetime('now', '-7 days')")
+      .execute(&pool)
+      .await
+      .unwrap();
+    assert!(delete_completed_tasks(&pool).await.unwrap().is_empty());
+    assert_eq!(cancel(TaskStatus::Cancelled).await.unwrap().skipped, 1);
+
+    let retry = BulkFilter {
+      status: Some(TaskStatus::Cancelled),
+      ..Default::default()
+    };
+    assert_eq!(
+      bulk_status(&pool, None, retry, BulkAction::Retry)
+        .await
+        .unwrap()
+        .updated,
+      1
+    );
+    assert_eq!(get_tasks_to_run(&pool, "test").await.unwrap()[0].id, task.id);
+  }
+
+  #[tokio::test]
+  async fn test_run_now_keeps_schedule() {
+    let pool = test_pool().await;
+
+    let task = create(
+      &pool,
+      None,
+      CreateTaskParams {
+        schedule: Some("0 0 3 * * *".to_string()),
+        start_at: i64::from(i32::MAX),
+        ..task_params("nightly")
+      },
+    )
+    .await
+    .unwrap();
+    assert!(get_tasks_to_run(&pool, "test").await.unwrap().is_empty());
+
+    let task = run_now(&pool, None, task.id).await.unwrap();
+    assert!(task.start_at <= Utc::now().timestamp());
+    assert_eq!(task.status, TaskStatus::New);
+    assert_eq!(task.schedule.as_deref(), Some("0 0 3 * * *"));
+    assert_eq!(task.schedule_anchor, Some(i64::from(i32::MAX)));
+
+    // Once the poller picks it up the task is in progress and can't be run again
+    assert_eq!(get_tasks_to_run(&pool, "test").await.unwrap().len(), 1);
+    assert!(matches!(
+      run_now(&pool, None, task.id).await,
+      Err(ApiError::Conflict(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_run_now_rejects_disabled_task() {
+    let pool = test_pool().await;
+
+    let task = create(&pool, None, task_params("paused")).await.unwrap();
+    set_enabled(&pool, None, task.id, false).await.unwrap();
+
+    assert!(matches!(
+      run_now(&pool, None, task.id).await,
+      Err(ApiError::Conflict(message)) if message.contains("disabled")
+    ));
+    assert!(get_tasks_to_run(&pool, "test").await.unwrap().is_empty());
+
+    set_enabled(&pool, None, task.id, true).await.unwrap();
+    run_now(&pool, None, task.id).await.unwrap();
+    assert_eq!(get_tasks_to_run(&pool, "test").await.unwrap().len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_soft_delete_and_restore() {
+    let pool = test_pool().await;
+
+    let task = create(&pool, None, task_params("deleted")).await.unwrap();
+
+    soft_delete(&pool, None, task.id).await.unwrap();
+    assert!(get_tasks_to_run(&pool, "test").await.unwrap().is_empty());
+    assert!(matches!(
+      soft_delete(&pool, None, task.id).await,
+      Err(ApiError::ResourceNotFound(_))
+    ));
+
+    let restored = restore(&pool, None, task.id).await.unwrap();
+    assert_eq!(restored.id, task.id);
+    assert!(matches!(
+      restore(&pool, None, task.id).await,
+      Err(ApiError::Conflict(_))
+    ));
+
+    // Purged only once deleted for longer than the retention window
+    soft_delete(&pool, None, task.id).await.unwrap();
+    assert!(purge_deleted_tasks(&pool).await.unwrap().is_empty());
+    sqlx::query("UPDATE tasks SET deleted_at = datetime('now', '-30 days')")
+      .execute(&pool)
+      .await
+      .unwrap();
+    assert_eq!(purge_deleted_tasks(&pool).await.unwrap(), vec![task.id]);
+  }
+
+  #[tokio::test]
+  async fn test_plugin_version_pinning() {
+    let pool = test_pool().await;
+
+    let params = |plugin_version: &str| CreateTaskParams {
+      plugin_version: Some(plugin_version.to_string()),
+      ..task_params("pinned")
+    };
+
+    assert!(matches!(
+      create(&pool, None, params("one")).await,
+      Err(ApiError::InvalidPluginVersion(version, _)) if version == "one"
+    ));
+
+    let task = create(&pool, None, params("^1.2")).await.unwrap();
+    assert_eq!(task.plugin_version.as_deref(), Some("^1.2"));
+
+    let unpin = PatchTaskParams {
+      plugin_version: Some(None),
+      ..Default::default()
+    };
+    assert_eq!(patch(&pool, None, task.id, unpin).await.unwrap().plugin_version, None);
+  }
+
+  #[tokio::test]
+  async fn test_empty_patch_checks_version() {
+    let pool = test_pool().await;
+
+    let task = create(&pool, None, task_params("guarded")).await.unwrap();
+    let renamed = PatchTaskParams {
+      name: Some("renamed".to_string()),
+      ..Default::default()
+    };
+    let task = patch(&pool, None, task.id, renamed).await.unwrap();
+
+    let stale = PatchTaskParams {
+      version: Some(task.version - 1),
+      ..Default::default()
+    };
+    assert!(matches!(
+      patch(&pool, None, task.id, stale).await,
+      Err(ApiError::Conflict(_))
+    ));
+
+    let audited = || async {
+      let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM audit_log WHERE entity_id = ?1")
+        .bind(task.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+      count
+    };
+    let before = audited().await;
+    let mut events = events::subscribe();
+
+    let current = PatchTaskParams {
+      version: Some(task.version),
+      ..Default::default()
+    };
+    assert_eq!(
+      patch(&pool, None, task.id, current).await.unwrap().version,
+      task.version
+    );
+
+    // A patch changing nothing is neither audited nor published
+    assert_eq!(audited().await, before);
+    while let Ok(event) = events.try_recv() {
+      assert_ne!(event.id, task.id);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_start_at_after_2038() {
+    let pool = test_pool().await;
+
+    // 2040-01-01T00:00:00Z, past the largest i32 timestamp
+    let start_at = 2_208_988_800;
+    assert!(start_at > i64::from(i32::MAX));
+
+    let task = create(
+      &pool,
+      None,
+      CreateTaskParams {
+        schedule: Some("@every 1d".to_string()),
+        start_at,
+        end_at: Some(start_at + 7 * 86_400),
+        ..task_params("far future")
+      },
+    )
+    .await
+    .unwrap();
+    assert_eq!(task.start_at, start_at);
+    assert_eq!(task.schedule_anchor, Some(start_at));
+    assert!(get_tasks_to_run(&pool, "test").await.unwrap().is_empty());
+
+    schedule_task(&pool, task.id, start_at + 86_400).await.unwrap();
+    let task = crate::service::query::tasks::find(&pool, task.id).await.unwrap();
+    assert_eq!(task.start_at, start_at + 86_400);
+    assert_eq!(task.end_at, Some(start_at + 7 * 86_400));
+  }
+
+  #[tokio::test]
+  async fn test_stale_locks_reclaimed_by_other_executors() {
+    let pool = test_pool().await;
+
+    let task = create(&pool, None, task_params("long running")).await.unwrap();
+
+    let claimed = get_tasks_to_run(&pool, "executor-a").await.unwrap();
+    assert_eq!(claimed[0].locked_by.as_deref(), Some("executor-a"));
+    assert!(get_tasks_to_run(&pool, "executor-b").await.unwrap().is_empty());
+
+    let age_lock = "UPDATE tasks SET locked_at = datetime('now', '-10 minutes') WHERE id = ?1";
+    sqlx::query(age_lock).bind(task.id).execute(&pool).await.unwrap();
+    assert!(get_tasks_to_run(&pool, "executor-a").await.unwrap().is_empty());
+
+    assert_eq!(refresh_locks(&pool, "executor-a").await.unwrap(), 1);
+    assert!(get_tasks_to_run(&pool, "executor-b").await.unwrap().is_empty());
+
+    sqlx::query(age_lock).bind(task.id).execute(&pool).await.unwrap();
+    let reclaimed = get_tasks_to_run(&pool, "executor-b").await.unwrap();
+    assert_eq!(reclaimed[0].id, task.id);
+    assert_eq!(reclaimed[0].locked_by.as_deref(), Some("executor-b"));
+
+    // Locks taken before executors were identified are reclaimed as well
+    let unowned = "UPDATE tasks SET locked_by = NULL, locked_at = datetime('now', '-10 minutes') WHERE id = ?1";
+    sqlx::query(unowned).bind(task.id).execute(&pool).await.unwrap();
+    assert_eq!(get_tasks_to_run(&pool, "executor-a").await.unwrap()[0].id, task.id);
+  }
+
+  #[tokio::test]
+  async fn test_locks_released_after_restart() {
+    let pool = test_pool().await;
+
+    let task = create(&pool, None, task_params("interrupted")).await.unwrap();
+    assert_eq!(get_tasks_to_run(&pool, "executor-a").await.unwrap().len(), 1);
+
+    // The executor crashes mid-run and comes back with the same id, its refreshed lock never goes stale
+    assert_eq!(refresh_locks(&pool, "executor-a").await.unwrap(), 1);
+    assert!(get_tasks_to_run(&pool, "executor-a").await.unwrap().is_empty());
+
+    assert!(release_locks(&pool, "executor-b").await.unwrap().is_empty());
+    assert_eq!(release_locks(&pool, "executor-a").await.unwrap(), vec![task.id]);
+    let claimed = get_tasks_to_run(&pool, "executor-a").await.unwrap();
+    assert_eq!(claimed[0].id, task.id);
+    assert_eq!(claimed[0].status, TaskStatus::InProgress);
+  }
+
+  #[tokio::test]
+  async fn test_pickup_published() {
+    let pool = test_pool().await;
+    let mut events = events::subscribe();
+
+    let task = create(&pool, None, task_params("picked up")).await.unwrap();
+    assert_eq!(get_tasks_to_run(&pool, "executor-a").await.unwrap().len(), 1);
+
+    // Other tests publish to the same channel, only the events of this task count
+    let mut statuses = Vec::new();
+    while let Ok(event) = events.try_recv() {
+      if event.id == task.id {
+        statuses.push((event.change, event.status));
+      }
+    }
+    assert_eq!(
+      statuses,
+      vec![
+        (Change::Created, Some(TaskStatus::New)),
+        (Change::Updated, Some(TaskStatus::InProgress)),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_schedule_interval_minimum() {
+    // Default `TASK_MIN_SCHEDULE_INTERVAL` of one minute
+    assert!(check_schedule_interval(None).is_ok());
+    assert!(check_schedule_interval(Some("@every 1m")).is_ok());
+    assert!(check_schedule_interval(Some("0 */5 * * * *")).is_ok());
+    assert!(matches!(
+      check_schedule_interval(Some("@every 5s")),
+      Err(ApiError::ScheduleTooFrequent(_, 5, 60))
+    ));
+    // Fires at seconds 0 and 10 of every minute, the 10s gap is what counts
+    assert!(matches!(
+      check_schedule_interval(Some("0,10 * * * * *")),
+      Err(ApiError::ScheduleTooFrequent(_, 10, 60))
+    ));
+    assert!(matches!(
+      check_schedule_interval(Some("not a cron")),
+      Err(ApiError::InvalidSchedule(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_concurrent_external_syncs() {
+    let pool = test_pool().await;
+
+    let synced = |modified_at: i64| CreateTaskParams {
+      external_id: Some("ISSUE-42".to_string()),
+      external_modified_at: DateTime::from_timestamp(modified_at, 0),
+      ..task_params("issue 42")
+    };
+
+    // Queries of the syncs interleave on the single connection, each reading before any of them writes
+    let tasks = futures::future::try_join_all((0..4).map(|_| create(&pool, None, synced(100))))
+      .await
+      .unwrap();
+    assert!(tasks.iter().all(|task| task.id == tasks[0].id));
+
+    let created: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_log WHERE action = 'create' AND entity_id = ?1")
+      .bind(tasks[0].id)
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+    assert_eq!(created, 1);
+
+    failed_task(&pool, tasks[0].id).await.unwrap();
+    let unchanged = create(&pool, None, synced(100)).await.unwrap();
+    assert_eq!(unchanged.status, TaskStatus::Failed);
+
+    let changed = create(&pool, None, synced(200)).await.unwrap();
+    assert_eq!(changed.status, TaskStatus::New);
+    assert_eq!(get_task(&pool, changed.id).await.unwrap().status, "new");
+  }
+
+  #[tokio::test]
+  async fn test_poller_queries_use_indexes() {
+    let pool = test_pool().await;
+
+    let plan = |query: &str| {
+      let query = format!("EXPLAIN QUERY PLAN {}", query);
+      let pool = pool.clone();
+
+      async move {
+        let rows: Vec<(i64, i64, i64, String)> = sqlx::query_as(&query).fetch_all(&pool).await.unwrap();
+        rows
+          .into_iter()
+          .map(|(_, _, _, detail)| detail)
+          .collect::<Vec<_>>()
+          .join("\n")
+      }
+    };
+
+    let poller = plan(SELECT_TASKS_TO_RUN).await;
+    assert!(poller.contains("USING INDEX idx_tasks_runnable"), "{}", poller);
+
+    let stale = plan(DELETE_STALE_TASKS).await;
+    assert!(stale.contains("USING INDEX idx_tasks_external_updated_at"), "{}", stale);
+
+    let finished = plan(DELETE_OLD_TASKS).await;
+    assert!(
+      finished.contains("USING INDEX idx_tasks_status_start_at"),
+      "{}",
+      finished
+    );
+  }
+}
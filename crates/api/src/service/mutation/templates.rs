@@ -0,0 +1,336 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+  entities::{
+    audit::{AuditAction, AuditEntity},
+    task::{MisfirePolicy, Task},
+    template::{TaskTemplate, TaskTemplateRow},
+  },
+  error::{ApiError, ApiResult},
+  service::options,
+};
+
+use super::{
+  audit,
+  tasks::{self, check_options_size, check_plugin_version, check_schedule_interval, CreateTaskParams},
+};
+
+// SQL Query Constants
+const FIND_TEMPLATE: &str = "SELECT * FROM task_templates WHERE id = ?1";
+const FIND_OTHER_TEMPLATE_BY_NAME: &str =
+  "SELECT * FROM task_templates WHERE name = ?1 COLLATE NOCASE AND (?2 IS NULL OR id != ?2)";
+const INSERT_TEMPLATE: &str = r#"
+  INSERT INTO task_templates (id, name, description, type, plugin_version, schedule, misfire_policy, options, created_by)
+  VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+  RETURNING *
+"#;
+const UPDATE_TEMPLATE: &str = r#"
+  UPDATE task_templates
+  SET name = ?1, description = ?2, type = ?3, plugin_version = ?4, schedule = ?5, misfire_policy = ?6, options = ?7,
+    updated_at = CURRENT_TIMESTAMP
+  WHERE id = ?8
+  RETURNING *
+"#;
+const DELETE_TEMPLATE: &str = "DELETE FROM task_templates WHERE id = ?1";
+
+/// Every field of a template, an update replaces all of them
+#[derive(Debug, Deserialize, Clone)]
+pub struct TemplateParams {
+  pub name: String,
+  pub description: Option<String>,
+  pub r#type: String,
+  pub plugin_version: Option<String>,
+  pub schedule: Option<String>,
+  pub misfire_policy: MisfirePolicy,
+  pub options: Value,
+}
+
+/// Creates a new task template
+///
+/// # Errors
+/// - Conflict if a template with the same name exists, compared case-insensitively
+/// - OptionsTooLarge if the options skeleton exceeds `TASK_MAX_OPTIONS_BYTES`
+/// - InvalidPluginVersion if `plugin_version` is not a semver requirement
+pub async fn create(pool: &SqlitePool, actor_id: Option<Uuid>, params: TemplateParams) -> ApiResult<TaskTemplate> {
+  check_template(&params)?;
+  ensure_name_is_free(pool, &params.name, None).await?;
+
+  let mut tx = pool.begin().await?;
+  let template = sqlx::query_as::<_, TaskTemplateRow>(INSERT_TEMPLATE)
+    .bind(Uuid::new_v4())
+    .bind(&params.name)
+    .bind(&params.description)
+    .bind(&params.r#type)
+    .bind(&params.plugin_version)
+    .bind(&params.schedule)
+    .bind(params.misfire_policy.to_string())
+    .bind(&params.options)
+    .bind(actor_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| name_conflict(err, &params.name))?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Create,
+    AuditEntity::TaskTemplate,
+    template.id,
+    json!(template),
+  )
+  .await?;
+
+  tx.commit().await?;
+
+  Ok(TaskTemplate::try_from(template)?)
+}
+
+/// Replaces every field of a task template, tasks already created from it are left as they are
+///
+/// # Errors
+/// - ResourceNotFound if the template doesn't exist
+/// - Conflict if another template has the same name, compared case-insensitively
+/// - OptionsTooLarge if the options skeleton exceeds `TASK_MAX_OPTIONS_BYTES`
+/// - InvalidPluginVersion if `plugin_version` is not a semver requirement
+pub async fn update(
+  pool: &SqlitePool,
+  actor_id: Option<Uuid>,
+  id: Uuid,
+  params: TemplateParams,
+) -> ApiResult<TaskTemplate> {
+  let existing = get_template(pool, id).await?;
+
+  check_template(&params)?;
+  ensure_name_is_free(pool, &params.name, Some(id)).await?;
+
+  let mut tx = pool.begin().await?;
+  let template = sqlx::query_as::<_, TaskTemplateRow>(UPDATE_TEMPLATE)
+    .bind(&params.name)
+    .bind(&params.description)
+    .bind(&params.r#type)
+    .bind(&params.plugin_version)
+    .bind(&params.schedule)
+    .bind(params.misfire_policy.to_string())
+    .bind(&params.options)
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| name_conflict(err, &params.name))?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::TaskTemplate,
+    id,
+    audit::diff(&json!(existing), &json!(template)),
+  )
+  .await?;
+
+  tx.commit().await?;
+
+  Ok(TaskTemplate::try_from(template)?)
+}
+
+/// Deletes a task template, tasks created from it are kept
+///
+/// # Errors
+/// - ResourceNotFound if the template doesn't exist
+pub async fn delete(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid) -> ApiResult<()> {
+  let existing = get_template(pool, id).await?;
+
+  let mut tx = pool.begin().await?;
+  sqlx::query(DELETE_TEMPLATE).bind(id).execute(&mut *tx).await?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Delete,
+    AuditEntity::TaskTemplate,
+    id,
+    json!(existing),
+  )
+  .await?;
+
+  tx.commit().await?;
+
+  Ok(())
+}
+
+/// What a task created from a template sets itself, `None` keeps the template's value
+#[derive(Debug, Deserialize, Clone)]
+pub struct InstantiateTemplateParams {
+  pub project_id: Uuid,
+  pub name: String,
+  /// Unix timestamp of the first run, computed from the schedule the task ends up with
+  pub start_at: i64,
+  pub end_at: Option<i64>,
+  pub schedule: Option<String>,
+  pub plugin_version: Option<String>,
+  pub misfire_policy: Option<MisfirePolicy>,
+  /// Merged over the template options, nested objects key by key
+  pub options: Option<Value>,
+}
+
+/// Creates a task in a project from a template with [`tasks::create`]
+///
+/// # Errors
+/// Any error of [`tasks::create`]
+pub async fn instantiate(
+  pool: &SqlitePool,
+  actor_id: Option<Uuid>,
+  template: TaskTemplate,
+  params: InstantiateTemplateParams,
+) -> ApiResult<Task> {
+  let mut options = template.options;
+  if let Some(overrides) = params.options {
+    options::merge(&mut options, overrides);
+  }
+
+  tasks::create(
+    pool,
+    actor_id,
+    CreateTaskParams {
+      r#type: template.r#type,
+      plugin_version: params.plugin_version.or(template.plugin_version),
+      name: params.name,
+      project_id: params.project_id,
+      schedule: params.schedule.or(template.schedule),
+      external_id: None,
+      external_modified_at: None,
+      start_at: params.start_at,
+      end_at: params.end_at,
+      misfire_policy: params.misfire_policy.unwrap_or(template.misfire_policy),
+      options,
+    },
+  )
+  .await
+}
+
+fn check_template(params: &TemplateParams) -> ApiResult<()> {
+  check_options_size(&params.options)?;
+  check_plugin_version(params.plugin_version.as_deref())?;
+  check_schedule_interval(params.schedule.as_deref())
+}
+
+async fn ensure_name_is_free(pool: &SqlitePool, name: &str, except_id: Option<Uuid>) -> ApiResult<()> {
+  let existing = sqlx::query_as::<_, TaskTemplateRow>(FIND_OTHER_TEMPLATE_BY_NAME)
+    .bind(name)
+    .bind(except_id)
+    .fetch_optional(pool)
+    .await?;
+
+  match existing {
+    Some(_) => Err(name_taken(name)),
+    None => Ok(()),
+  }
+}
+
+/// A template created or renamed concurrently can take the name after [`ensure_name_is_free`], the unique index
+/// rejects the write then
+fn name_conflict(err: sqlx::Error, name: &str) -> ApiError {
+  match err {
+    sqlx::Error::Database(e) if e.is_unique_violation() => name_taken(name),
+    err => err.into(),
+  }
+}
+
+fn name_taken(name: &str) -> ApiError {
+  ApiError::Conflict(format!("Template `{}` already exists", name))
+}
+
+async fn get_template(pool: &SqlitePool, id: Uuid) -> ApiResult<TaskTemplateRow> {
+  sqlx::query_as::<_, TaskTemplateRow>(FIND_TEMPLATE)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::ResourceNotFound(id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::service::test_utils::{test_pool, SEED_PROJECT_ID};
+
+  #[tokio::test]
+  async fn test_instantiate_template() {
+    let pool = test_pool().await;
+
+    let params = TemplateParams {
+      name: "nightly sync".to_string(),
+      description: None,
+      r#type: "http".to_string(),
+      plugin_version: Some("^1".to_string()),
+      schedule: Some("@every 1d".to_string()),
+      misfire_policy: MisfirePolicy::Skip,
+      options: json!({ "url": "https://example.com", "headers": { "accept": "json" } }),
+    };
+    let template = create(&pool, None, params.clone()).await.unwrap();
+
+    let duplicate = TemplateParams {
+      name: "Nightly Sync".to_string(),
+      ..params
+    };
+    assert!(matches!(
+      create(&pool, None, duplicate).await,
+      Err(ApiError::Conflict(_))
+    ));
+
+    let task = instantiate(
+      &pool,
+      None,
+      template,
+      InstantiateTemplateParams {
+        project_id: Uuid::parse_str(SEED_PROJECT_ID).unwrap(),
+        name: "platform sync".to_string(),
+        start_at: 0,
+        end_at: None,
+        schedule: None,
+        plugin_version: None,
+        misfire_policy: Some(MisfirePolicy::CatchUp),
+        options: Some(json!({ "headers": { "token": "secret" } })),
+      },
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(task.r#type, "http");
+    assert_eq!(task.plugin_version.as_deref(), Some("^1"));
+    assert_eq!(task.schedule.as_deref(), Some("@every 1d"));
+    assert_eq!(task.misfire_policy, MisfirePolicy::CatchUp);
+    assert_eq!(
+      task.options,
+      json!({ "url": "https://example.com", "headers": { "accept": "json", "token": "secret" } })
+    );
+  }
+
+  #[tokio::test]
+  async fn test_name_taken_concurrently_is_a_conflict() {
+    let pool = test_pool().await;
+
+    // Like a create racing another one past `ensure_name_is_free`
+    let insert = |name: &'static str| {
+      sqlx::query(INSERT_TEMPLATE)
+        .bind(Uuid::new_v4())
+        .bind(name)
+        .bind(None::<String>)
+        .bind("http")
+        .bind(None::<String>)
+        .bind(None::<String>)
+        .bind(MisfirePolicy::Skip.to_string())
+        .bind(json!({}))
+        .bind(None::<Uuid>)
+        .execute(&pool)
+    };
+    insert("nightly sync").await.unwrap();
+    let err = insert("Nightly Sync").await.unwrap_err();
+
+    assert!(matches!(
+      name_conflict(err, "Nightly Sync"),
+      ApiError::Conflict(message) if message.contains("Nightly Sync")
+    ));
+  }
+}
@@ -0,0 +1,27 @@
+use sqlx::SqlitePool;
+
+use crate::error::ApiResult;
+
+const REVOKE_TOKEN: &str = "INSERT INTO revoked_tokens (jti, expires_at) VALUES (?1, ?2) ON CONFLICT (jti) DO NOTHING";
+const DELETE_EXPIRED_TOKENS: &str = "DELETE FROM revoked_tokens WHERE expires_at < unixepoch()";
+
+/// Adds a token to the revocation list until its expiry time
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `jti` - The unique id of the token
+/// * `expires_at` - The token expiry as a unix timestamp
+pub async fn revoke(pool: &SqlitePool, jti: &str, expires_at: i64) -> ApiResult<()> {
+  sqlx::query(REVOKE_TOKEN)
+    .bind(jti)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+  Ok(())
+}
+
+/// Drops revocation entries for tokens that have expired anyway
+pub async fn delete_expired(pool: &SqlitePool) -> ApiResult<u64> {
+  Ok(sqlx::query(DELETE_EXPIRED_TOKENS).execute(pool).await?.rows_affected())
+}
@@ -1,17 +1,25 @@
-use anyhow::Context;
+use std::{env, time::Duration};
+
+use anyhow::{anyhow, Context};
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
 use rand_core::OsRng;
 use secrecy::{ExposeSecret, SecretBox};
 use serde::Deserialize;
-use sqlx::SqlitePool;
+use serde_json::json;
+use sqlx::{SqliteConnection, SqlitePool};
 use tokio::task;
 use tracing::{error, info};
 use uuid::Uuid;
 
+use crate::entities::audit::{AuditAction, AuditEntity};
 use crate::entities::user::User;
 use crate::error::{ApiError, ApiResult};
 
+use super::audit;
+
 const FIND_USER_BY_EMAIL: &str = "SELECT * FROM users WHERE email = ?1";
 const FIND_USER_BY_USERNAME: &str = "SELECT * FROM users WHERE username = ?1";
 const FIND_USER_BY_ID: &str = "SELECT * FROM users WHERE id = ?1";
@@ -19,6 +27,45 @@ const CREATE_USER: &str = "INSERT INTO users (id, username, email, password) VAL
 const UPDATE_USER: &str =
   "UPDATE users SET username = ?1, role = ?2, email = ?3, password = ?4 WHERE id = ?5 RETURNING *";
 const DELETE_USER: &str = "DELETE FROM users WHERE id = ?";
+const FIND_USER_LOCKED_UNTIL: &str = "SELECT locked_until FROM users WHERE id = ?1";
+const RESET_FAILED_LOGINS: &str = "UPDATE users SET failed_login_attempts = 0, locked_until = NULL WHERE id = ?1";
+const REGISTER_FAILED_LOGIN: &str = r#"
+  UPDATE users
+  SET
+    failed_login_attempts = CASE WHEN failed_login_attempts + 1 >= ?1 THEN 0 ELSE failed_login_attempts + 1 END,
+    locked_until = CASE WHEN failed_login_attempts + 1 >= ?1 THEN ?2 ELSE locked_until END
+  WHERE id = ?3
+"#;
+
+const DEFAULT_LOGIN_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_LOGIN_LOCKOUT: &str = "15m";
+
+const DEFAULT_ARGON2_MEMORY_KIB: u32 = 15000;
+const DEFAULT_ARGON2_ITERATIONS: u32 = 2;
+const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+
+/// Argon2id parameters used for hashing new passwords, read from the environment:
+///
+/// * `ARGON2_MEMORY_KIB` - memory cost in KiB (default 15000)
+/// * `ARGON2_ITERATIONS` - number of passes (default 2)
+/// * `ARGON2_PARALLELISM` - degree of parallelism (default 1)
+///
+/// OWASP recommends at least 19 MiB of memory with 2 iterations, or 12 MiB with 3. Raise memory
+/// first while a login on the target hardware stays well under a second, then add iterations.
+/// Existing hashes keep their own parameters in the PHC string, so changing these only
+/// affects passwords hashed afterwards.
+static ARGON2_PARAMS: Lazy<Result<Params, String>> = Lazy::new(load_argon2_params);
+
+/// Temporary account lockout after repeated failed logins, read from the environment:
+///
+/// * `LOGIN_MAX_ATTEMPTS` - consecutive failures before the account is locked (default 5)
+/// * `LOGIN_LOCKOUT` - how long the account stays locked, e.g. `15m` or `1h` (default 15m)
+static LOCKOUT_POLICY: Lazy<Result<LockoutPolicy, String>> = Lazy::new(load_lockout_policy);
+
+struct LockoutPolicy {
+  max_attempts: u32,
+  cooldown: chrono::Duration,
+}
 
 #[derive(Debug, Deserialize)]
 pub struct LoginParams {
@@ -26,10 +73,41 @@ pub struct LoginParams {
   pub password: SecretBox<String>,
 }
 
+/// Checks the user's credentials, locking the account for a cooldown after too many consecutive failures
+///
+/// # Errors
+/// - InvalidCredentials if the username or password is wrong
+/// - AccountLocked if the account is locked, even when the password is correct
 pub async fn login(pool: &SqlitePool, params: LoginParams) -> ApiResult<User> {
+  let policy = LOCKOUT_POLICY
+    .as_ref()
+    .map_err(|err| ApiError::Anyhow(anyhow!(err.clone())))?;
+
   let user = find_user_by_username(pool, &params.username).await?;
-  verify_password(SecretBox::from(Box::new(user.password.to_owned())), params.password).await?;
-  Ok(user)
+
+  let locked_until: Option<DateTime<Utc>> = sqlx::query_scalar(FIND_USER_LOCKED_UNTIL)
+    .bind(user.id)
+    .fetch_one(pool)
+    .await?;
+  if let Some(locked_until) = locked_until.filter(|locked_until| *locked_until > Utc::now()) {
+    return Err(ApiError::AccountLocked(locked_until));
+  }
+
+  match verify_password(SecretBox::from(Box::new(user.password.to_owned())), params.password).await {
+    Ok(()) => {
+      sqlx::query(RESET_FAILED_LOGINS).bind(user.id).execute(pool).await?;
+      Ok(user)
+    },
+    Err(err) => {
+      sqlx::query(REGISTER_FAILED_LOGIN)
+        .bind(policy.max_attempts)
+        .bind(Utc::now() + policy.cooldown)
+        .bind(user.id)
+        .execute(pool)
+        .await?;
+      Err(err)
+    },
+  }
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,7 +117,7 @@ pub struct CreateUserParams {
   pub password: SecretBox<String>,
 }
 
-pub async fn create(pool: &SqlitePool, mut params: CreateUserParams) -> ApiResult<User> {
+pub async fn create(pool: &SqlitePool, actor_id: Option<Uuid>, mut params: CreateUserParams) -> ApiResult<User> {
   // Check if user already exists
   if (check_user_exists(pool, &params.email).await?).is_some() {
     return Err(ApiError::UserAlreadyExist(params.email));
@@ -47,7 +125,22 @@ pub async fn create(pool: &SqlitePool, mut params: CreateUserParams) -> ApiResul
 
   let password = std::mem::take(&mut params.password);
   let hashed_password = hash_password(password).await?;
-  create_new_user(pool, params, &hashed_password).await
+  let mut tx = pool.begin().await?;
+  let user = create_new_user(&mut tx, params, &hashed_password).await?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Create,
+    AuditEntity::User,
+    user.id,
+    json!(user),
+  )
+  .await?;
+
+  tx.commit().await?;
+
+  Ok(user)
 }
 
 #[derive(Debug, Deserialize)]
@@ -63,6 +156,7 @@ pub struct UpdateUserParams {
 /// # Arguments
 ///
 /// * `pool` - A SQLite connection pool for database operations
+/// * `actor_id` - The UUID of the user performing the update, recorded in the audit log
 /// * `id` - The UUID of the user to update
 /// * `params` - The new user information containing:
 ///   - username: New username for the user
@@ -89,7 +183,7 @@ pub struct UpdateUserParams {
 ///     password: SecretBox::new("new_password".to_string()),
 /// };
 ///
-/// match update(&pool, user_id, params).await {
+/// match update(&pool, Some(admin_id), user_id, params).await {
 ///     Ok(updated_user) => println!("User updated successfully"),
 ///     Err(e) => eprintln!("Failed to update user: {}", e),
 /// }
@@ -100,13 +194,33 @@ pub struct UpdateUserParams {
 /// - Passwords are hashed using Argon2 before storage
 /// - The original password is securely cleared from memory after hashing
 /// - Database operations are performed using parameterized queries to prevent SQL injection
-pub async fn update(pool: &SqlitePool, id: Uuid, mut params: UpdateUserParams) -> ApiResult<User> {
+pub async fn update(
+  pool: &SqlitePool,
+  actor_id: Option<Uuid>,
+  id: Uuid,
+  mut params: UpdateUserParams,
+) -> ApiResult<User> {
   // Verify user exists
-  ensure_user_exists(pool, id).await?;
+  let existing = get_user(pool, id).await?;
 
   let password = std::mem::take(&mut params.password);
   let hashed_password = hash_password(password).await?;
-  update_existing_user(pool, id, params, &hashed_password).await
+  let mut tx = pool.begin().await?;
+  let user = update_existing_user(&mut tx, id, params, &hashed_password).await?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Update,
+    AuditEntity::User,
+    id,
+    audit::diff(&json!(existing), &json!(user)),
+  )
+  .await?;
+
+  tx.commit().await?;
+
+  Ok(user)
 }
 
 /// Deletes a user from the database by their ID
@@ -114,6 +228,7 @@ pub async fn update(pool: &SqlitePool, id: Uuid, mut params: UpdateUserParams) -
 /// # Arguments
 ///
 /// * `pool` - A SQLite connection pool for database operations
+/// * `actor_id` - The UUID of the user performing the deletion, recorded in the audit log
 /// * `id` - The UUID of the user to delete
 ///
 /// # Returns
@@ -129,7 +244,7 @@ pub async fn update(pool: &SqlitePool, id: Uuid, mut params: UpdateUserParams) -
 /// ```rust
 /// let user_id = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000")?;
 ///
-/// match delete(&pool, user_id).await {
+/// match delete(&pool, Some(admin_id), user_id).await {
 ///     Ok(()) => println!("User deleted successfully"),
 ///     Err(e) => eprintln!("Failed to delete user: {}", e),
 /// }
@@ -141,27 +256,44 @@ pub async fn update(pool: &SqlitePool, id: Uuid, mut params: UpdateUserParams) -
 /// - Ensures the user exists before attempting deletion
 /// - The deletion is performed atomically
 /// - Related data might need to be handled separately depending on foreign key constraints
-pub async fn delete(pool: &SqlitePool, id: Uuid) -> ApiResult<()> {
-  ensure_user_exists(pool, id).await?;
+pub async fn delete(pool: &SqlitePool, actor_id: Option<Uuid>, id: Uuid) -> ApiResult<()> {
+  let existing = get_user(pool, id).await?;
+
+  let mut tx = pool.begin().await?;
+  sqlx::query(DELETE_USER).bind(id).execute(&mut *tx).await?;
+
+  audit::record(
+    &mut *tx,
+    actor_id,
+    AuditAction::Delete,
+    AuditEntity::User,
+    id,
+    json!(existing),
+  )
+  .await?;
 
-  sqlx::query(DELETE_USER).bind(id).execute(pool).await?;
+  tx.commit().await?;
 
   Ok(())
 }
 
-async fn create_new_user(pool: &SqlitePool, params: CreateUserParams, hashed_password: &str) -> ApiResult<User> {
+async fn create_new_user(
+  conn: &mut SqliteConnection,
+  params: CreateUserParams,
+  hashed_password: &str,
+) -> ApiResult<User> {
   sqlx::query_as::<_, User>(CREATE_USER)
     .bind(Uuid::new_v4())
     .bind(params.username)
     .bind(params.email)
     .bind(hashed_password)
-    .fetch_one(pool)
+    .fetch_one(conn)
     .await
     .map_err(Into::into)
 }
 
 async fn update_existing_user(
-  pool: &SqlitePool,
+  conn: &mut SqliteConnection,
   id: Uuid,
   params: UpdateUserParams,
   hashed_password: &str,
@@ -172,19 +304,74 @@ async fn update_existing_user(
     .bind(params.email)
     .bind(hashed_password)
     .bind(id)
-    .fetch_one(pool)
+    .fetch_one(conn)
     .await
     .map_err(Into::into)
 }
 
+/// Checks that the Argon2 parameters from the environment are valid
+pub fn validate_argon2_params() -> anyhow::Result<()> {
+  ARGON2_PARAMS.as_ref().map(|_| ()).map_err(|err| anyhow!(err.clone()))
+}
+
+/// Checks that the login lockout settings from the environment are valid
+pub fn validate_lockout_policy() -> anyhow::Result<()> {
+  LOCKOUT_POLICY.as_ref().map(|_| ()).map_err(|err| anyhow!(err.clone()))
+}
+
+fn load_lockout_policy() -> Result<LockoutPolicy, String> {
+  let max_attempts = match env::var("LOGIN_MAX_ATTEMPTS") {
+    Ok(value) => value
+      .parse::<u32>()
+      .ok()
+      .filter(|attempts| *attempts > 0)
+      .ok_or_else(|| format!("LOGIN_MAX_ATTEMPTS must be a positive integer, got `{}`", value))?,
+    Err(_) => DEFAULT_LOGIN_MAX_ATTEMPTS,
+  };
+
+  let lockout = env::var("LOGIN_LOCKOUT").unwrap_or_else(|_| DEFAULT_LOGIN_LOCKOUT.to_string());
+  let cooldown: Duration = duration_str::parse(&lockout).map_err(|err| {
+    format!(
+      "LOGIN_LOCKOUT must be a duration like `15m`, got `{}`: {}",
+      lockout, err
+    )
+  })?;
+  let cooldown = chrono::Duration::from_std(cooldown).map_err(|err| format!("LOGIN_LOCKOUT is too large: {}", err))?;
+
+  Ok(LockoutPolicy { max_attempts, cooldown })
+}
+
+fn load_argon2_params() -> Result<Params, String> {
+  let memory = read_argon2_param("ARGON2_MEMORY_KIB", DEFAULT_ARGON2_MEMORY_KIB)?;
+  let iterations = read_argon2_param("ARGON2_ITERATIONS", DEFAULT_ARGON2_ITERATIONS)?;
+  let parallelism = read_argon2_param("ARGON2_PARALLELISM", DEFAULT_ARGON2_PARALLELISM)?;
+
+  Params::new(memory, iterations, parallelism, None).map_err(|err| format!("Invalid Argon2 parameters: {}", err))
+}
+
+fn read_argon2_param(name: &str, default: u32) -> Result<u32, String> {
+  match env::var(name) {
+    Ok(value) => value
+      .parse::<u32>()
+      .map_err(|_| format!("{} must be a positive integer, got `{}`", name, value)),
+    Err(_) => Ok(default),
+  }
+}
+
+fn argon2() -> ApiResult<Argon2<'static>> {
+  let params = ARGON2_PARAMS
+    .as_ref()
+    .map_err(|err| ApiError::Anyhow(anyhow!(err.clone())))?
+    .clone();
+
+  Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
 async fn hash_password(password: SecretBox<String>) -> ApiResult<String> {
+  let argon2_config = argon2()?;
+
   task::spawn_blocking(move || {
     let salt = SaltString::generate(&mut OsRng);
-    let argon2_config = Argon2::new(
-      Algorithm::Argon2id,
-      Version::V0x13,
-      Params::new(15000, 2, 1, None).unwrap(),
-    );
 
     argon2_config
       .hash_password(password.expose_secret().as_bytes(), &salt)
@@ -202,13 +389,17 @@ async fn verify_password(
   expected_password_hash: SecretBox<String>,
   password_candidate: SecretBox<String>,
 ) -> ApiResult<()> {
+  let argon2_config = argon2()?;
+
   task::spawn_blocking(move || {
     let parsed_hash = PasswordHash::new(expected_password_hash.expose_secret()).map_err(|err| {
       info!("Failed to parse password hash: {}", err);
       ApiError::InvalidCredentials()
     })?;
 
-    Argon2::default()
+    // Verification uses the algorithm, version and cost parameters stored in the hash itself,
+    // so passwords hashed before a parameter change keep working.
+    argon2_config
       .verify_password(password_candidate.expose_secret().as_bytes(), &parsed_hash)
       .map_err(|_| ApiError::InvalidCredentials())
   })
@@ -232,14 +423,119 @@ async fn check_user_exists(pool: &SqlitePool, email: &str) -> ApiResult<Option<U
     .map_err(ApiError::DatabaseError)
 }
 
-async fn ensure_user_exists(pool: &SqlitePool, id: Uuid) -> ApiResult<()> {
-  let user_exists = sqlx::query_as::<_, User>(FIND_USER_BY_ID)
+async fn get_user(pool: &SqlitePool, id: Uuid) -> ApiResult<User> {
+  sqlx::query_as::<_, User>(FIND_USER_BY_ID)
     .bind(id)
     .fetch_optional(pool)
-    .await?;
+    .await?
+    .ok_or_else(|| ApiError::ResourceNotFound(id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::service::test_utils::test_pool;
+
+  /// Inserts a user whose password is hashed with cheap parameters, `login` verifies it with them
+  async fn insert_user(pool: &SqlitePool, password: &str) -> User {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::new(8, 1, 1, None).unwrap())
+      .hash_password(password.as_bytes(), &salt)
+      .unwrap()
+      .to_string();
+
+    sqlx::query_as::<_, User>(CREATE_USER)
+      .bind(Uuid::new_v4())
+      .bind("locksmith")
+      .bind("locksmith@example.com")
+      .bind(hash)
+      .fetch_one(pool)
+      .await
+      .unwrap()
+  }
+
+  async fn login_with(pool: &SqlitePool, password: &str) -> ApiResult<User> {
+    let params = LoginParams {
+      username: "locksmith".to_string(),
+      password: SecretBox::new(Box::new(password.to_string())),
+    };
+    login(pool, params).await
+  }
+
+  async fn failed_attempts(pool: &SqlitePool, id: Uuid) -> i64 {
+    sqlx::query_scalar("SELECT failed_login_attempts FROM users WHERE id = ?1")
+      .bind(id)
+      .fetch_one(pool)
+      .await
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_login_locks_account_after_max_attempts() {
+    let pool = test_pool().await;
+    let user = insert_user(&pool, "correct horse").await;
+    let max_attempts = LOCKOUT_POLICY.as_ref().unwrap().max_attempts;
+
+    for _ in 0..max_attempts {
+      assert!(matches!(
+        login_with(&pool, "battery staple").await,
+        Err(ApiError::InvalidCredentials())
+      ));
+    }
+
+    // Locked now, the right password doesn't get in either
+    assert!(matches!(
+      login_with(&pool, "correct horse").await,
+      Err(ApiError::AccountLocked(locked_until)) if locked_until > Utc::now()
+    ));
+
+    // Once the cooldown is over the right password works again
+    sqlx::query("UPDATE users SET locked_until = datetime('now', '-1 minute') WHERE id = ?1")
+      .bind(user.id)
+      .execute(&pool)
+      .await
+      .unwrap();
+    assert_eq!(login_with(&pool, "correct horse").await.unwrap().id, user.id);
+  }
+
+  #[tokio::test]
+  async fn test_login_resets_failed_attempts() {
+    let pool = test_pool().await;
+    let user = insert_user(&pool, "correct horse").await;
+
+    for _ in 0..2 {
+      assert!(login_with(&pool, "battery staple").await.is_err());
+    }
+    assert_eq!(failed_attempts(&pool, user.id).await, 2);
+
+    login_with(&pool, "correct horse").await.unwrap();
+    assert_eq!(failed_attempts(&pool, user.id).await, 0);
+  }
+
+  #[tokio::test]
+  async fn test_verify_password_hashed_with_other_params() {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::new(
+      Algorithm::Argon2id,
+      Version::V0x13,
+      Params::new(8192, 3, 2, None).unwrap(),
+    )
+    .hash_password(b"correct horse", &salt)
+    .unwrap()
+    .to_string();
+
+    let verified = verify_password(
+      SecretBox::new(Box::new(hash.clone())),
+      SecretBox::new(Box::new("correct horse".to_string())),
+    )
+    .await;
+    assert!(verified.is_ok());
 
-  match user_exists {
-    Some(_) => Ok(()),
-    None => Err(ApiError::ResourceNotFound(id.to_string())),
+    let rejected = verify_password(
+      SecretBox::new(Box::new(hash)),
+      SecretBox::new(Box::new("battery staple".to_string())),
+    )
+    .await;
+    assert!(matches!(rejected, Err(ApiError::InvalidCredentials())));
   }
 }
@@ -0,0 +1,22 @@
+use serde_json::{Map, Value};
+
+/// Merges `overrides` over `base`, nested objects key by key while any other value replaces the base one
+///
+/// Shared by the template instantiation and the executor's project presets so both layer options alike.
+pub fn merge(base: &mut Value, overrides: Value) {
+  match (base, overrides) {
+    (Value::Object(base), Value::Object(overrides)) => merge_objects(base, overrides),
+    (base, overrides) => *base = overrides,
+  }
+}
+
+fn merge_objects(base: &mut Map<String, Value>, overrides: Map<String, Value>) {
+  for (key, value) in overrides {
+    match base.get_mut(&key) {
+      Some(existing) => merge(existing, value),
+      None => {
+        base.insert(key, value);
+      },
+    }
+  }
+}
@@ -0,0 +1,27 @@
+use std::{env, io, path::PathBuf};
+
+use once_cell::sync::Lazy;
+use uuid::Uuid;
+
+const DEFAULT_OUTPUT_DIR: &str = "data/outputs";
+
+/// Directory the task outputs streamed by plugins are written to, `TASK_OUTPUT_DIR` or `data/outputs`
+static OUTPUT_DIR: Lazy<PathBuf> = Lazy::new(|| {
+  env::var("TASK_OUTPUT_DIR")
+    .ok()
+    .filter(|dir| !dir.is_empty())
+    .map_or_else(|| PathBuf::from(DEFAULT_OUTPUT_DIR), PathBuf::from)
+});
+
+/// Path of the file holding the output of a task, it only exists once the plugin wrote something
+pub fn output_path(task_id: Uuid) -> PathBuf {
+  OUTPUT_DIR.join(task_id.to_string())
+}
+
+/// Removes the output of a task, a task without output is not an error
+pub async fn remove(task_id: Uuid) -> io::Result<()> {
+  match tokio::fs::remove_file(output_path(task_id)).await {
+    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+    _ => Ok(()),
+  }
+}
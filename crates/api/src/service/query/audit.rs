@@ -0,0 +1,42 @@
+use sqlx::SqlitePool;
+
+use crate::{entities::audit::AuditEntry, error::ApiResult};
+
+const LIST_AUDIT_QUERY: &str = "SELECT * FROM audit_log ORDER BY created_at DESC, id LIMIT ? OFFSET ?";
+const COUNT_AUDIT_QUERY: &str = "SELECT COUNT(*) FROM audit_log";
+
+/// Lists audit log entries with pagination, newest first
+///
+/// # Arguments
+/// * `pool` - Database connection pool
+/// * `page` - Page number (1-based)
+/// * `limit` - Number of items per page
+///
+/// # Returns
+/// A tuple containing the entries and total number of pages
+pub async fn list(pool: &SqlitePool, page: i64, limit: i64) -> ApiResult<(Vec<AuditEntry>, i64)> {
+  let (total_count, entries) = tokio::try_join!(get_total_count(pool), fetch_paginated_entries(pool, page, limit))?;
+
+  let total_pages = calculate_total_pages(total_count, limit);
+  Ok((entries, total_pages))
+}
+
+async fn get_total_count(pool: &SqlitePool) -> ApiResult<i64> {
+  let (count,): (i64,) = sqlx::query_as(COUNT_AUDIT_QUERY).fetch_one(pool).await?;
+  Ok(count)
+}
+
+fn calculate_total_pages(total_count: i64, limit: i64) -> i64 {
+  (total_count as f64 / limit as f64).ceil() as i64
+}
+
+async fn fetch_paginated_entries(pool: &SqlitePool, page: i64, limit: i64) -> ApiResult<Vec<AuditEntry>> {
+  let offset = (page - 1) * limit;
+
+  sqlx::query_as::<_, AuditEntry>(LIST_AUDIT_QUERY)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(Into::into)
+}
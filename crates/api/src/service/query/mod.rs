@@ -1,3 +1,19 @@
+use chrono::{DateTime, Utc};
+
+pub mod audit;
+pub mod plugins;
 pub mod projects;
+pub mod search;
 pub mod tasks;
+pub mod templates;
+pub mod tokens;
 pub mod users;
+
+/// Optional bounds on the `created_at` and `updated_at` columns of a list, unset bounds match every row
+#[derive(Debug, Default, Clone)]
+pub struct TimeRange {
+  pub created_after: Option<DateTime<Utc>>,
+  pub created_before: Option<DateTime<Utc>>,
+  pub updated_after: Option<DateTime<Utc>>,
+  pub updated_before: Option<DateTime<Utc>>,
+}
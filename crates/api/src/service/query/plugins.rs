@@ -0,0 +1,14 @@
+use sqlx::SqlitePool;
+
+use crate::{entities::plugin::PluginOptionsRow, error::ApiResult};
+
+const LIST_PLUGIN_OPTIONS_QUERY: &str = "SELECT * FROM plugin_options ORDER BY name";
+
+/// Lists the options pushed through the API for every plugin that has some
+pub async fn list_options(pool: &SqlitePool) -> ApiResult<Vec<PluginOptionsRow>> {
+  Ok(
+    sqlx::query_as::<_, PluginOptionsRow>(LIST_PLUGIN_OPTIONS_QUERY)
+      .fetch_all(pool)
+      .await?,
+  )
+}
@@ -1,4 +1,5 @@
 use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use uuid::Uuid;
 
 use crate::{
   entities::{
@@ -8,14 +9,18 @@ use crate::{
   error::ApiResult,
 };
 
-const LIST_PROJECTS_QUERY: &str = r#"
+use super::TimeRange;
+
+const SELECT_PROJECTS_QUERY: &str = r#"
   SELECT
     p.id as project_id,
     p.name as project_name,
     p.code as project_code,
     p.options as project_options,
+    p.created_by as project_created_by,
     p.created_at as project_created_at,
     p.updated_at as project_updated_at,
+    p.version as project_version,
     u.id as user_id,
     u.username as user_username,
     u.role as user_role,
@@ -25,26 +30,60 @@ const LIST_PROJECTS_QUERY: &str = r#"
     u.updated_at as user_updated_at
   FROM projects AS p
   LEFT OUTER JOIN users AS u ON p.owner_id = u.id
-  ORDER BY p.id LIMIT ? OFFSET ?
+"#;
+
+const PROJECTS_FILTER: &str = r#"
+  WHERE (?1 IS NULL OR datetime(p.created_at) > datetime(?1))
+  AND (?2 IS NULL OR datetime(p.created_at) < datetime(?2))
+  AND (?3 IS NULL OR datetime(p.updated_at) > datetime(?3))
+  AND (?4 IS NULL OR datetime(p.updated_at) < datetime(?4))
 "#;
 
 /// Fetches a paginated list of projects with their associated users
 ///
 /// # Arguments
 /// * `pool` - The database connection pool
+/// * `range` - Creation and update time bounds
 /// * `page` - The page number (1-based)
 /// * `limit` - The number of items per page
 ///
 /// # Returns
 /// A tuple containing the projects and the total number of pages
-pub async fn list(pool: &SqlitePool, page: i64, limit: i64) -> ApiResult<(Vec<Project>, i64)> {
-  let (total_count, projects) = tokio::try_join!(get_total_count(pool), fetch_projects(pool, page, limit))?;
+pub async fn list(pool: &SqlitePool, range: &TimeRange, page: i64, limit: i64) -> ApiResult<(Vec<Project>, i64)> {
+  let (total_count, projects) =
+    tokio::try_join!(get_total_count(pool, range), fetch_projects(pool, range, page, limit))?;
 
   let total_pages = calculate_total_pages(total_count, limit);
 
   Ok((projects, total_pages))
 }
 
+/// Fetches the projects following `after` in id order, a stable alternative to `list`
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `range` - Creation and update time bounds
+/// * `after` - Id of the last project of the previous page
+/// * `limit` - The number of items per page
+pub async fn list_after(pool: &SqlitePool, range: &TimeRange, after: Uuid, limit: i64) -> ApiResult<Vec<Project>> {
+  let query = format!(
+    "{} {} AND p.id > ?5 ORDER BY p.id LIMIT ?6",
+    SELECT_PROJECTS_QUERY, PROJECTS_FILTER
+  );
+
+  sqlx::query(&query)
+    .bind(range.created_after)
+    .bind(range.created_before)
+    .bind(range.updated_after)
+    .bind(range.updated_before)
+    .bind(after)
+    .bind(limit)
+    .map(map_row_to_project)
+    .fetch_all(pool)
+    .await
+    .map_err(Into::into)
+}
+
 /// Fetches a list of projects
 ///
 /// # Arguments
@@ -59,10 +98,18 @@ pub async fn list_all(pool: &SqlitePool) -> ApiResult<Vec<ProjectRow>> {
     .map_err(Into::into)
 }
 
-async fn fetch_projects(pool: &SqlitePool, page: i64, limit: i64) -> ApiResult<Vec<Project>> {
+async fn fetch_projects(pool: &SqlitePool, range: &TimeRange, page: i64, limit: i64) -> ApiResult<Vec<Project>> {
   let offset = (page - 1) * limit;
+  let query = format!(
+    "{} {} ORDER BY p.id LIMIT ?5 OFFSET ?6",
+    SELECT_PROJECTS_QUERY, PROJECTS_FILTER
+  );
 
-  sqlx::query(LIST_PROJECTS_QUERY)
+  sqlx::query(&query)
+    .bind(range.created_after)
+    .bind(range.created_before)
+    .bind(range.updated_after)
+    .bind(range.updated_before)
     .bind(limit)
     .bind(offset)
     .map(map_row_to_project)
@@ -71,8 +118,16 @@ async fn fetch_projects(pool: &SqlitePool, page: i64, limit: i64) -> ApiResult<V
     .map_err(Into::into)
 }
 
-async fn get_total_count(pool: &SqlitePool) -> ApiResult<i64> {
-  let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM projects").fetch_one(pool).await?;
+async fn get_total_count(pool: &SqlitePool, range: &TimeRange) -> ApiResult<i64> {
+  let query = format!("SELECT COUNT(*) FROM projects AS p {}", PROJECTS_FILTER);
+
+  let (count,): (i64,) = sqlx::query_as(&query)
+    .bind(range.created_after)
+    .bind(range.created_before)
+    .bind(range.updated_after)
+    .bind(range.updated_before)
+    .fetch_one(pool)
+    .await?;
   Ok(count)
 }
 
@@ -95,7 +150,46 @@ fn map_row_to_project(row: SqliteRow) -> Project {
       created_at: row.get("user_created_at"),
       updated_at: row.get("user_updated_at"),
     },
+    created_by: row.get("project_created_by"),
     created_at: row.get("project_created_at"),
     updated_at: row.get("project_updated_at"),
+    version: row.get("project_version"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::DateTime;
+
+  use super::*;
+  use crate::service::test_utils::{test_pool, SEED_PROJECT_ID};
+
+  #[tokio::test]
+  async fn test_list_filters_on_time_range() {
+    let pool = test_pool().await;
+    sqlx::query("UPDATE projects SET created_at = '2020-01-01 00:00:00' WHERE id = ?1")
+      .bind(Uuid::parse_str(SEED_PROJECT_ID).unwrap())
+      .execute(&pool)
+      .await
+      .unwrap();
+
+    let created_after = |value: &str| TimeRange {
+      created_after: Some(DateTime::parse_from_rfc3339(value).unwrap().to_utc()),
+      ..Default::default()
+    };
+
+    let (projects, total_pages) = list(&pool, &created_after("2019-12-31T23:59:59Z"), 1, 10)
+      .await
+      .unwrap();
+    assert_eq!(projects.len(), 1);
+    assert_eq!(projects[0].id.to_string(), SEED_PROJECT_ID);
+    assert_eq!(total_pages, 1);
+
+    let (projects, total_pages) = list(&pool, &created_after("2020-01-01T00:00:00Z"), 1, 10)
+      .await
+      .unwrap();
+    // Exclusive bound
+    assert!(projects.is_empty());
+    assert_eq!(total_pages, 0);
   }
 }
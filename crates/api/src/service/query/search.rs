@@ -0,0 +1,123 @@
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+
+use crate::{
+  entities::{search::SearchResult, task::TaskStatus},
+  error::ApiResult,
+};
+
+use super::users::like_pattern;
+
+const SEARCH_MATCHES: &str = r#"
+  SELECT 'project' AS kind, id, name, code, NULL AS external_id, NULL AS project_id, NULL AS status
+  FROM projects
+  WHERE name LIKE ?1 ESCAPE '\' OR code LIKE ?1 ESCAPE '\'
+  UNION ALL
+  SELECT 'task' AS kind, id, name, NULL, external_id, project_id, status
+  FROM tasks
+  WHERE deleted_at IS NULL AND (name LIKE ?1 ESCAPE '\' OR external_id LIKE ?1 ESCAPE '\')
+"#;
+
+/// Finds projects by name or code and tasks by name or external id, projects first
+///
+/// # Arguments
+/// * `pool` - Database connection pool
+/// * `term` - Case-insensitive substring to look for
+/// * `page` - Page number (1-based)
+/// * `limit` - Number of items per page
+///
+/// # Returns
+/// A tuple containing the results, the total number of matches and the total number of pages
+pub async fn search(pool: &SqlitePool, term: &str, page: i64, limit: i64) -> ApiResult<(Vec<SearchResult>, i64, i64)> {
+  let pattern = like_pattern(term);
+
+  let (total_count, results) = tokio::try_join!(
+    get_total_count(pool, &pattern),
+    fetch_paginated_results(pool, &pattern, page, limit)
+  )?;
+
+  let total_pages = (total_count as f64 / limit as f64).ceil() as i64;
+  Ok((results, total_count, total_pages))
+}
+
+async fn get_total_count(pool: &SqlitePool, pattern: &str) -> ApiResult<i64> {
+  let query = format!("SELECT COUNT(*) FROM ({})", SEARCH_MATCHES);
+
+  let (count,): (i64,) = sqlx::query_as(&query).bind(pattern).fetch_one(pool).await?;
+  Ok(count)
+}
+
+async fn fetch_paginated_results(
+  pool: &SqlitePool,
+  pattern: &str,
+  page: i64,
+  limit: i64,
+) -> ApiResult<Vec<SearchResult>> {
+  let offset = (page - 1) * limit;
+  let query = format!("{} ORDER BY kind, name, id LIMIT ?2 OFFSET ?3", SEARCH_MATCHES);
+
+  sqlx::query(&query)
+    .bind(pattern)
+    .bind(limit)
+    .bind(offset)
+    .try_map(map_result)
+    .fetch_all(pool)
+    .await
+    .map_err(Into::into)
+}
+
+fn map_result(row: SqliteRow) -> Result<SearchResult, sqlx::Error> {
+  match row.try_get::<&str, _>("kind")? {
+    "project" => Ok(SearchResult::Project {
+      id: row.try_get("id")?,
+      name: row.try_get("name")?,
+      code: row.try_get("code")?,
+    }),
+    _ => Ok(SearchResult::Task {
+      id: row.try_get("id")?,
+      name: row.try_get("name")?,
+      external_id: row.try_get("external_id")?,
+      project_id: row.try_get("project_id")?,
+      status: TaskStatus::decode(row.try_get("status")?)?,
+    }),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use uuid::Uuid;
+
+  use super::*;
+  use crate::service::{
+    mutation::tasks::{create, CreateTaskParams},
+    test_utils::{task_params, test_pool, SEED_PROJECT_ID},
+  };
+
+  #[tokio::test]
+  async fn test_search_projects_and_tasks() {
+    let pool = test_pool().await;
+
+    let project_id = Uuid::parse_str(SEED_PROJECT_ID).unwrap();
+    create(
+      &pool,
+      None,
+      CreateTaskParams {
+        external_id: Some("PLATFORM-42".to_string()),
+        ..task_params("sync")
+      },
+    )
+    .await
+    .unwrap();
+
+    let (results, total_count, total_pages) = search(&pool, "platform", 1, 1).await.unwrap();
+    assert_eq!((total_count, total_pages), (2, 2));
+    assert!(matches!(&results[..], [SearchResult::Project { code, .. }] if code == "ppf"));
+
+    let (results, _, _) = search(&pool, "platform", 2, 1).await.unwrap();
+    assert!(matches!(
+      &results[..],
+      [SearchResult::Task { external_id: Some(_), project_id: id, status: TaskStatus::New, .. }] if *id == project_id
+    ));
+
+    assert_eq!(search(&pool, "plat_orm", 1, 10).await.unwrap().1, 0);
+  }
+}
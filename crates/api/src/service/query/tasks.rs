@@ -1,66 +1,240 @@
+use futures::{channel::mpsc, SinkExt, TryStreamExt};
 use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use uuid::Uuid;
 
 use crate::{
-  entities::{project::ProjectRow, task::Task},
-  error::ApiResult,
+  entities::{
+    project::ProjectRow,
+    task::{ExportRecord, MisfirePolicy, Task, TaskRow, TaskStatus},
+  },
+  error::{ApiError, ApiResult},
+  executor::ExecutorStats,
+  timezone,
 };
 
-const LIST_TASKS_QUERY: &str = r#"
+use super::TimeRange;
+
+const SELECT_TASKS_QUERY: &str = r#"
   SELECT
     p.id as project_id,
     p.name as project_name,
     p.code as project_code,
     p.options as project_options,
     p.owner_id as project_owner_id,
+    p.created_by as project_created_by,
     p.created_at as project_created_at,
     p.updated_at as project_updated_at,
+    p.version as project_version,
     t.id as task_id,
     t.type as task_type,
+    t.plugin_version as task_plugin_version,
     t.status as task_status,
     t.options as task_options,
     t.start_at as task_start_at,
+    t.schedule_anchor as task_schedule_anchor,
+    t.locked_by as task_locked_by,
+    t.end_at as task_end_at,
+    t.misfire_policy as task_misfire_policy,
+    t.enabled as task_enabled,
     t.schedule as task_schedule,
     t.name as task_name,
     t.retries as task_retries,
     t.external_id as task_external_id,
     t.external_modified_at as task_external_modified_at,
+    t.created_by as task_created_by,
     t.created_at as task_created_at,
-    t.updated_at as task_updated_at
+    t.updated_at as task_updated_at,
+    t.version as task_version,
+    t.failed_results as task_failed_results
   FROM tasks AS t
   LEFT OUTER JOIN projects AS p ON t.project_id = p.id
-  ORDER BY t.id LIMIT ? OFFSET ?
 "#;
 
+const TASKS_FILTER: &str = r#"
+  WHERE t.deleted_at IS NULL
+  AND (?1 IS NULL OR datetime(t.created_at) > datetime(?1))
+  AND (?2 IS NULL OR datetime(t.created_at) < datetime(?2))
+  AND (?3 IS NULL OR datetime(t.updated_at) > datetime(?3))
+  AND (?4 IS NULL OR datetime(t.updated_at) < datetime(?4))
+"#;
+
+const EXPORT_PROJECTS_QUERY: &str = "SELECT * FROM projects ORDER BY id";
+const EXPORT_TASKS_QUERY: &str = "SELECT * FROM tasks ORDER BY id";
+const EXECUTOR_STATS_QUERY: &str = r#"
+  SELECT locked_by AS executor_id, COUNT(*) AS running_tasks, MAX(locked_at) AS last_seen_at
+  FROM tasks
+  WHERE status = 'in_progress' AND locked_by IS NOT NULL
+  GROUP BY locked_by
+  ORDER BY locked_by
+"#;
+
+/// Number of exported records buffered ahead of a slow client
+const EXPORT_BUFFER: usize = 64;
+
 /// Fetches a paginated list of tasks with their associated projects
 ///
 /// # Arguments
 /// * `pool` - The database connection pool
+/// * `range` - Creation and update time bounds
 /// * `page` - The page number (1-based)
 /// * `limit` - The number of items per page
 ///
 /// # Returns
 /// A tuple containing the tasks and the total number of pages
-pub async fn list(pool: &SqlitePool, page: i64, limit: i64) -> ApiResult<(Vec<Task>, i64)> {
-  let (total_count, tasks) = tokio::try_join!(get_total_count(pool), fetch_paginated_tasks(pool, page, limit))?;
+pub async fn list(pool: &SqlitePool, range: &TimeRange, page: i64, limit: i64) -> ApiResult<(Vec<Task>, i64)> {
+  let (total_count, tasks) = tokio::try_join!(
+    get_total_count(pool, range),
+    fetch_paginated_tasks(pool, range, page, limit)
+  )?;
 
   let total_pages = calculate_total_pages(total_count, limit);
   Ok((tasks, total_pages))
 }
 
-async fn fetch_paginated_tasks(pool: &SqlitePool, page: i64, limit: i64) -> ApiResult<Vec<Task>> {
+/// Fetches the tasks following `after` in id order, a stable alternative to `list` for large tables
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `range` - Creation and update time bounds
+/// * `after` - Id of the last task of the previous page
+/// * `limit` - The number of items per page
+pub async fn list_after(pool: &SqlitePool, range: &TimeRange, after: Uuid, limit: i64) -> ApiResult<Vec<Task>> {
+  let query = format!(
+    "{} {} AND t.id > ?5 ORDER BY t.id LIMIT ?6",
+    SELECT_TASKS_QUERY, TASKS_FILTER
+  );
+
+  sqlx::query(&query)
+    .bind(range.created_after)
+    .bind(range.created_before)
+    .bind(range.updated_after)
+    .bind(range.updated_before)
+    .bind(after)
+    .bind(limit)
+    .try_map(map_task)
+    .fetch_all(pool)
+    .await
+    .map_err(Into::into)
+}
+
+/// Fetches a single task with its project
+///
+/// # Errors
+/// - ResourceNotFound if the task doesn't exist or is deleted
+pub async fn find(pool: &SqlitePool, id: Uuid) -> ApiResult<Task> {
+  let query = format!("{} WHERE t.id = ?1 AND t.deleted_at IS NULL", SELECT_TASKS_QUERY);
+
+  sqlx::query(&query)
+    .bind(id)
+    .try_map(map_task)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::ResourceNotFound(id.to_string()))
+}
+
+/// Fetches the task an external system synced with its project, by the id it has there
+///
+/// # Errors
+/// - ResourceNotFound if no task has this external id or it is deleted
+pub async fn find_by_external_id(pool: &SqlitePool, external_id: &str) -> ApiResult<Task> {
+  let query = format!(
+    "{} WHERE t.external_id = ?1 AND t.deleted_at IS NULL",
+    SELECT_TASKS_QUERY
+  );
+
+  sqlx::query(&query)
+    .bind(external_id)
+    .try_map(map_task)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::ResourceNotFound(external_id.to_string()))
+}
+
+/// Counts the tasks every executor is running, keyed by `EXECUTOR_ID`, executors running none are left out
+pub async fn executor_stats(pool: &SqlitePool) -> ApiResult<Vec<ExecutorStats>> {
+  Ok(
+    sqlx::query_as::<_, ExecutorStats>(EXECUTOR_STATS_QUERY)
+      .fetch_all(pool)
+      .await?,
+  )
+}
+
+/// Streams every task, preceded by every project when `include_projects` is set
+///
+/// Rows are read with a database cursor and handed over through a bounded channel, so the tables are never
+/// loaded into memory at once. Dropping the receiver stops the export.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `include_projects` - Whether to export projects before the tasks
+pub fn export(pool: SqlitePool, include_projects: bool) -> mpsc::Receiver<ApiResult<ExportRecord>> {
+  let (mut tx, rx) = mpsc::channel(EXPORT_BUFFER);
+
+  tokio::spawn(async move {
+    if let Err(e) = send_export_records(&pool, include_projects, &mut tx).await {
+      let _ = tx.send(Err(e)).await;
+    }
+  });
+
+  rx
+}
+
+async fn send_export_records(
+  pool: &SqlitePool,
+  include_projects: bool,
+  tx: &mut mpsc::Sender<ApiResult<ExportRecord>>,
+) -> ApiResult<()> {
+  if include_projects {
+    let mut projects = sqlx::query_as::<_, ProjectRow>(EXPORT_PROJECTS_QUERY).fetch(pool);
+
+    while let Some(project) = projects.try_next().await? {
+      if tx.send(Ok(ExportRecord::Project(project))).await.is_err() {
+        return Ok(());
+      }
+    }
+  }
+
+  let mut tasks = sqlx::query_as::<_, TaskRow>(EXPORT_TASKS_QUERY).fetch(pool);
+
+  while let Some(task) = tasks.try_next().await? {
+    if tx.send(Ok(ExportRecord::Task(Box::new(task)))).await.is_err() {
+      return Ok(());
+    }
+  }
+
+  Ok(())
+}
+
+async fn fetch_paginated_tasks(pool: &SqlitePool, range: &TimeRange, page: i64, limit: i64) -> ApiResult<Vec<Task>> {
   let offset = (page - 1) * limit;
+  let query = format!(
+    "{} {} ORDER BY t.id LIMIT ?5 OFFSET ?6",
+    SELECT_TASKS_QUERY, TASKS_FILTER
+  );
 
-  sqlx::query(LIST_TASKS_QUERY)
+  sqlx::query(&query)
+    .bind(range.created_after)
+    .bind(range.created_before)
+    .bind(range.updated_after)
+    .bind(range.updated_before)
     .bind(limit)
     .bind(offset)
-    .map(map_task)
+    .try_map(map_task)
     .fetch_all(pool)
     .await
     .map_err(Into::into)
 }
 
-async fn get_total_count(pool: &SqlitePool) -> ApiResult<i64> {
-  let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tasks").fetch_one(pool).await?;
+async fn get_total_count(pool: &SqlitePool, range: &TimeRange) -> ApiResult<i64> {
+  let query = format!("SELECT COUNT(*) FROM tasks AS t {}", TASKS_FILTER);
+
+  let (count,): (i64,) = sqlx::query_as(&query)
+    .bind(range.created_after)
+    .bind(range.created_before)
+    .bind(range.updated_after)
+    .bind(range.updated_before)
+    .fetch_one(pool)
+    .await?;
   Ok(count)
 }
 
@@ -68,22 +242,33 @@ fn calculate_total_pages(total_count: i64, limit: i64) -> i64 {
   (total_count as f64 / limit as f64).ceil() as i64
 }
 
-fn map_task(row: SqliteRow) -> Task {
-  Task {
+fn map_task(row: SqliteRow) -> Result<Task, sqlx::Error> {
+  Ok(Task {
     id: row.get("task_id"),
     r#type: row.get("task_type"),
-    status: row.get("task_status"),
+    plugin_version: row.get("task_plugin_version"),
+    status: TaskStatus::decode(row.get("task_status"))?,
     options: row.get("task_options"),
     start_at: row.get("task_start_at"),
+    schedule_anchor: row.get("task_schedule_anchor"),
+    end_at: row.get("task_end_at"),
+    locked_by: row.get("task_locked_by"),
+    misfire_policy: MisfirePolicy::decode(row.get("task_misfire_policy"))?,
+    enabled: row.get("task_enabled"),
+    timezone: timezone::default_tz().to_string(),
+    utc_offset: timezone::utc_offset(row.get::<i64, _>("task_start_at")),
     schedule: row.get("task_schedule"),
     name: row.get("task_name"),
     retries: row.get("task_retries"),
     external_id: row.get("task_external_id"),
     external_modified_at: row.get("task_external_modified_at"),
+    created_by: row.get("task_created_by"),
     project: map_project_row(&row),
     created_at: row.get("task_created_at"),
     updated_at: row.get("task_updated_at"),
-  }
+    version: row.get("task_version"),
+    failed_results: row.get("task_failed_results"),
+  })
 }
 
 fn map_project_row(row: &SqliteRow) -> ProjectRow {
@@ -93,7 +278,112 @@ fn map_project_row(row: &SqliteRow) -> ProjectRow {
     code: row.get("project_code"),
     options: row.get("project_options"),
     owner_id: row.get("project_owner_id"),
+    created_by: row.get("project_created_by"),
     created_at: row.get("project_created_at"),
     updated_at: row.get("project_updated_at"),
+    version: row.get("project_version"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{DateTime, Duration, Utc};
+
+  use super::*;
+  use crate::service::{
+    mutation::tasks::create,
+    test_utils::{task_params, test_pool},
+  };
+
+  #[tokio::test]
+  async fn test_list_after_pages_by_id() {
+    let pool = test_pool().await;
+
+    for name in ["first", "second", "third"] {
+      create(&pool, None, task_params(name)).await.unwrap();
+    }
+
+    let range = TimeRange::default();
+    let (first_page, _) = list(&pool, &range, 1, 2).await.unwrap();
+    let rest = list_after(&pool, &range, first_page[1].id, 2).await.unwrap();
+
+    let mut ids: Vec<Uuid> = first_page.iter().chain(&rest).map(|task| task.id).collect();
+    assert_eq!(ids.len(), 3);
+    ids.dedup();
+    assert_eq!(ids.len(), 3);
+    assert!(list_after(&pool, &range, rest[0].id, 2).await.unwrap().is_empty());
+
+    let future = TimeRange {
+      created_after: Some(Utc::now() + Duration::hours(1)),
+      ..Default::default()
+    };
+    let (tasks, total_pages) = list(&pool, &future, 1, 2).await.unwrap();
+    assert!(tasks.is_empty());
+    assert_eq!(total_pages, 0);
+
+    let past = TimeRange {
+      created_after: Some(Utc::now() - Duration::hours(1)),
+      updated_before: Some(Utc::now() + Duration::hours(1)),
+      ..Default::default()
+    };
+    assert_eq!(list(&pool, &past, 1, 10).await.unwrap().0.len(), 3);
+  }
+
+  #[tokio::test]
+  async fn test_list_filters_on_time_range() {
+    let pool = test_pool().await;
+
+    let old = create(&pool, None, task_params("old")).await.unwrap();
+    let recent = create(&pool, None, task_params("recent")).await.unwrap();
+    // The update trigger stamps `updated_at`, only the creation time can be backdated
+    sqlx::query("UPDATE tasks SET created_at = '2020-01-01 00:00:00' WHERE id = ?1")
+      .bind(old.id)
+      .execute(&pool)
+      .await
+      .unwrap();
+
+    let ids = |tasks: Vec<Task>| tasks.into_iter().map(|task| task.id).collect::<Vec<_>>();
+    let at = |value: &str| Some(DateTime::parse_from_rfc3339(value).unwrap().to_utc());
+
+    let created_before = TimeRange {
+      created_before: at("2020-06-01T00:00:00Z"),
+      ..Default::default()
+    };
+    assert_eq!(ids(list(&pool, &created_before, 1, 10).await.unwrap().0), vec![old.id]);
+
+    let created_after = TimeRange {
+      created_after: at("2020-06-01T00:00:00Z"),
+      ..Default::default()
+    };
+    assert_eq!(
+      ids(list(&pool, &created_after, 1, 10).await.unwrap().0),
+      vec![recent.id]
+    );
+
+    // Bounds are exclusive and compared as instants whatever the offset they're given in
+    let exact = TimeRange {
+      created_after: at("2020-01-01T01:00:00+01:00"),
+      created_before: at("2020-01-01T00:00:01Z"),
+      ..Default::default()
+    };
+    assert!(list(&pool, &exact, 1, 10).await.unwrap().0.is_empty());
+
+    let around = TimeRange {
+      created_after: at("2020-01-01T00:59:59+01:00"),
+      created_before: at("2020-01-01T00:00:01Z"),
+      updated_after: Some(Utc::now() - Duration::hours(1)),
+      updated_before: Some(Utc::now() + Duration::hours(1)),
+    };
+    assert_eq!(ids(list(&pool, &around, 1, 10).await.unwrap().0), vec![old.id]);
+    assert_eq!(
+      ids(list_after(&pool, &around, Uuid::nil(), 10).await.unwrap()),
+      vec![old.id]
+    );
+
+    let updated_later = TimeRange {
+      updated_after: Some(Utc::now() + Duration::hours(1)),
+      ..Default::default()
+    };
+    assert!(list(&pool, &updated_later, 1, 10).await.unwrap().0.is_empty());
   }
-}
\ No newline at end of file
+}
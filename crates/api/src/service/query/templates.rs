@@ -0,0 +1,36 @@
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+  entities::template::{TaskTemplate, TaskTemplateRow},
+  error::{ApiError, ApiResult},
+};
+
+const LIST_TEMPLATES_QUERY: &str = "SELECT * FROM task_templates ORDER BY name";
+const FIND_TEMPLATE_QUERY: &str = "SELECT * FROM task_templates WHERE id = ?1";
+
+/// Lists every task template ordered by name
+pub async fn list(pool: &SqlitePool) -> ApiResult<Vec<TaskTemplate>> {
+  let rows = sqlx::query_as::<_, TaskTemplateRow>(LIST_TEMPLATES_QUERY)
+    .fetch_all(pool)
+    .await?;
+
+  rows
+    .into_iter()
+    .map(|row| TaskTemplate::try_from(row).map_err(Into::into))
+    .collect()
+}
+
+/// Fetches a single task template
+///
+/// # Errors
+/// - ResourceNotFound if the template doesn't exist
+pub async fn find(pool: &SqlitePool, id: Uuid) -> ApiResult<TaskTemplate> {
+  let row = sqlx::query_as::<_, TaskTemplateRow>(FIND_TEMPLATE_QUERY)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::ResourceNotFound(id.to_string()))?;
+
+  Ok(TaskTemplate::try_from(row)?)
+}
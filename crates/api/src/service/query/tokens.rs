@@ -0,0 +1,11 @@
+use sqlx::SqlitePool;
+
+use crate::error::ApiResult;
+
+const FIND_REVOKED_TOKEN: &str = "SELECT COUNT(*) FROM revoked_tokens WHERE jti = ?1";
+
+/// Checks whether the token with the given id has been revoked
+pub async fn is_revoked(pool: &SqlitePool, jti: &str) -> ApiResult<bool> {
+  let (count,): (i64,) = sqlx::query_as(FIND_REVOKED_TOKEN).bind(jti).fetch_one(pool).await?;
+  Ok(count > 0)
+}
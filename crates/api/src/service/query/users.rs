@@ -3,24 +3,42 @@ use uuid::Uuid;
 
 use crate::{entities::user::User, error::ApiResult};
 
-const LIST_USERS_QUERY: &str = "SELECT * FROM users ORDER BY id LIMIT ? OFFSET ?";
+const USERS_FILTER: &str = r#"
+  WHERE (?1 IS NULL OR role = ?1)
+  AND (?2 IS NULL OR username LIKE ?2 ESCAPE '\')
+  AND (?3 IS NULL OR email LIKE ?3 ESCAPE '\')
+"#;
 const FIND_USER_BY_ID_QUERY: &str = "SELECT * FROM users WHERE id = ?1";
-const COUNT_USERS_QUERY: &str = "SELECT COUNT(*) FROM users";
 
-/// Lists users with pagination
+/// Optional filters for the users list, unset fields match every user
+#[derive(Debug, Default)]
+pub struct UserFilter {
+  /// Exact role match
+  pub role: Option<String>,
+  /// Case-insensitive substring of the username
+  pub username: Option<String>,
+  /// Case-insensitive substring of the email
+  pub email: Option<String>,
+}
+
+/// Lists users matching the filter with pagination
 ///
 /// # Arguments
 /// * `pool` - Database connection pool
+/// * `filter` - Role and username/email search filters
 /// * `page` - Page number (1-based)
 /// * `limit` - Number of items per page
 ///
 /// # Returns
-/// A tuple containing the users and total number of pages
-pub async fn list(pool: &SqlitePool, page: i64, limit: i64) -> ApiResult<(Vec<User>, i64)> {
-  let (total_count, users) = tokio::try_join!(get_total_count(pool), fetch_paginated_users(pool, page, limit))?;
+/// A tuple containing the users, the total number of matching users and the total number of pages
+pub async fn list(pool: &SqlitePool, filter: &UserFilter, page: i64, limit: i64) -> ApiResult<(Vec<User>, i64, i64)> {
+  let (total_count, users) = tokio::try_join!(
+    get_total_count(pool, filter),
+    fetch_paginated_users(pool, filter, page, limit)
+  )?;
 
   let total_pages = calculate_total_pages(total_count, limit);
-  Ok((users, total_pages))
+  Ok((users, total_count, total_pages))
 }
 
 /// Finds a user by their ID
@@ -39,8 +57,15 @@ pub async fn find_by_id(pool: &SqlitePool, id: Uuid) -> ApiResult<Option<User>>
     .map_err(Into::into)
 }
 
-async fn get_total_count(pool: &SqlitePool) -> ApiResult<i64> {
-  let (count,): (i64,) = sqlx::query_as(COUNT_USERS_QUERY).fetch_one(pool).await?;
+async fn get_total_count(pool: &SqlitePool, filter: &UserFilter) -> ApiResult<i64> {
+  let query = format!("SELECT COUNT(*) FROM users {}", USERS_FILTER);
+
+  let (count,): (i64,) = sqlx::query_as(&query)
+    .bind(&filter.role)
+    .bind(filter.username.as_deref().map(like_pattern))
+    .bind(filter.email.as_deref().map(like_pattern))
+    .fetch_one(pool)
+    .await?;
   Ok(count)
 }
 
@@ -48,10 +73,14 @@ fn calculate_total_pages(total_count: i64, limit: i64) -> i64 {
   (total_count as f64 / limit as f64).ceil() as i64
 }
 
-async fn fetch_paginated_users(pool: &SqlitePool, page: i64, limit: i64) -> ApiResult<Vec<User>> {
+async fn fetch_paginated_users(pool: &SqlitePool, filter: &UserFilter, page: i64, limit: i64) -> ApiResult<Vec<User>> {
   let offset = (page - 1) * limit;
+  let query = format!("SELECT * FROM users {} ORDER BY id LIMIT ?4 OFFSET ?5", USERS_FILTER);
 
-  sqlx::query_as::<_, User>(LIST_USERS_QUERY)
+  sqlx::query_as::<_, User>(&query)
+    .bind(&filter.role)
+    .bind(filter.username.as_deref().map(like_pattern))
+    .bind(filter.email.as_deref().map(like_pattern))
     .bind(limit)
     .bind(offset)
     .fetch_all(pool)
@@ -59,6 +88,12 @@ async fn fetch_paginated_users(pool: &SqlitePool, page: i64, limit: i64) -> ApiR
     .map_err(Into::into)
 }
 
+/// Builds a `LIKE` substring pattern, escaping the wildcard characters of the search term
+pub(crate) fn like_pattern(term: &str) -> String {
+  let escaped = term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+  format!("%{}%", escaped)
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -69,4 +104,10 @@ mod tests {
     assert_eq!(calculate_total_pages(10, 5), 2);
     assert_eq!(calculate_total_pages(0, 5), 0);
   }
+
+  #[test]
+  fn test_like_pattern() {
+    assert_eq!(like_pattern("adm"), "%adm%");
+    assert_eq!(like_pattern("50%_off"), "%50\\%\\_off%");
+  }
 }
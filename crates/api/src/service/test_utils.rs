@@ -0,0 +1,39 @@
+//! Fixtures shared by the database tests
+
+use serde_json::json;
+use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use uuid::Uuid;
+
+use crate::{entities::task::MisfirePolicy, service::mutation::tasks::CreateTaskParams};
+
+/// Project inserted by the initial migrations, named `platform` with code `ppf`
+pub const SEED_PROJECT_ID: &str = "ce15d416-fdab-4579-8b0d-e7c93ec53dbb";
+
+/// Opens an in-memory database with every migration applied
+pub async fn test_pool() -> SqlitePool {
+  // A single connection keeps every query on the same in-memory database
+  let pool = SqlitePoolOptions::new()
+    .max_connections(1)
+    .connect("sqlite::memory:")
+    .await
+    .unwrap();
+  sqlx::migrate!("../../migrations").run(&pool).await.unwrap();
+  pool
+}
+
+/// Parameters of a one-shot `http` task in the seed project, due immediately
+pub fn task_params(name: &str) -> CreateTaskParams {
+  CreateTaskParams {
+    r#type: "http".to_string(),
+    plugin_version: None,
+    name: name.to_string(),
+    project_id: Uuid::parse_str(SEED_PROJECT_ID).unwrap(),
+    schedule: None,
+    external_id: None,
+    external_modified_at: None,
+    start_at: 0,
+    end_at: None,
+    misfire_policy: MisfirePolicy::default(),
+    options: json!({}),
+  }
+}
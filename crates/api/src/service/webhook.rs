@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use tokio::time::sleep;
+use tracing::warn;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+  reqwest::Client::builder()
+    .timeout(REQUEST_TIMEOUT)
+    .build()
+    .expect("Failed to build webhook HTTP client")
+});
+
+/// POSTs a JSON payload to a webhook, retrying failed deliveries with a linear backoff
+///
+/// # Arguments
+/// * `url` - The webhook endpoint
+/// * `payload` - The JSON body to send
+///
+/// # Errors
+/// The last delivery error once all attempts are exhausted
+pub async fn send(url: &str, payload: &Value) -> anyhow::Result<()> {
+  let mut attempt = 1;
+
+  loop {
+    let result = CLIENT
+      .post(url)
+      .json(payload)
+      .send()
+      .await
+      .and_then(|response| response.error_for_status());
+
+    match result {
+      Ok(_) => return Ok(()),
+      Err(e) if attempt < MAX_ATTEMPTS => {
+        warn!("Webhook delivery to {} failed (attempt {}): {}", url, attempt, e);
+        sleep(RETRY_BACKOFF * attempt).await;
+        attempt += 1;
+      },
+      Err(e) => return Err(e.into()),
+    }
+  }
+}
@@ -0,0 +1,40 @@
+use std::env;
+
+use chrono::{DateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+
+/// Timezone cron schedules are evaluated in, read from `OCTABOT_DEFAULT_TZ` as an IANA name (default UTC)
+///
+/// Changing it moves the next run of every existing cron task, `0 0 9 * * *` fires at 9:00 in the new zone.
+/// Interval schedules don't depend on it.
+pub static DEFAULT_TZ: Lazy<Result<Tz, String>> = Lazy::new(load_default_tz);
+
+fn load_default_tz() -> Result<Tz, String> {
+  match env::var("OCTABOT_DEFAULT_TZ") {
+    Ok(value) => value
+      .parse::<Tz>()
+      .map_err(|_| format!("OCTABOT_DEFAULT_TZ must be an IANA timezone name, got `{}`", value)),
+    Err(_) => Ok(Tz::UTC),
+  }
+}
+
+/// Checks that `OCTABOT_DEFAULT_TZ` from the environment is valid
+pub fn validate_default_tz() -> anyhow::Result<()> {
+  DEFAULT_TZ
+    .as_ref()
+    .map(|_| ())
+    .map_err(|err| anyhow::anyhow!(err.clone()))
+}
+
+/// The configured scheduling timezone, startup is refused on an invalid one so the UTC fallback is never used
+pub fn default_tz() -> Tz {
+  DEFAULT_TZ.as_ref().copied().unwrap_or(Tz::UTC)
+}
+
+/// UTC offset of the scheduling timezone at a unix timestamp, formatted as `+02:00`
+pub fn utc_offset(timestamp: i64) -> String {
+  let at = DateTime::from_timestamp(timestamp, 0).unwrap_or_default();
+
+  default_tz().offset_from_utc_datetime(&at.naive_utc()).fix().to_string()
+}
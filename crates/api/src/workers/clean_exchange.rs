@@ -7,7 +7,7 @@ use tokio::time::{sleep, Duration};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
 
-use crate::service::mutation;
+use crate::service::{logs, mutation, outputs};
 
 static QUERY_TIMEOUT: Duration = Duration::from_secs(15);
 
@@ -22,15 +22,22 @@ pub async fn run(pool: Arc<SqlitePool>, cancel_token: CancellationToken) -> Resu
         break;
       }
       _ = sleep(QUERY_TIMEOUT) => {
-        let affected_tasks = mutation::tasks::delete_by_update_date(&pool).await;
-
-        if let Err(e) = affected_tasks {
-          error!("Failed to delete old exchange tasks: {}", e);
-
-          continue;
+        let deleted_tasks = match mutation::tasks::delete_by_update_date(&pool).await {
+          Ok(ids) => ids,
+          Err(e) => {
+            error!("Failed to delete old exchange tasks: {}", e);
+
+            continue;
+          },
+        };
+        debug!("Delete {} old exchange tasks", deleted_tasks.len());
+
+        for id in &deleted_tasks {
+          if let Err(e) = outputs::remove(*id).await {
+            error!("Failed to remove the output of task {}: {}", id, e);
+          }
+          logs::remove(*id);
         }
-
-        debug!("Delete {} old exchange tasks", affected_tasks?);
       }
     }
   }
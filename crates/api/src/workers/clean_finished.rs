@@ -7,7 +7,7 @@ use tokio::time::{sleep, Duration};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
 
-use crate::service::mutation;
+use crate::service::{logs, mutation, outputs};
 
 static QUERY_TIMEOUT: Duration = Duration::from_secs(15);
 
@@ -22,15 +22,30 @@ pub async fn run(pool: Arc<SqlitePool>, cancel_token: CancellationToken) -> Resu
         break;
       }
       _ = sleep(QUERY_TIMEOUT) => {
-        let affected_tasks = mutation::tasks::delete_completed_tasks(&pool).await;
-
-        if let Err(e) = affected_tasks {
-          error!("Failed to delete tasks: {}", e);
-
-          continue;
+        let mut deleted_tasks = match mutation::tasks::delete_completed_tasks(&pool).await {
+          Ok(ids) => ids,
+          Err(e) => {
+            error!("Failed to delete tasks: {}", e);
+
+            continue;
+          },
+        };
+        debug!("Delete {} completed tasks", deleted_tasks.len());
+
+        match mutation::tasks::purge_deleted_tasks(&pool).await {
+          Ok(ids) => {
+            debug!("Purge {} deleted tasks", ids.len());
+            deleted_tasks.extend(ids);
+          },
+          Err(e) => error!("Failed to purge deleted tasks: {}", e),
         }
 
-        debug!("Delete {} completed tasks", affected_tasks?);
+        for id in &deleted_tasks {
+          if let Err(e) = outputs::remove(*id).await {
+            error!("Failed to remove the output of task {}: {}", id, e);
+          }
+          logs::remove(*id);
+        }
       }
     }
   }
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use tokio::select;
+use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+
+use crate::service::mutation;
+
+static QUERY_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub async fn run(pool: Arc<SqlitePool>, cancel_token: CancellationToken) -> Result<()> {
+  info!("Cleaning revoked tokens jobs started");
+
+  while !cancel_token.is_cancelled() {
+    select! {
+      biased;
+      _ = cancel_token.cancelled() => {
+        info!("Cleaning revoked tokens jobs stopped");
+        break;
+      }
+      _ = sleep(QUERY_TIMEOUT) => {
+        let affected_tokens = mutation::tokens::delete_expired(&pool).await;
+
+        if let Err(e) = affected_tokens {
+          error!("Failed to delete expired revoked tokens: {}", e);
+
+          continue;
+        }
+
+        debug!("Delete {} expired revoked tokens", affected_tokens?);
+      }
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,86 @@
+use std::{env, sync::Arc};
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tokio::select;
+use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{
+  entities::task::TaskRow,
+  service::{mutation, webhook},
+};
+
+static QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Optional webhook notified about every task moved to `dead`
+static DEAD_TASK_WEBHOOK_URL: Lazy<Option<String>> =
+  Lazy::new(|| env::var("DEAD_TASK_WEBHOOK_URL").ok().filter(|url| !url.is_empty()));
+
+pub async fn run(pool: Arc<SqlitePool>, cancel_token: CancellationToken) -> Result<()> {
+  info!("Dead tasks escalation jobs started");
+
+  while !cancel_token.is_cancelled() {
+    select! {
+      biased;
+      _ = cancel_token.cancelled() => {
+        info!("Dead tasks escalation jobs stopped");
+        break;
+      }
+      _ = sleep(QUERY_TIMEOUT) => {
+        let dead_tasks = match mutation::tasks::escalate_dead_tasks(&pool).await {
+          Ok(tasks) => tasks,
+          Err(e) => {
+            error!("Failed to escalate dead tasks: {}", e);
+
+            // Expiry doesn't depend on the escalation, it still runs
+            Vec::new()
+          }
+        };
+
+        for task in &dead_tasks {
+          warn!("Task {} ({}) exhausted its retries and is now dead", task.id, task.name);
+          notify(task).await;
+        }
+
+        let expired_tasks = match mutation::tasks::expire_tasks(&pool).await {
+          Ok(tasks) => tasks,
+          Err(e) => {
+            error!("Failed to expire tasks past their TTL: {}", e);
+
+            continue;
+          }
+        };
+
+        for task in &expired_tasks {
+          warn!("Task {} ({}) of type {} didn't complete within TASK_TTL and is now dead", task.id, task.name, task.r#type);
+          notify(task).await;
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
+async fn notify(task: &TaskRow) {
+  if let Some(url) = DEAD_TASK_WEBHOOK_URL.as_deref() {
+    if let Err(e) = webhook::send(url, &dead_task_payload(task)).await {
+      error!("Failed to notify webhook about dead task {}: {}", task.id, e);
+    }
+  }
+}
+
+fn dead_task_payload(task: &TaskRow) -> serde_json::Value {
+  json!({
+    "event": "task.dead",
+    "task_id": task.id,
+    "name": task.name,
+    "type": task.r#type,
+    "project_id": task.project_id,
+    "retries": task.retries,
+  })
+}
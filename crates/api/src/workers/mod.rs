@@ -1,2 +1,4 @@
 pub mod clean_exchange;
 pub mod clean_finished;
+pub mod clean_revoked_tokens;
+pub mod escalate_dead;
@@ -0,0 +1,132 @@
+use std::{
+  collections::HashMap,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+use octabot_plugins::bindings::exports::octahive::octabot::plugin::PluginResult;
+use serde_json::Value;
+
+/// Most results kept at once, new ones aren't cached while the cache is full of live entries
+const MAX_ENTRIES: usize = 1000;
+
+struct CachedResults {
+  results: Vec<PluginResult>,
+  expires_at: Instant,
+}
+
+/// Results of `process` calls keyed on the plugin and the task options, for plugins without side effects
+#[derive(Default)]
+pub struct ResultCache {
+  entries: Mutex<HashMap<(String, String), CachedResults>>,
+}
+
+impl ResultCache {
+  /// Returns the live results of a previous call with the same options
+  pub fn get(&self, plugin: &str, options: &Value) -> Option<Vec<PluginResult>> {
+    let entries = self.entries.lock().expect("result cache lock poisoned");
+
+    entries
+      .get(&cache_key(plugin, options))
+      .filter(|cached| cached.expires_at > Instant::now())
+      .map(|cached| cached.results.clone())
+  }
+
+  pub fn insert(&self, plugin: &str, options: &Value, results: &[PluginResult], ttl: Duration) {
+    let mut entries = self.entries.lock().expect("result cache lock poisoned");
+    let now = Instant::now();
+
+    if entries.len() >= MAX_ENTRIES {
+      entries.retain(|_, cached| cached.expires_at > now);
+      if entries.len() >= MAX_ENTRIES {
+        return;
+      }
+    }
+
+    entries.insert(
+      cache_key(plugin, options),
+      CachedResults {
+        results: results.to_vec(),
+        expires_at: now + ttl,
+      },
+    );
+  }
+
+  /// Drops every result of the plugin
+  pub fn invalidate(&self, plugin: &str) {
+    let mut entries = self.entries.lock().expect("result cache lock poisoned");
+    entries.retain(|(name, _), _| name != plugin);
+  }
+}
+
+/// Options are compared in their serialized form, tasks storing the same JSON share the cached results
+fn cache_key(plugin: &str, options: &Value) -> (String, String) {
+  (plugin.to_string(), options.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use octabot_plugins::bindings::exports::octahive::octabot::plugin::ActionData;
+  use serde_json::json;
+
+  use super::*;
+
+  fn action(name: &str) -> PluginResult {
+    PluginResult::Action(ActionData {
+      name: name.to_string(),
+      payload: "{}".to_string(),
+    })
+  }
+
+  fn action_names(results: Option<Vec<PluginResult>>) -> Option<Vec<String>> {
+    results.map(|results| {
+      results
+        .into_iter()
+        .map(|result| match result {
+          PluginResult::Action(action) => action.name,
+          PluginResult::Task(task) => task.name,
+        })
+        .collect()
+    })
+  }
+
+  #[test]
+  fn test_results_expire_after_ttl() {
+    let cache = ResultCache::default();
+    let options = json!({ "url": "https://example.com" });
+
+    cache.insert("http", &options, &[action("fetched")], Duration::from_millis(50));
+    assert_eq!(
+      action_names(cache.get("http", &options)),
+      Some(vec!["fetched".to_string()])
+    );
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(cache.get("http", &options).is_none());
+  }
+
+  #[test]
+  fn test_results_keyed_on_plugin_and_options() {
+    let cache = ResultCache::default();
+    let first = json!({ "url": "https://example.com/first" });
+    let second = json!({ "url": "https://example.com/second" });
+    let ttl = Duration::from_secs(60);
+
+    cache.insert("http", &first, &[action("first")], ttl);
+    cache.insert("http", &second, &[action("second")], ttl);
+
+    assert_eq!(action_names(cache.get("http", &first)), Some(vec!["first".to_string()]));
+    assert_eq!(
+      action_names(cache.get("http", &second)),
+      Some(vec!["second".to_string()])
+    );
+    assert!(cache
+      .get("http", &json!({ "url": "https://example.com/third" }))
+      .is_none());
+    assert!(cache.get("other", &first).is_none());
+
+    cache.invalidate("http");
+    assert!(cache.get("http", &first).is_none());
+    assert!(cache.get("http", &second).is_none());
+  }
+}
@@ -12,6 +12,9 @@ pub enum ExecutorError {
   #[error("Failed to read config file: {0}")]
   ConfigReadError(String),
 
+  #[error("Config references undefined environment variable `{0}`")]
+  UndefinedConfigVariable(String),
+
   #[error("Ocuured plugin error: {0}")]
   PluginError(#[from] PluginError),
 
@@ -33,6 +36,36 @@ pub enum ExecutorError {
   #[error("Failed to convert to chrono duration")]
   DurationConvertError,
 
+  #[error("Failed to read the plugin options set through the API: {0}")]
+  PluginOptionsReadError(String),
+
+  #[error("Plugin files not found: {0}")]
+  MissingPluginFiles(String),
+
+  #[error("Failed to initialize plugin {0}: {1}")]
+  PluginInitError(String, String),
+
+  #[error("Plugin {0} didn't finish initializing within {1} seconds")]
+  PluginInitTimeout(String, u64),
+
+  #[error("Invalid options for plugin {0}: {1}")]
+  InvalidPluginOptions(String, String),
+
+  #[error("Plugins failed the check: {0}")]
+  PluginCheckFailed(String),
+
+  #[error("Unknown options preset: {0}")]
+  UnknownPreset(String),
+
   #[error("Unknown plugin type: {0}")]
   UnknownPluginError(String),
+
+  #[error("No loaded version of plugin {0} matches `{1}`, loaded versions: {2}")]
+  IncompatiblePluginVersion(String, String, String),
+
+  #[error("Plugin {0} returned or enqueued {1} results, at most {2} are allowed")]
+  TooManyPluginResults(String, usize, usize),
+
+  #[error("All {1} results of plugin {0} failed, the first with: {2}")]
+  SubResultsFailed(String, usize, String),
 }
@@ -1,44 +1,306 @@
 #![allow(deprecated)]
-use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc, time::Duration};
+use std::{
+  any::Any,
+  collections::{HashMap, HashSet, VecDeque},
+  future::Future,
+  panic::AssertUnwindSafe,
+  path::Path,
+  pin::Pin,
+  sync::{
+    atomic::{AtomicU32, AtomicUsize, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
 
 use anyhow::{anyhow, Context, Result};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use cron::Schedule;
+use futures::FutureExt;
 use octabot_plugins::{
-  bindings::exports::octahive::octabot::plugin::PluginResult,
-  manager::{InstanceData, PluginActions, PluginManager},
-  state::State,
+  bindings::{exports::octahive::octabot::plugin::PluginResult, octahive::octabot::task_context::TaskContext},
+  capability::Capability,
+  manager::{InstanceData, PluginActions, PluginManager, PLUGINS_PATH},
+  ratelimit::RateLimit,
+  state::{HttpConfig, State, TaskLogLine, WasiConfig},
 };
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use sqlx::SqlitePool;
 use std::str::FromStr;
 use tokio::{
   sync::{
-    mpsc::{channel, Receiver, Sender},
-    Mutex,
+    mpsc::{channel, error::TrySendError, Receiver, Sender},
+    Mutex, Semaphore, SemaphorePermit,
   },
-  time::sleep,
+  time::{sleep, timeout, Instant},
 };
-use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info, instrument};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{debug, error, info, instrument, warn};
+use uuid::Uuid;
 use wasmtime::Store;
 
 use octabot_api::{
-  entities::{project::ProjectRow, task::Task},
-  service::{mutation, query},
+  entities::task::{MisfirePolicy, Task, TaskLog, TaskStatus},
+  executor::{EnqueueError, ExecutorHandle, PluginHealth, PluginMetadata, PluginReloadError, PluginStatus},
+  service::{logs, mutation, outputs, query, webhook},
+  timezone,
 };
 
-use crate::error::{ExecutorError, ExecutorResult};
+use crate::{
+  cache::ResultCache,
+  error::{ExecutorError, ExecutorResult},
+  interpolate::interpolate_env,
+  limits::{ProjectLimits, ProjectSlot},
+  presets::resolve_options,
+  task_queue::{self, DatabaseTaskQueue},
+};
 
 const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
 const CHANNEL_CAPACITY: usize = 500;
+/// Most missed occurrences a `catch_up` task runs after downtime, older ones are dropped
+const MAX_CATCH_UP_RUNS: usize = 10;
+const DEFAULT_SCALE_UP_DEPTH: usize = 10;
+const DEFAULT_IDLE_COOLDOWN_SECS: u64 = 60;
+const DEFAULT_MAX_PLUGIN_RESULTS: usize = 10_000;
+const DEFAULT_HEALTH_CHECK_INTERVAL_SECS: u64 = 60;
+const DEFAULT_KEYVALUE_SWEEP_SECS: u64 = 300;
+/// How often the locks of running tasks are renewed, well within the five minutes after which another executor
+/// reclaims them
+const LOCK_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A plugin listed several times under the same `name` with different files has every version loaded side by
+/// side, tasks pinning a `plugin_version` run on the highest matching one and the others on the highest overall
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PluginConfig {
   pub name: String,
   pub path: String,
   pub options: Option<Value>,
+  /// Number of workers dedicated to tasks of this plugin. When set, these tasks get their own queue and
+  /// never run on the shared pool; when unset they share the `num_workers` pool with the other plugins
+  pub workers: Option<u32>,
+  /// Host interfaces the plugin may use besides the WASI core, e.g. `["http", "logging"]`. Every capability
+  /// is granted when unset, an empty list grants none
+  pub capabilities: Option<Vec<Capability>>,
+  /// Seconds the results of a run are reused for tasks with the same options instead of calling the plugin
+  /// again. Only for plugins without side effects, nothing is cached when unset
+  pub result_cache_secs: Option<u64>,
+  /// Paces the outbound HTTP requests of the plugin, e.g. `{ "requests_per_sec": 1.5, "burst": 5 }` for one
+  /// bucket over all of them or with `"per_authority": true` for one per host. Requests over the limit wait
+  /// for their turn instead of failing, unlimited when unset
+  pub rate_limit: Option<RateLimit>,
+  /// How a failing child task or follow-up action returned by a run affects the others, `strict` by default
+  #[serde(default)]
+  pub sub_results: SubResultPolicy,
+  /// Host directories and environment the plugin may access, e.g. `"preopened_dirs": [{ "host": "./templates",
+  /// "guest": "/templates", "read_only": true }]`, `"env_passthrough": ["AWS_REGION"]` or
+  /// `"env": { "FEATURE_X": "1" }`. The plugin sees neither the filesystem nor the environment when unset.
+  /// What it prints goes to the task logs unless `"inherit_stdio": true`
+  #[serde(flatten)]
+  pub wasi: WasiConfig,
+  /// Rejects every outbound HTTP request of the plugin right away, like `http.deny_egress` does for all of them
+  #[serde(default)]
+  pub deny_egress: bool,
+}
+
+impl PluginConfig {
+  fn capabilities(&self) -> &[Capability] {
+    self.capabilities.as_deref().unwrap_or(&Capability::ALL)
+  }
+}
+
+/// What the executor does when a plugin file is missing or the plugin fails to initialize at startup
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum PluginErrorPolicy {
+  /// Refuse to start, so a broken plugin is noticed right away
+  #[default]
+  Fail,
+  /// Log the error and start without the plugin, its tasks fail as an unknown type
+  Skip,
+}
+
+/// What happens to the other results of a run when saving a child task or running a follow-up action fails
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SubResultPolicy {
+  /// Stop at the first failure and fail the task, the results handled before it are kept
+  #[default]
+  Strict,
+  /// Handle every result, log the failures to the task and only fail it when no result succeeded. The task
+  /// counts the failed results in `failed_results`
+  Lenient,
+}
+
+/// Outcomes of the results of one `process` call, handled according to the [`SubResultPolicy`] of its plugin
+struct SubResults {
+  policy: SubResultPolicy,
+  total: usize,
+  failures: Vec<anyhow::Error>,
+  /// Results dropped by follow-up actions that still succeeded
+  nested_failures: usize,
+}
+
+impl SubResults {
+  fn new(policy: SubResultPolicy, total: usize) -> Self {
+    Self {
+      policy,
+      total,
+      failures: Vec::new(),
+      nested_failures: 0,
+    }
+  }
+
+  /// Records the outcome of handling a result, the number of results its follow-up actions dropped when it
+  /// succeeded. Fails right away under [`SubResultPolicy::Strict`]
+  fn record(&mut self, outcome: Result<usize>) -> Result<()> {
+    match (outcome, self.policy) {
+      (Ok(dropped), _) => self.nested_failures += dropped,
+      (Err(e), SubResultPolicy::Strict) => return Err(e),
+      (Err(e), SubResultPolicy::Lenient) => self.failures.push(e),
+    }
+
+    Ok(())
+  }
+
+  /// # Returns
+  /// The number of results dropped, those of follow-up actions included, or an error when every result failed
+  fn finish(self, action_type: &str) -> Result<usize> {
+    match self.failures.first() {
+      Some(first) if self.failures.len() == self.total => {
+        Err(ExecutorError::SubResultsFailed(action_type.to_string(), self.total, first.to_string()).into())
+      },
+      _ => Ok(self.failures.len() + self.nested_failures),
+    }
+  }
+}
+
+/// Bounds of the shared pool when it scales with the load instead of keeping `num_workers` running
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Autoscale {
+  /// Workers kept running even when idle, the pool starts at this size. With 0 the first queued task starts one
+  min_workers: u32,
+  max_workers: u32,
+  /// Queue depth above which the poller starts extra workers, one per task queued over it
+  #[serde(default = "default_scale_up_depth")]
+  scale_up_depth: usize,
+  /// Seconds an extra worker waits for a task before it exits
+  #[serde(default = "default_idle_cooldown_secs")]
+  idle_cooldown_secs: u64,
+}
+
+impl Autoscale {
+  /// Size the pool grows to from `size` workers with `depth` tasks queued, `None` when it stays as it is
+  ///
+  /// A pool scaled down to no workers, possible with `min_workers` of 0, always gets one for a queued task,
+  /// otherwise the task would wait until the queue grows past `scale_up_depth`
+  fn scaled_size(&self, size: u32, depth: usize) -> Option<u32> {
+    let mut wanted = depth.saturating_sub(self.scale_up_depth).min(self.max_workers as usize) as u32;
+    if size == 0 && depth > 0 {
+      wanted = wanted.max(1);
+    }
+
+    (wanted > 0 && size < self.max_workers).then(|| (size + wanted).min(self.max_workers))
+  }
+}
+
+/// How long a plugin may take to load and initialize, and how often a failed attempt is repeated
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+struct PluginInit {
+  /// Seconds a single attempt may take, including the plugin's own `init`
+  timeout_secs: u64,
+  /// Attempts made after the first one fails or times out, invalid options are never retried
+  retries: u32,
+  /// Delay before the first retry in milliseconds, doubled for every further one
+  backoff_ms: u64,
+}
+
+impl Default for PluginInit {
+  fn default() -> Self {
+    Self {
+      timeout_secs: 30,
+      retries: 2,
+      backoff_ms: 1000,
+    }
+  }
+}
+
+/// What the workers do with the tasks of a plugin whose last health check failed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum UnhealthyPolicy {
+  /// Run them anyway, they fail and are retried as usual
+  #[default]
+  Run,
+  /// Put them back for `defer_secs` without counting an attempt
+  Defer,
+}
+
+/// Periodic `health` calls of the plugins exporting it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+struct HealthCheck {
+  /// Seconds between two rounds of checks
+  interval_secs: u64,
+  on_unhealthy: UnhealthyPolicy,
+  /// Seconds a task of an unhealthy plugin is pushed back by with the `defer` policy, the check interval
+  /// when unset
+  defer_secs: Option<u64>,
+}
+
+impl Default for HealthCheck {
+  fn default() -> Self {
+    Self {
+      interval_secs: DEFAULT_HEALTH_CHECK_INTERVAL_SECS,
+      on_unhealthy: UnhealthyPolicy::default(),
+      defer_secs: None,
+    }
+  }
+}
+
+impl HealthCheck {
+  /// How long tasks of an unhealthy plugin are deferred by, `None` when they run anyway
+  fn defer_delay(&self) -> Option<Duration> {
+    (self.on_unhealthy == UnhealthyPolicy::Defer)
+      .then(|| Duration::from_secs(self.defer_secs.unwrap_or(self.interval_secs)))
+  }
+}
+
+impl PluginInit {
+  fn backoff(&self, retry: u32) -> Duration {
+    Duration::from_millis(self.backoff_ms.saturating_mul(1 << retry.min(16)))
+  }
+}
+
+fn default_scale_up_depth() -> usize {
+  DEFAULT_SCALE_UP_DEPTH
+}
+
+fn default_idle_cooldown_secs() -> u64 {
+  DEFAULT_IDLE_COOLDOWN_SECS
+}
+
+fn default_max_plugin_results() -> usize {
+  DEFAULT_MAX_PLUGIN_RESULTS
+}
+
+fn default_keyvalue_sweep_secs() -> u64 {
+  DEFAULT_KEYVALUE_SWEEP_SECS
+}
+
+/// How tasks reach the workers
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum DispatchMode {
+  /// Due tasks are polled from the database
+  #[default]
+  Poll,
+  /// Nothing is polled, tasks only run when triggered with `POST /api/tasks/{id}/trigger`. Schedules are not
+  /// followed and tasks left `in_progress` by a crash are not picked up again
+  Push,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,15 +311,103 @@ struct ExecuteParams {
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Config {
+  /// Size of the shared pool running tasks of every plugin without its own `workers` override. Dedicated
+  /// workers are started in addition to this pool, so the total is `num_workers` plus all the overrides
   num_workers: u32,
+  /// Scales the shared pool between `min_workers` and `max_workers` with the queue depth, `num_workers` is
+  /// ignored when set
+  #[serde(default)]
+  autoscale: Option<Autoscale>,
+  /// Whether tasks are polled from the database or only run when triggered through the API, `poll` by default
+  #[serde(default)]
+  dispatch: DispatchMode,
+  /// Whether a missing plugin file or a plugin that fails to initialize stops the startup, `fail` by default
+  #[serde(default)]
+  on_plugin_error: PluginErrorPolicy,
+  /// Retries and timeouts of the HTTP requests plugins make
+  #[serde(default)]
+  http: HttpConfig,
+  /// Timeout and retries of loading each plugin, at startup and on reload
+  #[serde(default)]
+  plugin_init: PluginInit,
+  /// Most plugin `process` calls running at once across every worker, sub-actions included, unlimited when unset
+  #[serde(default)]
+  max_concurrent_invocations: Option<usize>,
+  /// Most results a single plugin `process` call may return, tasks it enqueues included, the task fails when a
+  /// plugin returns more
+  #[serde(default = "default_max_plugin_results")]
+  max_plugin_results: usize,
+  /// How often plugins exporting `health` are checked and what happens to their tasks while they're unhealthy
+  #[serde(default)]
+  health_check: HealthCheck,
+  /// Seconds between two sweeps dropping the expired keyvalue entries no plugin reads anymore
+  #[serde(default = "default_keyvalue_sweep_secs")]
+  keyvalue_sweep_secs: u64,
   plugins: Vec<PluginConfig>,
 }
 
 impl Config {
+  /// Loads the config, expanding `${VAR}` references in its strings from the environment
   fn from_file(path: &str) -> ExecutorResult<Self> {
     let file = std::fs::File::open(path).map_err(ExecutorError::ConfigOpenError)?;
 
-    serde_json::from_reader(file).map_err(|e| ExecutorError::ConfigReadError(e.to_string()))
+    let mut value: Value = serde_json::from_reader(file).map_err(|e| ExecutorError::ConfigReadError(e.to_string()))?;
+    interpolate_env(&mut value)?;
+
+    let config: Self = serde_json::from_value(value).map_err(|e| ExecutorError::ConfigReadError(e.to_string()))?;
+
+    if let Some(autoscale) = &config.autoscale {
+      if autoscale.max_workers == 0 || autoscale.min_workers > autoscale.max_workers {
+        return Err(ExecutorError::ConfigReadError(format!(
+          "autoscale needs 0 < max_workers and min_workers <= max_workers, got {} and {}",
+          autoscale.max_workers, autoscale.min_workers
+        )));
+      }
+    }
+
+    config
+      .http
+      .tls
+      .check()
+      .map_err(|e| ExecutorError::ConfigReadError(format!("http.tls: {}", e)))?;
+
+    if config.max_concurrent_invocations == Some(0) {
+      return Err(ExecutorError::ConfigReadError(
+        "max_concurrent_invocations must be greater than 0".to_string(),
+      ));
+    }
+
+    if config.health_check.interval_secs == 0 {
+      return Err(ExecutorError::ConfigReadError(
+        "health_check.interval_secs must be greater than 0".to_string(),
+      ));
+    }
+
+    if config.keyvalue_sweep_secs == 0 {
+      return Err(ExecutorError::ConfigReadError(
+        "keyvalue_sweep_secs must be greater than 0".to_string(),
+      ));
+    }
+
+    if let Some(plugin) = config.plugins.iter().find(|plugin| {
+      plugin
+        .rate_limit
+        .as_ref()
+        .is_some_and(|rate_limit| !(rate_limit.requests_per_sec > 0.0 && rate_limit.requests_per_sec.is_finite()))
+    }) {
+      return Err(ExecutorError::ConfigReadError(format!(
+        "rate_limit.requests_per_sec of plugin {} must be greater than 0",
+        plugin.name
+      )));
+    }
+
+    if config.max_plugin_results == 0 {
+      return Err(ExecutorError::ConfigReadError(
+        "max_plugin_results must be greater than 0".to_string(),
+      ));
+    }
+
+    Ok(config)
   }
 }
 
@@ -65,87 +415,764 @@ pub struct Plugin {
   pub instance: InstanceData,
   pub store: Arc<Mutex<Store<State>>>,
   pub options: Option<Value>,
+  /// File the plugin was loaded from, a reload replaces the instance loaded from the same file
+  pub path: String,
+  /// `Metadata.version` of the plugin, `None` when it's not semver and tasks can't pin it
+  pub version: Option<Version>,
+  /// How long the results of a `process` call are reused for the same options, not cached when unset
+  pub cache_ttl: Option<Duration>,
+  pub sub_results: SubResultPolicy,
+  /// Last health check, `None` until the first one or when the plugin doesn't export `health`
+  pub health: std::sync::Mutex<Option<PluginHealth>>,
+}
+
+impl Plugin {
+  fn is_unhealthy(&self) -> bool {
+    self
+      .health
+      .lock()
+      .expect("plugin health lock poisoned")
+      .as_ref()
+      .is_some_and(|health| !health.healthy)
+  }
+
+  /// Stores the outcome of a health check, logging when the plugin becomes unhealthy or recovers
+  fn record_health(&self, name: &str, error: Option<String>) {
+    let mut health = self.health.lock().expect("plugin health lock poisoned");
+    let was_healthy = health.as_ref().is_none_or(|health| health.healthy);
+
+    match &error {
+      Some(e) if was_healthy => warn!(
+        "Plugin {} version {} is unhealthy: {}",
+        name, self.instance.metadata.version, e
+      ),
+      None if !was_healthy => info!(
+        "Plugin {} version {} is healthy again",
+        name, self.instance.metadata.version
+      ),
+      _ => {},
+    }
+
+    *health = Some(PluginHealth {
+      healthy: error.is_none(),
+      error,
+      checked_at: Utc::now(),
+    });
+  }
+}
+
+/// Initialized plugins by name, each with every loaded version, a reloaded plugin replaces the previous
+/// instance while tasks already holding it finish on it
+pub struct PluginRegistry {
+  plugins: std::sync::RwLock<HashMap<String, Vec<Arc<Plugin>>>>,
+  /// Bounds the `process` calls running at once over every plugin, unlimited when unset
+  invocations: Option<Semaphore>,
+  /// Most results accepted from a single `process` call, a safety valve against runaway plugin output
+  max_results: usize,
+  /// Results of the plugins with `result_cache_secs` set
+  cache: ResultCache,
+}
+
+impl PluginRegistry {
+  pub fn new(max_invocations: Option<usize>, max_results: usize) -> Self {
+    Self {
+      plugins: Default::default(),
+      invocations: max_invocations.map(Semaphore::new),
+      max_results,
+      cache: ResultCache::default(),
+    }
+  }
+
+  /// Waits for a free invocation slot, the returned permit must be held for the whole `process` call
+  pub async fn acquire_invocation(&self) -> Option<SemaphorePermit<'_>> {
+    match &self.invocations {
+      Some(invocations) => invocations.acquire().await.ok(),
+      None => None,
+    }
+  }
+
+  /// Returns the highest loaded version of the plugin matching `requirement`, or the highest one when unset
+  ///
+  /// # Errors
+  /// - UnknownPluginError if no version of the plugin is loaded
+  /// - IncompatiblePluginVersion if none of the loaded versions matches `requirement`
+  pub fn get(&self, name: &str, requirement: Option<&VersionReq>) -> ExecutorResult<Arc<Plugin>> {
+    let plugins = self.plugins.read().expect("plugins lock poisoned");
+    let versions = plugins
+      .get(name)
+      .ok_or_else(|| ExecutorError::UnknownPluginError(name.to_string()))?;
+
+    // Ties keep the plugin listed last in the config, as before versions were tracked
+    let plugin = versions
+      .iter()
+      .filter(|plugin| match requirement {
+        Some(requirement) => plugin
+          .version
+          .as_ref()
+          .is_some_and(|version| requirement.matches(version)),
+        None => true,
+      })
+      .max_by(|a, b| a.version.cmp(&b.version));
+
+    plugin.cloned().ok_or_else(|| {
+      ExecutorError::IncompatiblePluginVersion(
+        name.to_string(),
+        requirement.map(ToString::to_string).unwrap_or_default(),
+        versions
+          .iter()
+          .map(|plugin| plugin.instance.metadata.version.clone())
+          .collect::<Vec<_>>()
+          .join(", "),
+      )
+    })
+  }
+
+  pub fn insert(&self, name: &str, plugin: Plugin) {
+    // A reloaded plugin may produce different results for the same options
+    self.cache.invalidate(&cache_name(name, &plugin));
+
+    let mut plugins = self.plugins.write().expect("plugins lock poisoned");
+    let versions = plugins.entry(name.to_string()).or_default();
+
+    match versions.iter_mut().find(|loaded| loaded.path == plugin.path) {
+      Some(loaded) => *loaded = Arc::new(plugin),
+      None => versions.push(Arc::new(plugin)),
+    }
+  }
+
+  /// Every loaded plugin with the name it's configured under
+  pub fn all(&self) -> Vec<(String, Arc<Plugin>)> {
+    let plugins = self.plugins.read().expect("plugins lock poisoned");
+
+    plugins
+      .iter()
+      .flat_map(|(name, versions)| versions.iter().map(|plugin| (name.clone(), plugin.clone())))
+      .collect()
+  }
+
+  /// Whether the last health check of the plugin the task would run on failed
+  fn is_unhealthy(&self, task: &Task) -> bool {
+    let requirement = task
+      .plugin_version
+      .as_deref()
+      .and_then(|requirement| VersionReq::parse(requirement).ok());
+
+    self
+      .get(&task.r#type, requirement.as_ref())
+      .is_ok_and(|plugin| plugin.is_unhealthy())
+  }
+
+  pub fn len(&self) -> usize {
+    self.plugins.read().expect("plugins lock poisoned").len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+/// Queue feeding a set of workers
+struct WorkerPool {
+  name: String,
+  /// Number of running workers, only changes when the pool autoscales
+  size: AtomicU32,
+  autoscale: Option<Autoscale>,
+  tx: Sender<Task>,
+  rx: Mutex<Receiver<Task>>,
+}
+
+impl WorkerPool {
+  fn new(name: &str, size: u32, autoscale: Option<Autoscale>) -> Self {
+    let (tx, rx) = channel::<Task>(CHANNEL_CAPACITY);
+
+    Self {
+      name: name.to_string(),
+      size: AtomicU32::new(size),
+      autoscale,
+      tx,
+      rx: Mutex::new(rx),
+    }
+  }
+
+  fn queue_depth(&self) -> usize {
+    self.tx.max_capacity() - self.tx.capacity()
+  }
+
+  /// Counts the workers to add for the current queue depth towards the pool size, the caller starts them
+  fn reserve_scale_up(&self) -> u32 {
+    let Some(autoscale) = &self.autoscale else {
+      return 0;
+    };
+
+    let depth = self.queue_depth();
+
+    self
+      .size
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |size| {
+        autoscale.scaled_size(size, depth)
+      })
+      .map(|previous| autoscale.scaled_size(previous, depth).unwrap_or(previous) - previous)
+      .unwrap_or(0)
+  }
+
+  /// Takes an idle worker out of the pool unless it's already at its floor, returns whether it may exit
+  fn try_retire(&self) -> bool {
+    self.autoscale.as_ref().is_some_and(|autoscale| {
+      self
+        .size
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |size| {
+          (size > autoscale.min_workers).then(|| size - 1)
+        })
+        .is_ok()
+    })
+  }
+
+  fn idle_timeout(&self) -> Option<Duration> {
+    self
+      .autoscale
+      .as_ref()
+      .map(|autoscale| Duration::from_secs(autoscale.idle_cooldown_secs))
+  }
+}
+
+/// Worker pools keyed by task type, tasks of plugins without a dedicated pool go to the shared one
+struct WorkerPools {
+  shared: Arc<WorkerPool>,
+  dedicated: HashMap<String, Arc<WorkerPool>>,
+}
+
+impl WorkerPools {
+  fn new(config: &Config) -> Self {
+    let dedicated = config
+      .plugins
+      .iter()
+      .filter_map(|plugin| {
+        plugin.workers.map(|workers| {
+          (
+            plugin.name.clone(),
+            Arc::new(WorkerPool::new(&plugin.name, workers, None)),
+          )
+        })
+      })
+      .collect();
+
+    Self {
+      shared: Arc::new(WorkerPool::new(
+        "shared",
+        config
+          .autoscale
+          .as_ref()
+          .map_or(config.num_workers, |autoscale| autoscale.min_workers),
+        config.autoscale.clone(),
+      )),
+      dedicated,
+    }
+  }
+
+  fn sender(&self, task_type: &str) -> &Sender<Task> {
+    &self.dedicated.get(task_type).unwrap_or(&self.shared).tx
+  }
+
+  fn iter(&self) -> impl Iterator<Item = &Arc<WorkerPool>> {
+    std::iter::once(&self.shared).chain(self.dedicated.values())
+  }
+}
+
+/// Ids of the tasks queued or running in this executor, so a task the poller selects again while it's still
+/// being processed isn't dispatched a second time
+#[derive(Default)]
+struct InFlight(std::sync::Mutex<HashSet<Uuid>>);
+
+impl InFlight {
+  /// Marks the task as in flight, returns `false` if it already was
+  fn insert(&self, id: Uuid) -> bool {
+    self.0.lock().expect("in-flight lock poisoned").insert(id)
+  }
+
+  fn remove(&self, id: &Uuid) {
+    self.0.lock().expect("in-flight lock poisoned").remove(id);
+  }
 }
 
 pub struct ExecutorSystem {
-  config: Config,
+  /// `EXECUTOR_ID`, or generated for this run, the tasks this executor runs are locked by it
+  id: Arc<str>,
   pool: Arc<SqlitePool>,
-  plugins: Arc<HashMap<String, Plugin>>,
-  tx: Sender<Task>,
-  rx: Arc<Mutex<Receiver<Task>>>,
+  plugins: Arc<PluginRegistry>,
+  plugin_manager: Arc<PluginManager>,
+  /// Configured plugins, with the options pushed through the API in place of the ones in config.json
+  plugin_configs: Arc<std::sync::RwLock<Vec<PluginConfig>>>,
+  plugin_init: PluginInit,
+  workers: Arc<WorkerPools>,
+  busy_workers: Arc<AtomicUsize>,
+  project_limits: Arc<ProjectLimits>,
+  in_flight: Arc<InFlight>,
+  worker_tasks: TaskTracker,
+  next_worker_id: AtomicU32,
+  dispatch: DispatchMode,
+  health_check: HealthCheck,
+}
+
+/// View of a running executor shared with the API
+struct ExecutorState {
+  id: Arc<str>,
+  workers: Arc<WorkerPools>,
+  plugins: Arc<PluginRegistry>,
+  plugin_manager: Arc<PluginManager>,
+  /// Configured plugins, with the options pushed through the API in place of the ones in config.json
+  plugin_configs: Arc<std::sync::RwLock<Vec<PluginConfig>>>,
+  plugin_init: PluginInit,
+  busy_workers: Arc<AtomicUsize>,
+  in_flight: Arc<InFlight>,
+}
+
+impl ExecutorState {
+  /// Copies of the configs of every file loaded under `name`, so none of them is locked while loading
+  fn configs_of(&self, name: &str) -> Vec<PluginConfig> {
+    self
+      .plugin_configs
+      .read()
+      .expect("plugin configs lock poisoned")
+      .iter()
+      .filter(|config| config.name == name)
+      .cloned()
+      .collect()
+  }
+}
+
+#[async_trait]
+impl ExecutorHandle for ExecutorState {
+  fn id(&self) -> &str {
+    &self.id
+  }
+
+  fn queue_depth(&self) -> usize {
+    self.workers.iter().map(|w| w.queue_depth()).sum()
+  }
+
+  fn queue_capacity(&self) -> usize {
+    self.workers.iter().map(|w| w.tx.max_capacity()).sum()
+  }
+
+  fn plugin_count(&self) -> usize {
+    self.plugins.len()
+  }
+
+  fn plugins(&self) -> Vec<PluginStatus> {
+    let mut plugins: Vec<PluginStatus> = self
+      .plugins
+      .all()
+      .into_iter()
+      .map(|(name, plugin)| PluginStatus {
+        name,
+        version: plugin.instance.metadata.version.clone(),
+        path: plugin.path.clone(),
+        reports_health: plugin.instance.reports_health(),
+        health: plugin.health.lock().expect("plugin health lock poisoned").clone(),
+      })
+      .collect();
+
+    plugins.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.path.cmp(&b.path)));
+    plugins
+  }
+
+  fn worker_count(&self) -> usize {
+    self
+      .workers
+      .iter()
+      .map(|w| w.size.load(Ordering::Relaxed) as usize)
+      .sum()
+  }
+
+  fn busy_workers(&self) -> usize {
+    self.busy_workers.load(Ordering::Relaxed)
+  }
+
+  /// Reloads every file configured under `name`, returning the metadata of the last one
+  async fn reload_plugin(&self, name: &str) -> Result<PluginMetadata, PluginReloadError> {
+    let mut reloaded = None;
+
+    for config in self.configs_of(name) {
+      let plugin = ExecutorSystem::load_plugin(&self.plugin_manager, &config, &self.plugin_init)
+        .await
+        .map_err(|e| PluginReloadError::Failed(name.to_string(), e.to_string()))?;
+
+      let metadata = plugin_metadata(&plugin);
+      self.plugins.insert(name, plugin);
+      info!("Plugin {} reloaded, version {}", name, metadata.version);
+
+      reloaded = Some(metadata);
+    }
+
+    reloaded.ok_or_else(|| PluginReloadError::NotFound(name.to_string()))
+  }
+
+  /// Loads every file configured under `name` with the new options before swapping any of them in, so a
+  /// version rejecting the options leaves all the previous instances running
+  async fn configure_plugin(&self, name: &str, options: Value) -> Result<PluginMetadata, PluginReloadError> {
+    let mut loaded = Vec::new();
+
+    for mut config in self.configs_of(name) {
+      config.options = Some(options.clone());
+
+      let plugin = ExecutorSystem::load_plugin(&self.plugin_manager, &config, &self.plugin_init)
+        .await
+        .map_err(|e| PluginReloadError::Failed(name.to_string(), e.to_string()))?;
+      loaded.push(plugin);
+    }
+
+    let mut metadata = None;
+    for plugin in loaded {
+      metadata = Some(plugin_metadata(&plugin));
+      self.plugins.insert(name, plugin);
+    }
+    let metadata = metadata.ok_or_else(|| PluginReloadError::NotFound(name.to_string()))?;
+
+    let mut configs = self.plugin_configs.write().expect("plugin configs lock poisoned");
+    for config in configs.iter_mut().filter(|config| config.name == name) {
+      config.options = Some(options.clone());
+    }
+    info!("Plugin {} reconfigured, version {}", name, metadata.version);
+
+    Ok(metadata)
+  }
+
+  fn enqueue(&self, task: Task) -> Result<(), EnqueueError> {
+    let id = task.id;
+
+    if !self.in_flight.insert(id) {
+      return Err(EnqueueError::AlreadyQueued(id));
+    }
+
+    let result = match self.workers.sender(&task.r#type).try_send(task) {
+      Ok(()) => return Ok(()),
+      Err(TrySendError::Full(_)) => EnqueueError::QueueFull(id),
+      Err(TrySendError::Closed(_)) => EnqueueError::Stopped(id),
+    };
+
+    self.in_flight.remove(&id);
+    Err(result)
+  }
 }
 
 impl ExecutorSystem {
   #[instrument(level = "debug", skip(pool))]
   pub async fn new(pool: Arc<SqlitePool>) -> ExecutorResult<Self> {
-    let (tx, rx) = channel::<Task>(CHANNEL_CAPACITY);
-
-    let config = Config::from_file("config.json")?;
-    let plugins = Self::initialize_plugins(&config.plugins).await?;
+    let mut config = Config::from_file("config.json")?;
+    Self::apply_option_overrides(&pool, &mut config.plugins).await?;
+    let plugin_manager = PluginManager::new()?
+      .with_http_config(config.http.clone())
+      .with_kv_reaper(Duration::from_secs(config.keyvalue_sweep_secs));
+    let plugin_configs = Self::check_plugin_files(&config)?;
+    let plugins = Self::initialize_plugins(
+      &plugin_manager,
+      &plugin_configs,
+      &config.plugin_init,
+      config.on_plugin_error,
+      config.max_concurrent_invocations,
+      config.max_plugin_results,
+    )
+    .await?;
+    let workers = WorkerPools::new(&config);
+    let id = executor_id();
+    info!("Executor id is {}", id);
 
     Ok(Self {
-      config,
+      id: id.into(),
       pool,
       plugins: Arc::new(plugins),
-      tx,
-      rx: Arc::new(Mutex::new(rx)),
+      plugin_manager: Arc::new(plugin_manager),
+      plugin_configs: Arc::new(std::sync::RwLock::new(config.plugins)),
+      plugin_init: config.plugin_init,
+      workers: Arc::new(workers),
+      busy_workers: Arc::new(AtomicUsize::new(0)),
+      project_limits: Arc::new(ProjectLimits::default()),
+      in_flight: Arc::new(InFlight::default()),
+      worker_tasks: TaskTracker::new(),
+      next_worker_id: AtomicU32::new(0),
+      dispatch: config.dispatch,
+      health_check: config.health_check,
     })
   }
 
-  async fn initialize_plugins(configs: &[PluginConfig]) -> ExecutorResult<HashMap<String, Plugin>> {
-    let mut plugins = HashMap::new();
-    let plugin_manager = PluginManager::new()?;
+  /// Loads and initializes every configured plugin without touching the database or starting the workers,
+  /// logging the metadata of each one, so a config can be checked before it's deployed
+  ///
+  /// # Errors
+  /// - PluginCheckFailed naming every plugin that couldn't be loaded or initialized
+  pub async fn validate() -> ExecutorResult<()> {
+    let config = Config::from_file("config.json")?;
+    let plugin_manager = PluginManager::new()?.with_http_config(config.http.clone());
+    let mut failed = Vec::new();
 
-    for config in configs {
-      let options = config.options.clone().unwrap_or_default();
-      let (instance, store) = plugin_manager.load_plugin(&config.path).await?;
-      let store = Arc::new(Mutex::new(store));
-
-      let mut store_guard = store.lock().await;
-      let plugin = match instance.init(&mut store_guard, &options.to_string()).await {
-        Ok(_) => {
-          info!("Plugin {} initialized successfully", instance.metadata.name);
-          instance
+    for plugin_config in &config.plugins {
+      match Self::load_plugin(&plugin_manager, plugin_config, &config.plugin_init).await {
+        Ok(plugin) => {
+          let metadata = &plugin.instance.metadata;
+          info!(
+            "Plugin {} OK: {} {} by {} - {}",
+            plugin_config.name, metadata.name, metadata.version, metadata.author, metadata.description
+          );
         },
         Err(e) => {
-          error!("Failed to initialize plugin {}: {}", config.name, e);
-          continue;
-        },
-      };
-      drop(store_guard);
-
-      plugins.insert(
-        config.name.clone(),
-        Plugin {
-          instance: plugin,
-          store,
-          options: config.options.clone(),
+          error!("Plugin {} failed: {}", plugin_config.name, e);
+          failed.push(plugin_config.name.clone());
         },
+      }
+    }
+
+    if !failed.is_empty() {
+      return Err(ExecutorError::PluginCheckFailed(failed.join(", ")));
+    }
+
+    info!("All {} plugins loaded and initialized", config.plugins.len());
+
+    Ok(())
+  }
+
+  /// Returns a handle the API uses to report on the queue, plugins and workers
+  pub fn handle(&self) -> Arc<dyn ExecutorHandle> {
+    Arc::new(ExecutorState {
+      id: self.id.clone(),
+      workers: self.workers.clone(),
+      plugins: self.plugins.clone(),
+      plugin_manager: self.plugin_manager.clone(),
+      plugin_configs: self.plugin_configs.clone(),
+      plugin_init: self.plugin_init.clone(),
+      busy_workers: self.busy_workers.clone(),
+      in_flight: self.in_flight.clone(),
+    })
+  }
+
+  /// Replaces the options from config.json with the ones pushed through the API before the executor last stopped
+  async fn apply_option_overrides(pool: &SqlitePool, configs: &mut [PluginConfig]) -> ExecutorResult<()> {
+    let overrides = query::plugins::list_options(pool)
+      .await
+      .map_err(|e| ExecutorError::PluginOptionsReadError(e.to_string()))?;
+
+    for row in overrides {
+      let mut configs = configs.iter_mut().filter(|config| config.name == row.name).peekable();
+      if configs.peek().is_none() {
+        warn!(
+          "Ignoring stored options of plugin {}, it's no longer configured",
+          row.name
+        );
+        continue;
+      }
+
+      info!(
+        "Plugin {} uses the options set through the API on {}",
+        row.name, row.updated_at
       );
+      for config in configs {
+        config.options = Some(row.options.clone());
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Checks upfront that every configured plugin file exists, so a wrong path is reported at startup
+  /// rather than when tasks of that type start failing
+  ///
+  /// # Returns
+  /// The plugins whose files exist, missing ones are only dropped with the `skip` policy
+  fn check_plugin_files(config: &Config) -> ExecutorResult<Vec<&PluginConfig>> {
+    let (present, missing): (Vec<&PluginConfig>, Vec<&PluginConfig>) = config
+      .plugins
+      .iter()
+      .partition(|plugin| Path::new(PLUGINS_PATH).join(&plugin.path).is_file());
+
+    if missing.is_empty() {
+      return Ok(present);
+    }
+
+    let missing = missing
+      .iter()
+      .map(|plugin| {
+        format!(
+          "{} ({})",
+          plugin.name,
+          Path::new(PLUGINS_PATH).join(&plugin.path).display()
+        )
+      })
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    match config.on_plugin_error {
+      PluginErrorPolicy::Fail => Err(ExecutorError::MissingPluginFiles(missing)),
+      PluginErrorPolicy::Skip => {
+        error!("Skipping plugins with missing files: {}", missing);
+        Ok(present)
+      },
+    }
+  }
+
+  async fn initialize_plugins(
+    plugin_manager: &PluginManager,
+    configs: &[&PluginConfig],
+    init: &PluginInit,
+    on_error: PluginErrorPolicy,
+    max_invocations: Option<usize>,
+    max_results: usize,
+  ) -> ExecutorResult<PluginRegistry> {
+    let plugins = PluginRegistry::new(max_invocations, max_results);
+
+    for config in configs {
+      match Self::load_plugin(plugin_manager, config, init).await {
+        Ok(plugin) => {
+          info!("Plugin {} initialized successfully", plugin.instance.metadata.name);
+          plugins.insert(&config.name, plugin);
+        },
+        Err(e) => match on_error {
+          PluginErrorPolicy::Fail => return Err(e),
+          PluginErrorPolicy::Skip => error!("Skipping plugin {}: {}", config.name, e),
+        },
+      }
     }
 
     Ok(plugins)
   }
 
+  /// Reads the plugin component from disk and initializes a new instance of it, retrying with backoff when
+  /// an attempt fails or times out
+  ///
+  /// Every attempt starts from a fresh instance, since one abandoned halfway through `init` can't be reused. A
+  /// guest busy in a loop yields on every epoch tick so the timeout still fires, and `init` traps at the deadline.
+  async fn load_plugin(
+    plugin_manager: &PluginManager,
+    config: &PluginConfig,
+    init: &PluginInit,
+  ) -> ExecutorResult<Plugin> {
+    let mut retry = 0;
+
+    loop {
+      let deadline = Instant::now() + Duration::from_secs(init.timeout_secs);
+      let attempt = timeout(
+        Duration::from_secs(init.timeout_secs),
+        Self::try_load_plugin(plugin_manager, config, deadline),
+      )
+      .await
+      .unwrap_or_else(|_| Err(ExecutorError::PluginInitTimeout(config.name.clone(), init.timeout_secs)));
+
+      match attempt {
+        Err(e) if retry < init.retries && !matches!(e, ExecutorError::InvalidPluginOptions(..)) => {
+          let delay = init.backoff(retry);
+          retry += 1;
+
+          warn!(
+            "Plugin {} failed to load, retrying in {:?} ({}/{}): {}",
+            config.name, delay, retry, init.retries, e
+          );
+          sleep(delay).await;
+        },
+        result => return result,
+      }
+    }
+  }
+
+  async fn try_load_plugin(
+    plugin_manager: &PluginManager,
+    config: &PluginConfig,
+    deadline: Instant,
+  ) -> ExecutorResult<Plugin> {
+    debug!(
+      "Loading plugin {} with capabilities {:?}",
+      config.name,
+      config.capabilities()
+    );
+    let (instance, mut store) = plugin_manager
+      .load_plugin(
+        &config.name,
+        &config.path,
+        config.capabilities(),
+        &config.wasi,
+        config.rate_limit.as_ref(),
+        config.deny_egress,
+      )
+      .await?;
+
+    store.data_mut().call_deadline = Some(deadline.into_std());
+    let initialized = Self::initialize_plugin(&instance, &mut store, config).await;
+    store.data_mut().call_deadline = None;
+    store.data_mut().drain_stdio();
+    initialized?;
+
+    let version = Version::parse(&instance.metadata.version)
+      .inspect_err(|e| {
+        warn!(
+          "Plugin {} version `{}` is not semver, tasks can't pin it: {}",
+          config.name, instance.metadata.version, e
+        )
+      })
+      .ok();
+
+    Ok(Plugin {
+      instance,
+      store: Arc::new(Mutex::new(store)),
+      options: config.options.clone(),
+      path: config.path.clone(),
+      version,
+      cache_ttl: config.result_cache_secs.map(Duration::from_secs),
+      sub_results: config.sub_results,
+      health: Default::default(),
+    })
+  }
+
+  /// Validates the options against the schema declared by the plugin, if any, and passes them to its `init`
+  async fn initialize_plugin(
+    instance: &InstanceData,
+    store: &mut Store<State>,
+    config: &PluginConfig,
+  ) -> ExecutorResult<()> {
+    let options = config.options.clone().unwrap_or_default();
+
+    if let Some(schema) = &instance.metadata.config_schema {
+      validate_plugin_options(schema, &options)
+        .map_err(|e| ExecutorError::InvalidPluginOptions(config.name.clone(), e))?;
+    }
+
+    instance
+      .init(store, &options.to_string())
+      .await
+      .map_err(|e| ExecutorError::PluginInitError(config.name.clone(), e.to_string()))
+  }
+
   #[instrument(level = "debug", skip(self, cancel_token))]
   pub async fn run(self, cancel_token: CancellationToken) -> Result<()> {
+    let this = Arc::new(self);
     let mut handlers = vec![];
     info!("Starting executor...");
 
-    handlers.push(self.spawn_task_poller(cancel_token.clone()));
-    handlers.extend(self.spawn_workers(cancel_token));
+    // Tasks this id held when the executor last stopped aren't running anymore, whether it crashed or not
+    if let Err(e) = mutation::tasks::release_locks(&this.pool, &this.id).await {
+      error!("Failed to release the task locks of executor {}: {}", this.id, e);
+    }
+
+    match this.dispatch {
+      DispatchMode::Poll => handlers.push(this.spawn_task_poller(cancel_token.clone())),
+      DispatchMode::Push => info!("Task polling is disabled, tasks only run when triggered through the API"),
+    }
+    if this.workers.iter().any(|workers| workers.autoscale.is_some()) {
+      handlers.push(this.spawn_autoscaler(cancel_token.clone()));
+    }
+    handlers.push(this.spawn_health_checker(cancel_token.clone()));
+    handlers.push(this.spawn_lock_refresher(cancel_token.clone()));
+    this.spawn_workers(&cancel_token);
 
     info!("Executor started");
 
+    // Only the autoscaler starts workers after startup, so once it's stopped no more can be added
     futures::future::join_all(handlers).await;
+    this.worker_tasks.close();
+    this.worker_tasks.wait().await;
     info!("Executor system stopped");
 
     Ok(())
   }
 
-  fn spawn_task_poller(&self, cancel_token: CancellationToken) -> tokio::task::JoinHandle<()> {
-    let pool = self.pool.clone();
-    let tx = self.tx.clone();
+  fn spawn_task_poller(self: &Arc<Self>, cancel_token: CancellationToken) -> tokio::task::JoinHandle<()> {
+    let this = self.clone();
 
     tokio::spawn(async move {
       info!("Task poller started");
@@ -155,14 +1182,10 @@ impl ExecutorSystem {
           _ = sleep(QUERY_TIMEOUT) => {
             debug!("Start polling task from db...");
 
-            match mutation::tasks::get_tasks_to_run(&pool).await {
+            match mutation::tasks::get_tasks_to_run(&this.pool, &this.id).await {
               Ok(tasks) => {
                 debug!("Found {} tasks to run", tasks.len());
-                for task in tasks {
-                  if let Err(e) = tx.send(task).await {
-                    error!("Failed to send task to executor: {}", e);
-                  }
-                }
+                Self::dispatch_tasks(&this.pool, &this.workers, &this.in_flight, tasks).await;
               },
               Err(e) => error!("Failed to get tasks to run: {}", e),
             }
@@ -176,61 +1199,326 @@ impl ExecutorSystem {
     })
   }
 
-  fn spawn_workers(&self, cancel_token: CancellationToken) -> Vec<tokio::task::JoinHandle<()>> {
-    info!("Starting {} workers...", self.config.num_workers);
+  /// Checks the autoscaled pools on every poll interval, in push mode as well
+  fn spawn_autoscaler(self: &Arc<Self>, cancel_token: CancellationToken) -> tokio::task::JoinHandle<()> {
+    let this = self.clone();
 
-    let handlers = (0..self.config.num_workers)
-      .map(|id| self.spawn_worker(id, cancel_token.clone()))
-      .collect();
+    tokio::spawn(async move {
+      loop {
+        tokio::select! {
+          _ = sleep(QUERY_TIMEOUT) => this.scale_up(&cancel_token),
+          _ = cancel_token.cancelled() => break,
+        }
+      }
+    })
+  }
+
+  /// Renews the locks of the running tasks on each interval, so other executors sharing the database only
+  /// reclaim them once this one is gone
+  fn spawn_lock_refresher(self: &Arc<Self>, cancel_token: CancellationToken) -> tokio::task::JoinHandle<()> {
+    let this = self.clone();
+
+    tokio::spawn(async move {
+      loop {
+        tokio::select! {
+          _ = sleep(LOCK_REFRESH_INTERVAL) => {
+            if let Err(e) = mutation::tasks::refresh_locks(&this.pool, &this.id).await {
+              error!("Failed to refresh the task locks of executor {}: {}", this.id, e);
+            }
+          }
+          _ = cancel_token.cancelled() => break,
+        }
+      }
+    })
+  }
+
+  /// Calls `health` of every plugin exporting it on each interval, in push mode as well
+  fn spawn_health_checker(self: &Arc<Self>, cancel_token: CancellationToken) -> tokio::task::JoinHandle<()> {
+    let this = self.clone();
+    let interval = Duration::from_secs(self.health_check.interval_secs);
+
+    tokio::spawn(async move {
+      loop {
+        tokio::select! {
+          _ = sleep(interval) => Self::check_plugins_health(&this.plugins).await,
+          _ = cancel_token.cancelled() => break,
+        }
+      }
+    })
+  }
+
+  /// Checks the plugins one after the other, a plugin busy with a task is checked once the task is done
+  ///
+  /// There's no timeout of its own, abandoning a call halfway would leave the instance unusable for the tasks,
+  /// so a check waiting on an upstream is bounded by the plugin HTTP timeouts.
+  async fn check_plugins_health(plugins: &PluginRegistry) {
+    for (name, plugin) in plugins.all() {
+      if !plugin.instance.reports_health() {
+        continue;
+      }
+
+      let result = {
+        let mut store = plugin.store.lock().await;
+        let result = plugin.instance.health(&mut store).await;
+        store.data_mut().drain_stdio();
+        result
+      };
+
+      plugin.record_health(&name, result.err().map(|e| e.to_string()));
+    }
+  }
+
+  /// Hands polled tasks to their worker pools without waiting for room in the queues
+  ///
+  /// A task that doesn't fit is released back to `new` so the next poll picks it up again, this keeps the
+  /// poller responsive to shutdown and to stale lock recovery while the workers are behind.
+  async fn dispatch_tasks(pool: &SqlitePool, workers: &WorkerPools, in_flight: &InFlight, tasks: Vec<Task>) {
+    let mut released = 0;
+
+    for task in tasks {
+      let id = task.id;
+
+      if !in_flight.insert(id) {
+        debug!("Task {} is already in flight, skipping", id);
+        continue;
+      }
+
+      match workers.sender(&task.r#type).try_send(task) {
+        Ok(()) => continue,
+        Err(TrySendError::Full(_)) => released += 1,
+        Err(TrySendError::Closed(_)) => error!("Failed to send task {} to executor: worker queue is closed", id),
+      }
+
+      in_flight.remove(&id);
+
+      if let Err(e) = mutation::tasks::release_task(pool, id).await {
+        error!("Failed to release task {}: {}", id, e);
+      }
+    }
+
+    if released > 0 {
+      warn!(
+        "Worker queues are full, released {} tasks until the next poll",
+        released
+      );
+    }
+  }
+
+  fn spawn_workers(&self, cancel_token: &CancellationToken) {
+    for workers in self.workers.iter() {
+      let size = workers.size.load(Ordering::Relaxed);
+      info!("Starting {} workers for the {} pool...", size, workers.name);
+
+      for _ in 0..size {
+        self.spawn_worker(workers.clone(), cancel_token.clone());
+      }
+    }
 
     info!("Workers started");
+  }
+
+  /// Starts extra workers in the autoscaled pools whose queue is backing up
+  fn scale_up(&self, cancel_token: &CancellationToken) {
+    for workers in self.workers.iter() {
+      let extra = workers.reserve_scale_up();
+      if extra == 0 {
+        continue;
+      }
 
-    handlers
+      info!(
+        "Queue of the {} pool holds {} tasks, starting {} more workers",
+        workers.name,
+        workers.queue_depth(),
+        extra
+      );
+
+      for _ in 0..extra {
+        self.spawn_worker(workers.clone(), cancel_token.clone());
+      }
+    }
   }
 
-  #[instrument(level = "debug", skip(self, cancel_token))]
-  fn spawn_worker(&self, id: u32, cancel_token: CancellationToken) -> tokio::task::JoinHandle<()> {
-    let rx = Arc::clone(&self.rx);
+  #[instrument(level = "debug", skip(self, workers, cancel_token))]
+  fn spawn_worker(&self, workers: Arc<WorkerPool>, cancel_token: CancellationToken) {
+    let id = self.next_worker_id.fetch_add(1, Ordering::Relaxed);
     let plugins = self.plugins.clone();
     let pool = self.pool.clone();
+    let busy_workers = self.busy_workers.clone();
+    let project_limits = self.project_limits.clone();
+    let in_flight = self.in_flight.clone();
+    let idle_timeout = workers.idle_timeout();
+    let defer_unhealthy = self.health_check.defer_delay();
 
-    tokio::spawn(async move {
+    self.worker_tasks.spawn(async move {
       loop {
-        let mut rx = rx.lock().await;
-
-        tokio::select! {
-          Some(task) = rx.recv() => {
-            debug!("Worker {} received task {:?}", id, task);
+        // Only hold the receiver while waiting for a task so other workers can pick up the next one
+        let next = async { workers.rx.lock().await.recv().await };
 
-            if let Err(e) = Self::process_task(&pool, &plugins, task).await {
-              error!("Worker {} failed to process task: {}", id, e);
+        let task = tokio::select! {
+          Some(task) = next => task,
+          _ = sleep(idle_timeout.unwrap_or_default()), if idle_timeout.is_some() => {
+            if workers.try_retire() {
+              info!("Worker {} was idle, leaving the {} pool", id, workers.name);
+              break;
             }
+            continue;
           }
           _ = cancel_token.cancelled() => {
             info!("Worker {} stopped", id);
             break;
           }
+        };
+
+        debug!("Worker {} received task {:?}", id, task);
+
+        if let Some(delay) = defer_unhealthy.filter(|_| plugins.is_unhealthy(&task)) {
+          debug!(
+            "Plugin {} is unhealthy, deferring task {} by {:?}",
+            task.r#type, task.id, delay
+          );
+
+          let start_at = Utc::now().timestamp() + delay.as_secs() as i64;
+          if let Err(e) = mutation::tasks::defer_task(&pool, task.id, start_at).await {
+            error!("Worker {} failed to defer task {}: {}", id, task.id, e);
+          }
+          in_flight.remove(&task.id);
+          continue;
         }
+
+        let _permit = match project_limits.try_acquire(&task.project) {
+          ProjectSlot::Unlimited => None,
+          ProjectSlot::Acquired(permit) => Some(permit),
+          ProjectSlot::Full => {
+            // Hand the task back to the poller instead of blocking this worker, so tasks of
+            // other projects keep running
+            debug!(
+              "Project {} is at its concurrency limit, releasing task {}",
+              task.project.code, task.id
+            );
+
+            if let Err(e) = mutation::tasks::release_task(&pool, task.id).await {
+              error!("Worker {} failed to release task {}: {}", id, task.id, e);
+            }
+            in_flight.remove(&task.id);
+            continue;
+          },
+        };
+
+        let task_id = task.id;
+
+        busy_workers.fetch_add(1, Ordering::Relaxed);
+        // A panic while processing fails the task instead of taking the worker down with it
+        match AssertUnwindSafe(Self::process_task(&pool, &plugins, task))
+          .catch_unwind()
+          .await
+        {
+          Ok(Ok(())) => {},
+          Ok(Err(e)) => error!("Worker {} failed to process task: {}", id, e),
+          Err(panic) => {
+            error!(
+              "Worker {} panicked processing task {}: {}",
+              id,
+              task_id,
+              panic_message(panic.as_ref())
+            );
+
+            if let Err(e) = mutation::tasks::failed_task(&pool, task_id).await {
+              error!("Worker {} failed to mark task {} as failed: {}", id, task_id, e);
+            }
+          },
+        }
+        busy_workers.fetch_sub(1, Ordering::Relaxed);
+        in_flight.remove(&task_id);
       }
-    })
+    });
   }
 
   #[instrument(level = "debug", skip(pool, plugins))]
-  async fn process_task(pool: &SqlitePool, plugins: &HashMap<String, Plugin>, task: Task) -> Result<()> {
-    let execute_params = ExecuteParams {
-      task_id: task.id.to_string(),
-      options: serde_json::to_value(&task.options)?,
+  async fn process_task(pool: &SqlitePool, plugins: &PluginRegistry, task: Task) -> Result<()> {
+    let started_at = Utc::now();
+    let result = Self::execute_task(pool, plugins, &task).await;
+
+    if let Some(url) = webhook_url(&task) {
+      let payload = webhook_payload(&task, started_at, &result);
+
+      // Delivery runs in the background so a slow or failing endpoint never holds up the worker
+      // or changes the outcome of the task itself
+      tokio::spawn(async move {
+        if let Err(e) = webhook::send(&url, &payload).await {
+          error!("Failed to deliver task webhook to {}: {}", url, e);
+        }
+      });
+    }
+
+    result
+  }
+
+  async fn execute_task(pool: &SqlitePool, plugins: &PluginRegistry, task: &Task) -> Result<()> {
+    // Every run streams a fresh output, what the previous run of a recurring task wrote is dropped
+    if let Err(e) = outputs::remove(task.id).await {
+      warn!("Failed to remove the previous output of task {}: {}", task.id, e);
+    }
+    logs::start_run(task.id);
+
+    let requirement = task
+      .plugin_version
+      .as_deref()
+      .map(VersionReq::parse)
+      .transpose()
+      .context("Invalid plugin version requirement");
+
+    let result: Result<usize> = match (resolve_options(&task.options, &task.project.options), requirement) {
+      (Ok(options), Ok(requirement)) => {
+        let execute_params = ExecuteParams {
+          task_id: task.id.to_string(),
+          options,
+        };
+        let context = task_context(task);
+
+        // Call process_action instead of directly working with plugin
+        Self::process_action(
+          pool,
+          plugins,
+          &context,
+          task.r#type.clone(),
+          requirement.as_ref(),
+          &execute_params,
+        )
+        .await
+      },
+      (Err(e), _) => Err(e.into()),
+      (_, Err(e)) => Err(e),
     };
 
-    // Call process_action instead of directly working with plugin
-    match Self::process_action(pool, plugins, task.r#type.clone(), &execute_params).await {
-      Ok(_) => {
-        if task.schedule.is_some() {
-          let start_at = calculate_next_run(&task).context("Failed to calculate next run time")?;
+    match result {
+      Ok(failed_results) => {
+        let failed_results = failed_results as i64;
+        if failed_results != task.failed_results {
+          mutation::tasks::set_failed_results(pool, task.id, failed_results)
+            .await
+            .context("Failed to record the failed results of the task")?;
+        }
 
-          mutation::tasks::schedule_task(pool, task.id, start_at)
+        if task.retries > 0 {
+          mutation::tasks::reset_retries(pool, task.id)
             .await
-            .context("Failed to schedule next task run")?;
+            .context("Failed to reset task retries")?;
+        }
+
+        if task.schedule.is_some() {
+          let start_at = calculate_next_run(task).context("Failed to calculate next run time")?;
+
+          if task.end_at.is_some_and(|end_at| start_at > end_at) {
+            debug!("Task {} reached its end_at, finishing instead of rescheduling", task.id);
+
+            mutation::tasks::completed_task(pool, task.id)
+              .await
+              .context("Failed to mark task as completed")?;
+          } else {
+            mutation::tasks::schedule_task(pool, task.id, start_at)
+              .await
+              .context("Failed to schedule next task run")?;
+          }
         } else {
           mutation::tasks::completed_task(pool, task.id)
             .await
@@ -248,84 +1536,285 @@ impl ExecutorSystem {
     }
   }
 
+  /// Runs `process` of the plugin, collecting what it logs and streams for the task
+  ///
+  /// # Returns
+  /// The results of the plugin and the number of tasks it enqueued meanwhile, at most `max_results`
+  async fn invoke_plugin(
+    pool: &SqlitePool,
+    plugins: &PluginRegistry,
+    context: &TaskContext,
+    plugin: &Plugin,
+    action: &ExecuteParams,
+  ) -> Result<(Vec<PluginResult>, usize)> {
+    let mut store = plugin.store.lock().await;
+    let action_str = serde_json::to_string(action).context("Failed to serialize action params")?;
+
+    let task_id = Uuid::parse_str(&action.task_id).ok();
+
+    store.data_mut().task_context = Some(context.clone());
+    store.data_mut().task_output = task_id.map(outputs::output_path);
+    store.data_mut().task_queue = Some(Arc::new(DatabaseTaskQueue { pool: pool.clone() }));
+    store.data_mut().task_enqueued = 0;
+    store.data_mut().max_enqueued = plugins.max_results;
+    store.data_mut().task_logs = Some(VecDeque::new());
+    let results = {
+      let _permit = plugins.acquire_invocation().await;
+      plugin.instance.process(&mut store, &action_str).await
+    };
+    store.data_mut().drain_stdio();
+    store.data_mut().task_context = None;
+    store.data_mut().task_output = None;
+    store.data_mut().task_queue = None;
+    let enqueued = std::mem::take(&mut store.data_mut().task_enqueued);
+    let lines = store.data_mut().task_logs.take().unwrap_or_default();
+
+    if let Some(task_id) = task_id {
+      logs::append(task_id, lines.into_iter().map(to_task_log));
+    }
+
+    Ok((results?, enqueued))
+  }
+
+  /// Runs the action on the plugin of `action_type` matching `requirement`, then the actions it returns on
+  /// whatever version of their plugin is loaded, all of them for the task of `context`
+  ///
+  /// # Returns
+  /// The number of results dropped under [`SubResultPolicy::Lenient`], those of follow-up actions included
   fn process_action<'a>(
     pool: &'a SqlitePool,
-    plugins: &'a HashMap<String, Plugin>,
+    plugins: &'a PluginRegistry,
+    context: &'a TaskContext,
     action_type: String,
+    requirement: Option<&'a VersionReq>,
     action: &'a ExecuteParams,
-  ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+  ) -> Pin<Box<dyn Future<Output = Result<usize>> + Send + 'a>> {
     Box::pin(async move {
-      let plugin = plugins
-        .get(&action_type)
-        .ok_or_else(|| ExecutorError::UnknownPluginError(action_type))?;
+      let plugin = plugins.get(&action_type, requirement)?;
+      let cache_name = cache_name(&action_type, &plugin);
 
-      let results = {
-        let mut store = plugin.store.lock().await;
-        let action_str = serde_json::to_string(action).context("Failed to serialize action params")?;
+      let cached = plugin
+        .cache_ttl
+        .and_then(|_| plugins.cache.get(&cache_name, &action.options));
 
-        plugin.instance.process(&mut store, &action_str).await?
+      let results = match cached {
+        Some(results) => {
+          debug!("Using cached results of {} for task {}", action_type, action.task_id);
+          results
+        },
+        None => {
+          let (results, enqueued) = Self::invoke_plugin(pool, plugins, context, &plugin, action).await?;
+          // Enqueued tasks are already saved, they still count so a plugin can't get around the limit with them
+          let produced = results.len() + enqueued;
+          if produced > plugins.max_results {
+            error!(
+              "Plugin {} returned {} results and enqueued {} tasks for task {}, more than the {} allowed",
+              action_type,
+              results.len(),
+              enqueued,
+              action.task_id,
+              plugins.max_results
+            );
+            return Err(ExecutorError::TooManyPluginResults(action_type, produced, plugins.max_results).into());
+          }
+          if let Some(ttl) = plugin.cache_ttl {
+            plugins.cache.insert(&cache_name, &action.options, &results, ttl);
+          }
+          results
+        },
       };
 
+      let mut sub_results = SubResults::new(plugin.sub_results, results.len());
+
       for result in results {
-        match result {
-          PluginResult::Action(action) => {
-            let params: ExecuteParams =
-              serde_json::from_str(&action.payload).context("Failed to deserialize action payload")?;
-            Self::process_action(pool, plugins, action.name, &params).await?;
-          },
-          PluginResult::Task(task) => {
-            let projects = query::projects::list_all(pool).await?;
-            let projects = projects
-              .into_iter()
-              .map(|p| (p.code.clone(), p))
-              .collect::<HashMap<String, ProjectRow>>();
-            let project = projects
-              .get(&task.project_code)
-              .context(format!("Project {} not found", task.project_code))?;
-
-            let naive = NaiveDateTime::from_timestamp(task.external_modified_at as i64, 0);
-            let external_modified_at: DateTime<Utc> = DateTime::<Utc>::from_utc(naive, Utc);
-
-            let task_params = mutation::tasks::CreateTaskParams {
-              name: task.name,
-              r#type: task.kind,
-              schedule: None,
-              project_id: project.id,
-              external_id: Some(task.external_id),
-              external_modified_at: Some(external_modified_at.to_utc()),
-              start_at: task.start_at as i32,
-              options: serde_json::to_value(task.options).context("Failed to parse task options")?,
-            };
-
-            mutation::tasks::create(pool, task_params).await?;
-          },
+        sub_results.record(Self::process_result(pool, plugins, context, result).await)?;
+      }
+
+      if !sub_results.failures.is_empty() {
+        warn!(
+          "{} of {} results of {} failed for task {}",
+          sub_results.failures.len(),
+          sub_results.total,
+          action_type,
+          action.task_id
+        );
+        if let Ok(task_id) = Uuid::parse_str(&action.task_id) {
+          logs::append(
+            task_id,
+            sub_results.failures.iter().map(|e| sub_result_log(&action_type, e)),
+          );
         }
       }
 
-      Ok(())
+      sub_results.finish(&action_type)
     })
   }
+
+  /// Saves a child task returned by a plugin or runs a follow-up action, returning the results the action dropped
+  async fn process_result(
+    pool: &SqlitePool,
+    plugins: &PluginRegistry,
+    context: &TaskContext,
+    result: PluginResult,
+  ) -> Result<usize> {
+    match result {
+      PluginResult::Action(action) => {
+        let params: ExecuteParams =
+          serde_json::from_str(&action.payload).context("Failed to deserialize action payload")?;
+        Self::process_action(pool, plugins, context, action.name, None, &params).await
+      },
+      PluginResult::Task(task) => {
+        task_queue::save(pool, task.into()).await?;
+        Ok(0)
+      },
+    }
+  }
+}
+
+/// Checks plugin options against a JSON Schema, returning every violation in a single message
+fn validate_plugin_options(schema: &str, options: &Value) -> Result<(), String> {
+  let schema: Value = serde_json::from_str(schema).map_err(|e| format!("plugin declares an invalid schema: {}", e))?;
+  let validator =
+    jsonschema::validator_for(&schema).map_err(|e| format!("plugin declares an invalid schema: {}", e))?;
+
+  let errors: Vec<String> = validator
+    .iter_errors(options)
+    .map(|e| format!("{} at `{}`", e, e.instance_path))
+    .collect();
+
+  if errors.is_empty() {
+    Ok(())
+  } else {
+    Err(errors.join("; "))
+  }
+}
+
+/// What plugins read through `get-task-context` while processing the task
+fn task_context(task: &Task) -> TaskContext {
+  TaskContext {
+    id: task.id.to_string(),
+    kind: task.r#type.clone(),
+    project_code: task.project.code.clone(),
+    schedule: task.schedule.clone(),
+    retries: u32::try_from(task.retries).unwrap_or_default(),
+  }
+}
+
+/// Returns the `webhook_url` from the task options, falling back to the project options
+fn webhook_url(task: &Task) -> Option<String> {
+  [&task.options, &task.project.options]
+    .into_iter()
+    .find_map(|options| options.get("webhook_url").and_then(Value::as_str))
+    .filter(|url| !url.is_empty())
+    .map(ToOwned::to_owned)
+}
+
+fn webhook_payload(task: &Task, started_at: DateTime<Utc>, result: &Result<()>) -> Value {
+  let finished_at = Utc::now();
+  let status = match result {
+    Ok(_) => TaskStatus::Finished,
+    Err(_) => TaskStatus::Failed,
+  };
+
+  json!({
+    "task_id": task.id,
+    "name": task.name,
+    "type": task.r#type,
+    "project": task.project.code,
+    "status": status.to_string(),
+    "started_at": started_at,
+    "finished_at": finished_at,
+    "duration_ms": (finished_at - started_at).num_milliseconds(),
+    "error": result.as_ref().err().map(|e| format!("{:#}", e)),
+  })
+}
+
+/// Name the results of a plugin are cached under, so versions loaded side by side don't share results
+fn cache_name(name: &str, plugin: &Plugin) -> String {
+  format!("{}@{}", name, plugin.instance.metadata.version)
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+  panic
+    .downcast_ref::<&str>()
+    .copied()
+    .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+    .unwrap_or("unknown panic")
+}
+
+/// `EXECUTOR_ID` from the environment, or a random id when several executors don't need to be told apart
+fn executor_id() -> String {
+  std::env::var("EXECUTOR_ID")
+    .ok()
+    .filter(|id| !id.trim().is_empty())
+    .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+fn plugin_metadata(plugin: &Plugin) -> PluginMetadata {
+  let metadata = &plugin.instance.metadata;
+
+  PluginMetadata {
+    name: metadata.name.clone(),
+    version: metadata.version.clone(),
+    author: metadata.author.clone(),
+    description: metadata.description.clone(),
+  }
+}
+
+/// Task log line of a result dropped under [`SubResultPolicy::Lenient`]
+fn sub_result_log(action_type: &str, error: &anyhow::Error) -> TaskLog {
+  TaskLog {
+    at: Utc::now(),
+    level: "warn".to_string(),
+    context: action_type.to_string(),
+    message: format!("Result skipped: {:#}", error),
+  }
+}
+
+fn to_task_log(line: TaskLogLine) -> TaskLog {
+  TaskLog {
+    at: line.at.into(),
+    level: line.level.to_string(),
+    context: line.context,
+    message: line.message,
+  }
 }
 
 #[instrument(level = "debug")]
-fn calculate_next_run(task: &Task) -> Result<i32> {
-  let start_at =
-    DateTime::from_timestamp(task.start_at as i64, 0).ok_or_else(|| ExecutorError::InvalidTimestampError)?;
+fn calculate_next_run(task: &Task) -> Result<i64> {
+  let start_at = DateTime::from_timestamp(task.start_at, 0).ok_or_else(|| ExecutorError::InvalidTimestampError)?;
+  let now = Utc::now();
 
   let next_run = if let Some(schedule) = &task.schedule {
     if schedule.starts_with("@every") {
-      calculate_interval_next_run(schedule, start_at)?
+      let anchor = task.schedule_anchor.unwrap_or(task.start_at);
+      calculate_interval_next_run(schedule, anchor, start_at.timestamp(), now, task.misfire_policy)?
     } else {
-      calculate_cron_next_run(schedule, start_at)?
+      calculate_cron_next_run(schedule, start_at, now, task.misfire_policy)?
     }
   } else {
-    start_at.timestamp() as i32
+    start_at.timestamp()
   };
 
-  Ok(next_run.max(Utc::now().timestamp() as i32))
+  // Catch-up keeps missed occurrences in the past so the poller runs them one after another,
+  // the other policies never schedule a run before now
+  let next_run = match task.misfire_policy {
+    MisfirePolicy::CatchUp => next_run,
+    MisfirePolicy::Skip | MisfirePolicy::FireOnce => next_run.max(now.timestamp()),
+  };
+
+  Ok(next_run)
 }
 
-fn calculate_interval_next_run(schedule: &str, start_at: DateTime<Utc>) -> Result<i32> {
+/// Picks the next `@every` occurrence after the run scheduled at `start_at`, occurrences being counted from
+/// `anchor` so runs that started late or on demand don't shift the ones after them
+fn calculate_interval_next_run(
+  schedule: &str,
+  anchor: i64,
+  start_at: i64,
+  now: DateTime<Utc>,
+  misfire_policy: MisfirePolicy,
+) -> Result<i64> {
   // Extract interval duration from schedule string
   let duration_str = schedule
     .strip_prefix("@every ")
@@ -337,9 +1826,6 @@ fn calculate_interval_next_run(schedule: &str, start_at: DateTime<Utc>) -> Resul
   // Convert to chrono::Duration
   let interval = chrono::Duration::from_std(std_duration).map_err(|_| ExecutorError::DurationConvertError)?;
 
-  // Calculate timestamps
-  let current_time = Utc::now().timestamp();
-  let start_time = start_at.timestamp();
   let interval_seconds = interval.num_seconds();
 
   // Ensure interval_seconds is not zero to avoid division by zero
@@ -347,23 +1833,164 @@ fn calculate_interval_next_run(schedule: &str, start_at: DateTime<Utc>) -> Resul
     return Err(anyhow!("Interval duration cannot be zero"));
   }
 
-  // Calculate number of intervals passed since start
-  let intervals_passed = (current_time - start_time) / interval_seconds + 1;
+  // Occurrence following the one that just ran, and the latest one already due
+  let next = (start_at - anchor).div_euclid(interval_seconds) + 1;
+  let due = (now.timestamp() - anchor).div_euclid(interval_seconds);
 
-  // Calculate next run timestamp
-  let next_run = start_time + (intervals_passed * interval_seconds);
+  let occurrence = match misfire_policy {
+    MisfirePolicy::Skip => next.max(due + 1),
+    MisfirePolicy::FireOnce => next,
+    MisfirePolicy::CatchUp => next.max(due - MAX_CATCH_UP_RUNS as i64 + 1),
+  };
 
-  // Convert to i32, checking for overflow
-  next_run.try_into().context("Next run timestamp exceeds i32 range")
+  Ok(anchor + occurrence * interval_seconds)
 }
 
-fn calculate_cron_next_run(schedule: &str, start_at: DateTime<Utc>) -> Result<i32> {
+fn calculate_cron_next_run(
+  schedule: &str,
+  start_at: DateTime<Utc>,
+  now: DateTime<Utc>,
+  misfire_policy: MisfirePolicy,
+) -> Result<i64> {
   let schedule = Schedule::from_str(schedule).map_err(|e| ExecutorError::ParseCronError(e.to_string()))?;
 
-  let next_run = schedule
-    .after(&start_at)
-    .next()
-    .ok_or_else(|| ExecutorError::CalculateCronScheduleError)?;
+  // Cron fields are evaluated in the configured timezone, the occurrences are the same instants either way
+  let tz = timezone::default_tz();
+  let start_at = start_at.with_timezone(&tz);
+  let now = now.with_timezone(&tz);
 
-  Ok(next_run.timestamp() as i32)
+  let next_run = match misfire_policy {
+    MisfirePolicy::Skip => schedule.after(&now).next(),
+    MisfirePolicy::FireOnce => schedule.after(&start_at).next(),
+    // Walk back from now to find the oldest of the most recent missed occurrences
+    MisfirePolicy::CatchUp => schedule
+      .after(&now)
+      .rev()
+      .take_while(|occurrence| *occurrence > start_at)
+      .take(MAX_CATCH_UP_RUNS)
+      .last()
+      .or_else(|| schedule.after(&start_at).next()),
+  }
+  .ok_or_else(|| ExecutorError::CalculateCronScheduleError)?;
+
+  Ok(next_run.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ANCHOR: i64 = 1_700_000_000;
+
+  fn at(timestamp: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(timestamp, 0).unwrap()
+  }
+
+  #[test]
+  fn test_interval_schedule_does_not_drift() {
+    for policy in [MisfirePolicy::Skip, MisfirePolicy::FireOnce, MisfirePolicy::CatchUp] {
+      let mut start_at = ANCHOR;
+
+      // Every run finishes a few seconds late, the next one still lands on the minute
+      for run in 1..=1000 {
+        let now = at(start_at + 7);
+        start_at = calculate_interval_next_run("@every 1m", ANCHOR, start_at, now, policy).unwrap();
+        assert_eq!(start_at, ANCHOR + run * 60, "{policy:?} drifted at run {run}");
+      }
+    }
+  }
+
+  #[test]
+  fn test_plugin_deny_egress_config() {
+    let config: PluginConfig = serde_json::from_value(serde_json::json!({
+      "name": "github",
+      "path": "github.wasm",
+      "deny_egress": true,
+    }))
+    .unwrap();
+    assert!(config.deny_egress);
+
+    let config: PluginConfig =
+      serde_json::from_value(serde_json::json!({ "name": "github", "path": "github.wasm" })).unwrap();
+    assert!(!config.deny_egress);
+  }
+
+  #[test]
+  fn test_strict_sub_results_stop_at_first_failure() {
+    let mut sub_results = SubResults::new(SubResultPolicy::Strict, 3);
+    assert!(sub_results.record(Ok(0)).is_ok());
+    assert!(sub_results
+      .record(Err(anyhow::anyhow!("Project NOPE not found")))
+      .is_err());
+
+    let sub_results = SubResults::new(SubResultPolicy::Strict, 2);
+    assert_eq!(sub_results.finish("github").unwrap(), 0);
+  }
+
+  #[test]
+  fn test_lenient_sub_results_count_failures() {
+    // One of three results failed and a follow-up action dropped two of its own
+    let mut sub_results = SubResults::new(SubResultPolicy::Lenient, 3);
+    assert!(sub_results.record(Ok(0)).is_ok());
+    assert!(sub_results
+      .record(Err(anyhow::anyhow!("Project NOPE not found")))
+      .is_ok());
+    assert!(sub_results.record(Ok(2)).is_ok());
+    assert_eq!(sub_results.finish("github").unwrap(), 3);
+
+    // Every result failed, the task fails with the first error
+    let mut sub_results = SubResults::new(SubResultPolicy::Lenient, 2);
+    assert!(sub_results
+      .record(Err(anyhow::anyhow!("Project NOPE not found")))
+      .is_ok());
+    assert!(sub_results.record(Err(anyhow::anyhow!("Invalid task"))).is_ok());
+    assert_eq!(
+      sub_results.finish("github").unwrap_err().to_string(),
+      "All 2 results of plugin github failed, the first with: Project NOPE not found"
+    );
+  }
+
+  #[test]
+  fn test_autoscale_from_zero_workers() {
+    let autoscale = Autoscale {
+      min_workers: 0,
+      max_workers: 4,
+      scale_up_depth: 10,
+      idle_cooldown_secs: 60,
+    };
+
+    // An empty pool starts a worker for the first queued task, a running one waits for the threshold
+    assert_eq!(autoscale.scaled_size(0, 0), None);
+    assert_eq!(autoscale.scaled_size(0, 1), Some(1));
+    assert_eq!(autoscale.scaled_size(1, 10), None);
+    assert_eq!(autoscale.scaled_size(1, 12), Some(3));
+    assert_eq!(autoscale.scaled_size(0, 100), Some(4));
+    assert_eq!(autoscale.scaled_size(4, 100), None);
+  }
+
+  #[test]
+  fn test_interval_schedule_realigns_after_off_schedule_runs() {
+    // A task run on demand halfway through an interval goes back to the anchor's cadence
+    let next = calculate_interval_next_run(
+      "@every 1h",
+      ANCHOR,
+      ANCHOR + 5400,
+      at(ANCHOR + 5410),
+      MisfirePolicy::Skip,
+    );
+    assert_eq!(next.unwrap(), ANCHOR + 7200);
+
+    // Run before the anchor is reached, the anchor stays the first occurrence
+    let next = calculate_interval_next_run("@every 1h", ANCHOR, ANCHOR - 600, at(ANCHOR - 590), MisfirePolicy::Skip);
+    assert_eq!(next.unwrap(), ANCHOR);
+
+    // After downtime skip jumps to the next aligned occurrence and catch-up replays the last ones in order
+    let now = at(ANCHOR + 20 * 3600 + 60);
+    let next = calculate_interval_next_run("@every 1h", ANCHOR, ANCHOR, now, MisfirePolicy::Skip);
+    assert_eq!(next.unwrap(), ANCHOR + 21 * 3600);
+    let next = calculate_interval_next_run("@every 1h", ANCHOR, ANCHOR, now, MisfirePolicy::CatchUp);
+    assert_eq!(next.unwrap(), ANCHOR + 11 * 3600);
+    let next = calculate_interval_next_run("@every 1h", ANCHOR, ANCHOR, now, MisfirePolicy::FireOnce);
+    assert_eq!(next.unwrap(), ANCHOR + 3600);
+  }
 }
@@ -0,0 +1,94 @@
+use serde_json::Value;
+
+use crate::error::{ExecutorError, ExecutorResult};
+
+/// Expands `${VAR}` references in every string of a config value from the process environment,
+/// `$$` is kept as a literal `$`
+///
+/// Only string values are expanded, after the JSON is parsed, so a variable can't change the
+/// structure of the config whatever it contains.
+pub(crate) fn interpolate_env(value: &mut Value) -> ExecutorResult<()> {
+  interpolate(value, &|name| std::env::var(name).ok())
+}
+
+fn interpolate(value: &mut Value, lookup: &impl Fn(&str) -> Option<String>) -> ExecutorResult<()> {
+  match value {
+    Value::String(s) => *s = expand(s, lookup)?,
+    Value::Array(items) => {
+      for item in items {
+        interpolate(item, lookup)?;
+      }
+    },
+    Value::Object(map) => {
+      for item in map.values_mut() {
+        interpolate(item, lookup)?;
+      }
+    },
+    _ => {},
+  }
+
+  Ok(())
+}
+
+fn expand(input: &str, lookup: &impl Fn(&str) -> Option<String>) -> ExecutorResult<String> {
+  let mut output = String::with_capacity(input.len());
+  let mut rest = input;
+
+  while let Some(start) = rest.find('$') {
+    output.push_str(&rest[..start]);
+    rest = &rest[start + 1..];
+
+    if let Some(after) = rest.strip_prefix('$') {
+      output.push('$');
+      rest = after;
+    } else if let Some(after) = rest.strip_prefix('{') {
+      let end = after
+        .find('}')
+        .ok_or_else(|| ExecutorError::ConfigReadError(format!("unterminated variable reference in `{}`", input)))?;
+      let name = &after[..end];
+
+      output.push_str(&lookup(name).ok_or_else(|| ExecutorError::UndefinedConfigVariable(name.to_string()))?);
+      rest = &after[end + 1..];
+    } else {
+      output.push('$');
+    }
+  }
+
+  output.push_str(rest);
+
+  Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn lookup(name: &str) -> Option<String> {
+    (name == "SLACK_TOKEN").then(|| "xoxb-\"secret\"".to_string())
+  }
+
+  #[test]
+  fn test_interpolate() {
+    let mut config = json!({
+      "num_workers": 4,
+      "plugins": [{ "options": { "token": "Bearer ${SLACK_TOKEN}", "price": "$$5", "raw": "a $ b" } }]
+    });
+    interpolate(&mut config, &lookup).unwrap();
+
+    assert_eq!(
+      config,
+      json!({
+        "num_workers": 4,
+        "plugins": [{ "options": { "token": "Bearer xoxb-\"secret\"", "price": "$5", "raw": "a $ b" } }]
+      })
+    );
+
+    assert!(matches!(
+      interpolate(&mut json!("${MISSING}"), &lookup),
+      Err(ExecutorError::UndefinedConfigVariable(name)) if name == "MISSING"
+    ));
+    assert!(interpolate(&mut json!("${SLACK_TOKEN"), &lookup).is_err());
+  }
+}
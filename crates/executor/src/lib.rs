@@ -1,2 +1,7 @@
+mod cache;
 pub mod error;
 pub mod executor;
+mod interpolate;
+mod limits;
+mod presets;
+mod task_queue;
@@ -0,0 +1,188 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+use octabot_api::entities::project::ProjectRow;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+/// Project option holding the maximum number of the project's tasks that may run at once
+const MAX_CONCURRENT_OPTION: &str = "max_concurrent";
+
+pub enum ProjectSlot {
+  /// The project has no concurrency limit
+  Unlimited,
+  /// A slot was taken, it is freed when the permit is dropped
+  Acquired(OwnedSemaphorePermit),
+  /// The project already runs as many tasks as it is allowed to
+  Full,
+}
+
+/// Per-project semaphores enforcing the `max_concurrent` project option, so one project's bursts
+/// can't take over every worker
+#[derive(Default)]
+pub struct ProjectLimits {
+  semaphores: Mutex<HashMap<Uuid, ProjectSemaphore>>,
+}
+
+struct ProjectSemaphore {
+  limit: usize,
+  /// Permits to forget as they're released, the limit was lowered while they were held
+  deficit: usize,
+  semaphore: Arc<Semaphore>,
+}
+
+impl ProjectSemaphore {
+  fn new(limit: usize) -> Self {
+    Self {
+      limit,
+      deficit: 0,
+      semaphore: Arc::new(Semaphore::new(limit)),
+    }
+  }
+
+  /// Resizes the semaphore in place, so tasks holding permits of the old limit still count against the new one
+  fn set_limit(&mut self, limit: usize) {
+    if limit > self.limit {
+      let added = limit - self.limit;
+      let repaid = added.min(self.deficit);
+      self.deficit -= repaid;
+      self.semaphore.add_permits(added - repaid);
+    } else {
+      self.deficit += self.limit - limit;
+    }
+    self.limit = limit;
+  }
+
+  fn forget_released(&mut self) {
+    if self.deficit > 0 {
+      self.deficit -= self.semaphore.forget_permits(self.deficit);
+    }
+  }
+}
+
+impl ProjectLimits {
+  /// Tries to take a slot for a task of the project without waiting
+  pub fn try_acquire(&self, project: &ProjectRow) -> ProjectSlot {
+    let Some(limit) = max_concurrent(project) else {
+      return ProjectSlot::Unlimited;
+    };
+
+    let semaphore = {
+      let mut semaphores = self.semaphores.lock().expect("project limits lock poisoned");
+      let entry = semaphores
+        .entry(project.id)
+        .or_insert_with(|| ProjectSemaphore::new(limit));
+
+      if entry.limit != limit {
+        entry.set_limit(limit);
+      }
+      entry.forget_released();
+
+      entry.semaphore.clone()
+    };
+
+    match semaphore.try_acquire_owned() {
+      Ok(permit) => ProjectSlot::Acquired(permit),
+      Err(_) => ProjectSlot::Full,
+    }
+  }
+}
+
+fn max_concurrent(project: &ProjectRow) -> Option<usize> {
+  project
+    .options
+    .get(MAX_CONCURRENT_OPTION)
+    .and_then(|value| value.as_u64())
+    .filter(|limit| *limit > 0)
+    .map(|limit| limit as usize)
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::Utc;
+  use serde_json::json;
+
+  use super::*;
+
+  fn project(id: Uuid, max_concurrent: Option<u64>) -> ProjectRow {
+    ProjectRow {
+      id,
+      name: "platform".to_string(),
+      code: "PPF".to_string(),
+      options: max_concurrent.map_or_else(|| json!({}), |limit| json!({ MAX_CONCURRENT_OPTION: limit })),
+      owner_id: Uuid::new_v4(),
+      created_by: None,
+      created_at: Utc::now(),
+      updated_at: Utc::now(),
+      version: 1,
+    }
+  }
+
+  fn acquire(limits: &ProjectLimits, project: &ProjectRow, count: usize) -> Vec<OwnedSemaphorePermit> {
+    (0..count)
+      .map(|_| match limits.try_acquire(project) {
+        ProjectSlot::Acquired(permit) => permit,
+        _ => panic!("expected a free slot"),
+      })
+      .collect()
+  }
+
+  fn is_full(limits: &ProjectLimits, project: &ProjectRow) -> bool {
+    matches!(limits.try_acquire(project), ProjectSlot::Full)
+  }
+
+  #[test]
+  fn test_slots() {
+    let limits = ProjectLimits::default();
+    let id = Uuid::new_v4();
+
+    assert!(matches!(limits.try_acquire(&project(id, None)), ProjectSlot::Unlimited));
+    assert!(matches!(
+      limits.try_acquire(&project(id, Some(0))),
+      ProjectSlot::Unlimited
+    ));
+
+    let limited = project(id, Some(2));
+    let mut permits = acquire(&limits, &limited, 2);
+    assert!(is_full(&limits, &limited));
+
+    // Another project has slots of its own
+    acquire(&limits, &project(Uuid::new_v4(), Some(1)), 1);
+
+    permits.pop();
+    permits.extend(acquire(&limits, &limited, 1));
+    assert!(is_full(&limits, &limited));
+  }
+
+  #[test]
+  fn test_limit_change_counts_running_tasks() {
+    let limits = ProjectLimits::default();
+    let id = Uuid::new_v4();
+
+    let mut running = acquire(&limits, &project(id, Some(3)), 3);
+
+    // Lowered while 3 tasks run, nothing starts until only one is left
+    let lowered = project(id, Some(1));
+    assert!(is_full(&limits, &lowered));
+    running.pop();
+    assert!(is_full(&limits, &lowered));
+    running.pop();
+    assert!(is_full(&limits, &lowered));
+    running.pop();
+    running.extend(acquire(&limits, &lowered, 1));
+    assert!(is_full(&limits, &lowered));
+
+    // Raised, the task still running takes one of the new slots
+    let raised = project(id, Some(4));
+    running.extend(acquire(&limits, &raised, 3));
+    assert!(is_full(&limits, &raised));
+
+    // Lowered then raised again before any task finished
+    assert!(is_full(&limits, &project(id, Some(2))));
+    assert!(is_full(&limits, &project(id, Some(4))));
+    running.clear();
+    acquire(&limits, &raised, 4);
+  }
+}
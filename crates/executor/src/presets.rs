@@ -0,0 +1,66 @@
+use octabot_api::service::options;
+use serde_json::Value;
+
+use crate::error::{ExecutorError, ExecutorResult};
+
+/// Task option naming the project preset its options are based on
+const PRESET_OPTION: &str = "preset";
+/// Project option holding the named presets
+const PRESETS_OPTION: &str = "presets";
+
+/// Builds the options a task runs with, merging the task options over the project preset they reference
+///
+/// Nested objects are merged key by key, any other task value replaces the preset one. The `preset` key
+/// itself is not passed on to the plugin.
+///
+/// # Errors
+/// - UnknownPreset if the task references a preset the project doesn't define
+pub(crate) fn resolve_options(task_options: &Value, project_options: &Value) -> ExecutorResult<Value> {
+  let Some(name) = task_options.get(PRESET_OPTION).and_then(Value::as_str) else {
+    return Ok(task_options.clone());
+  };
+
+  let mut options = project_options
+    .get(PRESETS_OPTION)
+    .and_then(|presets| presets.get(name))
+    .cloned()
+    .ok_or_else(|| ExecutorError::UnknownPreset(name.to_string()))?;
+
+  let mut overrides = task_options.clone();
+  if let Some(overrides) = overrides.as_object_mut() {
+    overrides.remove(PRESET_OPTION);
+  }
+  options::merge(&mut options, overrides);
+
+  Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  #[test]
+  fn test_resolve_options() {
+    let project = json!({
+      "presets": {
+        "nightly": { "channel": "#builds", "http": { "timeout": 30, "retries": 2 } }
+      }
+    });
+
+    let options = resolve_options(&json!({ "preset": "nightly", "http": { "timeout": 60 } }), &project).unwrap();
+    assert_eq!(
+      options,
+      json!({ "channel": "#builds", "http": { "timeout": 60, "retries": 2 } })
+    );
+
+    let inline = json!({ "channel": "#alerts" });
+    assert_eq!(resolve_options(&inline, &project).unwrap(), inline);
+
+    assert!(matches!(
+      resolve_options(&json!({ "preset": "weekly" }), &project),
+      Err(ExecutorError::UnknownPreset(name)) if name == "weekly"
+    ));
+  }
+}
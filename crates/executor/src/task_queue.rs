@@ -0,0 +1,160 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::DateTime;
+use octabot_plugins::{bindings::exports::octahive::octabot::plugin::TaskData, task_queue::TaskQueue};
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::SqlitePool;
+
+use octabot_api::{
+  entities::task::MisfirePolicy,
+  service::{mutation, query},
+};
+
+/// Task created by a plugin, returned from `process` or enqueued while it runs
+#[derive(Deserialize, Debug)]
+pub struct PluginTask {
+  pub name: String,
+  pub kind: String,
+  pub project_code: String,
+  pub external_id: String,
+  /// Unix timestamp
+  pub external_modified_at: i64,
+  /// Unix timestamp
+  pub start_at: i64,
+  pub options: Value,
+}
+
+impl From<TaskData> for PluginTask {
+  fn from(task: TaskData) -> Self {
+    Self {
+      name: task.name,
+      kind: task.kind,
+      project_code: task.project_code,
+      external_id: task.external_id,
+      external_modified_at: i64::from(task.external_modified_at),
+      start_at: i64::from(task.start_at),
+      options: Value::String(task.options),
+    }
+  }
+}
+
+/// Creates the task in the project with its code, compared case-insensitively, or updates the one with the same
+/// external id
+pub async fn save(pool: &SqlitePool, task: PluginTask) -> Result<()> {
+  let project = query::projects::list_all(pool)
+    .await?
+    .into_iter()
+    .find(|p| p.code.eq_ignore_ascii_case(&task.project_code))
+    .context(format!("Project {} not found", task.project_code))?;
+
+  let external_modified_at =
+    DateTime::from_timestamp(task.external_modified_at, 0).context("External modification time out of range")?;
+
+  let params = mutation::tasks::CreateTaskParams {
+    name: task.name,
+    r#type: task.kind,
+    plugin_version: None,
+    schedule: None,
+    project_id: project.id,
+    external_id: Some(task.external_id),
+    external_modified_at: Some(external_modified_at),
+    start_at: task.start_at,
+    end_at: None,
+    misfire_policy: MisfirePolicy::default(),
+    options: task.options,
+  };
+
+  mutation::tasks::create(pool, None, params).await?;
+
+  Ok(())
+}
+
+/// Saves the tasks of `enqueue-task` as soon as the plugin hands them over
+pub struct DatabaseTaskQueue {
+  pub pool: SqlitePool,
+}
+
+#[async_trait]
+impl TaskQueue for DatabaseTaskQueue {
+  async fn enqueue(&self, task: &str) -> Result<(), String> {
+    let task: PluginTask = serde_json::from_str(task).map_err(|e| format!("Invalid task: {}", e))?;
+
+    save(&self.pool, task).await.map_err(|e| format!("{:#}", e))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+  use sqlx::sqlite::SqlitePoolOptions;
+
+  use super::*;
+
+  async fn test_pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+      .max_connections(1)
+      .connect("sqlite::memory:")
+      .await
+      .unwrap();
+    sqlx::migrate!("../../migrations").run(&pool).await.unwrap();
+    pool
+  }
+
+  fn plugin_task(name: &str, project_code: &str) -> PluginTask {
+    PluginTask {
+      name: name.to_string(),
+      kind: "http".to_string(),
+      project_code: project_code.to_string(),
+      external_id: "ISSUE-1".to_string(),
+      external_modified_at: 1_700_000_000,
+      start_at: 0,
+      options: json!({}),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_save_upserts_by_external_id() {
+    let pool = test_pool().await;
+
+    // The seed project has code `ppf`, plugins may return it in any case
+    save(&pool, plugin_task("first", "PPF")).await.unwrap();
+    save(&pool, plugin_task("second", "ppf")).await.unwrap();
+
+    let task = query::tasks::find_by_external_id(&pool, "ISSUE-1").await.unwrap();
+    assert_eq!(task.name, "second");
+    assert_eq!(task.project.code, "ppf");
+
+    let err = save(&pool, plugin_task("third", "NOPE")).await.unwrap_err();
+    assert_eq!(err.to_string(), "Project NOPE not found");
+  }
+
+  #[tokio::test]
+  async fn test_enqueue_rejects_invalid_tasks() {
+    let pool = test_pool().await;
+    let queue = DatabaseTaskQueue { pool: pool.clone() };
+
+    let task = json!({
+      "name": "enqueued",
+      "kind": "http",
+      "project_code": "ppf",
+      "external_id": "ISSUE-2",
+      "external_modified_at": 1_700_000_000,
+      "start_at": 0,
+      "options": { "url": "https://example.com" },
+    });
+    queue.enqueue(&task.to_string()).await.unwrap();
+    let saved = query::tasks::find_by_external_id(&pool, "ISSUE-2").await.unwrap();
+    assert_eq!(saved.options, json!({ "url": "https://example.com" }));
+
+    let err = queue.enqueue(r#"{"name": "enqueued"}"#).await.unwrap_err();
+    assert!(err.starts_with("Invalid task: missing field"), "{err}");
+
+    let mut unknown = task;
+    unknown["project_code"] = json!("NOPE");
+    assert_eq!(
+      queue.enqueue(&unknown.to_string()).await.unwrap_err(),
+      "Project NOPE not found"
+    );
+  }
+}
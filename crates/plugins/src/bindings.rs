@@ -1,5 +1,6 @@
 wasmtime::component::bindgen!({
   path: "wit/",
+  world: "octabot",
   async: true,
   trappable_imports: true,
 });
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// Host interface a plugin can be granted on top of the WASI core it always gets
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+  /// Outbound HTTP requests through `wasi:http`
+  Http,
+  /// The plugin's store behind `wasi:keyvalue` and the `octahive:octabot/buckets` housekeeping
+  Keyvalue,
+  /// Writing to the host log through `wasi:logging`
+  Logging,
+  /// Recording counters, gauges and histograms through `octahive:octabot/metrics`
+  Metrics,
+  /// Reading the task being processed through `octahive:octabot/task-context`
+  TaskContext,
+  /// Streaming the task output through `octahive:octabot/task-output`
+  TaskOutput,
+  /// Creating tasks while processing one through `octahive:octabot/task-queue`
+  TaskQueue,
+}
+
+impl Capability {
+  pub const ALL: [Capability; 7] = [
+    Capability::Http,
+    Capability::Keyvalue,
+    Capability::Logging,
+    Capability::Metrics,
+    Capability::TaskContext,
+    Capability::TaskOutput,
+    Capability::TaskQueue,
+  ];
+}
@@ -0,0 +1,348 @@
+use std::{
+  io::{self, Write},
+  pin::Pin,
+  task::{ready, Context, Poll},
+};
+
+use bytes::Bytes;
+use flate2::write::{GzDecoder, ZlibDecoder};
+use http_body_util::{combinators::BoxBody, BodyExt};
+use hyper::{
+  body::{Body, Frame},
+  header::{HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH},
+  HeaderMap,
+};
+use wasmtime_wasi_http::{bindings::http::types::ErrorCode, body::HyperOutgoingBody, types::IncomingResponse};
+
+/// Request header a plugin sets to have a compressed response decoded by the host, it's removed before the
+/// request is sent
+pub const DECOMPRESS_HEADER: HeaderName = HeaderName::from_static("x-octabot-decompress");
+
+const SUPPORTED_ENCODINGS: &str = "gzip, deflate";
+
+/// Strips the opt-in header from the request and advertises the encodings the host can decode
+///
+/// # Returns
+/// Whether the plugin asked for the response to be decoded
+pub fn prepare_request(request: &mut hyper::Request<HyperOutgoingBody>) -> bool {
+  let headers = request.headers_mut();
+  if headers.remove(DECOMPRESS_HEADER).is_none() {
+    return false;
+  }
+
+  headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(SUPPORTED_ENCODINGS));
+  true
+}
+
+/// Decodes a gzip or deflate encoded response body as it streams in, responses in another encoding are
+/// passed through untouched
+///
+/// The body fails with `HTTP-response-content-coding` once it decodes to more than `max_decoded_bytes`.
+pub fn decode_response(mut response: IncomingResponse, max_decoded_bytes: u64) -> IncomingResponse {
+  let Some(codec) = Codec::for_headers(response.resp.headers()) else {
+    return response;
+  };
+
+  let headers = response.resp.headers_mut();
+  headers.remove(CONTENT_ENCODING);
+  headers.remove(CONTENT_LENGTH);
+
+  response.resp = response.resp.map(|body| {
+    DecodedBody {
+      inner: body,
+      decoder: Some(Decoder {
+        codec,
+        max_bytes: max_decoded_bytes,
+        remaining: max_decoded_bytes,
+      }),
+    }
+    .boxed()
+  });
+
+  response
+}
+
+enum Codec {
+  Gzip(GzDecoder<Vec<u8>>),
+  Deflate(ZlibDecoder<Vec<u8>>),
+}
+
+impl Codec {
+  fn for_headers(headers: &HeaderMap) -> Option<Self> {
+    let encoding = headers.get(CONTENT_ENCODING)?.to_str().ok()?.trim();
+
+    if encoding.eq_ignore_ascii_case("gzip") || encoding.eq_ignore_ascii_case("x-gzip") {
+      Some(Self::Gzip(GzDecoder::new(Vec::new())))
+    } else if encoding.eq_ignore_ascii_case("deflate") {
+      Some(Self::Deflate(ZlibDecoder::new(Vec::new())))
+    } else {
+      None
+    }
+  }
+
+  fn write(&mut self, chunk: &[u8]) -> io::Result<usize> {
+    match self {
+      Self::Gzip(decoder) => decoder.write(chunk),
+      Self::Deflate(decoder) => decoder.write(chunk),
+    }
+  }
+
+  fn output(&mut self) -> &mut Vec<u8> {
+    match self {
+      Self::Gzip(decoder) => decoder.get_mut(),
+      Self::Deflate(decoder) => decoder.get_mut(),
+    }
+  }
+
+  fn finish(self) -> io::Result<Vec<u8>> {
+    match self {
+      Self::Gzip(decoder) => decoder.finish(),
+      Self::Deflate(decoder) => decoder.finish(),
+    }
+  }
+}
+
+struct Decoder {
+  codec: Codec,
+  max_bytes: u64,
+  /// Decoded bytes the body may still grow by
+  remaining: u64,
+}
+
+impl Decoder {
+  /// Feeds a compressed chunk and takes whatever it decoded so far
+  ///
+  /// The chunk is written a step at a time so a highly compressed one never expands much past the limit.
+  fn decode(&mut self, mut chunk: &[u8]) -> io::Result<Bytes> {
+    let mut decoded = Vec::new();
+
+    while !chunk.is_empty() {
+      let written = self.codec.write(chunk)?;
+      if written == 0 {
+        return Err(io::ErrorKind::WriteZero.into());
+      }
+      chunk = &chunk[written..];
+
+      let output = std::mem::take(self.codec.output());
+      self.consume(output.len())?;
+      decoded.extend_from_slice(&output);
+    }
+
+    Ok(Bytes::from(decoded))
+  }
+
+  /// Flushes the decoder and takes the rest of the decoded data, a truncated gzip stream fails here
+  fn finish(self) -> io::Result<Bytes> {
+    let Self {
+      codec,
+      max_bytes,
+      remaining,
+    } = self;
+    let output = codec.finish()?;
+    Self::check_size(output.len(), max_bytes, remaining)?;
+
+    Ok(Bytes::from(output))
+  }
+
+  fn consume(&mut self, len: usize) -> io::Result<()> {
+    self.remaining = Self::check_size(len, self.max_bytes, self.remaining)?;
+
+    Ok(())
+  }
+
+  /// Returns the bytes left once `len` more are decoded, fails past the limit
+  fn check_size(len: usize, max_bytes: u64, remaining: u64) -> io::Result<u64> {
+    remaining
+      .checked_sub(len as u64)
+      .ok_or_else(|| io::Error::other(format!("decoded response body is larger than {} bytes", max_bytes)))
+  }
+}
+
+fn content_coding_error(err: io::Error) -> ErrorCode {
+  ErrorCode::HttpResponseContentCoding(Some(err.to_string()))
+}
+
+struct DecodedBody {
+  inner: BoxBody<Bytes, ErrorCode>,
+  /// Taken once the inner body ends
+  decoder: Option<Decoder>,
+}
+
+impl Body for DecodedBody {
+  type Data = Bytes;
+  type Error = ErrorCode;
+
+  fn poll_frame(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, ErrorCode>>> {
+    let this = self.get_mut();
+
+    loop {
+      let Some(decoder) = this.decoder.as_mut() else {
+        return Poll::Ready(None);
+      };
+
+      match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+        Some(Ok(frame)) => match frame.into_data() {
+          Ok(chunk) => {
+            let decoded = decoder.decode(&chunk).map_err(content_coding_error)?;
+            if !decoded.is_empty() {
+              return Poll::Ready(Some(Ok(Frame::data(decoded))));
+            }
+          },
+          Err(frame) => return Poll::Ready(Some(Ok(frame))),
+        },
+        Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+        None => {
+          let decoder = this.decoder.take().expect("decoder is present until the body ends");
+          let rest = decoder.finish().map_err(content_coding_error)?;
+          return Poll::Ready((!rest.is_empty()).then(|| Ok(Frame::data(rest))));
+        },
+      }
+    }
+  }
+
+  fn is_end_stream(&self) -> bool {
+    self.decoder.is_none()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::VecDeque, convert::Infallible, time::Duration};
+
+  use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+  };
+  use http_body_util::Empty;
+
+  use super::*;
+
+  /// Body yielding the given chunks as separate frames
+  struct Chunks(VecDeque<Bytes>);
+
+  impl Body for Chunks {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, ErrorCode>>> {
+      Poll::Ready(self.get_mut().0.pop_front().map(|chunk| Ok(Frame::data(chunk))))
+    }
+  }
+
+  fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+  }
+
+  fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+  }
+
+  fn encoded_response(encoding: Option<&str>, body: &[u8], chunk_size: usize) -> IncomingResponse {
+    let mut builder = hyper::Response::builder().header(CONTENT_LENGTH, body.len());
+    if let Some(encoding) = encoding {
+      builder = builder.header(CONTENT_ENCODING, encoding);
+    }
+    let chunks = body.chunks(chunk_size).map(Bytes::copy_from_slice).collect();
+
+    IncomingResponse {
+      resp: builder.body(Chunks(chunks).boxed()).unwrap(),
+      worker: None,
+      between_bytes_timeout: Duration::from_secs(1),
+    }
+  }
+
+  async fn read_body(response: IncomingResponse) -> Result<Bytes, ErrorCode> {
+    Ok(response.resp.into_body().collect().await?.to_bytes())
+  }
+
+  fn payload() -> Vec<u8> {
+    (0..20_000u32).flat_map(|i| i.to_string().into_bytes()).collect()
+  }
+
+  #[tokio::test]
+  async fn test_decodes_across_chunks() {
+    let payload = payload();
+
+    for (encoding, encoded) in [("gzip", gzip(&payload)), ("deflate", deflate(&payload))] {
+      let response = decode_response(encoded_response(Some(encoding), &encoded, 7), u64::MAX);
+      assert!(response.resp.headers().get(CONTENT_ENCODING).is_none());
+      assert!(response.resp.headers().get(CONTENT_LENGTH).is_none());
+      assert_eq!(read_body(response).await.unwrap(), payload);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_truncated_gzip_fails() {
+    let encoded = gzip(&payload());
+    let truncated = &encoded[..encoded.len() - 10];
+
+    let response = decode_response(encoded_response(Some("gzip"), truncated, 64), u64::MAX);
+    assert!(matches!(
+      read_body(response).await,
+      Err(ErrorCode::HttpResponseContentCoding(Some(_)))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_decoded_size_limited() {
+    // Zeros compress about a thousand times, a single small chunk is enough to go past the limit
+    let encoded = gzip(&vec![0; 1024 * 1024]);
+    assert!(encoded.len() < 4096);
+
+    let response = decode_response(encoded_response(Some("gzip"), &encoded, encoded.len()), 64 * 1024);
+    assert!(matches!(
+      read_body(response).await,
+      Err(ErrorCode::HttpResponseContentCoding(Some(_)))
+    ));
+
+    let response = decode_response(encoded_response(Some("gzip"), &encoded, encoded.len()), 1024 * 1024);
+    assert_eq!(read_body(response).await.unwrap().len(), 1024 * 1024);
+  }
+
+  #[tokio::test]
+  async fn test_other_encodings_passed_through() {
+    let encoded = gzip(b"kept as is");
+
+    for encoding in [Some("br"), None] {
+      let response = decode_response(encoded_response(encoding, &encoded, 4), u64::MAX);
+      assert_eq!(
+        response
+          .resp
+          .headers()
+          .get(CONTENT_ENCODING)
+          .map(|value| value.to_str().unwrap()),
+        encoding
+      );
+      assert_eq!(read_body(response).await.unwrap(), encoded);
+    }
+  }
+
+  fn request(opt_in: bool) -> hyper::Request<HyperOutgoingBody> {
+    let mut builder = hyper::Request::builder()
+      .uri("http://example.com")
+      .header(ACCEPT_ENCODING, "br");
+    if opt_in {
+      builder = builder.header(DECOMPRESS_HEADER, "1");
+    }
+
+    builder
+      .body(Empty::new().map_err(|never: Infallible| match never {}).boxed())
+      .unwrap()
+  }
+
+  #[test]
+  fn test_prepare_request() {
+    let mut opted_in = request(true);
+    assert!(prepare_request(&mut opted_in));
+    assert!(opted_in.headers().get(DECOMPRESS_HEADER).is_none());
+    assert_eq!(opted_in.headers()[ACCEPT_ENCODING], SUPPORTED_ENCODINGS);
+
+    let mut other = request(false);
+    assert!(!prepare_request(&mut other));
+    assert_eq!(other.headers()[ACCEPT_ENCODING], "br");
+  }
+}
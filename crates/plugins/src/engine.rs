@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{thread, time::Duration};
 
 use anyhow::Result;
 use wasmtime::{
@@ -6,7 +6,16 @@ use wasmtime::{
   WasmBacktraceDetails,
 };
 
-use crate::{bindings::wasi, keyvalue, state::State};
+use crate::{
+  bindings::{octahive, wasi},
+  capability::Capability,
+  keyvalue,
+  state::State,
+};
+
+/// How often the epoch of the engine advances, a guest that never calls the host yields at least this often and
+/// overruns its call deadline by at most this much
+pub const EPOCH_TICK: Duration = Duration::from_millis(10);
 
 pub struct Config {
   inner: wasmtime::Config,
@@ -20,6 +29,7 @@ impl Default for Config {
     inner.async_support(true);
     inner.wasm_component_model(true);
     inner.wasm_backtrace_details(WasmBacktraceDetails::Enable);
+    inner.epoch_interruption(true);
 
     Self { inner }
   }
@@ -27,36 +37,38 @@ impl Default for Config {
 
 pub struct EngineBuilder {
   engine: wasmtime::Engine,
-  linker: Linker<State>,
 }
 
 impl EngineBuilder {
   fn new(config: &Config) -> Result<Self> {
     let engine = wasmtime::Engine::new(&config.inner)?;
-    let mut linker: Linker<State> = Linker::new(&engine);
 
-    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
-    wasmtime_wasi_http::add_only_http_to_linker_async(&mut linker)?;
-    keyvalue::add_to_linker(&mut linker, |ctx| {
-      keyvalue::WasiKeyValue::new(&ctx.wasi_keyvalue_ctx, &mut ctx.table)
-    })?;
-    wasi::logging::logging::add_to_linker::<State, HasSelf<State>>(&mut linker, |ctx| ctx)?;
-
-    Ok(Self { engine, linker })
+    Ok(Self { engine })
   }
 
   pub fn build(self) -> Engine {
-    Engine {
-      inner: self.engine,
-      linker: Arc::new(self.linker),
-    }
+    spawn_epoch_ticker(&self.engine);
+
+    Engine { inner: self.engine }
   }
 }
 
+/// Advances the epoch of `engine` every [`EPOCH_TICK`] until the engine is dropped
+fn spawn_epoch_ticker(engine: &wasmtime::Engine) {
+  let engine = engine.weak();
+
+  thread::spawn(move || {
+    while let Some(engine) = engine.upgrade() {
+      engine.increment_epoch();
+      drop(engine);
+      thread::sleep(EPOCH_TICK);
+    }
+  });
+}
+
 #[derive(Clone)]
 pub struct Engine {
   pub inner: wasmtime::Engine,
-  pub linker: Arc<Linker<State>>,
 }
 
 impl AsRef<wasmtime::Engine> for Engine {
@@ -69,4 +81,38 @@ impl Engine {
   pub fn builder(config: &Config) -> Result<EngineBuilder> {
     EngineBuilder::new(config)
   }
+
+  /// Builds a linker with the WASI core and only the host interfaces in `capabilities`
+  pub fn linker(&self, capabilities: &[Capability]) -> Result<Linker<State>> {
+    let mut linker: Linker<State> = Linker::new(&self.inner);
+
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+
+    for capability in Capability::ALL.iter().filter(|c| capabilities.contains(c)) {
+      match capability {
+        Capability::Http => wasmtime_wasi_http::add_only_http_to_linker_async(&mut linker)?,
+        Capability::Keyvalue => {
+          keyvalue::add_to_linker(&mut linker, |ctx| {
+            keyvalue::WasiKeyValue::new(&ctx.wasi_keyvalue_ctx, &mut ctx.table)
+          })?;
+          octahive::octabot::buckets::add_to_linker::<State, HasSelf<State>>(&mut linker, |ctx| ctx)?;
+        },
+        Capability::Logging => wasi::logging::logging::add_to_linker::<State, HasSelf<State>>(&mut linker, |ctx| ctx)?,
+        Capability::Metrics => {
+          octahive::octabot::metrics::add_to_linker::<State, HasSelf<State>>(&mut linker, |ctx| ctx)?
+        },
+        Capability::TaskContext => {
+          octahive::octabot::task_context::add_to_linker::<State, HasSelf<State>>(&mut linker, |ctx| ctx)?
+        },
+        Capability::TaskOutput => {
+          octahive::octabot::task_output::add_to_linker::<State, HasSelf<State>>(&mut linker, |ctx| ctx)?
+        },
+        Capability::TaskQueue => {
+          octahive::octabot::task_queue::add_to_linker::<State, HasSelf<State>>(&mut linker, |ctx| ctx)?
+        },
+      }
+    }
+
+    Ok(linker)
+  }
 }
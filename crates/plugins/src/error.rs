@@ -16,6 +16,9 @@ pub enum PluginError {
   #[error("Failed to initialize component: {0}")]
   InitComponentError(String),
 
+  #[error("Can't preopen directory {0}: {1}")]
+  PreopenDirError(String, String),
+
   #[error("Parse bot config error: {0}")]
   ParseBotConfigError(String),
 
@@ -42,6 +45,9 @@ pub enum PluginError {
 
   #[error("Error to calling plugin api: {0}")]
   CallPluginError(String),
+
+  #[error("Plugin is unhealthy: {0}")]
+  UnhealthyError(String),
 }
 
 impl From<WitError> for PluginError {
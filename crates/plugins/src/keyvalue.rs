@@ -17,9 +17,33 @@ use self::generated::wasi::keyvalue;
 use anyhow::Result;
 use parking_lot::Mutex;
 use std::time::{Duration, Instant};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+  collections::HashMap,
+  sync::{Arc, Weak},
+};
+use tracing::debug;
 use wasmtime::component::{HasData, Resource, ResourceTable, ResourceTableError};
 
+use crate::{bindings::octahive::octabot::buckets, state::State};
+
+/// Bucket opened with an empty identifier, holding the preset data
+const DEFAULT_BUCKET: &str = "";
+
+/// Buckets whose identifier starts with this prefix aren't scoped to the plugin, every plugin opening the same
+/// identifier shares them
+pub const SHARED_BUCKET_PREFIX: &str = "shared:";
+
+/// Longest expiry `touch` sets, larger TTLs would overflow `Instant`
+const MAX_TTL: Duration = Duration::from_secs(10 * 365 * 86400);
+
+/// Expiry of the entries plugins write
+const ENTRY_TTL: Duration = Duration::from_secs(3600);
+
+type BucketData = Arc<Mutex<HashMap<String, CacheEntry>>>;
+
+/// Bucket key in the store, the owning plugin and the identifier, no plugin for shared buckets
+type BucketKey = (Option<String>, String);
+
 struct CacheEntry {
   value: Vec<u8>,
   expires_at: Instant,
@@ -40,13 +64,71 @@ impl From<ResourceTableError> for Error {
 
 #[doc(hidden)]
 pub struct Bucket {
-  shared_data: Arc<Mutex<HashMap<String, CacheEntry>>>,
+  shared_data: BucketData,
+}
+
+/// Buckets of every plugin, shared by the contexts of the stores created from one plugin manager
+///
+/// Keeping them outside of a store means a plugin's data survives when it's reloaded.
+#[derive(Clone, Default)]
+pub struct KeyValueStore {
+  buckets: Arc<Mutex<HashMap<BucketKey, BucketData>>>,
+}
+
+impl KeyValueStore {
+  /// Drops the expired entries of every bucket, returning how many were removed
+  ///
+  /// Buckets are swept one at a time so plugins only wait for the bucket being swept.
+  pub fn sweep(&self) -> usize {
+    sweep_buckets(&self.buckets)
+  }
+
+  /// Sweeps the buckets on every `interval` in the background, so entries written once and never read again
+  /// don't stay in memory. The task stops once every handle to the store is dropped
+  pub fn spawn_reaper(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+    let buckets = Arc::downgrade(&self.buckets);
+
+    tokio::spawn(async move {
+      let mut ticks = tokio::time::interval(interval);
+      ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+      ticks.tick().await;
+
+      loop {
+        ticks.tick().await;
+
+        let Some(buckets) = Weak::upgrade(&buckets) else {
+          break;
+        };
+
+        let removed = sweep_buckets(&buckets);
+        if removed > 0 {
+          debug!("Removed {} expired keyvalue entries", removed);
+        }
+      }
+    })
+  }
+}
+
+fn sweep_buckets(buckets: &Mutex<HashMap<BucketKey, BucketData>>) -> usize {
+  let buckets: Vec<BucketData> = buckets.lock().values().cloned().collect();
+
+  buckets
+    .iter()
+    .map(|bucket| {
+      let mut data = bucket.lock();
+      let before = data.len();
+      cleanup_expired_entries(&mut data);
+      before - data.len()
+    })
+    .sum()
 }
 
 /// Builder-style structure used to create a [`WasiKeyValueCtx`].
 pub struct WasiKeyValueCtxBuilder {
   in_memory_data: HashMap<String, Vec<u8>>,
   ttl: Duration,
+  store: KeyValueStore,
+  namespace: String,
 }
 
 impl Default for WasiKeyValueCtxBuilder {
@@ -54,6 +136,8 @@ impl Default for WasiKeyValueCtxBuilder {
     Self {
       in_memory_data: HashMap::new(),
       ttl: Duration::from_secs(86400), // Default 1 day TTL
+      store: KeyValueStore::default(),
+      namespace: String::new(),
     }
   }
 }
@@ -69,6 +153,18 @@ impl WasiKeyValueCtxBuilder {
     self
   }
 
+  /// Store the buckets live in, a new empty one by default
+  pub fn store(mut self, store: KeyValueStore) -> Self {
+    self.store = store;
+    self
+  }
+
+  /// Name of the plugin the buckets are scoped to
+  pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+    self.namespace = namespace.into();
+    self
+  }
+
   /// Preset data for the In-Memory provider.
   pub fn in_memory_data<I, K, V>(mut self, data: I) -> Self
   where
@@ -97,9 +193,14 @@ impl WasiKeyValueCtxBuilder {
       })
       .collect();
 
-    WasiKeyValueCtx {
-      in_memory_data: Arc::new(Mutex::new(cache_data)),
+    let ctx = WasiKeyValueCtx {
+      store: self.store,
+      namespace: self.namespace,
+    };
+    if !cache_data.is_empty() {
+      ctx.open(DEFAULT_BUCKET).lock().extend(cache_data);
     }
+    ctx
   }
 }
 
@@ -109,8 +210,13 @@ fn cleanup_expired_entries(data: &mut HashMap<String, CacheEntry>) {
 }
 
 /// Capture the state necessary for use in the `wasi-keyvalue` API implementation.
+///
+/// Bucket identifiers are scoped to the namespace, the name the plugin is configured under, so plugins can use
+/// the same identifiers, `""` included, without seeing each other's keys. Identifiers starting with
+/// [`SHARED_BUCKET_PREFIX`] opt out of the scoping to share data between plugins on purpose.
 pub struct WasiKeyValueCtx {
-  in_memory_data: Arc<Mutex<HashMap<String, CacheEntry>>>,
+  store: KeyValueStore,
+  namespace: String,
 }
 
 impl WasiKeyValueCtx {
@@ -118,6 +224,102 @@ impl WasiKeyValueCtx {
   pub fn builder() -> WasiKeyValueCtxBuilder {
     WasiKeyValueCtxBuilder::new()
   }
+
+  fn key(&self, identifier: &str) -> BucketKey {
+    let namespace = (!identifier.starts_with(SHARED_BUCKET_PREFIX)).then(|| self.namespace.clone());
+    (namespace, identifier.to_string())
+  }
+
+  fn get(&self, identifier: &str) -> Option<BucketData> {
+    self.store.buckets.lock().get(&self.key(identifier)).cloned()
+  }
+
+  pub(crate) fn open(&self, identifier: &str) -> BucketData {
+    self
+      .store
+      .buckets
+      .lock()
+      .entry(self.key(identifier))
+      .or_default()
+      .clone()
+  }
+
+  /// Identifiers of the plugin's buckets and of the shared ones opened so far, sorted
+  pub fn bucket_names(&self) -> Vec<String> {
+    let mut names: Vec<String> = self
+      .store
+      .buckets
+      .lock()
+      .keys()
+      .filter(|(namespace, _)| namespace.as_ref().is_none_or(|namespace| *namespace == self.namespace))
+      .map(|(_, identifier)| identifier.clone())
+      .collect();
+    names.sort();
+    names
+  }
+
+  /// Drops every entry of a bucket, returning how many live ones were removed
+  pub fn clear(&self, identifier: &str) -> u64 {
+    let Some(bucket) = self.get(identifier) else {
+      return 0;
+    };
+
+    let mut data = bucket.lock();
+    cleanup_expired_entries(&mut data);
+
+    let removed = data.len() as u64;
+    data.clear();
+    removed
+  }
+
+  /// Extends the expiry of a live entry, under the bucket lock so it can't race with a concurrent write
+  ///
+  /// # Returns
+  /// Whether the entry existed and was updated
+  pub fn touch(&self, identifier: &str, key: &str, ttl: Duration) -> bool {
+    let Some(bucket) = self.get(identifier) else {
+      return false;
+    };
+
+    let mut data = bucket.lock();
+    let now = Instant::now();
+
+    match data.get_mut(key) {
+      Some(entry) if entry.expires_at > now => {
+        entry.expires_at = now + ttl.min(MAX_TTL);
+        true
+      },
+      _ => false,
+    }
+  }
+
+  /// Writes `new` only if the live value of the key is `expected`, `None` standing for a missing or expired
+  /// key, under the bucket lock so concurrent swaps can't both apply
+  ///
+  /// # Returns
+  /// Whether the value was written
+  pub fn compare_and_swap(&self, identifier: &str, key: &str, expected: Option<&[u8]>, new: Vec<u8>) -> bool {
+    let bucket = self.open(identifier);
+    let mut data = bucket.lock();
+    let now = Instant::now();
+
+    let current = data
+      .get(key)
+      .filter(|entry| entry.expires_at > now)
+      .map(|entry| entry.value.as_slice());
+    if current != expected {
+      return false;
+    }
+
+    data.insert(
+      key.to_string(),
+      CacheEntry {
+        value: new,
+        expires_at: now + ENTRY_TTL,
+      },
+    );
+    true
+  }
 }
 
 /// A wrapper capturing the needed internal `wasi-keyvalue` state.
@@ -135,12 +337,9 @@ impl<'a> WasiKeyValue<'a> {
 
 impl keyvalue::store::Host for WasiKeyValue<'_> {
   fn open(&mut self, identifier: String) -> Result<Resource<Bucket>, Error> {
-    match identifier.as_str() {
-      "" => Ok(self.table.push(Bucket {
-        shared_data: self.ctx.in_memory_data.clone(),
-      })?),
-      _ => Err(Error::NoSuchStore),
-    }
+    Ok(self.table.push(Bucket {
+      shared_data: self.ctx.open(&identifier),
+    })?)
   }
 
   fn convert_error(&mut self, err: Error) -> Result<keyvalue::store::Error> {
@@ -176,7 +375,7 @@ impl keyvalue::store::HostBucket for WasiKeyValue<'_> {
       key,
       CacheEntry {
         value,
-        expires_at: Instant::now() + Duration::from_secs(3600), // You might want to make this configurable
+        expires_at: Instant::now() + ENTRY_TTL,
       },
     );
     Ok(())
@@ -238,8 +437,153 @@ pub fn add_to_linker<T: Send + 'static>(
   Ok(())
 }
 
+impl buckets::Host for State {
+  async fn list_buckets(&mut self) -> wasmtime::Result<Vec<String>> {
+    Ok(self.wasi_keyvalue_ctx.bucket_names())
+  }
+
+  async fn clear(&mut self, bucket: String) -> wasmtime::Result<u64> {
+    Ok(self.wasi_keyvalue_ctx.clear(&bucket))
+  }
+
+  async fn touch(&mut self, bucket: String, key: String, ttl_seconds: u64) -> wasmtime::Result<bool> {
+    Ok(
+      self
+        .wasi_keyvalue_ctx
+        .touch(&bucket, &key, Duration::from_secs(ttl_seconds)),
+    )
+  }
+
+  async fn compare_and_swap(
+    &mut self,
+    bucket: String,
+    key: String,
+    expected: Option<Vec<u8>>,
+    new: Vec<u8>,
+  ) -> wasmtime::Result<bool> {
+    Ok(
+      self
+        .wasi_keyvalue_ctx
+        .compare_and_swap(&bucket, &key, expected.as_deref(), new),
+    )
+  }
+}
+
 struct HasWasiKeyValue;
 
 impl HasData for HasWasiKeyValue {
   type Data<'a> = WasiKeyValue<'a>;
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn insert(bucket: &BucketData, key: &str) {
+    insert_expiring(bucket, key, Instant::now() + Duration::from_secs(60));
+  }
+
+  fn insert_expiring(bucket: &BucketData, key: &str, expires_at: Instant) {
+    bucket.lock().insert(
+      key.to_string(),
+      CacheEntry {
+        value: b"value".to_vec(),
+        expires_at,
+      },
+    );
+  }
+
+  #[test]
+  fn test_buckets_are_scoped_to_the_plugin() {
+    let store = KeyValueStore::default();
+    let first = WasiKeyValueCtx::builder()
+      .store(store.clone())
+      .namespace("first")
+      .build();
+    let second = WasiKeyValueCtx::builder().store(store).namespace("second").build();
+
+    insert(&first.open(DEFAULT_BUCKET), "key");
+    assert!(second.open(DEFAULT_BUCKET).lock().is_empty());
+
+    insert(&first.open("shared:cache"), "key");
+    assert_eq!(second.open("shared:cache").lock().len(), 1);
+
+    assert_eq!(second.bucket_names(), vec!["", "shared:cache"]);
+    assert_eq!(second.clear(DEFAULT_BUCKET), 0);
+    assert_eq!(first.clear(DEFAULT_BUCKET), 1);
+  }
+
+  #[test]
+  fn test_sweep_drops_expired_entries() {
+    let store = KeyValueStore::default();
+    let ctx = WasiKeyValueCtx::builder()
+      .store(store.clone())
+      .namespace("plugin")
+      .build();
+    let bucket = ctx.open("cache");
+    let shared = ctx.open("shared:cache");
+
+    insert(&bucket, "live");
+    insert_expiring(&bucket, "expired", Instant::now());
+    insert_expiring(&shared, "expired", Instant::now());
+
+    assert_eq!(store.sweep(), 2);
+    assert_eq!(bucket.lock().len(), 1);
+    assert!(shared.lock().is_empty());
+    assert_eq!(store.sweep(), 0);
+  }
+
+  #[test]
+  fn test_compare_and_swap_under_contention() {
+    let store = KeyValueStore::default();
+    let contexts: Vec<WasiKeyValueCtx> = (0..8)
+      .map(|_| {
+        WasiKeyValueCtx::builder()
+          .store(store.clone())
+          .namespace("plugin")
+          .build()
+      })
+      .collect();
+
+    // Every worker races to take the lead, only one may see the key missing
+    let leaders = std::thread::scope(|scope| {
+      let handles: Vec<_> = contexts
+        .iter()
+        .enumerate()
+        .map(|(id, ctx)| scope.spawn(move || ctx.compare_and_swap("election", "leader", None, vec![id as u8])))
+        .collect();
+
+      handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .filter(|won| *won)
+        .count()
+    });
+    assert_eq!(leaders, 1);
+
+    // Increments retried until their swap applies are never lost
+    std::thread::scope(|scope| {
+      for ctx in &contexts {
+        scope.spawn(move || {
+          for _ in 0..100 {
+            loop {
+              let current = ctx.open("counters").lock().get("hits").map(|entry| entry.value.clone());
+              let next = current
+                .as_ref()
+                .map_or(0, |value| u32::from_le_bytes(value[..].try_into().unwrap()))
+                + 1;
+
+              if ctx.compare_and_swap("counters", "hits", current.as_deref(), next.to_le_bytes().to_vec()) {
+                break;
+              }
+            }
+          }
+        });
+      }
+    });
+
+    let hits = contexts[0].open("counters").lock()["hits"].value.clone();
+    assert_eq!(u32::from_le_bytes(hits[..].try_into().unwrap()), 800);
+    assert!(!contexts[0].compare_and_swap("counters", "hits", None, vec![]));
+  }
+}
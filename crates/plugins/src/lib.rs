@@ -1,7 +1,15 @@
 pub mod bindings;
+pub mod capability;
+pub mod decompress;
 pub mod engine;
 pub mod error;
 pub mod keyvalue;
 pub mod manager;
+pub mod metrics;
+pub mod output;
 pub mod plugin;
+pub mod ratelimit;
 pub mod state;
+pub mod stdio;
+pub mod task_context;
+pub mod task_queue;
@@ -1,20 +1,28 @@
 use std::{
   fmt,
   path::{Path, PathBuf},
+  sync::Arc,
+  time::Duration,
 };
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use wasmtime::{component::Component, Store};
+use wasmtime::{
+  component::{Component, Instance, TypedFunc},
+  Store,
+};
 
 use crate::{
   bindings::{
     exports::octahive::octabot::plugin::{Metadata, PluginResult as Result},
     Octabot,
   },
+  capability::Capability,
   engine::{Config, Engine},
   error::{PluginError, PluginResult},
-  state::State,
+  keyvalue::{KeyValueStore, WasiKeyValueCtx},
+  ratelimit::{RateLimit, RateLimiter},
+  state::{HttpConfig, State, WasiConfig},
 };
 
 #[async_trait]
@@ -24,13 +32,29 @@ pub trait PluginActions: Send + 'static {
   async fn init(&self, store: &mut Store<State>, config: &str) -> PluginResult<()>;
 
   async fn process(&self, store: &mut Store<State>, params: &str) -> PluginResult<Vec<Result>>;
+
+  /// Calls the `health` export of the plugin, a plugin without it is always healthy
+  async fn health(&self, store: &mut Store<State>) -> PluginResult<()>;
 }
 
+/// Name the optional `health` interface is exported under
+const HEALTH_INTERFACE: &str = "octahive:octabot/health@0.1.0";
+
+type HealthCheck = TypedFunc<(), (std::result::Result<(), String>,)>;
+
 pub struct InstanceData {
   interface: Octabot,
+  health: Option<HealthCheck>,
   pub metadata: Metadata,
 }
 
+impl InstanceData {
+  /// Whether the plugin exports `health`, only those are checked periodically
+  pub fn reports_health(&self) -> bool {
+    self.health.is_some()
+  }
+}
+
 #[async_trait]
 impl PluginActions for InstanceData {
   async fn load(&self, store: &mut Store<State>) -> PluginResult<Metadata> {
@@ -63,12 +87,47 @@ impl PluginActions for InstanceData {
         .map_err(|e| PluginError::CallPluginError(e.to_string()))??,
     )
   }
+
+  async fn health(&self, store: &mut Store<State>) -> PluginResult<()> {
+    let Some(check) = &self.health else {
+      return Ok(());
+    };
+
+    let (result,) = check
+      .call_async(&mut *store, ())
+      .await
+      .map_err(|e| PluginError::CallPluginError(e.to_string()))?;
+    check
+      .post_return_async(&mut *store)
+      .await
+      .map_err(|e| PluginError::CallPluginError(e.to_string()))?;
+
+    result.map_err(PluginError::UnhealthyError)
+  }
+}
+
+/// Looks up the `health` export, plugins built without it load as before
+fn find_health_check(instance: &Instance, store: &mut Store<State>) -> PluginResult<Option<HealthCheck>> {
+  let check = instance
+    .get_export_index(&mut *store, None, HEALTH_INTERFACE)
+    .and_then(|interface| instance.get_export_index(&mut *store, Some(&interface), "check"));
+
+  match check {
+    Some(check) => instance
+      .get_typed_func(&mut *store, &check)
+      .map(Some)
+      .map_err(|e| PluginError::InitComponentError(format!("invalid health export: {}", e))),
+    None => Ok(None),
+  }
 }
 
 pub const PLUGINS_PATH: &str = "./plugins";
 
 pub struct PluginManager {
   engine: Engine,
+  http_config: HttpConfig,
+  /// Keyvalue buckets of every loaded plugin, each scoped to the name the plugin is configured under
+  kv_store: KeyValueStore,
 }
 
 impl PluginManager {
@@ -79,34 +138,106 @@ impl PluginManager {
       .map_err(|e| PluginError::InitWasmEngineError(e.to_string()))?
       .build();
 
-    Ok(Self { engine })
+    Ok(Self {
+      engine,
+      http_config: HttpConfig::default(),
+      kv_store: KeyValueStore::default(),
+    })
   }
 
-  pub async fn load_plugin(&self, path: impl AsRef<Path>) -> PluginResult<(InstanceData, Store<State>)> {
+  /// Sets the retry and timeout settings for the outbound requests of the plugins loaded afterwards
+  pub fn with_http_config(mut self, http_config: HttpConfig) -> Self {
+    self.http_config = http_config;
+    self
+  }
+
+  /// Drops expired keyvalue entries of every plugin on each `interval`, until the manager is dropped
+  pub fn with_kv_reaper(self, interval: Duration) -> Self {
+    self.kv_store.spawn_reaper(interval);
+    self
+  }
+
+  /// Instantiates the plugin component with only the host interfaces in `capabilities` linked and only the
+  /// directories and environment variables of `wasi` visible, its outbound requests paced by `rate_limit` or
+  /// rejected altogether with `deny_egress`
+  ///
+  /// Imports of interfaces the plugin wasn't granted still resolve so that components built against the full
+  /// SDK load, but calling them traps.
+  pub async fn load_plugin(
+    &self,
+    name: &str,
+    path: impl AsRef<Path>,
+    capabilities: &[Capability],
+    wasi: &WasiConfig,
+    rate_limit: Option<&RateLimit>,
+    deny_egress: bool,
+  ) -> PluginResult<(InstanceData, Store<State>)> {
     let path = PathBuf::from(PLUGINS_PATH).join(path);
     let component =
       Component::from_file(&self.engine.inner, path).map_err(|e| PluginError::ReadComponentError(e.to_string()))?;
 
-    let mut store = wasmtime::Store::new(&self.engine.inner, State::default());
+    let mut linker = self
+      .engine
+      .linker(capabilities)
+      .map_err(|e| PluginError::InitComponentError(e.to_string()))?;
+    linker
+      .define_unknown_imports_as_traps(&component)
+      .map_err(|e| PluginError::InitComponentError(e.to_string()))?;
+
+    let state = self.plugin_state(name, wasi, rate_limit, deny_egress)?;
+    let mut store = wasmtime::Store::new(&self.engine.inner, state);
+    store.epoch_deadline_callback(|store| store.data().on_epoch_deadline());
+    store.set_epoch_deadline(1);
 
-    let interface = Octabot::instantiate_async(&mut store, &component, &self.engine.linker)
+    let instance = linker
+      .instantiate_async(&mut store, &component)
       .await
       .map_err(|e| PluginError::InitComponentError(e.to_string()))?;
+    let interface = Octabot::new(&mut store, &instance).map_err(|e| PluginError::InitComponentError(e.to_string()))?;
+    let health = find_health_check(&instance, &mut store)?;
 
     let metadata = interface
       .octahive_octabot_plugin()
       .call_load(&mut store)
       .await
       .map_err(|e| PluginError::CallPluginError(e.to_string()))?;
+    store.data_mut().plugin = metadata.name.clone();
 
     Ok((
       InstanceData {
         interface,
+        health,
         metadata: metadata.clone(),
       },
       store,
     ))
   }
+
+  /// Host state of a plugin, complete before the component is instantiated so even `load` runs under it
+  ///
+  /// Keyvalue buckets are scoped to `name` rather than the name in the plugin's metadata, so the same component
+  /// configured twice keeps two sets of data and two components claiming the same name don't share theirs.
+  fn plugin_state(
+    &self,
+    name: &str,
+    wasi: &WasiConfig,
+    rate_limit: Option<&RateLimit>,
+    deny_egress: bool,
+  ) -> PluginResult<State> {
+    let mut state = State::new();
+    state.configure_wasi(wasi)?;
+    state.http_config = self.http_config.clone();
+    state.http_config.deny_egress |= deny_egress;
+    state.rate_limiter = rate_limit
+      .cloned()
+      .map(|rate_limit| Arc::new(RateLimiter::new(rate_limit)));
+    state.wasi_keyvalue_ctx = WasiKeyValueCtx::builder()
+      .store(self.kv_store.clone())
+      .namespace(name)
+      .build();
+
+    Ok(state)
+  }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -138,3 +269,40 @@ impl PluginLocation {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_plugin_state_denies_egress() {
+    let wasi = WasiConfig::default();
+    let denied = |manager: &PluginManager, deny_egress| {
+      let state = manager.plugin_state("github", &wasi, None, deny_egress).unwrap();
+      state.http_config.deny_egress
+    };
+
+    let manager = PluginManager::new().unwrap();
+    assert!(!denied(&manager, false));
+    assert!(denied(&manager, true));
+
+    // Set for every plugin, a plugin config can't lift it
+    let manager = manager.with_http_config(HttpConfig {
+      deny_egress: true,
+      ..Default::default()
+    });
+    assert!(denied(&manager, false));
+  }
+
+  #[test]
+  fn test_keyvalue_scoped_to_config_name() {
+    let manager = PluginManager::new().unwrap();
+    let wasi = WasiConfig::default();
+    let first = manager.plugin_state("github", &wasi, None, false).unwrap();
+    let second = manager.plugin_state("github-enterprise", &wasi, None, false).unwrap();
+
+    first.wasi_keyvalue_ctx.open("cache");
+    assert_eq!(first.wasi_keyvalue_ctx.bucket_names(), vec!["cache"]);
+    assert!(second.wasi_keyvalue_ctx.bucket_names().is_empty());
+  }
+}
@@ -0,0 +1,29 @@
+use metrics::Label;
+
+use crate::{bindings::octahive::octabot::metrics::Host, state::State};
+
+impl Host for State {
+  async fn counter(&mut self, name: String, value: u64, tags: Vec<(String, String)>) -> wasmtime::Result<()> {
+    metrics::counter!(name, self.labels(tags)).increment(value);
+
+    Ok(())
+  }
+
+  async fn gauge(&mut self, name: String, value: f64, tags: Vec<(String, String)>) -> wasmtime::Result<()> {
+    metrics::gauge!(name, self.labels(tags)).set(value);
+
+    Ok(())
+  }
+}
+
+impl State {
+  /// Plugin tags plus a `plugin` label so metrics from different plugins don't mix
+  fn labels(&self, tags: Vec<(String, String)>) -> Vec<Label> {
+    tags
+      .into_iter()
+      .filter(|(key, _)| key != "plugin")
+      .chain(std::iter::once(("plugin".to_string(), self.plugin.clone())))
+      .map(|(key, value)| Label::new(key, value))
+      .collect()
+  }
+}
@@ -0,0 +1,25 @@
+use std::{io, path::Path};
+
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use crate::{bindings::octahive::octabot::task_output::Host, state::State};
+
+impl Host for State {
+  async fn write(&mut self, chunk: Vec<u8>) -> wasmtime::Result<Result<(), String>> {
+    let Some(path) = &self.task_output else {
+      return Ok(Err("no task is being processed".to_string()));
+    };
+
+    Ok(append(path, &chunk).await.map_err(|e| e.to_string()))
+  }
+}
+
+async fn append(path: &Path, chunk: &[u8]) -> io::Result<()> {
+  if let Some(dir) = path.parent() {
+    tokio::fs::create_dir_all(dir).await?;
+  }
+
+  let mut file = OpenOptions::new().create(true).append(true).open(path).await?;
+  file.write_all(chunk).await?;
+  file.flush().await
+}
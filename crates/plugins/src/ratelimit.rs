@@ -0,0 +1,116 @@
+use std::{
+  collections::HashMap,
+  time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+
+/// Pace of the outbound requests of a plugin, the `rate_limit` of its config
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RateLimit {
+  /// Requests sent per second on average
+  pub requests_per_sec: f64,
+  /// Requests that may go out back to back after an idle period
+  #[serde(default = "default_burst")]
+  pub burst: u32,
+  /// Gives every `host:port` its own bucket instead of sharing one between all requests of the plugin
+  #[serde(default)]
+  pub per_authority: bool,
+}
+
+fn default_burst() -> u32 {
+  1
+}
+
+/// Token bucket over the outbound requests of a plugin, a request finding the bucket empty waits for its token
+/// instead of failing
+///
+/// Tokens are reserved when a request asks for one, so waiting requests go out in order one interval apart.
+#[derive(Debug)]
+pub struct RateLimiter {
+  config: RateLimit,
+  buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+  /// Goes negative while requests wait, each one owing a token
+  tokens: f64,
+  updated_at: Instant,
+}
+
+impl RateLimiter {
+  pub fn new(config: RateLimit) -> Self {
+    Self {
+      config,
+      buckets: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Waits until a request to `authority` may be sent
+  pub async fn acquire(&self, authority: &str) {
+    let delay = self.reserve(authority, Instant::now());
+
+    if !delay.is_zero() {
+      tracing::debug!(
+        "Delaying request to {} by {:?} to stay within the rate limit",
+        authority,
+        delay
+      );
+      sleep(delay).await;
+    }
+  }
+
+  /// Takes a token from the bucket of `authority` and returns how long to wait for it
+  fn reserve(&self, authority: &str, now: Instant) -> Duration {
+    let key = if self.config.per_authority { authority } else { "" };
+    let burst = f64::from(self.config.burst.max(1));
+    let rate = self.config.requests_per_sec;
+
+    let mut buckets = self.buckets.lock();
+    let bucket = buckets.entry(key.to_string()).or_insert_with(|| Bucket {
+      tokens: burst,
+      updated_at: now,
+    });
+
+    let elapsed = now.saturating_duration_since(bucket.updated_at).as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * rate).min(burst) - 1.0;
+    bucket.updated_at = now;
+
+    if bucket.tokens >= 0.0 {
+      Duration::ZERO
+    } else {
+      Duration::from_secs_f64(-bucket.tokens / rate)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_reserve_paces_requests() {
+    let limiter = RateLimiter::new(RateLimit {
+      requests_per_sec: 2.0,
+      burst: 2,
+      per_authority: true,
+    });
+    let start = Instant::now();
+
+    assert_eq!(limiter.reserve("api.github.com:443", start), Duration::ZERO);
+    assert_eq!(limiter.reserve("api.github.com:443", start), Duration::ZERO);
+    assert_eq!(limiter.reserve("api.github.com:443", start), Duration::from_millis(500));
+    assert_eq!(limiter.reserve("api.github.com:443", start), Duration::from_secs(1));
+    assert_eq!(limiter.reserve("slack.com:443", start), Duration::ZERO);
+
+    // The two waiting requests used the tokens refilled during the next second
+    let later = start + Duration::from_secs(1);
+    assert_eq!(limiter.reserve("api.github.com:443", later), Duration::from_millis(500));
+
+    let idle = start + Duration::from_secs(60);
+    assert_eq!(limiter.reserve("api.github.com:443", idle), Duration::ZERO);
+  }
+}
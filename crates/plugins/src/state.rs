@@ -1,24 +1,36 @@
-use std::time::Instant;
-use std::{collections::HashMap, sync::Arc};
+use std::time::{Instant, SystemTime};
+use std::{
+  collections::{BTreeMap, HashMap, VecDeque},
+  net::Ipv6Addr,
+  path::PathBuf,
+  sync::Arc,
+};
 
 use bytes::Bytes;
+use http::uri::Authority;
 use http_body_util::combinators::BoxBody;
 use http_body_util::BodyExt;
 use http_body_util::Empty;
 use hyper::{
+  body::Body,
   client::conn::http1::SendRequest,
   header::{self, HeaderValue},
 };
 use lazy_static::lazy_static;
+use rustls::{
+  crypto::CryptoProvider, pki_types::ServerName, version::TLS13, ClientConfig, RootCertStore, SupportedProtocolVersion,
+};
+use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
 use std::time::Duration;
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
 use tokio::time::timeout;
 use tokio::{net::TcpStream, time::sleep};
-use wasmtime::component::ResourceTable;
+use wasmtime::{component::ResourceTable, UpdateDeadline};
 use wasmtime_wasi::{
   p2::{IoView, WasiCtx, WasiCtxBuilder, WasiView},
   runtime::AbortOnDropJoinHandle,
+  DirPerms, FilePerms,
 };
 use wasmtime_wasi_http::{
   bindings::http::types::ErrorCode,
@@ -30,8 +42,13 @@ use wasmtime_wasi_http::{
 };
 
 use crate::{
-  bindings::wasi,
+  bindings::{octahive::octabot::task_context::TaskContext, wasi},
+  decompress,
+  error::{PluginError, PluginResult},
   keyvalue::{WasiKeyValueCtx, WasiKeyValueCtxBuilder},
+  ratelimit::RateLimiter,
+  stdio::CapturedOutput,
+  task_queue::TaskQueue,
 };
 
 lazy_static! {
@@ -57,8 +74,237 @@ pub(crate) fn dns_error(rcode: String, info_code: u16) -> ErrorCode {
   })
 }
 
+/// Resilience settings for outbound plugin requests, the `http` section of the executor config
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct HttpConfig {
+  /// Attempts made after the first one fails
+  pub max_retries: u32,
+  /// Delay before the first retry in milliseconds, doubled for every following one
+  pub retry_backoff_ms: u64,
+  /// Time budget for a request including its retries in milliseconds, no retry starts past it
+  pub max_retry_time_ms: Option<u64>,
+  /// Upper bound in milliseconds for the connect timeout a plugin sets on its requests
+  pub connect_timeout_ms: Option<u64>,
+  /// Upper bound in milliseconds for the first byte and between bytes timeouts a plugin sets on its requests
+  pub read_timeout_ms: Option<u64>,
+  /// Longest wait in milliseconds honored from the `Retry-After` header of a 429 or 503 response, a longer one
+  /// is cut to it
+  pub max_retry_after_ms: u64,
+  /// Protocol versions and cipher suites allowed for HTTPS requests
+  pub tls: TlsConfig,
+  /// Rejects every request with `HTTP-request-denied` before it leaves the host, for plugins that must not reach
+  /// the network even when granted the `http` capability
+  pub deny_egress: bool,
+  /// Most bytes a response the host decompresses for a plugin may decode to, its body fails past it
+  pub max_decompressed_bytes: u64,
+}
+
+impl Default for HttpConfig {
+  fn default() -> Self {
+    Self {
+      max_retries: 1,
+      retry_backoff_ms: 200,
+      max_retry_time_ms: None,
+      connect_timeout_ms: None,
+      read_timeout_ms: None,
+      max_retry_after_ms: 60_000,
+      tls: TlsConfig::default(),
+      deny_egress: false,
+      max_decompressed_bytes: 64 * 1024 * 1024,
+    }
+  }
+}
+
+impl HttpConfig {
+  /// Caps the timeouts requested by the plugin with the configured ones
+  fn clamp(&self, mut config: OutgoingRequestConfig) -> OutgoingRequestConfig {
+    if let Some(max) = self.connect_timeout_ms.map(Duration::from_millis) {
+      config.connect_timeout = config.connect_timeout.min(max);
+    }
+    if let Some(max) = self.read_timeout_ms.map(Duration::from_millis) {
+      config.first_byte_timeout = config.first_byte_timeout.min(max);
+      config.between_bytes_timeout = config.between_bytes_timeout.min(max);
+    }
+
+    config
+  }
+
+  fn retry_delay(&self, retry: u32) -> Duration {
+    Duration::from_millis(self.retry_backoff_ms.saturating_mul(2u64.saturating_pow(retry - 1)))
+  }
+
+  /// How long to wait before sending again a request answered with a 429 or 503 carrying `Retry-After`, in
+  /// seconds or as an HTTP date, other responses aren't retried
+  fn retry_after<B>(&self, response: &hyper::Response<B>, now: SystemTime) -> Option<Duration> {
+    let status = response.status();
+    if status != http::StatusCode::TOO_MANY_REQUESTS && status != http::StatusCode::SERVICE_UNAVAILABLE {
+      return None;
+    }
+
+    let value = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+    let delay = match value.parse::<u64>() {
+      Ok(secs) => Duration::from_secs(secs),
+      Err(_) => httpdate::parse_http_date(value)
+        .ok()?
+        .duration_since(now)
+        .unwrap_or_default(),
+    };
+
+    Some(delay.min(Duration::from_millis(self.max_retry_after_ms)))
+  }
+}
+
+/// Only TLS 1.3, for `min_version` set to `1.3`
+static TLS13_ONLY: &[&SupportedProtocolVersion] = &[&TLS13];
+
+/// TLS policy of outbound plugin requests, rustls' safe defaults when left empty
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TlsConfig {
+  /// Oldest protocol version negotiated, `1.2` or `1.3`
+  pub min_version: Option<TlsVersion>,
+  /// Cipher suites offered, in order of preference, by their IANA names like `TLS13_AES_256_GCM_SHA384`. Every
+  /// suite of the crypto provider is offered when empty
+  pub cipher_suites: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlsVersion {
+  #[serde(rename = "1.2")]
+  Tls12,
+  #[serde(rename = "1.3")]
+  Tls13,
+}
+
+impl TlsConfig {
+  /// Checks the policy can be applied with the installed crypto provider, so an unknown cipher suite or one no
+  /// allowed version supports is reported at startup rather than failing every request
+  pub fn check(&self) -> Result<(), String> {
+    self.client_config(RootCertStore::empty()).map(|_| ())
+  }
+
+  fn client_config(&self, root_cert_store: RootCertStore) -> Result<ClientConfig, String> {
+    let mut provider = CryptoProvider::get_default()
+      .map(|provider| provider.as_ref().clone())
+      .ok_or_else(|| {
+        "no TLS crypto provider installed, install one at startup with \
+         `rustls::crypto::ring::default_provider().install_default()`"
+          .to_string()
+      })?;
+
+    if !self.cipher_suites.is_empty() {
+      let suites = self
+        .cipher_suites
+        .iter()
+        .map(|name| {
+          provider
+            .cipher_suites
+            .iter()
+            .find(|suite| format!("{:?}", suite.suite()) == *name)
+            .copied()
+            .ok_or_else(|| format!("unknown cipher suite `{}`", name))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+      provider.cipher_suites = suites;
+    }
+
+    let versions = match self.min_version {
+      Some(TlsVersion::Tls13) => TLS13_ONLY,
+      Some(TlsVersion::Tls12) | None => rustls::DEFAULT_VERSIONS,
+    };
+
+    Ok(
+      ClientConfig::builder_with_provider(Arc::new(provider))
+        .with_protocol_versions(versions)
+        .map_err(|e| e.to_string())?
+        .with_root_certificates(root_cert_store)
+        .with_no_client_auth(),
+    )
+  }
+}
+
+/// What a plugin sees of the host through WASI besides stdio, nothing by default
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct WasiConfig {
+  /// Host directories mapped into the plugin, it has no filesystem access without them
+  pub preopened_dirs: Vec<PreopenedDir>,
+  /// Host environment variables passed to the plugin by name, unset ones are skipped. The rest of the host
+  /// environment is never visible to plugins
+  pub env_passthrough: Vec<String>,
+  /// Environment variables set for the plugin, taking precedence over the passed through ones
+  pub env: BTreeMap<String, String>,
+  /// Lets the plugin write straight to the host stdout and stderr for debugging, otherwise what it prints is
+  /// logged with the plugin name and added to the logs of the task being processed
+  pub inherit_stdio: bool,
+}
+
+impl WasiConfig {
+  fn env_vars(&self) -> Vec<(String, String)> {
+    let mut vars: BTreeMap<String, String> = self
+      .env_passthrough
+      .iter()
+      .filter_map(|name| std::env::var(name).ok().map(|value| (name.clone(), value)))
+      .collect();
+    vars.extend(self.env.clone());
+
+    vars.into_iter().collect()
+  }
+}
+
+/// Host directory a plugin may access, mapped into its filesystem under `guest`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PreopenedDir {
+  pub host: PathBuf,
+  /// Path the plugin opens the directory by, e.g. `/templates`
+  pub guest: String,
+  /// Lets the plugin only read the directory and its files
+  #[serde(default)]
+  pub read_only: bool,
+}
+
+impl PreopenedDir {
+  fn perms(&self) -> (DirPerms, FilePerms) {
+    if self.read_only {
+      (DirPerms::READ, FilePerms::READ)
+    } else {
+      (DirPerms::all(), FilePerms::all())
+    }
+  }
+}
+
+/// Builds the `host:port` the pool connects to from a request authority, keeping IPv6 hosts bracketed and
+/// filling in the default port of the scheme. Authorities carrying credentials are rejected.
+pub(crate) fn normalize_authority(authority: &Authority, use_tls: bool) -> Result<String, ErrorCode> {
+  if authority.as_str().contains('@') {
+    return Err(ErrorCode::HttpRequestUriInvalid);
+  }
+
+  let host = authority.host();
+  match host.strip_prefix('[').and_then(|host| host.strip_suffix(']')) {
+    Some(ipv6) => {
+      ipv6.parse::<Ipv6Addr>().map_err(|_| ErrorCode::HttpRequestUriInvalid)?;
+    },
+    None if host.is_empty() || host.contains(':') => return Err(ErrorCode::HttpRequestUriInvalid),
+    None => {},
+  }
+
+  let port = authority.port_u16().unwrap_or(if use_tls { 443 } else { 80 });
+
+  Ok(format!("{host}:{port}"))
+}
+
+/// Host of a normalized authority without the port and the IPv6 brackets, as the TLS server name expects it
+fn authority_host(authority: &str) -> &str {
+  let host = authority.rsplit_once(':').map_or(authority, |(host, _)| host);
+  host
+    .strip_prefix('[')
+    .and_then(|host| host.strip_suffix(']'))
+    .unwrap_or(host)
+}
+
 impl HttpConnectionPool {
-  const MAX_RETRIES: u32 = 2;
   const MAX_CONNECTION_AGE: Duration = Duration::from_secs(300); // 5 minutes
 
   pub fn new(max_connections: usize) -> Self {
@@ -68,37 +314,59 @@ impl HttpConnectionPool {
     }
   }
 
+  /// Takes an idle connection to `authority` or opens a new one
+  ///
+  /// The returned permit counts the connection against the pool limit, it must be held while the connection is
+  /// in use and is released when dropped, whether the connection goes back to the pool or not.
   async fn get_connection(
     &self,
     authority: &str,
     use_tls: bool,
+    tls: &TlsConfig,
     connect_timeout: Duration,
-  ) -> Result<(SendRequest<HyperOutgoingBody>, Option<AbortOnDropJoinHandle<()>>), ErrorCode> {
-    let _permit = self.semaphore.acquire().await.unwrap();
+  ) -> Result<
+    (
+      SendRequest<HyperOutgoingBody>,
+      Option<AbortOnDropJoinHandle<()>>,
+      OwnedSemaphorePermit,
+    ),
+    ErrorCode,
+  > {
+    let permit = self
+      .semaphore
+      .clone()
+      .acquire_owned()
+      .await
+      .map_err(|_| ErrorCode::InternalError(Some("connection pool is closed".to_string())))?;
 
     // Try to get an existing connection
-    let mut connections = self.connections.lock().await;
-    if let Some(connection_list) = connections.get_mut(authority) {
-      while let Some(conn) = connection_list.pop() {
-        // Check both idle timeout and total age
-        if conn.last_used.elapsed() < Duration::from_secs(60)
-          && conn.created_at.elapsed() < Self::MAX_CONNECTION_AGE
-          && conn.sender.is_ready()
-        {
-          return Ok((conn.sender, None));
+    {
+      let mut connections = self.connections.lock().await;
+      if let Some(connection_list) = connections.get_mut(authority) {
+        while let Some(conn) = connection_list.pop() {
+          // Check both idle timeout and total age
+          if conn.last_used.elapsed() < Duration::from_secs(60)
+            && conn.created_at.elapsed() < Self::MAX_CONNECTION_AGE
+            && conn.sender.is_ready()
+          {
+            return Ok((conn.sender, None, permit));
+          }
+          // If connection is too old, let it drop and create a new one
         }
-        // If connection is too old, let it drop and create a new one
       }
     }
 
     // Create new connection if none available
-    self.create_connection(authority, use_tls, connect_timeout).await
+    let (sender, worker) = self.create_connection(authority, use_tls, tls, connect_timeout).await?;
+
+    Ok((sender, worker, permit))
   }
 
   async fn create_connection(
     &self,
     authority: &str,
     use_tls: bool,
+    tls: &TlsConfig,
     connect_timeout: Duration,
   ) -> Result<(SendRequest<HyperOutgoingBody>, Option<AbortOnDropJoinHandle<()>>), ErrorCode> {
     let tcp_stream = TcpStream::connect(authority)
@@ -106,86 +374,73 @@ impl HttpConnectionPool {
       .map_err(|_| ErrorCode::ConnectionRefused)?;
 
     if use_tls {
-      #[cfg(not(any(target_arch = "riscv64", target_arch = "s390x")))]
-      {
-        use rustls::{pki_types::ServerName, RootCertStore};
-
-        let mut root_cert_store = RootCertStore::empty();
-
-        // Читаем сертификаты из директории certs
-        tracing::info!("Loading custom certificates from certs directory");
-        if let Ok(entries) = std::fs::read_dir("certs") {
-          for entry in entries {
-            if let Ok(entry) = entry {
-              let path = entry.path();
-              if path.is_file() {
-                tracing::debug!("Reading certificate file: {:?}", path);
-                if let Ok(cert_data) = std::fs::read(&path) {
-                  // Пытаемся распарсить как PEM
-                  let mut cert_slice = cert_data.as_slice();
-                  let pem_certs = rustls_pemfile::certs(&mut cert_slice);
-                  let mut found_pem = false;
-                  for cert_result in pem_certs {
-                    if let Ok(cert) = cert_result {
-                      let _ = root_cert_store.add(cert);
-                      found_pem = true;
-                      tracing::debug!("Successfully loaded PEM certificate from {:?}", path);
-                    }
-                  }
-                  if !found_pem {
-                    // Пытаемся добавить как DER
-                    let cert = rustls::pki_types::CertificateDer::from(cert_data);
+      let mut root_cert_store = RootCertStore::empty();
+
+      // Читаем сертификаты из директории certs
+      tracing::info!("Loading custom certificates from certs directory");
+      if let Ok(entries) = std::fs::read_dir("certs") {
+        for entry in entries {
+          if let Ok(entry) = entry {
+            let path = entry.path();
+            if path.is_file() {
+              tracing::debug!("Reading certificate file: {:?}", path);
+              if let Ok(cert_data) = std::fs::read(&path) {
+                // Пытаемся распарсить как PEM
+                let mut cert_slice = cert_data.as_slice();
+                let pem_certs = rustls_pemfile::certs(&mut cert_slice);
+                let mut found_pem = false;
+                for cert_result in pem_certs {
+                  if let Ok(cert) = cert_result {
                     let _ = root_cert_store.add(cert);
-                    tracing::debug!("Successfully loaded DER certificate from {:?}", path);
+                    found_pem = true;
+                    tracing::debug!("Successfully loaded PEM certificate from {:?}", path);
                   }
-                } else {
-                  tracing::warn!("Failed to read certificate file: {:?}", path);
                 }
+                if !found_pem {
+                  // Пытаемся добавить как DER
+                  let cert = rustls::pki_types::CertificateDer::from(cert_data);
+                  let _ = root_cert_store.add(cert);
+                  tracing::debug!("Successfully loaded DER certificate from {:?}", path);
+                }
+              } else {
+                tracing::warn!("Failed to read certificate file: {:?}", path);
               }
             }
           }
-        } else {
-          tracing::debug!("No certs directory found or unable to read it");
         }
+      } else {
+        tracing::debug!("No certs directory found or unable to read it");
+      }
 
-        // Добавляем стандартные корневые сертификаты
-        root_cert_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-        tracing::info!("Loaded {} root certificates total", root_cert_store.len());
-        let config = rustls::ClientConfig::builder()
-          .with_root_certificates(root_cert_store)
-          .with_no_client_auth();
-        let connector = tokio_rustls::TlsConnector::from(std::sync::Arc::new(config));
-        let mut parts = authority.split(':');
-        let host = parts.next().unwrap_or(authority);
-        let domain = ServerName::try_from(host)
-          .map_err(|_| dns_error("invalid dns name".to_string(), 0))?
-          .to_owned();
-
-        let stream = connector
-          .connect(domain, tcp_stream)
-          .await
-          .map_err(|_| ErrorCode::TlsProtocolError)?;
-        let io = TokioIo::new(stream);
-
-        let (sender, conn) = timeout(connect_timeout, hyper::client::conn::http1::handshake(io))
-          .await
-          .map_err(|_| ErrorCode::ConnectionTimeout)?
-          .map_err(hyper_request_error)?;
-
-        let worker = wasmtime_wasi::runtime::spawn(async move {
-          if let Err(e) = conn.await {
-            tracing::warn!("connection error: {}", e);
-          }
-        });
+      // Добавляем стандартные корневые сертификаты
+      root_cert_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+      tracing::info!("Loaded {} root certificates total", root_cert_store.len());
+      let config = tls
+        .client_config(root_cert_store)
+        .map_err(|e| ErrorCode::InternalError(Some(e)))?;
+      let connector = tokio_rustls::TlsConnector::from(std::sync::Arc::new(config));
+      let domain = ServerName::try_from(authority_host(authority))
+        .map_err(|_| dns_error("invalid dns name".to_string(), 0))?
+        .to_owned();
 
-        Ok((sender, Some(worker)))
-      }
-      #[cfg(any(target_arch = "riscv64", target_arch = "s390x"))]
-      {
-        Err(ErrorCode::InternalError(Some(
-          "unsupported architecture for SSL".to_string(),
-        )))
-      }
+      let stream = connector
+        .connect(domain, tcp_stream)
+        .await
+        .map_err(|_| ErrorCode::TlsProtocolError)?;
+      let io = TokioIo::new(stream);
+
+      let (sender, conn) = timeout(connect_timeout, hyper::client::conn::http1::handshake(io))
+        .await
+        .map_err(|_| ErrorCode::ConnectionTimeout)?
+        .map_err(hyper_request_error)?;
+
+      let worker = wasmtime_wasi::runtime::spawn(async move {
+        if let Err(e) = conn.await {
+          tracing::warn!("connection error: {}", e);
+        }
+      });
+
+      Ok((sender, Some(worker)))
     } else {
       let io = TokioIo::new(tcp_stream);
       let (sender, conn) = timeout(connect_timeout, hyper::client::conn::http1::handshake(io))
@@ -212,29 +467,149 @@ impl HttpConnectionPool {
 
     let mut connections = self.connections.lock().await;
     connections.entry(authority).or_insert_with(Vec::new).push(conn);
-    self.semaphore.add_permits(1);
   }
 }
 
+/// Most log lines kept for a single `process` call, the oldest are dropped first
+pub const MAX_TASK_LOG_LINES: usize = 1000;
+
+/// A line a plugin logged while processing a task
+#[derive(Debug, Clone)]
+pub struct TaskLogLine {
+  pub at: SystemTime,
+  pub level: &'static str,
+  pub context: String,
+  pub message: String,
+}
+
 pub struct State {
+  /// Name of the plugin running in this store, set once its metadata is loaded
+  pub plugin: String,
   pub table: ResourceTable,
   pub ctx: WasiCtx,
   pub http: WasiHttpCtx,
   pub wasi_keyvalue_ctx: WasiKeyValueCtx,
+  pub http_config: HttpConfig,
+  /// Paces the outbound requests of the plugin when it has a `rate_limit`
+  pub rate_limiter: Option<Arc<RateLimiter>>,
+  /// The task being processed, set by the executor around each `process` call like `task_output`
+  pub task_context: Option<TaskContext>,
+  /// Output file of the task being processed, set by the executor around each `process` call
+  pub task_output: Option<PathBuf>,
+  /// Where `enqueue-task` saves tasks, set by the executor around each `process` call like `task_output`
+  pub task_queue: Option<Arc<dyn TaskQueue>>,
+  /// Tasks `enqueue-task` saved during the `process` call, reset by the executor like `task_queue`
+  pub task_enqueued: usize,
+  /// Most tasks `enqueue-task` saves during a `process` call, further ones are rejected
+  pub max_enqueued: usize,
+  /// Lines logged during the task being processed, collected when the executor sets it around a `process` call
+  pub task_logs: Option<VecDeque<TaskLogLine>>,
+  /// When the running call traps if the guest is still busy, set by the executor around calls that must end in
+  /// time. Without it the guest only yields to the host on every epoch tick
+  pub call_deadline: Option<Instant>,
+  stdout: CapturedOutput,
+  stderr: CapturedOutput,
 }
 
 impl State {
   pub fn new() -> Self {
-    let mut builder = WasiCtxBuilder::new();
-    builder.inherit_stdio();
+    let stdout = CapturedOutput::default();
+    let stderr = CapturedOutput::default();
 
     Self {
+      plugin: String::new(),
       table: ResourceTable::new(),
-      ctx: builder.build(),
+      ctx: wasi_ctx_builder(false, &stdout, &stderr).build(),
       http: WasiHttpCtx::new(),
       wasi_keyvalue_ctx: WasiKeyValueCtxBuilder::new().ttl(Duration::from_secs(86400)).build(),
+      http_config: HttpConfig::default(),
+      rate_limiter: None,
+      task_context: None,
+      task_output: None,
+      task_queue: None,
+      task_enqueued: 0,
+      max_enqueued: usize::MAX,
+      task_logs: None,
+      call_deadline: None,
+      stdout,
+      stderr,
+    }
+  }
+
+  /// Decides what a guest that reached its epoch deadline does: trap once past `call_deadline`, otherwise yield
+  /// so the executor's own timeouts and the other plugins get to run, then go on for another tick
+  pub(crate) fn on_epoch_deadline(&self) -> wasmtime::Result<UpdateDeadline> {
+    match self.call_deadline {
+      Some(deadline) if Instant::now() >= deadline => Err(anyhow::anyhow!(
+        "Plugin {} didn't finish the call before its deadline",
+        self.plugin
+      )),
+      _ => Ok(UpdateDeadline::Yield(1)),
+    }
+  }
+
+  /// Replaces the WASI context with one exposing only the directories and environment variables of `config`
+  pub fn configure_wasi(&mut self, config: &WasiConfig) -> PluginResult<()> {
+    let mut builder = wasi_ctx_builder(config.inherit_stdio, &self.stdout, &self.stderr);
+    builder.envs(&config.env_vars());
+
+    for dir in &config.preopened_dirs {
+      let (dir_perms, file_perms) = dir.perms();
+      builder
+        .preopened_dir(&dir.host, &dir.guest, dir_perms, file_perms)
+        .map_err(|e| PluginError::PreopenDirError(dir.host.display().to_string(), e.to_string()))?;
+    }
+
+    self.ctx = builder.build();
+
+    Ok(())
+  }
+
+  /// Logs what the plugin printed since the last call and adds it to the task logs when they're collected,
+  /// stdout at `info` and stderr at `warn`
+  pub fn drain_stdio(&mut self) {
+    for message in self.stdout.take_lines() {
+      tracing::info!(plugin = %self.plugin, "stdout: {}", message);
+      self.push_task_log(TaskLogLine {
+        at: SystemTime::now(),
+        level: "info",
+        context: "stdout".to_string(),
+        message,
+      });
+    }
+
+    for message in self.stderr.take_lines() {
+      tracing::warn!(plugin = %self.plugin, "stderr: {}", message);
+      self.push_task_log(TaskLogLine {
+        at: SystemTime::now(),
+        level: "warn",
+        context: "stderr".to_string(),
+        message,
+      });
     }
   }
+
+  fn push_task_log(&mut self, line: TaskLogLine) {
+    if let Some(logs) = &mut self.task_logs {
+      if logs.len() == MAX_TASK_LOG_LINES {
+        logs.pop_front();
+      }
+
+      logs.push_back(line);
+    }
+  }
+}
+
+fn wasi_ctx_builder(inherit_stdio: bool, stdout: &CapturedOutput, stderr: &CapturedOutput) -> WasiCtxBuilder {
+  let mut builder = WasiCtxBuilder::new();
+
+  if inherit_stdio {
+    builder.inherit_stdio();
+  } else {
+    builder.stdout(stdout.clone()).stderr(stderr.clone());
+  }
+
+  builder
 }
 
 impl Default for State {
@@ -268,69 +643,154 @@ impl WasiHttpView for State {
   where
     Self: Sized,
   {
+    if self.http_config.deny_egress {
+      tracing::warn!("Denied request of plugin {} to {}", self.plugin, request.uri());
+      return Err(ErrorCode::HttpRequestDenied.into());
+    }
+
     request
       .headers_mut()
       .insert(header::USER_AGENT, HeaderValue::from_str("Octabot").unwrap());
 
-    Ok(default_send_request(request, config))
+    Ok(default_send_request(
+      request,
+      config,
+      self.http_config.clone(),
+      self.rate_limiter.clone(),
+    ))
   }
 }
 
 pub fn default_send_request(
   request: hyper::Request<HyperOutgoingBody>,
   config: OutgoingRequestConfig,
+  http_config: HttpConfig,
+  rate_limiter: Option<Arc<RateLimiter>>,
 ) -> HostFutureIncomingResponse {
-  let handle = wasmtime_wasi::runtime::spawn(async move { Ok(default_send_request_handler(request, config).await) });
+  let handle = wasmtime_wasi::runtime::spawn(async move {
+    Ok(default_send_request_handler(request, config, &http_config, rate_limiter.as_deref()).await)
+  });
   HostFutureIncomingResponse::pending(handle)
 }
 
 pub async fn default_send_request_handler(
-  request: hyper::Request<HyperOutgoingBody>,
+  mut request: hyper::Request<HyperOutgoingBody>,
   config: OutgoingRequestConfig,
+  http_config: &HttpConfig,
+  rate_limiter: Option<&RateLimiter>,
 ) -> Result<IncomingResponse, ErrorCode> {
-  let authority = if let Some(authority) = request.uri().authority() {
-    if authority.port().is_some() {
-      authority.to_string()
-    } else {
-      let port = if config.use_tls { 443 } else { 80 };
-      format!("{}:{port}", authority)
-    }
+  let decompress = decompress::prepare_request(&mut request);
+  let response = send_request_with_retries(request, config, http_config, rate_limiter).await?;
+
+  Ok(if decompress {
+    decompress::decode_response(response, http_config.max_decompressed_bytes)
   } else {
-    return Err(ErrorCode::HttpRequestUriInvalid);
-  };
+    response
+  })
+}
 
+async fn send_request_with_retries(
+  request: hyper::Request<HyperOutgoingBody>,
+  config: OutgoingRequestConfig,
+  http_config: &HttpConfig,
+  rate_limiter: Option<&RateLimiter>,
+) -> Result<IncomingResponse, ErrorCode> {
+  let config = http_config.clamp(config);
+  let deadline = http_config
+    .max_retry_time_ms
+    .map(|max| Instant::now() + Duration::from_millis(max));
+
+  let authority = request
+    .uri()
+    .authority()
+    .ok_or(ErrorCode::HttpRequestUriInvalid)
+    .and_then(|authority| normalize_authority(authority, config.use_tls))?;
+
+  // A request without a body can be sent again as it was, a retry after an error is an empty request
+  let replay = request.body().is_end_stream().then(|| RequestHead::of(&request));
   let mut retries = 0;
 
+  if let Some(rate_limiter) = rate_limiter {
+    rate_limiter.acquire(&authority).await;
+  }
+
   // Try to send the original request first
-  match send_request(&authority, request, &config).await {
-    Ok(response) => Ok(response),
-    Err(mut error) => {
-      retries += 1;
-
-      while retries < HttpConnectionPool::MAX_RETRIES {
-        sleep(Duration::from_millis(100 * 2u64.pow(retries))).await;
-
-        match send_empty_request(&authority, &config).await {
-          Ok(response) => return Ok(response),
-          Err(e) => {
-            error = e;
-            retries += 1;
-          },
-        }
-      }
+  let mut result = send_request(&authority, request, &config, &http_config.tls).await;
 
-      Err(error)
-    },
+  while retries < http_config.max_retries {
+    let (delay, resend) = match (&result, &replay) {
+      (Err(_), _) => (http_config.retry_delay(retries + 1), None),
+      (Ok(response), Some(head)) => match http_config.retry_after(&response.resp, SystemTime::now()) {
+        Some(delay) => (delay, Some(head)),
+        None => break,
+      },
+      (Ok(_), None) => break,
+    };
+    if deadline.is_some_and(|deadline| Instant::now() + delay >= deadline) {
+      break;
+    }
+
+    // The connection of a response that is retried is released while waiting
+    drop(result);
+    retries += 1;
+
+    sleep(delay).await;
+    if let Some(rate_limiter) = rate_limiter {
+      rate_limiter.acquire(&authority).await;
+    }
+
+    result = match resend {
+      Some(head) => send_request(&authority, head.request(), &config, &http_config.tls).await,
+      None => send_empty_request(&authority, &config, &http_config.tls).await,
+    };
   }
+
+  result
+}
+
+/// What is sent again of a request without a body
+struct RequestHead {
+  method: http::Method,
+  uri: http::Uri,
+  version: http::Version,
+  headers: header::HeaderMap,
+}
+
+impl RequestHead {
+  fn of<B>(request: &hyper::Request<B>) -> Self {
+    Self {
+      method: request.method().clone(),
+      uri: request.uri().clone(),
+      version: request.version(),
+      headers: request.headers().clone(),
+    }
+  }
+
+  fn request(&self) -> hyper::Request<HyperOutgoingBody> {
+    let mut request = hyper::Request::new(empty_body());
+    *request.method_mut() = self.method.clone();
+    *request.uri_mut() = self.uri.clone();
+    *request.version_mut() = self.version;
+    *request.headers_mut() = self.headers.clone();
+
+    request
+  }
+}
+
+fn empty_body() -> HyperOutgoingBody {
+  let empty_body: Empty<Bytes> = Empty::new();
+
+  BoxBody::new(empty_body.map_err(|never: Infallible| -> ErrorCode { match never {} }))
 }
 
 async fn send_request(
   authority: &str,
   request: hyper::Request<HyperOutgoingBody>,
   config: &OutgoingRequestConfig,
+  tls: &TlsConfig,
 ) -> Result<IncomingResponse, ErrorCode> {
-  let (mut sender, worker) = HTTP_POOL
-    .get_connection(authority, config.use_tls, config.connect_timeout)
+  let (mut sender, worker, _permit) = HTTP_POOL
+    .get_connection(authority, config.use_tls, tls, config.connect_timeout)
     .await?;
 
   let resp = timeout(config.first_byte_timeout, sender.send_request(request))
@@ -350,19 +810,19 @@ async fn send_request(
   })
 }
 
-async fn send_empty_request(authority: &str, config: &OutgoingRequestConfig) -> Result<IncomingResponse, ErrorCode> {
-  let (mut sender, worker) = HTTP_POOL
-    .get_connection(authority, config.use_tls, config.connect_timeout)
+async fn send_empty_request(
+  authority: &str,
+  config: &OutgoingRequestConfig,
+  tls: &TlsConfig,
+) -> Result<IncomingResponse, ErrorCode> {
+  let (mut sender, worker, _permit) = HTTP_POOL
+    .get_connection(authority, config.use_tls, tls, config.connect_timeout)
     .await?;
 
-  let empty_body: Empty<Bytes> = Empty::new();
-  let mapped_body = empty_body.map_err(|never: Infallible| -> ErrorCode { match never {} });
-  let boxed_body = BoxBody::new(mapped_body);
-
   let request = hyper::Request::builder()
     .method(http::Method::GET)
     .uri("/")
-    .body(boxed_body)
+    .body(empty_body())
     .map_err(|_| ErrorCode::HttpProtocolError)?;
 
   let resp = timeout(config.first_byte_timeout, sender.send_request(request))
@@ -410,6 +870,186 @@ impl wasi::logging::logging::Host for State {
       },
     }
 
+    self.push_task_log(TaskLogLine {
+      at: SystemTime::now(),
+      level: level_name(level),
+      context,
+      message,
+    });
+
     Ok(())
   }
 }
+
+fn level_name(level: wasi::logging::logging::Level) -> &'static str {
+  match level {
+    wasi::logging::logging::Level::Trace => "trace",
+    wasi::logging::logging::Level::Debug => "debug",
+    wasi::logging::logging::Level::Info => "info",
+    wasi::logging::logging::Level::Warn => "warn",
+    wasi::logging::logging::Level::Error => "error",
+    wasi::logging::logging::Level::Critical => "critical",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_epoch_deadline_traps_past_call_deadline() {
+    let mut state = State::new();
+    assert!(matches!(state.on_epoch_deadline(), Ok(UpdateDeadline::Yield(1))));
+
+    state.call_deadline = Some(Instant::now() + Duration::from_secs(60));
+    assert!(matches!(state.on_epoch_deadline(), Ok(UpdateDeadline::Yield(1))));
+
+    state.call_deadline = Some(Instant::now() - Duration::from_millis(1));
+    assert!(state.on_epoch_deadline().is_err());
+  }
+
+  fn normalize(authority: &str, use_tls: bool) -> Result<String, ErrorCode> {
+    normalize_authority(&authority.parse().unwrap(), use_tls)
+  }
+
+  #[test]
+  fn test_normalize_authority() {
+    assert_eq!(normalize("127.0.0.1", false).unwrap(), "127.0.0.1:80");
+    assert_eq!(normalize("127.0.0.1:8080", true).unwrap(), "127.0.0.1:8080");
+    assert_eq!(normalize("[::1]", true).unwrap(), "[::1]:443");
+    assert_eq!(normalize("[2001:db8::1]:8443", false).unwrap(), "[2001:db8::1]:8443");
+    assert_eq!(normalize("example.com", true).unwrap(), "example.com:443");
+    assert_eq!(normalize("example.com:8080", false).unwrap(), "example.com:8080");
+
+    assert!(matches!(
+      normalize("user:secret@example.com", true),
+      Err(ErrorCode::HttpRequestUriInvalid)
+    ));
+    assert!(matches!(
+      normalize("user@[::1]:8080", false),
+      Err(ErrorCode::HttpRequestUriInvalid)
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_connection_permits_released() {
+    let pool = HttpConnectionPool::new(4);
+    let tls = TlsConfig::default();
+    let connect_timeout = Duration::from_secs(1);
+
+    // Nothing listens on port 1, every attempt fails after taking a permit
+    for _ in 0..100 {
+      assert!(pool
+        .get_connection("127.0.0.1:1", false, &tls, connect_timeout)
+        .await
+        .is_err());
+      assert_eq!(pool.semaphore.available_permits(), 4);
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let authority = listener.local_addr().unwrap().to_string();
+
+    for i in 0..100 {
+      let (sender, _worker, _permit) = pool
+        .get_connection(&authority, false, &tls, connect_timeout)
+        .await
+        .unwrap();
+      assert_eq!(pool.semaphore.available_permits(), 3);
+
+      // Half of the connections go back to the pool, the others are dropped as if they weren't ready
+      if i % 2 == 0 {
+        pool.return_connection(authority.clone(), sender).await;
+      }
+    }
+
+    assert_eq!(pool.semaphore.available_permits(), 4);
+  }
+
+  #[test]
+  fn test_egress_denied() {
+    let mut state = State::new();
+    state.http_config.deny_egress = true;
+
+    let request = hyper::Request::builder()
+      .uri("https://example.com/")
+      .body(empty_body())
+      .unwrap();
+    let config = OutgoingRequestConfig {
+      use_tls: true,
+      connect_timeout: Duration::from_secs(1),
+      first_byte_timeout: Duration::from_secs(1),
+      between_bytes_timeout: Duration::from_secs(1),
+    };
+
+    let error = state.send_request(request, config).err().unwrap();
+    assert!(matches!(error.downcast_ref(), Some(ErrorCode::HttpRequestDenied)));
+  }
+
+  #[test]
+  fn test_retry_after() {
+    let http_config = HttpConfig::default();
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+    let response = |status: u16, retry_after: Option<&str>| {
+      let mut response = hyper::Response::builder().status(status);
+      if let Some(value) = retry_after {
+        response = response.header(header::RETRY_AFTER, value);
+      }
+      response.body(()).unwrap()
+    };
+
+    assert_eq!(
+      http_config.retry_after(&response(429, Some("5")), now),
+      Some(Duration::from_secs(5))
+    );
+    assert_eq!(
+      http_config.retry_after(&response(503, Some("Wed, 21 Oct 2015 07:28:20 GMT")), now),
+      Some(Duration::from_secs(20))
+    );
+    // A date already past means right away
+    assert_eq!(
+      http_config.retry_after(&response(503, Some("Wed, 21 Oct 2015 07:27:00 GMT")), now),
+      Some(Duration::ZERO)
+    );
+    assert_eq!(
+      http_config.retry_after(&response(429, Some("3600")), now),
+      Some(Duration::from_secs(60))
+    );
+
+    assert_eq!(http_config.retry_after(&response(429, None), now), None);
+    assert_eq!(http_config.retry_after(&response(429, Some("soon")), now), None);
+    assert_eq!(http_config.retry_after(&response(500, Some("5")), now), None);
+  }
+
+  #[test]
+  fn test_tls_policy_checked() {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    assert!(TlsConfig::default().check().is_ok());
+
+    let tls13 = TlsConfig {
+      min_version: Some(TlsVersion::Tls13),
+      cipher_suites: vec!["TLS13_AES_256_GCM_SHA384".to_string()],
+    };
+    assert!(tls13.check().is_ok());
+
+    let unknown = TlsConfig {
+      cipher_suites: vec!["TLS13_AES_256_GCM".to_string()],
+      ..Default::default()
+    };
+    assert_eq!(unknown.check().unwrap_err(), "unknown cipher suite `TLS13_AES_256_GCM`");
+
+    // The only suite allowed is a TLS 1.2 one
+    let unusable = TlsConfig {
+      min_version: Some(TlsVersion::Tls13),
+      cipher_suites: vec!["TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384".to_string()],
+    };
+    assert!(unusable.check().is_err());
+  }
+
+  #[test]
+  fn test_authority_host() {
+    assert_eq!(authority_host("[::1]:443"), "::1");
+    assert_eq!(authority_host("127.0.0.1:80"), "127.0.0.1");
+    assert_eq!(authority_host("example.com:443"), "example.com");
+  }
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+use wasmtime_wasi::p2::{OutputStream, Pollable, StdoutStream, StreamResult};
+
+/// Most bytes kept between two drains, what the plugin writes past it is dropped
+const MAX_CAPTURED_BYTES: usize = 64 * 1024;
+
+/// Stdout or stderr of a plugin collected in memory instead of going to the host streams
+///
+/// Writes never fail so a chatty plugin doesn't trap, the output is cut at `MAX_CAPTURED_BYTES` until the
+/// next drain.
+#[derive(Clone, Default)]
+pub struct CapturedOutput {
+  buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CapturedOutput {
+  /// Takes what was written since the last call, split into lines
+  pub fn take_lines(&self) -> Vec<String> {
+    let bytes = std::mem::take(&mut *self.buffer.lock());
+
+    String::from_utf8_lossy(&bytes)
+      .lines()
+      .filter(|line| !line.trim().is_empty())
+      .map(str::to_string)
+      .collect()
+  }
+}
+
+impl StdoutStream for CapturedOutput {
+  fn stream(&self) -> Box<dyn OutputStream> {
+    Box::new(self.clone())
+  }
+
+  fn isatty(&self) -> bool {
+    false
+  }
+}
+
+impl OutputStream for CapturedOutput {
+  fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+    let mut buffer = self.buffer.lock();
+    let room = MAX_CAPTURED_BYTES.saturating_sub(buffer.len());
+    buffer.extend_from_slice(&bytes[..bytes.len().min(room)]);
+
+    Ok(())
+  }
+
+  fn flush(&mut self) -> StreamResult<()> {
+    Ok(())
+  }
+
+  fn check_write(&mut self) -> StreamResult<usize> {
+    Ok(MAX_CAPTURED_BYTES)
+  }
+}
+
+#[async_trait::async_trait]
+impl Pollable for CapturedOutput {
+  async fn ready(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_take_lines() {
+    let mut output = CapturedOutput::default();
+    output.write(Bytes::from_static(b"first\n\nsecond")).unwrap();
+
+    assert_eq!(output.take_lines(), vec!["first", "second"]);
+    assert!(output.take_lines().is_empty());
+
+    output.write(Bytes::from(vec![b'x'; MAX_CAPTURED_BYTES + 1])).unwrap();
+    assert_eq!(output.take_lines()[0].len(), MAX_CAPTURED_BYTES);
+  }
+}
@@ -0,0 +1,10 @@
+use crate::{
+  bindings::octahive::octabot::task_context::{Host, TaskContext},
+  state::State,
+};
+
+impl Host for State {
+  async fn get_task_context(&mut self) -> wasmtime::Result<Option<TaskContext>> {
+    Ok(self.task_context.clone())
+  }
+}
@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+
+use crate::{bindings::octahive::octabot::task_queue::Host, state::State};
+
+/// Saves the tasks a plugin enqueues while processing one, implemented by the executor over its database
+#[async_trait]
+pub trait TaskQueue: Send + Sync {
+  /// Creates or updates the task described by the JSON object `task`, returning why it was rejected otherwise
+  async fn enqueue(&self, task: &str) -> Result<(), String>;
+}
+
+impl Host for State {
+  async fn enqueue_task(&mut self, task: String) -> wasmtime::Result<Result<(), String>> {
+    let Some(queue) = self.task_queue.clone() else {
+      return Ok(Err("no task is being processed".to_string()));
+    };
+    if self.task_enqueued >= self.max_enqueued {
+      return Ok(Err(format!(
+        "at most {} tasks can be enqueued while processing a task",
+        self.max_enqueued
+      )));
+    }
+
+    let result = queue.enqueue(&task).await;
+    if result.is_ok() {
+      self.task_enqueued += 1;
+    }
+
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use super::*;
+
+  /// Accepts every task but `invalid`
+  struct TestQueue;
+
+  #[async_trait]
+  impl TaskQueue for TestQueue {
+    async fn enqueue(&self, task: &str) -> Result<(), String> {
+      match task {
+        "invalid" => Err("Invalid task".to_string()),
+        _ => Ok(()),
+      }
+    }
+  }
+
+  #[tokio::test]
+  async fn test_enqueue_limited_per_process_call() {
+    let mut state = State::new();
+    state.task_queue = Some(Arc::new(TestQueue));
+    state.max_enqueued = 2;
+
+    assert_eq!(state.enqueue_task("{}".to_string()).await.unwrap(), Ok(()));
+    // Rejected tasks aren't counted
+    assert!(state.enqueue_task("invalid".to_string()).await.unwrap().is_err());
+    assert_eq!(state.enqueue_task("{}".to_string()).await.unwrap(), Ok(()));
+    assert_eq!(
+      state.enqueue_task("{}".to_string()).await.unwrap(),
+      Err("at most 2 tasks can be enqueued while processing a task".to_string())
+    );
+    assert_eq!(state.task_enqueued, 2);
+  }
+}
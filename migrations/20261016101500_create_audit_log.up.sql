@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS `audit_log` (
+  `id` BLOB NOT NULL PRIMARY KEY,
+  `actor_id` BLOB,
+  `action` TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+  `entity_type` TEXT NOT NULL,
+  `entity_id` BLOB NOT NULL,
+  `diff` TEXT NOT NULL DEFAULT '{}' CHECK (json_valid (diff)),
+  `created_at` TIMESTAMP NOT NULL DEFAULT (DATETIME ('now')),
+  FOREIGN KEY (actor_id) REFERENCES users (id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_entity ON audit_log (entity_type, entity_id);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log (created_at);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS `revoked_tokens` (
+  `jti` TEXT NOT NULL PRIMARY KEY,
+  `expires_at` INTEGER NOT NULL,
+  `created_at` TIMESTAMP NOT NULL DEFAULT (DATETIME ('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_revoked_tokens_expires_at ON revoked_tokens (expires_at);
@@ -0,0 +1,2 @@
+-- The original casing of project codes is not recorded, nothing to revert
+SELECT 1;
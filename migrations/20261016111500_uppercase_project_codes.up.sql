@@ -0,0 +1,3 @@
+-- Project codes are normalized to uppercase on write, bring existing rows in line.
+-- The column is UNIQUE COLLATE NOCASE, so this can't produce duplicates.
+UPDATE projects SET code = UPPER(code) WHERE code != UPPER(code);
@@ -0,0 +1,4 @@
+ALTER TABLE tasks
+    ADD COLUMN misfire_policy TEXT NOT NULL DEFAULT 'fire_once' CHECK (
+      misfire_policy IN ('skip', 'fire_once', 'catch_up')
+    );
@@ -0,0 +1,3 @@
+ALTER TABLE tasks DROP COLUMN created_by;
+
+ALTER TABLE projects DROP COLUMN created_by;
@@ -0,0 +1,6 @@
+-- Rows created before this migration, and tasks created by plugins, have no creator
+ALTER TABLE projects
+    ADD COLUMN created_by BLOB NULL REFERENCES users (id) ON DELETE SET NULL;
+
+ALTER TABLE tasks
+    ADD COLUMN created_by BLOB NULL REFERENCES users (id) ON DELETE SET NULL;
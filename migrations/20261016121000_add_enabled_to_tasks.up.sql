@@ -0,0 +1 @@
+ALTER TABLE tasks ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT 1;
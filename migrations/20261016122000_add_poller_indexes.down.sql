@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_tasks_external_updated_at;
+DROP INDEX IF EXISTS idx_tasks_runnable;
@@ -0,0 +1,10 @@
+-- Runnable tasks by due time, matching the filter of the poller so it doesn't scan the whole table
+CREATE INDEX IF NOT EXISTS idx_tasks_runnable ON tasks (start_at)
+WHERE
+  status NOT IN ('finished', 'in_progress', 'dead')
+  AND enabled = 1;
+
+-- External tasks by last update, for the cleanup of the ones no sync touches anymore
+CREATE INDEX IF NOT EXISTS idx_tasks_external_updated_at ON tasks (datetime (updated_at))
+WHERE
+  external_id IS NOT NULL;
@@ -0,0 +1 @@
+ALTER TABLE tasks ADD COLUMN plugin_version TEXT;
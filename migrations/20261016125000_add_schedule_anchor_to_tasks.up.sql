@@ -0,0 +1,3 @@
+ALTER TABLE tasks ADD COLUMN schedule_anchor INTEGER;
+
+UPDATE tasks SET schedule_anchor = start_at;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS `task_templates` (
+  `id` BLOB NOT NULL PRIMARY KEY,
+  `name` TEXT NOT NULL UNIQUE COLLATE NOCASE,
+  `description` TEXT,
+  `type` TEXT NOT NULL,
+  `plugin_version` TEXT,
+  `schedule` TEXT,
+  `misfire_policy` TEXT NOT NULL DEFAULT 'fire_once',
+  `options` TEXT NOT NULL DEFAULT '{}' CHECK (json_valid (options)),
+  `created_by` BLOB NULL REFERENCES users (id) ON DELETE SET NULL,
+  `created_at` TIMESTAMP NOT NULL DEFAULT (DATETIME ('now')),
+  `updated_at` TIMESTAMP NOT NULL DEFAULT (DATETIME ('now'))
+);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS `plugin_options` (
+  `name` TEXT NOT NULL PRIMARY KEY,
+  `options` TEXT NOT NULL CHECK (json_valid (options)),
+  `updated_by` BLOB NULL REFERENCES users (id) ON DELETE SET NULL,
+  `updated_at` TIMESTAMP NOT NULL DEFAULT (DATETIME ('now'))
+);
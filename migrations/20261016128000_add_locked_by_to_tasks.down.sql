@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_tasks_locked_by;
+ALTER TABLE tasks DROP COLUMN locked_by;
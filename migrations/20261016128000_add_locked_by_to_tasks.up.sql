@@ -0,0 +1,4 @@
+ALTER TABLE tasks
+    ADD COLUMN locked_by TEXT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_locked_by ON tasks (locked_by);
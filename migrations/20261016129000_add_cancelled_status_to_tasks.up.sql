@@ -0,0 +1,86 @@
+-- SQLite can't alter a CHECK constraint, so the table is rebuilt with 'cancelled' allowed
+CREATE TABLE IF NOT EXISTS `tasks_new` (
+  `id` BLOB NOT NULL PRIMARY KEY,
+  `name` TEXT NOT NULL,
+  `type` TEXT NOT NULL,
+  `status` TEXT NOT NULL DEFAULT 'new' CHECK (
+    status IN (
+      'new',
+      'in_progress',
+      'failed',
+      'finished',
+      'retried',
+      'dead',
+      'cancelled'
+    )
+  ),
+  `project_id` BLOB NOT NULL,
+  `retries` INTEGER NOT NULL DEFAULT 0,
+  `external_id` TEXT UNIQUE,
+  `external_modified_at` TIMESTAMP,
+  `schedule` TEXT,
+  `start_at` INTEGER NOT NULL,
+  `options` TEXT NOT NULL DEFAULT '{}' CHECK (json_valid (options)),
+  `created_at` TIMESTAMP NOT NULL DEFAULT (DATETIME ('now')),
+  `updated_at` TIMESTAMP NOT NULL DEFAULT (DATETIME ('now')),
+  `locked_at` TIMESTAMP NULL,
+  `end_at` INTEGER NULL,
+  `misfire_policy` TEXT NOT NULL DEFAULT 'fire_once' CHECK (
+    misfire_policy IN ('skip', 'fire_once', 'catch_up')
+  ),
+  `created_by` BLOB NULL REFERENCES users (id) ON DELETE SET NULL,
+  `enabled` BOOLEAN NOT NULL DEFAULT 1,
+  `plugin_version` TEXT,
+  `deleted_at` DATETIME,
+  `schedule_anchor` INTEGER,
+  `locked_by` TEXT NULL,
+  FOREIGN KEY (project_id) REFERENCES projects (id) ON DELETE CASCADE
+);
+
+INSERT INTO tasks_new (
+  id, name, type, status, project_id, retries, external_id, external_modified_at, schedule, start_at, options,
+  created_at, updated_at, locked_at, end_at, misfire_policy, created_by, enabled, plugin_version, deleted_at,
+  schedule_anchor, locked_by
+)
+SELECT
+  id, name, type, status, project_id, retries, external_id, external_modified_at, schedule, start_at, options,
+  created_at, updated_at, locked_at, end_at, misfire_policy, created_by, enabled, plugin_version, deleted_at,
+  schedule_anchor, locked_by
+FROM tasks;
+
+DROP TABLE tasks;
+
+ALTER TABLE tasks_new RENAME TO tasks;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_project_id ON tasks (project_id);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_external_id ON tasks (external_id);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks (status);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_start_at ON tasks (start_at);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_status_start_at ON tasks (status, start_at);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_locked_at ON tasks (locked_at);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_locked_by ON tasks (locked_by);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_runnable ON tasks (start_at)
+WHERE
+  status NOT IN ('finished', 'in_progress', 'dead', 'cancelled')
+  AND enabled = 1;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_external_updated_at ON tasks (datetime (updated_at))
+WHERE
+  external_id IS NOT NULL;
+
+CREATE TRIGGER IF NOT EXISTS trig_tasks_updated_at AFTER
+UPDATE ON tasks FOR EACH ROW BEGIN
+UPDATE tasks
+SET
+  updated_at = DATETIME ('now')
+WHERE
+  id = NEW.id;
+
+END;
@@ -0,0 +1,27 @@
+DROP TRIGGER IF EXISTS trig_tasks_updated_at;
+
+CREATE TRIGGER IF NOT EXISTS trig_tasks_updated_at AFTER
+UPDATE ON tasks FOR EACH ROW BEGIN
+UPDATE tasks
+SET
+  updated_at = DATETIME ('now')
+WHERE
+  id = NEW.id;
+
+END;
+
+DROP TRIGGER IF EXISTS trig_projects_updated_at;
+
+CREATE TRIGGER IF NOT EXISTS trig_projects_updated_at AFTER
+UPDATE ON projects FOR EACH ROW BEGIN
+UPDATE projects
+SET
+  updated_at = DATETIME ('now')
+WHERE
+  id = NEW.id;
+
+END;
+
+ALTER TABLE projects DROP COLUMN version;
+
+ALTER TABLE tasks DROP COLUMN version;
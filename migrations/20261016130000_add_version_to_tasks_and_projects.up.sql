@@ -0,0 +1,36 @@
+-- Bumped on every update, statements returning the row bump it themselves so the returned version is current
+ALTER TABLE tasks ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+ALTER TABLE projects ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+DROP TRIGGER IF EXISTS trig_tasks_updated_at;
+
+CREATE TRIGGER IF NOT EXISTS trig_tasks_updated_at AFTER
+UPDATE ON tasks FOR EACH ROW BEGIN
+UPDATE tasks
+SET
+  updated_at = DATETIME ('now'),
+  version = CASE
+    WHEN NEW.version = OLD.version THEN OLD.version + 1
+    ELSE NEW.version
+  END
+WHERE
+  id = NEW.id;
+
+END;
+
+DROP TRIGGER IF EXISTS trig_projects_updated_at;
+
+CREATE TRIGGER IF NOT EXISTS trig_projects_updated_at AFTER
+UPDATE ON projects FOR EACH ROW BEGIN
+UPDATE projects
+SET
+  updated_at = DATETIME ('now'),
+  version = CASE
+    WHEN NEW.version = OLD.version THEN OLD.version + 1
+    ELSE NEW.version
+  END
+WHERE
+  id = NEW.id;
+
+END;
@@ -0,0 +1,2 @@
+-- Results the last successful run dropped under the lenient sub-result policy of its plugin
+ALTER TABLE tasks ADD COLUMN failed_results INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,59 @@
+use std::env;
+
+use anyhow::{anyhow, bail, Result};
+use octabot_api::listen::ListenAddr;
+use tracing_subscriber::filter::Directive;
+
+/// Settings read from the environment at startup
+pub struct AppConfig {
+  pub listen: ListenAddr,
+  pub database_url: String,
+  pub log_level: Directive,
+}
+
+impl AppConfig {
+  /// Reads the required settings and checks the optional ones, reporting every missing or invalid
+  /// variable at once instead of stopping at the first
+  pub fn from_env() -> Result<Self> {
+    let mut errors = Vec::new();
+
+    let listen = collect(&mut errors, ListenAddr::from_env());
+    let database_url = collect(&mut errors, required("DATABASE_URL"));
+    let log_level = collect(
+      &mut errors,
+      required("OCTABOT_LOG_LEVEL").and_then(|level| {
+        level
+          .parse::<Directive>()
+          .map_err(|err| anyhow!("OCTABOT_LOG_LEVEL `{}` is invalid: {}", level, err))
+      }),
+    );
+    errors.extend(octabot_api::validate_settings());
+
+    match (listen, database_url, log_level) {
+      (Some(listen), Some(database_url), Some(log_level)) if errors.is_empty() => Ok(Self {
+        listen,
+        database_url,
+        log_level,
+      }),
+      _ => bail!(
+        "Invalid configuration:\n{}",
+        errors
+          .iter()
+          .map(|err| format!("  - {}", err))
+          .collect::<Vec<_>>()
+          .join("\n")
+      ),
+    }
+  }
+}
+
+fn required(name: &str) -> Result<String> {
+  env::var(name)
+    .ok()
+    .filter(|value| !value.is_empty())
+    .ok_or_else(|| anyhow!("{} must be set", name))
+}
+
+fn collect<T>(errors: &mut Vec<anyhow::Error>, result: Result<T>) -> Option<T> {
+  result.map_err(|err| errors.push(err)).ok()
+}
@@ -0,0 +1,68 @@
+use std::{env, str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use sqlx::{
+  sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+  SqlitePool,
+};
+
+const DEFAULT_MAX_CONNECTIONS: u32 = 100;
+const DEFAULT_MIN_CONNECTIONS: u32 = 5;
+const DEFAULT_ACQUIRE_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_BUSY_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_JOURNAL_MODE: &str = "wal";
+const DEFAULT_SYNCHRONOUS: &str = "normal";
+
+/// Opens the SQLite pool, tuned from the environment:
+///
+/// * `DB_MAX_CONNECTIONS` - pool size limit (default 100)
+/// * `DB_MIN_CONNECTIONS` - connections kept open while idle (default 5)
+/// * `DB_ACQUIRE_TIMEOUT` - seconds to wait for a free connection before failing (default 30)
+/// * `DB_BUSY_TIMEOUT` - seconds SQLite waits on a locked database before returning `SQLITE_BUSY` (default 5)
+/// * `DB_JOURNAL_MODE` - `delete`, `truncate`, `persist`, `memory`, `wal` or `off` (default wal)
+/// * `DB_SYNCHRONOUS` - `off`, `normal`, `full` or `extra` (default normal)
+///
+/// The API, the executor and the cleanup workers all write concurrently, WAL lets readers proceed
+/// during a write and `normal` sync is durable in WAL mode except for a power loss right after commit.
+pub async fn connect(db_url: &str) -> Result<SqlitePool> {
+  let max_connections = env_or("DB_MAX_CONNECTIONS", DEFAULT_MAX_CONNECTIONS)?;
+  let min_connections = env_or("DB_MIN_CONNECTIONS", DEFAULT_MIN_CONNECTIONS)?;
+  let acquire_timeout = env_or("DB_ACQUIRE_TIMEOUT", DEFAULT_ACQUIRE_TIMEOUT_SECS)?;
+  let busy_timeout = env_or("DB_BUSY_TIMEOUT", DEFAULT_BUSY_TIMEOUT_SECS)?;
+
+  if min_connections > max_connections {
+    return Err(anyhow!(
+      "DB_MIN_CONNECTIONS ({}) must not exceed DB_MAX_CONNECTIONS ({})",
+      min_connections,
+      max_connections
+    ));
+  }
+
+  let journal_mode = env::var("DB_JOURNAL_MODE").unwrap_or_else(|_| DEFAULT_JOURNAL_MODE.to_string());
+  let journal_mode = SqliteJournalMode::from_str(&journal_mode).context("Invalid DB_JOURNAL_MODE")?;
+  let synchronous = env::var("DB_SYNCHRONOUS").unwrap_or_else(|_| DEFAULT_SYNCHRONOUS.to_string());
+  let synchronous = SqliteSynchronous::from_str(&synchronous).context("Invalid DB_SYNCHRONOUS")?;
+
+  let connect_options = SqliteConnectOptions::from_str(db_url)
+    .context("Invalid DATABASE_URL")?
+    .busy_timeout(Duration::from_secs(busy_timeout))
+    .journal_mode(journal_mode)
+    .synchronous(synchronous);
+
+  SqlitePoolOptions::new()
+    .max_connections(max_connections)
+    .min_connections(min_connections)
+    .acquire_timeout(Duration::from_secs(acquire_timeout))
+    .connect_with(connect_options)
+    .await
+    .context("Database connection failed")
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> Result<T> {
+  match env::var(name) {
+    Ok(value) => value
+      .parse()
+      .map_err(|_| anyhow!("{} must be a non-negative integer, got `{}`", name, value)),
+    Err(_) => Ok(default),
+  }
+}
@@ -1,33 +1,60 @@
 use std::{env, sync::Arc};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use futures::FutureExt;
-use octabot_api::workers::{clean_exchange, clean_finished};
-use sqlx::sqlite::SqlitePoolOptions;
+use octabot_api::log_level::LogLevelHandle;
+use octabot_api::workers::{clean_exchange, clean_finished, clean_revoked_tokens, escalate_dead};
 use tokio::signal;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
-use tracing_subscriber::EnvFilter;
+use tracing::{error, info, Level};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+use config::AppConfig;
 use octabot_executor::executor::ExecutorSystem;
 
+mod config;
+mod db;
 mod utils;
 
+/// Loads and initializes the plugins from `config.json`, then exits without starting the server
+const CHECK_FLAG: &str = "--check";
+/// Writes the OpenAPI document of the api to stdout as JSON, then exits
+const DUMP_OPENAPI_FLAG: &str = "--dump-openapi";
+
 #[tokio::main]
 async fn main() -> Result<()> {
+  // Plugin HTTPS requests use the process default provider, ring builds on every target including riscv64 and s390x
   rustls::crypto::ring::default_provider()
     .install_default()
     .expect("Failed to install default rustls crypto provider");
 
   dotenvy::dotenv().ok();
 
-  let log_level = env::var("OCTABOT_LOG_LEVEL").expect("OCTABOT_LOG_LEVEL is not set in .env file");
-  let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
+  if env::args().skip(1).any(|arg| arg == DUMP_OPENAPI_FLAG) {
+    println!("{}", octabot_api::openapi().to_pretty_json()?);
+    return Ok(());
+  }
+
+  if env::args().skip(1).any(|arg| arg == CHECK_FLAG) {
+    let env_filter = EnvFilter::from_default_env().add_directive(Level::INFO.into());
+    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+
+    return Ok(ExecutorSystem::validate().await?);
+  }
+
+  let config = AppConfig::from_env()?;
 
-  let env_filter = EnvFilter::from_default_env().add_directive(log_level.parse()?);
+  let env_filter = EnvFilter::from_default_env().add_directive(config.log_level);
 
-  // Initialize tracing subscriber with the environment filter
-  tracing_subscriber::fmt().with_env_filter(env_filter).init();
+  // Initialize tracing subscriber with the environment filter, admins can replace it with POST /api/admin/log-level
+  let (env_filter, log_level) = LogLevelHandle::new(env_filter);
+  tracing_subscriber::registry()
+    .with(env_filter)
+    .with(fmt::layer())
+    .init();
+
+  // Plugins report metrics from the executor, so the recorder has to be in place before it starts
+  octabot_api::metrics::install_recorder()?;
 
   let cancel_token = CancellationToken::new();
 
@@ -63,23 +90,35 @@ async fn main() -> Result<()> {
     }
   });
 
-  let pool = SqlitePoolOptions::new()
-    .max_connections(100)
-    .min_connections(5)
-    .connect(&db_url)
+  let pool = db::connect(&config.database_url).await?;
+
+  // Apply the schema from `migrations/`, embedded into the binary at compile time
+  sqlx::migrate!()
+    .run(&pool)
     .await
-    .expect("Database connection failed");
+    .context("Failed to apply database migrations")?;
+  info!("Database migrations applied");
 
   let shared_pool = Arc::new(pool);
 
   let executor_system = ExecutorSystem::new(shared_pool.clone()).await?;
+  let executor_handle = executor_system.handle();
 
   if let Err(err) = utils::join_all(
     vec![
-      octabot_api::run(shared_pool.clone(), cancel_token.clone()).boxed(),
+      octabot_api::run(
+        config.listen,
+        shared_pool.clone(),
+        executor_handle,
+        log_level,
+        cancel_token.clone(),
+      )
+      .boxed(),
       executor_system.run(cancel_token.clone()).boxed(),
       clean_finished::run(shared_pool.clone(), cancel_token.clone()).boxed(),
       clean_exchange::run(shared_pool.clone(), cancel_token.clone()).boxed(),
+      clean_revoked_tokens::run(shared_pool.clone(), cancel_token.clone()).boxed(),
+      escalate_dead::run(shared_pool.clone(), cancel_token.clone()).boxed(),
     ],
     cancel_token,
   )